@@ -0,0 +1,113 @@
+//! Benchmarks of scanning and looking up icons in generated themes.
+//!
+//! Run with `cargo bench`. The themes are generated once per benchmark group in a temporary
+//! directory, see the `fixture` module for their layout.
+
+#[allow(dead_code)]
+#[path = "../src/fixture.rs"]
+mod fixture;
+
+use criterion::{BenchmarkId, Criterion, criterion_group, criterion_main};
+use fixture::{Fixture, FixtureOptions};
+use icon::{IconSearch, Icons};
+use std::hint::black_box;
+
+fn icons(fixture: &Fixture, list_directories: bool) -> Icons {
+    IconSearch::new_from(fixture.dirs())
+        .with_directory_listing(list_directories)
+        .search()
+        .icons()
+}
+
+fn cold_scan(c: &mut Criterion) {
+    let fixture = Fixture::generate(&FixtureOptions::default()).unwrap();
+    let mut group = c.benchmark_group("cold_scan");
+
+    for list_directories in [false, true] {
+        group.bench_with_input(
+            BenchmarkId::new("list_directories", list_directories),
+            &list_directories,
+            |b, &list_directories| b.iter(|| icons(&fixture, list_directories)),
+        );
+    }
+
+    group.finish();
+}
+
+fn warm_lookup(c: &mut Criterion) {
+    let fixture = Fixture::generate(&FixtureOptions::default()).unwrap();
+    let theme = fixture.leaf_theme();
+    let mut group = c.benchmark_group("warm_lookup");
+
+    for list_directories in [false, true] {
+        let icons = icons(&fixture, list_directories);
+        let lookups = [
+            ("own", fixture.own_icon()),
+            ("inherited", fixture.inherited_icon()),
+            ("missing", "does-not-exist"),
+        ];
+
+        for (kind, name) in lookups {
+            let id = BenchmarkId::new(kind, format!("list_directories={list_directories}"));
+            group.bench_function(id, |b| {
+                b.iter(|| icons.find_icon(black_box(name), 48, 1, theme))
+            });
+        }
+    }
+
+    group.finish();
+}
+
+fn bulk_lookup(c: &mut Criterion) {
+    let fixture = Fixture::generate(&FixtureOptions::default()).unwrap();
+    let theme = fixture.leaf_theme();
+    let mut group = c.benchmark_group("bulk_lookup");
+
+    for list_directories in [false, true] {
+        let icons = icons(&fixture, list_directories);
+
+        group.bench_with_input(
+            BenchmarkId::new("list_directories", list_directories),
+            &icons,
+            |b, icons| {
+                b.iter(|| {
+                    for name in &fixture.icon_names {
+                        black_box(icons.find_icon(name, 32, 1, theme));
+                    }
+                })
+            },
+        );
+    }
+
+    group.finish();
+}
+
+// the naive approach of scanning everything again for every lookup, as a baseline.
+fn oneshot(c: &mut Criterion) {
+    let fixture = Fixture::generate(&FixtureOptions::default()).unwrap();
+    let theme = fixture.leaf_theme();
+    let names = &fixture.icon_names[..10];
+    let mut group = c.benchmark_group("ten_lookups");
+
+    group.bench_function("oneshot", |b| {
+        b.iter(|| {
+            for name in names {
+                black_box(icons(&fixture, false).find_icon(name, 48, 1, theme));
+            }
+        })
+    });
+
+    group.bench_function("reused", |b| {
+        b.iter(|| {
+            let icons = icons(&fixture, false);
+            for name in names {
+                black_box(icons.find_icon(name, 48, 1, theme));
+            }
+        })
+    });
+
+    group.finish();
+}
+
+criterion_group!(benches, cold_scan, warm_lookup, bulk_lookup, oneshot);
+criterion_main!(benches);
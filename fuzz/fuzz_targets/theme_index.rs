@@ -0,0 +1,21 @@
+//! Parses arbitrary bytes as an `index.theme`, and matches every directory against a few sizes.
+//!
+//! Run with `cargo +nightly fuzz run theme_index`. `resources/example.index.theme` makes a good
+//! seed for the corpus.
+
+#![no_main]
+
+use icon::theme::ThemeIndex;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let Ok(index) = ThemeIndex::parse(data) else {
+        return;
+    };
+
+    for directory in &index.directories {
+        for (size, scale) in [(0, 1), (48, 1), (48, 2), (u32::MAX, u32::MAX)] {
+            let _ = directory.matches_size(size, scale);
+        }
+    }
+});
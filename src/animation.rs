@@ -0,0 +1,227 @@
+//! Detecting animated icons.
+//!
+//! Some icons, mostly those of status and tray items, are animated PNGs (APNG) or, if
+//! [registered](crate::IconSearch::with_file_type) as `FileType::Other("gif")`, GIFs.
+//! [`IconFile::animation`] reads just enough of such a file to tell whether it is animated and
+//! how many frames it has, so that applications can decide between displaying it statically and
+//! playing it back.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use icon::Icons;
+//!
+//! let icon = Icons::new().find_icon("process-working", 16, 1, "hicolor").unwrap();
+//! if icon.animation().unwrap().is_animated() {
+//!     // play it back
+//! }
+//! ```
+
+use crate::IconFile;
+use std::io::{self, BufReader, Read};
+
+/// Whether an icon file is animated, and how many frames it has.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct Animation {
+    /// The number of frames. Still images have a single frame.
+    pub frame_count: u32,
+}
+
+impl Animation {
+    /// A still image.
+    pub const STILL: Self = Self { frame_count: 1 };
+
+    pub fn is_animated(&self) -> bool {
+        self.frame_count > 1
+    }
+}
+
+impl IconFile {
+    /// Reads whether this icon is animated.
+    ///
+    /// APNGs and GIFs are recognized by their contents, regardless of their extension. All other
+    /// files are considered [still](Animation::STILL).
+    pub fn animation(&self) -> io::Result<Animation> {
+        let mut reader = BufReader::new(std::fs::File::open(&self.path)?);
+
+        let mut signature = [0; 8];
+        let len = read_up_to(&mut reader, &mut signature)?;
+        let signature = &signature[..len];
+
+        let frame_count = if signature == PNG_SIGNATURE {
+            png_frame_count(reader)?
+        } else if signature.starts_with(b"GIF87a") || signature.starts_with(b"GIF89a") {
+            // the signature read includes the first two bytes of the logical screen descriptor
+            gif_frame_count((&signature[6..]).chain(reader))?
+        } else {
+            1
+        };
+
+        Ok(Animation { frame_count })
+    }
+}
+
+const PNG_SIGNATURE: &[u8] = b"\x89PNG\r\n\x1a\n";
+
+/// Counts the frames of a PNG, after its signature.
+///
+/// Animated PNGs have an `acTL` chunk before their image data.
+fn png_frame_count(mut reader: impl Read) -> io::Result<u32> {
+    loop {
+        let mut header = [0; 8];
+        reader.read_exact(&mut header)?;
+        let len = u32::from_be_bytes(header[..4].try_into().unwrap());
+
+        match &header[4..] {
+            b"acTL" => {
+                let mut frames = [0; 4];
+                reader.read_exact(&mut frames)?;
+                return Ok(u32::from_be_bytes(frames).max(1));
+            }
+            b"IDAT" | b"IEND" => return Ok(1),
+            // skip the chunk's data and CRC
+            _ => skip(&mut reader, u64::from(len) + 4)?,
+        }
+    }
+}
+
+/// Counts the frames of a GIF, after its signature.
+///
+/// Every image descriptor in the data stream is a frame.
+fn gif_frame_count(mut reader: impl Read) -> io::Result<u32> {
+    const TRAILER: u8 = 0x3b;
+    const EXTENSION: u8 = 0x21;
+    const IMAGE: u8 = 0x2c;
+
+    // the size of a color table following a descriptor, according to its packed fields
+    let color_table_len = |packed: u8| match packed & 0x80 {
+        0 => 0,
+        _ => 3 << ((packed & 0x07) + 1),
+    };
+
+    let mut screen_descriptor = [0; 7];
+    reader.read_exact(&mut screen_descriptor)?;
+    skip(&mut reader, color_table_len(screen_descriptor[4]))?;
+
+    let mut frame_count = 0u32;
+    loop {
+        let mut introducer = [0];
+        if reader.read(&mut introducer)? == 0 {
+            // a missing trailer is common enough to forgive
+            break;
+        }
+
+        match introducer[0] {
+            TRAILER => break,
+            EXTENSION => {
+                skip(&mut reader, 1)?; // label
+                skip_sub_blocks(&mut reader)?;
+            }
+            IMAGE => {
+                let mut image_descriptor = [0; 9];
+                reader.read_exact(&mut image_descriptor)?;
+                skip(&mut reader, color_table_len(image_descriptor[8]))?;
+                skip(&mut reader, 1)?; // LZW minimum code size
+                skip_sub_blocks(&mut reader)?;
+
+                frame_count = frame_count.saturating_add(1);
+            }
+            _ => return Err(io::Error::new(io::ErrorKind::InvalidData, "malformed GIF")),
+        }
+    }
+
+    Ok(frame_count.max(1))
+}
+
+fn skip_sub_blocks(reader: &mut impl Read) -> io::Result<()> {
+    loop {
+        let mut len = [0];
+        reader.read_exact(&mut len)?;
+        if len[0] == 0 {
+            return Ok(());
+        }
+        skip(reader, len[0].into())?;
+    }
+}
+
+fn skip(reader: &mut impl Read, len: u64) -> io::Result<()> {
+    let skipped = io::copy(&mut reader.take(len), &mut io::sink())?;
+    if skipped < len {
+        return Err(io::ErrorKind::UnexpectedEof.into());
+    }
+    Ok(())
+}
+
+// like `read_exact`, but stopping early at the end of the file.
+fn read_up_to(reader: &mut impl Read, buf: &mut [u8]) -> io::Result<usize> {
+    let mut len = 0;
+    while len < buf.len() {
+        match reader.read(&mut buf[len..])? {
+            0 => break,
+            read => len += read,
+        }
+    }
+    Ok(len)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::IconFile;
+    use crate::animation::{Animation, PNG_SIGNATURE};
+
+    fn png_chunk(kind: &[u8; 4], data: &[u8]) -> Vec<u8> {
+        let mut chunk = (data.len() as u32).to_be_bytes().to_vec();
+        chunk.extend(kind);
+        chunk.extend(data);
+        chunk.extend([0; 4]); // CRC, which isn't checked
+        chunk
+    }
+
+    #[test]
+    fn test_animation() {
+        let dir = tempfile::tempdir().unwrap();
+        let animation = |name: &str, contents: &[u8]| {
+            let path = dir.path().join(name);
+            std::fs::write(&path, contents).unwrap();
+            IconFile::from_path(&path)
+                .unwrap_or(IconFile {
+                    path,
+                    file_type: crate::FileType::Other("gif"),
+                })
+                .animation()
+                .unwrap()
+        };
+
+        let mut png = PNG_SIGNATURE.to_vec();
+        png.extend(png_chunk(b"IHDR", &[0; 13]));
+        let mut apng = png.clone();
+        png.extend(png_chunk(b"IDAT", &[0; 4]));
+        assert_eq!(animation("still.png", &png), Animation::STILL);
+
+        apng.extend(png_chunk(b"acTL", &[0, 0, 0, 12, 0, 0, 0, 0]));
+        apng.extend(png_chunk(b"IDAT", &[0; 4]));
+        let apng = animation("spinner.png", &apng);
+        assert!(apng.is_animated());
+        assert_eq!(apng.frame_count, 12);
+
+        // an SVG named `.png` isn't animated either
+        assert_eq!(animation("wrong.png", b"<svg/>"), Animation::STILL);
+
+        // a 1x1 GIF with a global color table, a graphic control extension and two frames
+        let mut gif = b"GIF89a\x01\x00\x01\x00\x80\x00\x00".to_vec();
+        gif.extend([0; 6]); // global color table
+        let frame = [
+            &b"\x21\xf9\x04\x00\x0a\x00\x00\x00"[..], // graphic control extension
+            b"\x2c\x00\x00\x00\x00\x01\x00\x01\x00\x00", // image descriptor
+            b"\x02\x02\x44\x01\x00",                  // image data
+        ]
+        .concat();
+        gif.extend(&frame);
+        let still_gif = [&gif[..], b"\x3b"].concat();
+        gif.extend(&frame);
+        gif.push(0x3b);
+
+        assert_eq!(animation("still.gif", &still_gif), Animation::STILL);
+        assert_eq!(animation("spinner.gif", &gif).frame_count, 2);
+    }
+}
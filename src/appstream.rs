@@ -0,0 +1,220 @@
+//! Resolving the icons of AppStream components.
+//!
+//! Software centres show applications that aren't installed, whose icons come from AppStream
+//! metadata rather than an icon theme. A component lists one or more [`AppStreamIcon`]s:
+//!
+//! - `stock` icons are looked up in the icon theme, like the icons of desktop entries.
+//! - `cached` icons are files in the metadata's icon cache, in a directory per catalog (its
+//!   _origin_) and size, e.g. `/usr/share/swcatalog/icons/fedora/64x64/firefox.png`.
+//! - `local` icons are absolute paths.
+//!
+//! `remote` icons have to be downloaded, which is beyond this crate, and are left out.
+//!
+//! # Example
+//!
+//! ```
+//! use icon::Icons;
+//! use icon::appstream::AppStreamIcon;
+//!
+//! let icons = Icons::new();
+//! let component = [
+//!     AppStreamIcon::Stock("firefox".into()),
+//!     AppStreamIcon::Cached {
+//!         name: "org.mozilla.firefox.png".into(),
+//!         width: 64,
+//!         scale: 1,
+//!     },
+//! ];
+//! let icon = icons.find_appstream_icon(&component, "fedora", 64, 1, "Adwaita");
+//! ```
+
+use crate::IconFile;
+use crate::audit::{AccessKind, allows};
+use crate::environment::Environment;
+use crate::theme::Icons;
+use std::path::{Path, PathBuf};
+
+/// The directories AppStream metadata caches icons in, in order of precedence.
+///
+/// See [`Environment::appstream_icon_dirs`] to avoid reading the environment of the current
+/// process.
+pub fn appstream_icon_dirs() -> Vec<PathBuf> {
+    Environment::current().appstream_icon_dirs()
+}
+
+/// An `<icon>` of an AppStream component.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum AppStreamIcon {
+    /// An icon name, to be looked up in the icon theme.
+    Stock(String),
+    /// A file name in the icon cache of the component's catalog.
+    Cached {
+        name: String,
+        /// The width of the icon, which determines the directory it is cached in.
+        width: u32,
+        scale: u32,
+    },
+    /// An absolute path.
+    Local {
+        path: PathBuf,
+        /// The width of the icon, if known.
+        width: Option<u32>,
+        scale: u32,
+    },
+}
+
+impl Icons {
+    /// Resolves the icon of an AppStream component from the catalog `origin`, as it would be shown
+    /// at `size` and `scale`.
+    ///
+    /// Stock icons are preferred, looked up in `theme` as [`find_icon`](Self::find_icon) would.
+    /// Otherwise, of the cached and local icons that exist, the smallest one at least as large as
+    /// requested is picked, or the largest one if all are smaller. Icons without a width are
+    /// picked last.
+    pub fn find_appstream_icon(
+        &self,
+        icons: &[AppStreamIcon],
+        origin: &str,
+        size: u32,
+        scale: u32,
+        theme: &str,
+    ) -> Option<IconFile> {
+        self.find_appstream_icon_in(appstream_icon_dirs(), icons, origin, size, scale, theme)
+    }
+
+    /// Like [`find_appstream_icon`](Self::find_appstream_icon), but with the icon caches in the
+    /// given directories.
+    pub fn find_appstream_icon_in<I, P>(
+        &self,
+        dirs: I,
+        icons: &[AppStreamIcon],
+        origin: &str,
+        size: u32,
+        scale: u32,
+        theme: &str,
+    ) -> Option<IconFile>
+    where
+        I: IntoIterator<Item = P>,
+        P: AsRef<Path>,
+    {
+        let stock = icons.iter().find_map(|icon| match icon {
+            AppStreamIcon::Stock(name) => self.find_icon(name, size, scale, theme),
+            _ => None,
+        });
+        if stock.is_some() {
+            return stock;
+        }
+
+        // the origin and file names come from metadata, so they must not lead elsewhere
+        let is_file_name =
+            |name: &str| !name.is_empty() && name != "." && name != ".." && !name.contains('/');
+        let dirs = dirs
+            .into_iter()
+            .map(|dir| dir.as_ref().join(origin))
+            .filter(|_| is_file_name(origin))
+            .collect::<Vec<_>>();
+
+        let exists =
+            |path: &Path| allows(self.audit.as_ref(), path, AccessKind::Probe) && path.is_file();
+        let candidates = icons.iter().filter_map(|icon| match icon {
+            AppStreamIcon::Stock(_) => None,
+            AppStreamIcon::Cached { name, width, scale } => {
+                let size_dir = match *scale {
+                    0 | 1 => format!("{width}x{width}"),
+                    scale => format!("{width}x{width}@{scale}"),
+                };
+                let path = (dirs.iter())
+                    .map(|dir| dir.join(&size_dir).join(name))
+                    .filter(|_| is_file_name(name))
+                    .find(|path| exists(path))?;
+                Some((Some(width * (*scale).max(1)), path))
+            }
+            AppStreamIcon::Local { path, width, scale } => {
+                let path = Some(path).filter(|path| path.is_absolute() && exists(path))?;
+                Some((width.map(|width| width * (*scale).max(1)), path.clone()))
+            }
+        });
+
+        // the smallest icon that doesn't need to be scaled up, else the largest
+        let wanted = size * scale;
+        let (_, path) = candidates.min_by_key(|(pixels, _)| match *pixels {
+            Some(pixels) if pixels >= wanted => (0, pixels - wanted),
+            Some(pixels) => (1, wanted - pixels),
+            None => (2, 0),
+        })?;
+
+        self.file_types.icon_file(&path)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::IconSearch;
+    use crate::appstream::AppStreamIcon;
+    use std::fs;
+
+    #[test]
+    fn test_find_appstream_icon() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = dir.path().join("swcatalog/icons");
+        for file in ["fedora/64x64/gimp.png", "fedora/128x128/gimp.png"] {
+            fs::create_dir_all(cache.join(file).parent().unwrap()).unwrap();
+            fs::write(cache.join(file), b"").unwrap();
+        }
+        let theme = dir.path().join("icons/hicolor");
+        fs::create_dir_all(theme.join("48")).unwrap();
+        fs::write(
+            theme.join("index.theme"),
+            "[Icon Theme]\nName=Hicolor\nComment=Fallback\nDirectories=48\n\n[48]\nSize=48\n",
+        )
+        .unwrap();
+        fs::write(theme.join("48/firefox.png"), b"").unwrap();
+
+        let icons = IconSearch::new_from(vec![dir.path().join("icons")])
+            .search()
+            .icons();
+        let cached = |width| AppStreamIcon::Cached {
+            name: "gimp.png".into(),
+            width,
+            scale: 1,
+        };
+        let find = |component: &[AppStreamIcon], origin, size| {
+            icons
+                .find_appstream_icon_in([&cache], component, origin, size, 1, "hicolor")
+                .map(|icon| icon.path)
+        };
+
+        let gimp = [cached(64), cached(128), cached(256)];
+        assert_eq!(
+            find(&gimp, "fedora", 96),
+            Some(cache.join("fedora/128x128/gimp.png"))
+        );
+        assert_eq!(
+            find(&gimp, "fedora", 64),
+            Some(cache.join("fedora/64x64/gimp.png"))
+        );
+        assert_eq!(
+            find(&gimp, "fedora", 512),
+            Some(cache.join("fedora/128x128/gimp.png"))
+        );
+        assert_eq!(find(&gimp, "debian", 64), None);
+        assert_eq!(find(&gimp, "..", 64), None);
+
+        // stock icons win over cached ones
+        let firefox = [cached(64), AppStreamIcon::Stock("firefox".into())];
+        assert_eq!(
+            find(&firefox, "fedora", 64),
+            Some(theme.join("48/firefox.png"))
+        );
+
+        let local = [AppStreamIcon::Local {
+            path: theme.join("48/firefox.png"),
+            width: None,
+            scale: 1,
+        }];
+        assert_eq!(
+            find(&local, "fedora", 64),
+            Some(theme.join("48/firefox.png"))
+        );
+    }
+}
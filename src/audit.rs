@@ -0,0 +1,196 @@
+//! Bounding and recording file system access.
+//!
+//! Security-reviewed applications, such as password managers or signing tools, may show icons
+//! but must be able to tell which files they access. An [`Audit`], set with
+//! [`IconSearch::with_audit`](crate::IconSearch::with_audit), guarantees that scanning and looking
+//! up icons only touches the search directories:
+//!
+//! - the environment and the user's settings are never consulted, e.g. for the
+//!   [current theme](crate::Icons::current_theme).
+//! - paths outside the search directories, including symbolic links pointing out of them, are
+//!   skipped as if they didn't exist. Symbolic links are resolved to check where they lead.
+//! - every path accessed is recorded, along with whether it was allowed.
+//!
+//! Construct the search with [`IconSearch::new_from`](crate::IconSearch::new_from), as the
+//! default directories are read from the environment. Reading the icon files found, e.g. with
+//! [`IconFile::read`](crate::IconFile::read), is up to the application and not recorded.
+//!
+//! # Example
+//!
+//! ```
+//! use icon::IconSearch;
+//! use icon::audit::Audit;
+//!
+//! let audit = Audit::new();
+//! let icons = IconSearch::new_from(vec!["/usr/share/icons".into()])
+//!     .with_audit(audit.clone())
+//!     .search()
+//!     .icons();
+//! icons.find_icon("firefox", 48, 1, "hicolor");
+//!
+//! for access in audit.accesses() {
+//!     println!("{:?} {:?}", access.kind, access.path);
+//! }
+//! ```
+
+use std::path::{Component, Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+/// Confines file system access to the search directories, recording every path accessed.
+///
+/// Clones share the same record.
+#[derive(Debug, Clone, Default)]
+pub struct Audit(Arc<Mutex<AuditState>>);
+
+#[derive(Debug, Default)]
+struct AuditState {
+    /// The search directories, as given and with symbolic links resolved.
+    roots: Arc<[PathBuf]>,
+    accesses: Vec<Access>,
+}
+
+/// A path the crate accessed or tried to access.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Access {
+    pub path: PathBuf,
+    pub kind: AccessKind,
+    /// Whether the path is inside the search directories. Paths that aren't are never accessed.
+    pub allowed: bool,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum AccessKind {
+    /// Listing the entries of a directory.
+    ListDirectory,
+    /// Checking whether a file exists and can be opened, without reading it.
+    Probe,
+    /// Reading a file, such as a theme's `index.theme`.
+    ReadFile,
+}
+
+impl Audit {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// All accesses so far, in the order they happened.
+    pub fn accesses(&self) -> Vec<Access> {
+        self.state().accesses.clone()
+    }
+
+    /// The paths that were skipped because they are outside the search directories.
+    pub fn denied(&self) -> Vec<PathBuf> {
+        (self.state().accesses.iter())
+            .filter(|access| !access.allowed)
+            .map(|access| access.path.clone())
+            .collect()
+    }
+
+    /// Forgets all accesses so far.
+    pub fn clear(&self) {
+        self.state().accesses.clear();
+    }
+
+    fn state(&self) -> std::sync::MutexGuard<'_, AuditState> {
+        self.0.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Confines all further accesses to `roots`.
+    pub(crate) fn confine_to(&self, roots: &[PathBuf]) {
+        let mut resolved = roots.to_vec();
+        resolved.extend(roots.iter().flat_map(std::fs::canonicalize));
+        self.state().roots = resolved.into();
+    }
+
+    /// Records an access to `path`, returning whether it is allowed.
+    pub(crate) fn check(&self, path: &Path, kind: AccessKind) -> bool {
+        let roots = self.state().roots.clone();
+        let inside = |path: &Path| roots.iter().any(|root| path.starts_with(root));
+
+        // `..` could lead anywhere, and symbolic links must be followed to see where they lead.
+        // paths that don't exist can't lead anywhere, but their directory might. Only paths that
+        // are inside lexically are resolved, without holding the lock.
+        let allowed = !path.components().any(|c| c == Component::ParentDir)
+            && inside(path)
+            && {
+                let resolved = std::fs::canonicalize(path).ok().or_else(|| {
+                    let dir = std::fs::canonicalize(path.parent()?).ok()?;
+                    Some(dir.join(path.file_name()?))
+                });
+                resolved.is_none_or(|resolved| inside(&resolved))
+            };
+
+        self.state().accesses.push(Access {
+            path: path.to_owned(),
+            kind,
+            allowed,
+        });
+        allowed
+    }
+}
+
+/// Whether `audit`, if any, allows accessing `path`.
+pub(crate) fn allows(audit: Option<&Audit>, path: &Path, kind: AccessKind) -> bool {
+    audit.is_none_or(|audit| audit.check(path, kind))
+}
+
+// audits are about what happens to a theme, not what it is
+impl PartialEq for Audit {
+    fn eq(&self, _other: &Self) -> bool {
+        true
+    }
+}
+
+impl Eq for Audit {}
+
+impl std::hash::Hash for Audit {
+    fn hash<H: std::hash::Hasher>(&self, _state: &mut H) {}
+}
+
+#[cfg(test)]
+mod test {
+    use crate::IconSearch;
+    use crate::audit::{AccessKind, Audit};
+    use std::fs;
+
+    #[test]
+    fn test_audit() {
+        let dir = tempfile::tempdir().unwrap();
+        let icons_dir = dir.path().join("icons");
+        let theme = icons_dir.join("hicolor");
+        fs::create_dir_all(theme.join("48")).unwrap();
+        fs::write(
+            theme.join("index.theme"),
+            "[Icon Theme]\nName=Hicolor\nComment=Fallback\nDirectories=48\n\n[48]\nSize=48\n",
+        )
+        .unwrap();
+        fs::write(theme.join("48/firefox.png"), b"").unwrap();
+        // a link out of the search directories
+        fs::write(dir.path().join("secret.png"), b"").unwrap();
+        std::os::unix::fs::symlink(dir.path().join("secret.png"), theme.join("48/htop.png"))
+            .unwrap();
+
+        let audit = Audit::new();
+        let icons = IconSearch::new_from(vec![icons_dir.clone()])
+            .with_audit(audit.clone())
+            .search()
+            .icons();
+
+        assert!(icons.find_icon("firefox", 48, 1, "hicolor").is_some());
+        assert!(icons.find_icon("htop", 48, 1, "hicolor").is_none());
+        assert_eq!(audit.denied(), [theme.join("48/htop.png")]);
+
+        let accesses = audit.accesses();
+        assert!(
+            accesses
+                .iter()
+                .all(|access| access.path.starts_with(&icons_dir))
+        );
+        assert!(accesses.iter().any(|access| {
+            access.path == theme.join("index.theme") && access.kind == AccessKind::ReadFile
+        }));
+
+        audit.clear();
+        assert!(audit.accesses().is_empty());
+    }
+}
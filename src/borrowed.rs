@@ -0,0 +1,237 @@
+//! Looking up icons without allocating.
+//!
+//! [`Icons::find_icon`] returns an [`IconFile`] with a newly built path. Code that resolves icons
+//! in a hot path, such as a compositor drawing a frame, can use [`Icons::find_icon_ref`] instead,
+//! which returns an [`IconRef`] borrowing from the [`Icons`]: the theme, its directory and the
+//! type of the file found. The path is only built on request, into a buffer the caller can reuse.
+//!
+//! Lookups allocate nothing in themes with a [directory listing](crate::listing), and for
+//! standalone icons. Other themes are probed on the file system, which builds paths as usual.
+//! With [scale suffixes](crate::IconSearch::with_scale_suffixes) and a scale above 1, the
+//! suffixed names are built as well.
+//!
+//! # Example
+//!
+//! ```
+//! use icon::IconSearch;
+//! use std::path::PathBuf;
+//!
+//! let icons = IconSearch::new()
+//!     .with_directory_listing(true)
+//!     .search()
+//!     .icons();
+//!
+//! let mut path = PathBuf::new();
+//! for name in ["firefox", "folder", "user-trash"] {
+//!     if let Some(icon) = icons.find_icon_ref(name, 24, 1, "Adwaita") {
+//!         icon.write_path(&mut path);
+//!         println!("{name}: {path:?}");
+//!     }
+//! }
+//! ```
+
+use crate::theme::{DirectoryIndex, Icons, Theme};
+use crate::{FileType, IconFile};
+use std::borrow::Cow;
+use std::path::PathBuf;
+
+/// An icon found by [`Icons::find_icon_ref`], borrowing from the [`Icons`] it was found in.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum IconRef<'a> {
+    /// An icon in a theme, at `{base_dir}/{directory}/{name}.{ext}`.
+    Themed {
+        theme: &'a Theme,
+        /// The index of the base directory in the theme's
+        /// [`base_dirs`](crate::theme::ThemeInfo::base_dirs).
+        base_dir: usize,
+        /// The index of the directory in the theme's
+        /// [`directories`](crate::theme::ThemeIndex::directories).
+        directory: usize,
+        /// The name of the file without extension, which is the icon name unless a
+        /// [scale suffix](crate::IconSearch::with_scale_suffixes) was added.
+        name: Cow<'a, str>,
+        file_type: FileType,
+    },
+    /// A standalone icon.
+    Standalone(&'a IconFile),
+}
+
+impl IconRef<'_> {
+    pub fn file_type(&self) -> FileType {
+        match self {
+            IconRef::Themed { file_type, .. } => *file_type,
+            IconRef::Standalone(file) => file.file_type,
+        }
+    }
+
+    /// The theme the icon was found in, which may be a parent of the requested theme. `None` for
+    /// standalone icons.
+    pub fn theme(&self) -> Option<&Theme> {
+        match self {
+            IconRef::Themed { theme, .. } => Some(theme),
+            IconRef::Standalone(_) => None,
+        }
+    }
+
+    /// The description of the theme directory the icon was found in.
+    pub fn directory(&self) -> Option<&DirectoryIndex> {
+        match self {
+            IconRef::Themed {
+                theme, directory, ..
+            } => theme.info.index.directories.get(*directory),
+            IconRef::Standalone(_) => None,
+        }
+    }
+
+    /// Writes the path of the icon's file into `path`, replacing its contents but keeping its
+    /// allocation.
+    pub fn write_path(&self, path: &mut PathBuf) {
+        path.as_mut_os_string().clear();
+        match self {
+            IconRef::Themed {
+                theme,
+                base_dir,
+                directory,
+                name,
+                file_type,
+            } => {
+                path.push(&theme.info.base_dirs[*base_dir]);
+                path.push(&theme.info.index.directories[*directory].directory_name);
+                path.push(name.as_ref());
+                path.as_mut_os_string().push(".");
+                path.as_mut_os_string().push(file_type.ext());
+            }
+            IconRef::Standalone(file) => path.push(&file.path),
+        }
+    }
+
+    /// The icon's file, as [`Icons::find_icon`] would have returned it.
+    pub fn to_icon_file(&self) -> IconFile {
+        match self {
+            IconRef::Themed { file_type, .. } => {
+                let mut path = PathBuf::new();
+                self.write_path(&mut path);
+                IconFile {
+                    path,
+                    file_type: *file_type,
+                }
+            }
+            IconRef::Standalone(file) => (*file).clone(),
+        }
+    }
+}
+
+impl Icons {
+    /// Like [`find_icon`](Self::find_icon), but borrowing the result from these icons, see
+    /// [`borrowed`](crate::borrowed).
+    ///
+    /// [Name fallback](crate::IconSearch::with_name_fallback) isn't applied, and with
+    /// [adaptive ordering](crate::IconSearch::with_adaptive_ordering), listed themes try the
+    /// directories matching the size exactly in index order.
+    pub fn find_icon_ref<'a>(
+        &'a self,
+        icon_name: &'a str,
+        size: u32,
+        scale: u32,
+        theme: &str,
+    ) -> Option<IconRef<'a>> {
+        if icon_name.is_empty() {
+            return None;
+        }
+
+        let found = self.iter_chain(theme).find_map(|theme| {
+            let (base_dir, directory, name, file_type) = match &theme.listing {
+                Some(listing) if !(theme.scale_suffixes && scale > 1) => {
+                    let (base_dir, directory, file_type) =
+                        theme.locate_listed_ref(listing, icon_name, size, scale)?;
+                    (base_dir, directory, Cow::Borrowed(icon_name), file_type)
+                }
+                _ => {
+                    let (base_dir, directory, file) =
+                        theme.locate_icon_here(icon_name, size, scale)?;
+                    let name = match file.icon_name() {
+                        Some(name) if name != icon_name => Cow::Owned(name.to_owned()),
+                        _ => Cow::Borrowed(icon_name),
+                    };
+                    (base_dir, directory, name, file.file_type)
+                }
+            };
+
+            Some(IconRef::Themed {
+                theme,
+                base_dir,
+                directory,
+                name,
+                file_type,
+            })
+        });
+        found.or_else(|| {
+            let suffixed = (self.scale_suffixes && scale > 1)
+                .then(|| {
+                    self.standalone_icons(&format!("{icon_name}@{scale}x"))
+                        .first()
+                })
+                .flatten();
+            let file = suffixed.or_else(|| self.standalone_icons(icon_name).first())?;
+            Some(IconRef::Standalone(file))
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::IconSearch;
+    use crate::borrowed::IconRef;
+    use std::fs;
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_find_icon_ref() {
+        let dir = tempfile::tempdir().unwrap();
+        let theme = dir.path().join("Oak");
+        for sub_dir in ["16", "48", "scalable"] {
+            fs::create_dir_all(theme.join(sub_dir)).unwrap();
+        }
+        fs::write(
+            theme.join("index.theme"),
+            "[Icon Theme]\nName=Oak\nComment=\nDirectories=16,48,scalable\n\n\
+             [16]\nSize=16\nType=Fixed\n\n[48]\nSize=48\nType=Fixed\n\n\
+             [scalable]\nSize=48\nType=Scalable\nMinSize=8\nMaxSize=512\n",
+        )
+        .unwrap();
+        fs::write(theme.join("16/acorn.png"), "").unwrap();
+        fs::write(theme.join("48/acorn.png"), "").unwrap();
+        fs::write(theme.join("scalable/acorn.svg"), "").unwrap();
+        fs::write(theme.join("16/leaf.png"), "").unwrap();
+        fs::write(dir.path().join("bark.png"), "").unwrap();
+
+        for listing in [false, true] {
+            let icons = IconSearch::new_from(vec![dir.path().to_owned()])
+                .with_directory_listing(listing)
+                .search()
+                .icons();
+
+            let mut path = PathBuf::new();
+            for size in [16, 32, 48, 128] {
+                for name in ["acorn", "leaf", "bark", "missing"] {
+                    let icon = icons.find_icon_ref(name, size, 1, "Oak");
+                    let file = icons.find_icon(name, size, 1, "Oak");
+                    assert_eq!(icon.as_ref().map(IconRef::to_icon_file), file);
+
+                    if let Some(icon) = icon {
+                        icon.write_path(&mut path);
+                        assert_eq!(path, file.unwrap().path);
+                    }
+                }
+            }
+
+            let icon = icons.find_icon_ref("acorn", 48, 1, "Oak").unwrap();
+            assert_eq!(icon.theme().unwrap().info.internal_name, "Oak");
+            assert_eq!(icon.directory().unwrap().directory_name, "48");
+            assert!(matches!(
+                icons.find_icon_ref("bark", 48, 1, "Oak"),
+                Some(IconRef::Standalone(_))
+            ));
+        }
+    }
+}
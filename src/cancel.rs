@@ -0,0 +1,60 @@
+//! Aborting scans that are no longer needed.
+//!
+//! Scanning all icon themes takes a while, especially with
+//! [directory listing](crate::IconSearch::with_directory_listing). An application that quits, or
+//! that starts a newer scan, can abort one in progress by cancelling the [`CancellationToken`]
+//! passed to [`IconSearch::try_search`](crate::IconSearch::try_search),
+//! [`IconLocations::try_resolve`](crate::IconLocations::try_resolve) or
+//! [`Icons::try_refresh`](crate::Icons::try_refresh), from any thread.
+//!
+//! # Example
+//!
+//! ```
+//! use icon::IconSearch;
+//! use icon::cancel::CancellationToken;
+//!
+//! let token = CancellationToken::new();
+//! let scan = std::thread::spawn({
+//!     let token = token.clone();
+//!     move || IconSearch::new().try_search(&token)?.try_icons(&token)
+//! });
+//!
+//! // the user quit
+//! token.cancel();
+//! let _ = scan.join().unwrap();
+//! ```
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use thiserror::Error;
+
+/// A flag to abort scans with. Clones share the same flag.
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken(Arc<AtomicBool>);
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Aborts all scans using this token. Scans stop shortly after, not immediately.
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+
+    pub(crate) fn check(&self) -> Result<(), Cancelled> {
+        match self.is_cancelled() {
+            true => Err(Cancelled),
+            false => Ok(()),
+        }
+    }
+}
+
+/// The error of a scan that was [cancelled](CancellationToken::cancel).
+#[derive(Error, Debug, Copy, Clone, PartialEq, Eq, Hash)]
+#[error("the scan was cancelled")]
+pub struct Cancelled;
@@ -0,0 +1,524 @@
+//! Reacting to changes of the installed icon themes.
+//!
+//! An [`Icons`] is a snapshot of the icons installed when it was created. [`Icons::refresh`]
+//! searches the same directories again, updating it in place, and reports what changed as a
+//! [`ScanDiff`]. Callbacks registered with [`Icons::on_change`] are called whenever a refresh finds
+//! a change, including the user switching to another icon theme.
+//!
+//! With the `watch` feature, an [`IconWatcher`](crate::watch::IconWatcher) refreshes automatically
+//! when icon themes are installed or removed, or the user's settings change.
+//!
+//! With the `stream` feature, changes are also available as a [`Stream`](futures_core::Stream) of
+//! [`IconEvent`]s through [`Icons::events`], for use alongside other event sources in async code.
+//!
+//! # Example
+//!
+//! ```
+//! use icon::Icons;
+//!
+//! let mut icons = Icons::new();
+//! icons.on_change(|diff| println!("new themes: {:?}", diff.added_themes));
+//!
+//! // ... after installing a theme:
+//! let diff = icons.refresh();
+//! ```
+
+use crate::IconSearch;
+use crate::cancel::{CancellationToken, Cancelled};
+use crate::theme::Icons;
+use std::collections::BTreeSet;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+/// The changes found by [`Icons::refresh`].
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct ScanDiff {
+    /// Themes that were installed, by internal name.
+    pub added_themes: BTreeSet<String>,
+    /// Themes that were removed, by internal name.
+    pub removed_themes: BTreeSet<String>,
+    /// Themes whose index, directories or parents changed, by internal name.
+    pub changed_themes: BTreeSet<String>,
+    /// Standalone icons that were added, by name.
+    pub added_standalone_icons: BTreeSet<String>,
+    /// Standalone icons that were removed, by name.
+    pub removed_standalone_icons: BTreeSet<String>,
+    /// Set if the user switched to another icon theme.
+    pub theme_switch: Option<ThemeSwitch>,
+}
+
+/// The user switching from one icon theme to another, as detected by
+/// [`Icons::current_theme`].
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ThemeSwitch {
+    pub from: Option<String>,
+    pub to: Option<String>,
+}
+
+impl ScanDiff {
+    /// Compares the themes and standalone icons of two [`Icons`].
+    ///
+    /// As this doesn't know about the user's settings, [`theme_switch`](Self::theme_switch) is
+    /// always `None`.
+    pub fn between(old: &Icons, new: &Icons) -> Self {
+        let mut diff = ScanDiff::default();
+
+        for (key, theme) in &old.themes {
+            let name = key.to_string_lossy().into_owned();
+            match new.themes.get(key) {
+                None => {
+                    diff.removed_themes.insert(name);
+                }
+                Some(new_theme) if !Arc::ptr_eq(theme, new_theme) && theme != new_theme => {
+                    diff.changed_themes.insert(name);
+                }
+                Some(_) => {}
+            }
+        }
+
+        diff.added_themes = new
+            .themes
+            .keys()
+            .filter(|name| !old.themes.contains_key(*name))
+            .map(|name| name.to_string_lossy().into_owned())
+            .collect();
+
+        diff.added_standalone_icons = new
+            .standalone_icons
+            .keys()
+            .filter(|name| !old.standalone_icons.contains_key(*name))
+            .cloned()
+            .collect();
+        diff.removed_standalone_icons = old
+            .standalone_icons
+            .keys()
+            .filter(|name| !new.standalone_icons.contains_key(*name))
+            .cloned()
+            .collect();
+
+        diff
+    }
+
+    /// Whether nothing changed.
+    pub fn is_empty(&self) -> bool {
+        self.added_themes.is_empty()
+            && self.removed_themes.is_empty()
+            && self.changed_themes.is_empty()
+            && self.added_standalone_icons.is_empty()
+            && self.removed_standalone_icons.is_empty()
+            && self.theme_switch.is_none()
+    }
+}
+
+/// A change notification, as produced by [`Icons::events`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum IconEvent {
+    /// Themes or standalone icons were installed, removed or changed. The
+    /// [`theme_switch`](ScanDiff::theme_switch) of the diff is always `None`.
+    Changed(ScanDiff),
+    /// The user switched to another icon theme.
+    ThemeSwitched(ThemeSwitch),
+}
+
+impl IconEvent {
+    /// Splits a [`ScanDiff`] into events.
+    pub fn from_diff(mut diff: ScanDiff) -> Vec<IconEvent> {
+        let mut events = Vec::new();
+
+        if let Some(switch) = diff.theme_switch.take() {
+            events.push(IconEvent::ThemeSwitched(switch));
+        }
+        if !diff.is_empty() {
+            events.insert(0, IconEvent::Changed(diff));
+        }
+
+        events
+    }
+}
+
+/// A new [generation](Icons::generation), greater than all others handed out so far.
+pub(crate) fn next_generation() -> u64 {
+    static GENERATION: AtomicU64 = AtomicU64::new(1);
+    GENERATION.fetch_add(1, Ordering::Relaxed)
+}
+
+type Callback = Box<dyn FnMut(&ScanDiff) + Send>;
+
+/// The change callbacks of an [`Icons`], shared with its clones.
+#[derive(Clone, Default)]
+pub(crate) struct Listeners(Arc<Mutex<ListenerState>>);
+
+#[derive(Default)]
+struct ListenerState {
+    /// The callbacks by the ID they were registered with.
+    callbacks: Vec<(u64, Callback)>,
+    next_id: u64,
+    /// The callbacks removed while a refresh was calling them.
+    removed: Vec<u64>,
+    /// The user's icon theme as of the last refresh, once anyone is interested in it.
+    current_theme: Option<Option<String>>,
+}
+
+impl Listeners {
+    fn state(&self) -> std::sync::MutexGuard<'_, ListenerState> {
+        lock(&self.0)
+    }
+}
+
+fn lock(state: &Mutex<ListenerState>) -> std::sync::MutexGuard<'_, ListenerState> {
+    // a panicking callback doesn't leave the state inconsistent
+    state.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+impl std::fmt::Debug for Listeners {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Listeners({})", self.state().callbacks.len())
+    }
+}
+
+impl Icons {
+    /// Registers a callback to be called with the changes whenever [`refresh`](Self::refresh)
+    /// finds any.
+    ///
+    /// Callbacks are shared with clones of this `Icons`.
+    pub fn on_change<F>(&self, callback: F)
+    where
+        F: FnMut(&ScanDiff) + Send + 'static,
+    {
+        self.add_listener(Box::new(callback));
+    }
+
+    // like `on_change`, returning the ID of the callback.
+    fn add_listener(&self, callback: Callback) -> u64 {
+        let mut state = self.listeners.state();
+        // audited icons don't consult the user's settings
+        if self.audit.is_none() {
+            (state.current_theme).get_or_insert_with(|| self.current_theme());
+        }
+        let id = state.next_id;
+        state.next_id += 1;
+        state.callbacks.push((id, callback));
+        id
+    }
+
+    /// A counter that changes whenever the icons that would be found may have changed: when
+    /// [`refresh`](Self::refresh) (and thereby watching the directories) finds changes, when a theme
+    /// is [rescanned](Self::rescan_theme), or when a [memory theme](crate::memory) is added.
+    ///
+    /// Caches of looked up icons, e.g. of textures, can store the generation along with each
+    /// entry (see [`IconMatch::generation`](crate::IconMatch::generation)) and discard entries of
+    /// older generations, rather than comparing paths or modification times.
+    ///
+    /// Generations are drawn from a counter shared by the whole process, so they only grow, and
+    /// two `Icons` that weren't cloned from each other never have the same generation. A cache
+    /// shared by several `Icons` can thereby tell their entries apart too. Generations are never
+    /// 0.
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    /// Searches the [base directories](Self::base_dirs) again, replacing the themes and standalone
+    /// icons with the ones found, and returns what changed.
+    ///
+    /// If anything changed, the callbacks registered with [`on_change`](Self::on_change) are
+    /// called. Once a callback is registered, this also checks whether the user switched themes,
+    /// unless the icons are [audited](IconSearch::with_audit).
+    pub fn refresh(&mut self) -> ScanDiff {
+        self.try_refresh(&CancellationToken::new())
+            .expect("the token is never cancelled")
+    }
+
+    /// Like [`refresh`](Self::refresh), but aborting once `token` is
+    /// [cancelled](CancellationToken::cancel). A cancelled refresh leaves this `Icons` unchanged.
+    pub fn try_refresh(&mut self, token: &CancellationToken) -> Result<ScanDiff, Cancelled> {
+        let mut search = IconSearch::new_from(self.base_dirs.clone())
+            .with_precedence(self.precedence)
+            .with_directory_listing(self.list_directories)
+            .with_adaptive_ordering(self.adaptive_ordering)
+            .with_scale_suffixes(self.scale_suffixes)
+            .with_inferred_indexes(self.infer_indexes)
+            .with_hicolor_bootstrap(self.bootstrap_hicolor)
+            .with_compliance(self.compliance);
+        search.file_types = self.file_types.clone();
+        search.audit = self.audit.clone();
+        search.resolve_beneath = self.resolve_beneath;
+        search.environment = self.environment.clone();
+        let mut new = search.try_search(token)?.try_icons(token)?;
+        for theme in &self.memory_themes {
+            new.insert_memory_theme(theme.clone());
+        }
+
+        let mut diff = ScanDiff::between(self, &new);
+        self.themes = new.themes;
+        self.memory_themes = new.memory_themes;
+        self.standalone_icons = new.standalone_icons;
+        self.report = new.report;
+        self.current_theme = new.current_theme;
+
+        // take the callbacks out, so that they may register more
+        let mut callbacks = {
+            let mut state = self.listeners.state();
+
+            if let Some(current_theme) = &mut state.current_theme {
+                let theme = self.current_theme();
+                if *current_theme != theme {
+                    diff.theme_switch = Some(ThemeSwitch {
+                        from: std::mem::replace(current_theme, theme.clone()),
+                        to: theme,
+                    });
+                }
+            }
+
+            std::mem::take(&mut state.callbacks)
+        };
+
+        if !diff.is_empty() {
+            self.generation = next_generation();
+            for (_, callback) in &mut callbacks {
+                callback(&diff);
+            }
+        }
+
+        let mut state = self.listeners.state();
+        let removed = std::mem::take(&mut state.removed);
+        callbacks.retain(|(id, _)| !removed.contains(id));
+        callbacks.append(&mut state.callbacks);
+        state.callbacks = callbacks;
+
+        Ok(diff)
+    }
+}
+
+#[cfg(feature = "stream")]
+pub use stream::IconEvents;
+
+#[cfg(feature = "stream")]
+mod stream {
+    use crate::change::{IconEvent, ListenerState, lock};
+    use crate::theme::Icons;
+    use std::collections::VecDeque;
+    use std::pin::Pin;
+    use std::sync::{Arc, Mutex, Weak};
+    use std::task::{Context, Poll, Waker};
+
+    /// A [`Stream`](futures_core::Stream) of the changes found by [`Icons::refresh`], as returned
+    /// by [`Icons::events`].
+    ///
+    /// The stream ends once the `Icons` it was created from, and all of its clones, are dropped.
+    /// Dropping the stream unregisters it from them.
+    #[derive(Debug)]
+    pub struct IconEvents {
+        queue: Arc<Mutex<Queue>>,
+        _listener: ListenerHandle,
+    }
+
+    #[derive(Debug, Default)]
+    struct Queue {
+        events: VecDeque<IconEvent>,
+        waker: Option<Waker>,
+        /// Set once no more events can arrive.
+        closed: bool,
+    }
+
+    /// Owned by the callback, closing the queue when the callback is dropped with the `Icons`.
+    struct Sender(Arc<Mutex<Queue>>);
+
+    impl Drop for Sender {
+        fn drop(&mut self) {
+            let mut queue = self.0.lock().unwrap_or_else(|e| e.into_inner());
+            queue.closed = true;
+            if let Some(waker) = queue.waker.take() {
+                waker.wake();
+            }
+        }
+    }
+
+    /// Unregisters a callback when dropped, without keeping the callbacks alive.
+    struct ListenerHandle {
+        listeners: Weak<Mutex<ListenerState>>,
+        id: u64,
+    }
+
+    impl Drop for ListenerHandle {
+        fn drop(&mut self) {
+            let Some(listeners) = self.listeners.upgrade() else {
+                return;
+            };
+            let mut state = lock(&listeners);
+            match state.callbacks.iter().position(|(id, _)| *id == self.id) {
+                Some(position) => drop(state.callbacks.remove(position)),
+                // taken out by a refresh that is calling it
+                None => state.removed.push(self.id),
+            }
+        }
+    }
+
+    impl std::fmt::Debug for ListenerHandle {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            write!(f, "ListenerHandle({})", self.id)
+        }
+    }
+
+    impl Icons {
+        /// Returns a stream of the changes found by [`refresh`](Self::refresh) from now on.
+        ///
+        /// Combined with an [`IconWatcher`](crate::watch::IconWatcher), this notifies about
+        /// changes as they happen on disk.
+        pub fn events(&self) -> IconEvents {
+            let queue = Arc::new(Mutex::new(Queue::default()));
+            let sender = Sender(queue.clone());
+
+            let id = self.add_listener(Box::new(move |diff| {
+                let mut queue = sender.0.lock().unwrap_or_else(|e| e.into_inner());
+                queue.events.extend(IconEvent::from_diff(diff.clone()));
+                if let Some(waker) = queue.waker.take() {
+                    waker.wake();
+                }
+            }));
+
+            IconEvents {
+                queue,
+                _listener: ListenerHandle {
+                    listeners: Arc::downgrade(&self.listeners.0),
+                    id,
+                },
+            }
+        }
+    }
+
+    impl futures_core::Stream for IconEvents {
+        type Item = IconEvent;
+
+        fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+            let mut queue = self.queue.lock().unwrap_or_else(|e| e.into_inner());
+
+            if let Some(event) = queue.events.pop_front() {
+                Poll::Ready(Some(event))
+            } else if queue.closed {
+                Poll::Ready(None)
+            } else {
+                queue.waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::IconSearch;
+    use crate::cancel::{CancellationToken, Cancelled};
+    use std::fs;
+    use std::sync::{Arc, Mutex};
+
+    #[test]
+    fn test_refresh() {
+        let dir = tempfile::tempdir().unwrap();
+        let write_theme = |name: &str, comment: &str| {
+            fs::create_dir_all(dir.path().join(name)).unwrap();
+            fs::write(
+                dir.path().join(name).join("index.theme"),
+                format!("[Icon Theme]\nName={name}\nComment={comment}\nDirectories=\n"),
+            )
+            .unwrap();
+        };
+
+        write_theme("hicolor", "Fallback");
+        write_theme("Birch", "Wooden");
+
+        let mut icons = IconSearch::new_from(vec![dir.path().into()])
+            .search()
+            .icons();
+
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let seen_by_callback = seen.clone();
+        icons.on_change(move |diff| seen_by_callback.lock().unwrap().push(diff.clone()));
+        let generation = icons.generation();
+
+        // nothing changed: no callback
+        assert!(icons.refresh().is_empty());
+        assert!(seen.lock().unwrap().is_empty());
+        assert_eq!(icons.generation(), generation);
+
+        write_theme("Birch", "Still wooden");
+        write_theme("Oak", "Also wooden");
+        fs::remove_dir_all(dir.path().join("hicolor")).unwrap();
+        fs::write(dir.path().join("standalone.png"), b"").unwrap();
+
+        let diff = icons.refresh();
+        assert_eq!(diff.added_themes, ["Oak".to_string()].into());
+        assert_eq!(diff.removed_themes, ["hicolor".to_string()].into());
+        assert_eq!(diff.changed_themes, ["Birch".to_string()].into());
+        assert_eq!(
+            diff.added_standalone_icons,
+            ["standalone".to_string()].into()
+        );
+        assert!(icons.theme("Oak").is_some());
+        assert!(icons.generation() > generation);
+
+        // generations are unique within the process
+        let other = IconSearch::new_from(vec![dir.path().into()])
+            .search()
+            .icons();
+        assert!(other.generation() > icons.generation());
+
+        assert_eq!(*seen.lock().unwrap(), [diff]);
+    }
+
+    #[cfg(feature = "stream")]
+    #[test]
+    fn test_events() {
+        use crate::change::IconEvent;
+        use futures_core::Stream;
+        use std::pin::pin;
+        use std::task::{Context, Poll, Waker};
+
+        let dir = tempfile::tempdir().unwrap();
+        let mut icons = IconSearch::new_from(vec![dir.path().into()])
+            .search()
+            .icons();
+
+        let mut events = pin!(icons.events());
+        let mut cx = Context::from_waker(Waker::noop());
+        assert_eq!(events.as_mut().poll_next(&mut cx), Poll::Pending);
+
+        fs::write(dir.path().join("standalone.png"), b"").unwrap();
+        let diff = icons.refresh();
+        assert_eq!(
+            events.as_mut().poll_next(&mut cx),
+            Poll::Ready(Some(IconEvent::Changed(diff)))
+        );
+        assert_eq!(events.as_mut().poll_next(&mut cx), Poll::Pending);
+
+        // dropped streams are unregistered
+        for _ in 0..3 {
+            drop(icons.events());
+        }
+        assert_eq!(format!("{:?}", icons.listeners), "Listeners(1)");
+
+        drop(icons);
+        assert_eq!(events.as_mut().poll_next(&mut cx), Poll::Ready(None));
+    }
+
+    #[test]
+    fn test_cancelled_refresh() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir_all(dir.path().join("hicolor")).unwrap();
+        fs::write(
+            dir.path().join("hicolor/index.theme"),
+            "[Icon Theme]\nName=Hicolor\nComment=Fallback\nDirectories=\n",
+        )
+        .unwrap();
+
+        let token = CancellationToken::new();
+        token.cancel();
+        let search = || IconSearch::new_from(vec![dir.path().into()]);
+        assert_eq!(search().try_search(&token).err(), Some(Cancelled));
+        assert_eq!(search().search().try_icons(&token).err(), Some(Cancelled));
+
+        let mut icons = search().search().icons();
+        fs::remove_dir_all(dir.path().join("hicolor")).unwrap();
+        assert_eq!(icons.try_refresh(&token), Err(Cancelled));
+        assert!(icons.theme("hicolor").is_some());
+    }
+}
@@ -0,0 +1,123 @@
+//! Configuration files for system-wide and per-user defaults.
+//!
+//! With the `config` feature enabled, [`IconSearch::new`](crate::IconSearch::new) reads
+//! `icon-rs.toml` from the XDG configuration directories, which allows administrators and users to
+//! redirect icon search without patching every application. The system-wide files (usually
+//! `/etc/xdg/icon-rs.toml`) are read first, followed by the user's file
+//! (`$XDG_CONFIG_HOME/icon-rs.toml`).
+//!
+//! The files contain [`SearchOptions`]:
+//!
+//! ```toml
+//! # directories to search in addition to the default ones
+//! add_directories = ["/srv/icons"]
+//! # default directories not to search
+//! remove_directories = ["/usr/share/pixmaps"]
+//! # the theme to use when a requested theme doesn't exist
+//! fallback_theme = "Adwaita"
+//! ```
+
+use crate::{Environment, SearchOptions};
+use std::path::{Path, PathBuf};
+
+/// The file name of configuration files.
+pub const FILE_NAME: &str = "icon-rs.toml";
+
+#[derive(Debug, thiserror::Error)]
+pub enum ConfigError {
+    #[error("failed to read configuration file {0:?}")]
+    Io(PathBuf, #[source] std::io::Error),
+    #[error("invalid configuration file {0:?}")]
+    Parse(PathBuf, #[source] toml::de::Error),
+}
+
+/// Reads and merges all configuration files of the current environment.
+///
+/// Missing files are not an error.
+pub fn load() -> Result<SearchOptions, ConfigError> {
+    // configuration directories are in order of preference, so the least important comes last
+    let files = Environment::current()
+        .all_config_dirs()
+        .iter()
+        .rev()
+        .map(|dir| dir.join(FILE_NAME))
+        .collect::<Vec<_>>();
+
+    load_from(&files)
+}
+
+/// Reads and merges the given configuration files, with later files taking precedence.
+///
+/// Missing files are not an error.
+pub fn load_from<P: AsRef<Path>>(files: &[P]) -> Result<SearchOptions, ConfigError> {
+    let mut options = SearchOptions::default();
+
+    for path in files {
+        let path = path.as_ref();
+
+        let contents = match std::fs::read_to_string(path) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => continue,
+            Err(e) => return Err(ConfigError::Io(path.into(), e)),
+        };
+
+        let file = parse(&contents).map_err(|e| ConfigError::Parse(path.into(), e))?;
+        options.merge(file);
+    }
+
+    Ok(options)
+}
+
+/// Parses a single configuration file.
+pub fn parse(contents: &str) -> Result<SearchOptions, toml::de::Error> {
+    toml::from_str(contents)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::IconSearch;
+    use crate::config::parse;
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_merge_and_apply() {
+        let mut options = parse(
+            "add_directories = [\"/srv/icons\", \"/opt/icons\"]\nfallback_theme = \"Adwaita\"\n",
+        )
+        .unwrap();
+        let user = parse(
+            "remove_directories = [\"/usr/share/pixmaps\", \"/opt/icons\"]\nfallback_theme = \"breeze\"\n",
+        )
+        .unwrap();
+        options.merge(user);
+
+        let search = options.apply(IconSearch::new_from(vec![
+            "/usr/share/icons".into(),
+            "/usr/share/pixmaps".into(),
+        ]));
+
+        assert_eq!(
+            search.dirs,
+            [PathBuf::from("/usr/share/icons"), "/srv/icons".into()]
+        );
+        assert_eq!(search.fallback_theme.as_deref(), Some("breeze"));
+
+        assert!(parse("unknown_key = 1").is_err());
+    }
+
+    #[test]
+    fn test_embedded_section() {
+        #[derive(serde::Deserialize)]
+        struct AppConfig {
+            icons: crate::SearchOptions,
+        }
+
+        let config: AppConfig =
+            toml::from_str("[icons]\ndirectories = [\"/usr/share/icons\"]\nsysroot = \"/mnt\"\n")
+                .unwrap();
+
+        let search = config.icons.apply(IconSearch::new_empty());
+        assert_eq!(search.dirs, [PathBuf::from("/usr/share/icons")]);
+        assert_eq!(search.sysroot, Some("/mnt".into()));
+    }
+}
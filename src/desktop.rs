@@ -0,0 +1,642 @@
+//! Working with the icons of desktop entries (`.desktop` files).
+//!
+//! Desktop entries name their icon in the `Icon` key, either as an icon name to look up in the
+//! current icon theme or as an absolute path.
+
+use crate::audit::{AccessKind, allows};
+use crate::environment::Environment;
+use crate::icon::IconFile;
+use crate::theme::Icons;
+use crate::{IconMatch, MatchKind};
+use freedesktop_entry_parser::Entry;
+use std::collections::HashSet;
+use std::os::unix::fs::MetadataExt;
+use std::path::{Path, PathBuf};
+
+/// The directories desktop entries are installed in, in order of precedence.
+///
+/// That is `$XDG_DATA_HOME/applications` followed by `$XDG_DATA_DIRS/applications`.
+///
+/// See [`Environment::application_dirs`] to avoid reading the environment of the current process.
+pub fn application_dirs() -> Vec<PathBuf> {
+    Environment::current().application_dirs()
+}
+
+/// Finds the desktop entries in the given directories, returning their desktop file ID and path.
+///
+/// Directories are searched recursively, following symbolic links unless they lead back into a
+/// directory being searched. When an ID appears more than once, the first occurrence wins, as it
+/// does for desktop environments.
+pub fn desktop_entries<I, P>(dirs: I) -> Vec<(String, PathBuf)>
+where
+    I: IntoIterator<Item = P>,
+    P: AsRef<Path>,
+{
+    fn walk(
+        dir: &Path,
+        prefix: &str,
+        ancestors: &mut HashSet<(u64, u64)>,
+        seen: &mut HashSet<String>,
+        out: &mut Vec<(String, PathBuf)>,
+    ) {
+        let Ok(entries) = dir.read_dir() else {
+            return;
+        };
+
+        // symbolic links to directories are followed, but not back into a directory being walked
+        let Ok(metadata) = dir.metadata() else {
+            return;
+        };
+        let dir_id = (metadata.dev(), metadata.ino());
+        if !ancestors.insert(dir_id) {
+            return;
+        }
+
+        let mut entries = entries.flatten().map(|e| e.path()).collect::<Vec<_>>();
+        entries.sort();
+
+        for path in entries {
+            let Some(file_name) = path.file_name().and_then(|n| n.to_str()) else {
+                continue;
+            };
+
+            if path.is_dir() {
+                // "the desktop file ID is the path of the desktop file relative to the
+                //  $XDG_DATA_DIRS component, with / replaced by -"
+                let prefix = format!("{prefix}{file_name}-");
+                walk(&path, &prefix, ancestors, seen, out);
+            } else if file_name.ends_with(".desktop") {
+                let id = format!("{prefix}{file_name}");
+                if seen.insert(id.clone()) {
+                    out.push((id, path));
+                }
+            }
+        }
+
+        ancestors.remove(&dir_id);
+    }
+
+    let mut ancestors = HashSet::new();
+    let mut seen = HashSet::new();
+    let mut entries = Vec::new();
+
+    for dir in dirs {
+        walk(dir.as_ref(), "", &mut ancestors, &mut seen, &mut entries);
+    }
+
+    entries
+}
+
+/// The result of [`Icons::audit_desktop_entries`].
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct DesktopAudit {
+    /// The amount of desktop entries that specify an icon.
+    pub checked: usize,
+    /// Desktop entries whose icon could not be found.
+    pub missing: Vec<MissingIcon>,
+    /// Desktop entries that could not be read or parsed.
+    pub errors: Vec<EntryError>,
+    /// The themes audited that aren't installed, in the order given. No icons are looked up in
+    /// them.
+    pub unknown_themes: Vec<String>,
+}
+
+impl DesktopAudit {
+    /// Whether every theme audited is installed, and every desktop entry that was checked has a
+    /// resolvable icon.
+    pub fn is_ok(&self) -> bool {
+        self.missing.is_empty() && self.errors.is_empty() && self.unknown_themes.is_empty()
+    }
+}
+
+/// A desktop entry whose icon could not be found.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct MissingIcon {
+    pub desktop_id: String,
+    pub path: PathBuf,
+    /// The value of the entry's `Icon` key.
+    pub icon: String,
+}
+
+/// A desktop entry that could not be read or parsed.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct EntryError {
+    pub desktop_id: String,
+    pub path: PathBuf,
+    pub message: String,
+}
+
+/// Where the icon found by [`Icons::icon_for_desktop_file`] came from.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum DesktopIconSource {
+    /// The `Icon` key, naming an icon that was looked up.
+    IconName,
+    /// The `Icon` key, being the absolute path of the icon.
+    IconPath,
+    /// The desktop file ID, e.g. `org.mozilla.firefox` for `org.mozilla.firefox.desktop`, which
+    /// was looked up as the `Icon` key is missing or names an icon that doesn't exist.
+    DesktopId,
+}
+
+/// An icon of a desktop entry, see [`Icons::icon_for_desktop_file`].
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct DesktopIcon {
+    pub icon: IconMatch,
+    pub source: DesktopIconSource,
+}
+
+impl Icons {
+    /// Finds the icon of the desktop entry at `path`, as a window switcher that matched a window
+    /// to its desktop entry would show it.
+    ///
+    /// The `Icon` key is an absolute path or an icon name, which is looked up in `theme`. Names
+    /// with the extension of an icon file, e.g. `firefox.png`, are looked up without it. If the
+    /// key is missing, can't be read or doesn't lead to an icon, the desktop file ID (the file
+    /// name without `.desktop`) is looked up instead, as applications often name their icon
+    /// after it.
+    pub fn icon_for_desktop_file(
+        &self,
+        path: &Path,
+        size: u32,
+        scale: u32,
+        theme: &str,
+    ) -> Option<DesktopIcon> {
+        let entry = Entry::parse_file(path).ok();
+        let icon = (entry.as_ref())
+            .and_then(|entry| entry.section("Desktop Entry").attr("Icon"))
+            .filter(|icon| !icon.is_empty());
+
+        let found = icon.and_then(|icon| {
+            let icon_path = Path::new(icon);
+            if icon_path.is_absolute() {
+                let file = self.desktop_icon_file(icon_path)?;
+                let icon = IconMatch {
+                    base_dir: icon_path.parent()?.to_owned(),
+                    file,
+                    theme: None,
+                    directory: None,
+                    kind: MatchKind::Unsized,
+                    generation: self.generation,
+                };
+                return Some((icon, DesktopIconSource::IconPath));
+            }
+
+            let name = self.desktop_icon_name(icon)?;
+            let icon = self.find_icon_match(name, size, scale, theme)?;
+            Some((icon, DesktopIconSource::IconName))
+        });
+
+        let (icon, source) = found.or_else(|| {
+            let desktop_id = path.file_name()?.to_str()?.strip_suffix(".desktop")?;
+            let icon = self.find_icon_match(desktop_id, size, scale, theme)?;
+            Some((icon, DesktopIconSource::DesktopId))
+        })?;
+        Some(DesktopIcon { icon, source })
+    }
+
+    /// Finds the icon of a window by its window class, as taskbars showing arbitrary X11 or
+    /// Wayland windows do. On Wayland, the app ID is the `class`, and there is no `instance`.
+    ///
+    /// The common heuristics are tried in order:
+    ///
+    /// 1. the [icon](Self::icon_for_desktop_file) of the desktop entry whose `StartupWMClass` is
+    ///    the class or instance,
+    /// 2. the icon of the desktop entry whose ID is the class or instance, ignoring case,
+    /// 3. the class and instance themselves, and their lowercase versions, as icon names.
+    pub fn find_icon_for_wm_class(
+        &self,
+        class: &str,
+        instance: Option<&str>,
+        size: u32,
+        scale: u32,
+        theme: &str,
+    ) -> Option<IconMatch> {
+        self.find_icon_for_wm_class_in(application_dirs(), class, instance, size, scale, theme)
+    }
+
+    /// Like [`find_icon_for_wm_class`](Self::find_icon_for_wm_class), but with the desktop
+    /// entries in the given directories.
+    pub fn find_icon_for_wm_class_in<I, P>(
+        &self,
+        dirs: I,
+        class: &str,
+        instance: Option<&str>,
+        size: u32,
+        scale: u32,
+        theme: &str,
+    ) -> Option<IconMatch>
+    where
+        I: IntoIterator<Item = P>,
+        P: AsRef<Path>,
+    {
+        let names = std::iter::once(class)
+            .chain(instance)
+            .filter(|name| !name.is_empty())
+            .collect::<Vec<_>>();
+        if names.is_empty() {
+            return None;
+        }
+
+        let entries = desktop_entries(dirs)
+            .into_iter()
+            .filter_map(|(desktop_id, path)| {
+                let entry = Entry::parse_file(&path).ok()?;
+                let section = entry.section("Desktop Entry");
+                if section.attr("Hidden") == Some("true") {
+                    return None;
+                }
+                let wm_class = section.attr("StartupWMClass").map(str::to_owned);
+                Some((desktop_id, path, wm_class))
+            })
+            .collect::<Vec<_>>();
+
+        let by_wm_class = (entries.iter())
+            .filter(|(_, _, wm_class)| wm_class.as_deref().is_some_and(|c| names.contains(&c)));
+        let by_id = entries.iter().filter(|(desktop_id, _, _)| {
+            let id = desktop_id.strip_suffix(".desktop").unwrap_or(desktop_id);
+            names.iter().any(|name| name.eq_ignore_ascii_case(id))
+        });
+        let from_entry = by_wm_class.chain(by_id).find_map(|(_, path, _)| {
+            let found = self.icon_for_desktop_file(path, size, scale, theme)?;
+            Some(found.icon)
+        });
+        if from_entry.is_some() {
+            return from_entry;
+        }
+
+        let lowercase = names.iter().map(|name| name.to_lowercase());
+        (names.iter().map(|name| name.to_string()))
+            .chain(lowercase)
+            .find_map(|name| self.find_icon_match(&name, size, scale, theme))
+    }
+
+    /// Checks whether the icons of all installed desktop entries can be found.
+    ///
+    /// Each icon is looked up in the given `themes` in order, each including its parents but
+    /// neither the standalone icons nor [more generic names](crate::naming::fallback_names),
+    /// making this suitable for checking coverage of a set of themes in distribution QA. Themes
+    /// that aren't installed are reported as [unknown](DesktopAudit::unknown_themes) rather than
+    /// falling back to another theme. Entries marked `Hidden` are considered deleted and skipped.
+    ///
+    /// Icons are resolved as by [`icon_for_desktop_file`](Self::icon_for_desktop_file): names
+    /// with the extension of an icon file are looked up without it, and absolute paths must lead
+    /// to an icon file of a [file type](crate::IconSearch::with_file_type) looked for.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// let icons = icon::Icons::new();
+    /// let audit = icons.audit_desktop_entries(&["Adwaita"], 32, 1);
+    ///
+    /// for missing in audit.missing {
+    ///     println!("{}: icon {} not found", missing.desktop_id, missing.icon);
+    /// }
+    /// ```
+    pub fn audit_desktop_entries<S: AsRef<str>>(
+        &self,
+        themes: &[S],
+        size: u32,
+        scale: u32,
+    ) -> DesktopAudit {
+        self.audit_desktop_entries_in(application_dirs(), themes, size, scale)
+    }
+
+    /// Like [`audit_desktop_entries`](Self::audit_desktop_entries), but for the desktop entries
+    /// in the given directories.
+    pub fn audit_desktop_entries_in<I, P, S>(
+        &self,
+        dirs: I,
+        themes: &[S],
+        size: u32,
+        scale: u32,
+    ) -> DesktopAudit
+    where
+        I: IntoIterator<Item = P>,
+        P: AsRef<Path>,
+        S: AsRef<str>,
+    {
+        let mut audit = DesktopAudit::default();
+        let mut known_themes = Vec::new();
+        for theme in themes {
+            match self.theme(theme.as_ref()) {
+                Some(theme) => known_themes.push(theme),
+                None => audit.unknown_themes.push(theme.as_ref().to_owned()),
+            }
+        }
+
+        for (desktop_id, path) in desktop_entries(dirs) {
+            let entry = match Entry::parse_file(&path) {
+                Ok(entry) => entry,
+                Err(e) => {
+                    audit.errors.push(EntryError {
+                        desktop_id,
+                        path,
+                        message: e.to_string(),
+                    });
+                    continue;
+                }
+            };
+
+            let section = entry.section("Desktop Entry");
+            if section.attr("Hidden") == Some("true") {
+                continue;
+            }
+            let Some(icon) = section.attr("Icon").filter(|icon| !icon.is_empty()) else {
+                continue;
+            };
+
+            audit.checked += 1;
+
+            let found = if Path::new(icon).is_absolute() {
+                self.desktop_icon_file(Path::new(icon)).is_some()
+            } else {
+                self.desktop_icon_name(icon).is_some_and(|name| {
+                    (known_themes.iter()).any(|theme| theme.find_icon(name, size, scale).is_some())
+                })
+            };
+
+            if !found {
+                audit.missing.push(MissingIcon {
+                    desktop_id,
+                    path,
+                    icon: icon.into(),
+                });
+            }
+        }
+
+        audit
+    }
+
+    // the icon file the absolute `Icon` key of a desktop entry leads to, if it may be accessed
+    // and is of a file type looked for.
+    fn desktop_icon_file(&self, icon_path: &Path) -> Option<IconFile> {
+        let exists =
+            allows(self.audit.as_ref(), icon_path, AccessKind::Probe) && icon_path.is_file();
+        self.file_types.icon_file(icon_path).filter(|_| exists)
+    }
+
+    // the icon name the `Icon` key of a desktop entry names, without the extension of an icon
+    // file, e.g. `firefox` for `firefox.png`.
+    fn desktop_icon_name<'a>(&self, icon: &'a str) -> Option<&'a str> {
+        let icon_path = Path::new(icon);
+        match self.file_types.from_path(icon_path) {
+            Some(_) => icon_path.file_stem()?.to_str(),
+            None => Some(icon),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::IconSearch;
+    use crate::desktop::{DesktopIconSource, desktop_entries};
+    use std::fs;
+
+    #[test]
+    fn test_desktop_entries() {
+        let dir = tempfile::tempdir().unwrap();
+        let apps = dir.path().join("applications");
+        fs::create_dir_all(apps.join("vendor")).unwrap();
+        fs::create_dir_all(dir.path().join("linked")).unwrap();
+        for file in ["editor.desktop", "vendor/app.desktop", "notes.txt"] {
+            fs::write(apps.join(file), "[Desktop Entry]\n").unwrap();
+        }
+        fs::write(
+            dir.path().join("linked/player.desktop"),
+            "[Desktop Entry]\n",
+        )
+        .unwrap();
+        std::os::unix::fs::symlink(dir.path().join("linked"), apps.join("linked")).unwrap();
+        // links back into a directory being walked are not followed again
+        std::os::unix::fs::symlink("..", apps.join("vendor/loop")).unwrap();
+        std::os::unix::fs::symlink(&apps, apps.join("self")).unwrap();
+
+        let ids = (desktop_entries([&apps]).into_iter())
+            .map(|(id, _)| id)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            ids,
+            [
+                "editor.desktop",
+                "linked-player.desktop",
+                "vendor-app.desktop"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_find_icon_for_wm_class() {
+        let dir = tempfile::tempdir().unwrap();
+        let apps = dir.path().join("applications");
+        let theme = dir.path().join("icons/hicolor");
+        fs::create_dir_all(&apps).unwrap();
+        fs::create_dir_all(theme.join("48x48/apps")).unwrap();
+        fs::write(
+            theme.join("index.theme"),
+            "[Icon Theme]\nName=Hicolor\nDirectories=48x48/apps\n\n[48x48/apps]\nSize=48\n",
+        )
+        .unwrap();
+        for icon in ["code", "firefox", "gimp", "htop"] {
+            fs::write(theme.join(format!("48x48/apps/{icon}.png")), "").unwrap();
+        }
+        for (file, keys) in [
+            (
+                "visual-studio-code.desktop",
+                "Icon=code\nStartupWMClass=Code",
+            ),
+            ("org.mozilla.firefox.desktop", "Icon=firefox"),
+        ] {
+            fs::write(
+                apps.join(file),
+                format!("[Desktop Entry]\nType=Application\nName=Test\n{keys}\n"),
+            )
+            .unwrap();
+        }
+
+        let icons = IconSearch::new_from(vec![dir.path().join("icons")])
+            .search()
+            .icons();
+        let find = |class, instance| {
+            let icon =
+                (icons.find_icon_for_wm_class_in([&apps], class, instance, 48, 1, "hicolor"))?;
+            Some(icon.file.path.file_stem()?.to_str()?.to_owned())
+        };
+
+        assert_eq!(find("Code", Some("code")).as_deref(), Some("code"));
+        assert_eq!(
+            find("org.mozilla.Firefox", None).as_deref(),
+            Some("firefox")
+        );
+        assert_eq!(find("Gimp-2.10", Some("gimp")).as_deref(), Some("gimp"));
+        assert_eq!(find("HTOP", None).as_deref(), Some("htop"));
+        assert_eq!(find("Unknown", None), None);
+        assert_eq!(find("", None), None);
+    }
+
+    #[test]
+    fn test_icon_for_desktop_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let apps = dir.path().join("applications");
+        let theme = dir.path().join("icons/hicolor");
+        fs::create_dir_all(&apps).unwrap();
+        fs::create_dir_all(theme.join("48x48/apps")).unwrap();
+        fs::write(
+            theme.join("index.theme"),
+            "[Icon Theme]\nName=Hicolor\nDirectories=48x48/apps\n\n[48x48/apps]\nSize=48\n",
+        )
+        .unwrap();
+        for icon in ["firefox", "org.gnome.Nautilus"] {
+            fs::write(theme.join(format!("48x48/apps/{icon}.png")), "").unwrap();
+        }
+        let logo = dir.path().join("logo.svg");
+        fs::write(&logo, "").unwrap();
+
+        let icons = IconSearch::new_from(vec![dir.path().join("icons")])
+            .search()
+            .icons();
+        let find = |file: &str, icon: &str| {
+            let path = apps.join(file);
+            let entry = format!("[Desktop Entry]\nType=Application\nName=Test\n{icon}\n");
+            fs::write(&path, entry).unwrap();
+            let found = icons.icon_for_desktop_file(&path, 48, 1, "hicolor")?;
+            Some((found.icon.file.path, found.source))
+        };
+
+        assert_eq!(
+            find("browser.desktop", "Icon=firefox"),
+            Some((
+                theme.join("48x48/apps/firefox.png"),
+                DesktopIconSource::IconName
+            ))
+        );
+        assert_eq!(
+            find("browser.desktop", "Icon=firefox.png"),
+            Some((
+                theme.join("48x48/apps/firefox.png"),
+                DesktopIconSource::IconName
+            ))
+        );
+        assert_eq!(
+            find("logo.desktop", &format!("Icon={}", logo.display())),
+            Some((logo.clone(), DesktopIconSource::IconPath))
+        );
+        for icon in ["", "Icon=missing", "Icon=/missing.png"] {
+            assert_eq!(
+                find("org.gnome.Nautilus.desktop", icon),
+                Some((
+                    theme.join("48x48/apps/org.gnome.Nautilus.png"),
+                    DesktopIconSource::DesktopId
+                ))
+            );
+        }
+        assert_eq!(find("missing.desktop", "Icon=missing"), None);
+    }
+
+    #[test]
+    fn test_audit_desktop_entries() {
+        let dir = tempfile::tempdir().unwrap();
+        let apps = dir.path().join("applications");
+        let theme = dir.path().join("icons/hicolor");
+
+        fs::create_dir_all(apps.join("vendor")).unwrap();
+        fs::create_dir_all(theme.join("32x32/apps")).unwrap();
+        fs::write(
+            theme.join("index.theme"),
+            "[Icon Theme]\nName=Hicolor\nDirectories=32x32/apps\n\n[32x32/apps]\nSize=32\n",
+        )
+        .unwrap();
+        fs::write(theme.join("32x32/apps/present.png"), "").unwrap();
+        fs::write(theme.join("32x32/apps/present-beta.png"), "").unwrap();
+        fs::write(dir.path().join("icons/standalone.png"), "").unwrap();
+
+        for (file, icon) in [
+            ("present.desktop", "present"),
+            ("vendor/missing.desktop", "missing"),
+            // neither standalone icons nor more generic names count
+            ("standalone.desktop", "standalone"),
+            ("generic.desktop", "present-beta-nightly"),
+            ("no-icon.desktop", ""),
+        ] {
+            fs::write(
+                apps.join(file),
+                format!("[Desktop Entry]\nType=Application\nName=Test\nIcon={icon}\n"),
+            )
+            .unwrap();
+        }
+
+        let icons = IconSearch::new_from(vec![dir.path().join("icons")])
+            .with_name_fallback(true)
+            .search()
+            .icons();
+        let audit = icons.audit_desktop_entries_in([&apps], &["hicolor"], 32, 1);
+
+        assert_eq!(audit.checked, 4);
+        assert!(audit.errors.is_empty());
+        let mut missing = (audit.missing.iter())
+            .map(|missing| (&missing.desktop_id[..], &missing.icon[..]))
+            .collect::<Vec<_>>();
+        missing.sort();
+        assert_eq!(
+            missing,
+            [
+                ("generic.desktop", "present-beta-nightly"),
+                ("standalone.desktop", "standalone"),
+                ("vendor-missing.desktop", "missing"),
+            ]
+        );
+        assert!(audit.unknown_themes.is_empty());
+
+        // an unknown theme doesn't fall back to hicolor
+        let audit = icons.audit_desktop_entries_in([&apps], &["Oak", "hicolor"], 32, 1);
+        assert_eq!(audit.unknown_themes, ["Oak"]);
+        assert_eq!(audit.missing.len(), 3);
+        let audit = icons.audit_desktop_entries_in([&apps], &["Oak"], 32, 1);
+        assert_eq!(audit.missing.len(), 4);
+        assert!(!audit.is_ok());
+    }
+
+    #[test]
+    fn test_audit_desktop_entry_icons() {
+        let dir = tempfile::tempdir().unwrap();
+        let apps = dir.path().join("applications");
+        let theme = dir.path().join("icons/hicolor");
+        fs::create_dir_all(&apps).unwrap();
+        fs::create_dir_all(theme.join("32x32/apps")).unwrap();
+        fs::write(
+            theme.join("index.theme"),
+            "[Icon Theme]\nName=Hicolor\nDirectories=32x32/apps\n\n[32x32/apps]\nSize=32\n",
+        )
+        .unwrap();
+        fs::write(theme.join("32x32/apps/firefox.png"), "").unwrap();
+        let logo = dir.path().join("logo.svg");
+        let notes = dir.path().join("notes.txt");
+        fs::write(&logo, "").unwrap();
+        fs::write(&notes, "").unwrap();
+
+        for (file, icon) in [
+            ("name.desktop", "firefox".into()),
+            ("extension.desktop", "firefox.png".into()),
+            ("path.desktop", logo.display().to_string()),
+            // absolute paths must lead to an icon file
+            ("text.desktop", notes.display().to_string()),
+            ("directory.desktop", dir.path().display().to_string()),
+        ] {
+            fs::write(
+                apps.join(file),
+                format!("[Desktop Entry]\nType=Application\nName=Test\nIcon={icon}\n"),
+            )
+            .unwrap();
+        }
+
+        let icons = IconSearch::new_from(vec![dir.path().join("icons")])
+            .search()
+            .icons();
+        let audit = icons.audit_desktop_entries_in([&apps], &["hicolor"], 32, 1);
+
+        assert_eq!(audit.checked, 5);
+        let missing = (audit.missing.iter())
+            .map(|missing| &missing.desktop_id[..])
+            .collect::<Vec<_>>();
+        assert_eq!(missing, ["directory.desktop", "text.desktop"]);
+    }
+}
@@ -0,0 +1,224 @@
+//! Comparing the contents of two themes.
+//!
+//! See [`Theme::diff`].
+
+use crate::theme::{DirectoryIndex, DirectoryType, Theme};
+use std::collections::{BTreeMap, BTreeSet};
+
+/// The differences between two themes, as returned by [`Theme::diff`].
+///
+/// "Left" refers to the theme `diff` was called on, "right" to the theme it was compared with.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct ThemeDiff {
+    /// Icons that exist in the left theme, but not in the right one.
+    pub only_in_left: BTreeSet<String>,
+    /// Icons that exist in the right theme, but not in the left one.
+    pub only_in_right: BTreeSet<String>,
+    /// Icons that exist in both themes, but not in the same sizes, by icon name.
+    pub size_differences: BTreeMap<String, SizeDifference>,
+    /// Icons that exist in a context in one theme, but not in the same context in the other, by
+    /// context name. Icons in directories without a context are listed under an empty name.
+    pub context_gaps: BTreeMap<String, ContextGap>,
+}
+
+/// Sizes an icon is available in in only one of two themes.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct SizeDifference {
+    pub only_in_left: BTreeSet<Rendition>,
+    pub only_in_right: BTreeSet<Rendition>,
+}
+
+/// Icons that are present in a context in only one of two themes.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct ContextGap {
+    pub only_in_left: BTreeSet<String>,
+    pub only_in_right: BTreeSet<String>,
+}
+
+/// The size an icon is available in, as described by the directory it is in.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Rendition {
+    pub size: u32,
+    pub scale: u32,
+    /// Whether the icon is scalable, in which case [`size`](Self::size) is only its nominal size.
+    pub scalable: bool,
+}
+
+impl From<&DirectoryIndex> for Rendition {
+    fn from(dir: &DirectoryIndex) -> Self {
+        Self {
+            size: dir.size,
+            scale: dir.scale,
+            scalable: dir.directory_type == DirectoryType::Scalable,
+        }
+    }
+}
+
+impl ThemeDiff {
+    /// Whether the two themes compared had the same icons, sizes and contexts.
+    pub fn is_empty(&self) -> bool {
+        self.only_in_left.is_empty()
+            && self.only_in_right.is_empty()
+            && self.size_differences.is_empty()
+            && self.context_gaps.is_empty()
+    }
+}
+
+/// What we know about the icons in a theme, for diffing purposes.
+#[derive(Default)]
+struct Coverage {
+    renditions: BTreeMap<String, BTreeSet<Rendition>>,
+    contexts: BTreeMap<String, BTreeSet<String>>,
+}
+
+impl Coverage {
+    fn of(theme: &Theme) -> Self {
+        let mut coverage = Coverage::default();
+
+        for (sub_dir, file) in theme.iter_files() {
+            let Some(name) = file.icon_name() else {
+                continue;
+            };
+
+            coverage
+                .renditions
+                .entry(name.into())
+                .or_default()
+                .insert(sub_dir.into());
+            coverage
+                .contexts
+                .entry(sub_dir.context.clone().unwrap_or_default())
+                .or_default()
+                .insert(name.into());
+        }
+
+        coverage
+    }
+}
+
+fn split<T: Ord + Clone>(left: &BTreeSet<T>, right: &BTreeSet<T>) -> (BTreeSet<T>, BTreeSet<T>) {
+    (
+        left.difference(right).cloned().collect(),
+        right.difference(left).cloned().collect(),
+    )
+}
+
+impl Theme {
+    /// Compares the icons of this theme with those of another theme.
+    ///
+    /// Only the icons of the themes themselves are compared, not those of the themes they inherit
+    /// from. This reads both themes' directories from disk.
+    pub fn diff(&self, other: &Theme) -> ThemeDiff {
+        let left = Coverage::of(self);
+        let right = Coverage::of(other);
+
+        let left_names = left.renditions.keys().cloned().collect();
+        let right_names = right.renditions.keys().cloned().collect();
+        let (only_in_left, only_in_right) = split(&left_names, &right_names);
+
+        let size_differences = left
+            .renditions
+            .iter()
+            .filter_map(|(name, left)| {
+                let right = right.renditions.get(name)?;
+                let (only_in_left, only_in_right) = split(left, right);
+
+                let difference = SizeDifference {
+                    only_in_left,
+                    only_in_right,
+                };
+                (difference != SizeDifference::default()).then(|| (name.clone(), difference))
+            })
+            .collect();
+
+        let empty = BTreeSet::new();
+        let contexts: BTreeSet<_> = left.contexts.keys().chain(right.contexts.keys()).collect();
+        let context_gaps = contexts
+            .into_iter()
+            .filter_map(|context| {
+                let in_left = left.contexts.get(context).unwrap_or(&empty);
+                let in_right = right.contexts.get(context).unwrap_or(&empty);
+                let (only_in_left, only_in_right) = split(in_left, in_right);
+
+                let gap = ContextGap {
+                    only_in_left,
+                    only_in_right,
+                };
+                (gap != ContextGap::default()).then(|| (context.clone(), gap))
+            })
+            .collect();
+
+        ThemeDiff {
+            only_in_left,
+            only_in_right,
+            size_differences,
+            context_gaps,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::IconSearch;
+    use crate::diff::Rendition;
+    use std::fs;
+
+    #[test]
+    fn test_diff_themes() {
+        let dir = tempfile::tempdir().unwrap();
+
+        let index = "[Icon Theme]\nName=Test\nDirectories=16x16/apps,scalable/places\n\n\
+                     [16x16/apps]\nSize=16\nType=Fixed\nContext=Applications\n\n\
+                     [scalable/places]\nSize=16\nType=Scalable\nContext=Places\n";
+        for (theme, files) in [
+            (
+                "left",
+                &["16x16/apps/firefox.png", "16x16/apps/htop.png"][..],
+            ),
+            (
+                "right",
+                &["16x16/apps/firefox.png", "scalable/places/htop.svg"][..],
+            ),
+        ] {
+            for file in files.iter().chain(&["scalable/places/folder.svg"]) {
+                let path = dir.path().join(theme).join(file);
+                fs::create_dir_all(path.parent().unwrap()).unwrap();
+                fs::write(path, "").unwrap();
+            }
+            fs::write(dir.path().join(theme).join("index.theme"), index).unwrap();
+        }
+
+        let icons = IconSearch::new_from(vec![dir.path().into()])
+            .search()
+            .icons();
+        let left = icons.theme("left").unwrap();
+        let right = icons.theme("right").unwrap();
+
+        assert!(left.diff(&left).is_empty());
+
+        let diff = left.diff(&right);
+        assert!(diff.only_in_left.is_empty());
+        assert!(diff.only_in_right.is_empty());
+
+        let htop = &diff.size_differences["htop"];
+        let fixed = Rendition {
+            size: 16,
+            scale: 1,
+            scalable: false,
+        };
+        let scalable = Rendition {
+            scalable: true,
+            ..fixed
+        };
+        assert_eq!(htop.only_in_left.iter().collect::<Vec<_>>(), [&fixed]);
+        assert_eq!(htop.only_in_right.iter().collect::<Vec<_>>(), [&scalable]);
+
+        assert!(
+            diff.context_gaps["Applications"]
+                .only_in_left
+                .contains("htop")
+        );
+        assert!(diff.context_gaps["Places"].only_in_right.contains("htop"));
+        assert!(!diff.context_gaps.contains_key(""));
+    }
+}
@@ -0,0 +1,183 @@
+//! Themes installed more than once.
+//!
+//! A theme can be installed in several search directories, e.g. as a copy in `~/.icons` that a
+//! user extracted years ago and as a package in `/usr/share/icons`. Their icons are merged, but
+//! only one of their `index.theme` files describes the theme: with the default
+//! [`Precedence`], the copy in the earliest search directory wins, so a stale copy in the user's
+//! directories shadows a newer system theme. [`Icons::duplicate_themes`] finds such themes and
+//! tells which copy wins.
+//!
+//! Directories without an `index.theme`, such as the `hicolor` directory applications install
+//! their icons into, only add icons to a theme and aren't copies of it.
+//!
+//! # Example
+//!
+//! ```
+//! use icon::Icons;
+//!
+//! for duplicate in Icons::new().duplicate_themes() {
+//!     if duplicate.winner_is_older() {
+//!         println!("{:?} shadows a newer copy of {}", duplicate.copies[0].dir, duplicate.internal_name);
+//!     }
+//! }
+//! ```
+
+use crate::Precedence;
+use crate::audit::{AccessKind, allows};
+use crate::theme::{Icons, ThemeIndex, search_order};
+use std::path::PathBuf;
+use std::time::SystemTime;
+
+/// A theme with an `index.theme` in more than one search directory.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DuplicateTheme {
+    pub internal_name: String,
+    /// The copies of the theme, in the order they are consulted in. The first one wins: its index
+    /// describes the theme.
+    pub copies: Vec<ThemeCopy>,
+    /// Why the first copy wins.
+    pub reason: WinReason,
+}
+
+/// One copy of a [`DuplicateTheme`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ThemeCopy {
+    /// The theme directory, e.g. `~/.icons/Papirus`.
+    pub dir: PathBuf,
+    /// The position of the search directory the copy is in, see [`Icons::base_dirs`].
+    pub search_position: usize,
+    /// The copy's index, or `None` if it doesn't parse.
+    pub index: Option<ThemeIndex>,
+    /// When the copy's index was last modified, if known.
+    pub modified: Option<SystemTime>,
+}
+
+/// Why a copy of a [`DuplicateTheme`] wins.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum WinReason {
+    /// It is in the earliest search directory, see [`Precedence::UserFirst`].
+    SearchOrder,
+    /// It is in the latest search directory, see [`Precedence::SystemFirst`].
+    SystemFirst,
+}
+
+impl DuplicateTheme {
+    /// Whether the index of every copy is the same, i.e. the copies are likely the same version.
+    pub fn is_identical(&self) -> bool {
+        let first = self.copies.first().and_then(|copy| copy.index.as_ref());
+        (self.copies.iter()).all(|copy| copy.index.is_some() && copy.index.as_ref() == first)
+    }
+
+    /// Whether the winning copy's index is older than that of a copy it shadows, which suggests
+    /// a stale copy is hiding a newer version of the theme.
+    pub fn winner_is_older(&self) -> bool {
+        let Some(winner) = self.copies.first().and_then(|copy| copy.modified) else {
+            return false;
+        };
+        (self.copies[1..].iter()).any(|copy| copy.modified.is_some_and(|other| other > winner))
+    }
+}
+
+impl Icons {
+    /// All themes installed in more than one search directory, in the order of
+    /// [`iter_themes`](Self::iter_themes), see [`duplicates`](crate::duplicates).
+    ///
+    /// This reads the index of every copy, not just the winning one.
+    pub fn duplicate_themes(&self) -> Vec<DuplicateTheme> {
+        let reason = match self.precedence {
+            Precedence::UserFirst => WinReason::SearchOrder,
+            Precedence::SystemFirst => WinReason::SystemFirst,
+        };
+
+        let mut duplicates = Vec::new();
+        for (_, theme) in self.iter_themes() {
+            // the directories are in the order they are consulted in, so the first copy wins
+            let copies = (theme.info.base_dirs.iter())
+                .map(|dir| (dir, dir.join("index.theme")))
+                .filter(|(_, index)| {
+                    allows(self.audit.as_ref(), index, AccessKind::Probe) && index.is_file()
+                })
+                .map(|(dir, index)| {
+                    let readable = allows(self.audit.as_ref(), &index, AccessKind::ReadFile);
+                    ThemeCopy {
+                        dir: dir.clone(),
+                        search_position: search_order(&self.base_dirs, dir),
+                        index: readable
+                            .then(|| ThemeIndex::parse_from_file(&index).ok())
+                            .flatten(),
+                        modified: index.metadata().and_then(|meta| meta.modified()).ok(),
+                    }
+                })
+                .collect::<Vec<_>>();
+
+            if copies.len() > 1 {
+                duplicates.push(DuplicateTheme {
+                    internal_name: theme.info.internal_name.clone(),
+                    copies,
+                    reason,
+                });
+            }
+        }
+        duplicates
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::duplicates::WinReason;
+    use crate::fixture::ThemeFixture;
+    use crate::{IconSearch, Precedence};
+    use std::fs;
+    use std::path::Path;
+    use std::time::{Duration, SystemTime};
+
+    fn set_modified(dir: &Path, time: SystemTime) {
+        let index = fs::File::options()
+            .write(true)
+            .open(dir.join("index.theme"))
+            .unwrap();
+        index.set_modified(time).unwrap();
+    }
+
+    #[test]
+    fn test_duplicate_themes() {
+        let dir = tempfile::tempdir().unwrap();
+        let (user, system) = (dir.path().join("user"), dir.path().join("system"));
+        let oak = |comment| {
+            ThemeFixture::new("Oak")
+                .with_comment(comment)
+                .with_directory("48", 48)
+        };
+        oak("1.0").write(&user.join("Oak")).unwrap();
+        oak("2.0").write(&system.join("Oak")).unwrap();
+        oak("").write(&system.join("Birch")).unwrap();
+        // icons added to a theme without an index aren't a copy of it
+        fs::create_dir_all(user.join("Birch/48")).unwrap();
+
+        let now = SystemTime::now();
+        set_modified(&user.join("Oak"), now - Duration::from_secs(3600));
+        set_modified(&system.join("Oak"), now);
+
+        let search = IconSearch::new_from(vec![user.clone(), system.clone()]);
+        let duplicates = search.clone().search().icons().duplicate_themes();
+        assert_eq!(duplicates.len(), 1);
+        let oak = &duplicates[0];
+        assert_eq!(oak.internal_name, "Oak");
+        assert_eq!(oak.reason, WinReason::SearchOrder);
+        assert_eq!(oak.copies[0].dir, user.join("Oak"));
+        assert_eq!(oak.copies[0].search_position, 0);
+        assert_eq!(oak.copies[1].dir, system.join("Oak"));
+        assert_eq!(oak.copies[1].index.as_ref().unwrap().comment, "2.0");
+        assert!(oak.winner_is_older());
+        assert!(!oak.is_identical());
+
+        let duplicates = (search.with_precedence(Precedence::SystemFirst))
+            .search()
+            .icons()
+            .duplicate_themes();
+        let oak = &duplicates[0];
+        assert_eq!(oak.reason, WinReason::SystemFirst);
+        assert_eq!(oak.copies[0].dir, system.join("Oak"));
+        assert!(!oak.winner_is_older());
+    }
+}
@@ -0,0 +1,606 @@
+//! Lossless editing of `index.theme` files.
+//!
+//! [`ThemeIndex`] only keeps what lookups need, so writing it back out would throw away comments,
+//! translations, vendor groups and the original key order. [`IndexDocument`] instead keeps every
+//! line of the file around, allowing tools to patch a theme in place with a minimal diff.
+//!
+//! # Example
+//!
+//! ```
+//! use icon::edit::IndexDocument;
+//!
+//! let mut doc = IndexDocument::parse("[Icon Theme]\n# a comment\nName=Birch\nDirectories=\n");
+//! doc.set("Icon Theme", "Comment", "Wooden icons");
+//!
+//! assert_eq!(
+//!     doc.to_string(),
+//!     "[Icon Theme]\n# a comment\nName=Birch\nDirectories=\nComment=Wooden icons\n"
+//! );
+//! ```
+
+use crate::theme::{DirectoryIndex, DirectoryType, ThemeIndex, ThemeParseError};
+use std::borrow::Cow;
+use std::path::Path;
+use std::{fmt, iter};
+
+/// The name of the main group of an `index.theme` file.
+pub const ICON_THEME_GROUP: &str = "Icon Theme";
+
+/// An `index.theme` file which preserves comments, key order and unknown groups.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IndexDocument {
+    /// Lines before the first group header (usually comments).
+    preamble: Vec<(String, Ending)>,
+    groups: Vec<Group>,
+    /// Whether the source ended in a newline, so that it can be reproduced exactly.
+    trailing_newline: bool,
+    /// The line ending of the source's first line, `"\r\n"` or `"\n"`, used for lines without
+    /// one of their own.
+    line_ending: &'static str,
+}
+
+/// The terminator of a line in the source, `"\r\n"` or `"\n"`. `None` for lines that were added,
+/// and for the last line of a source that doesn't end in a newline.
+type Ending = Option<&'static str>;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Group {
+    name: String,
+    /// The header as it appeared in the source, if unchanged.
+    raw_header: Option<String>,
+    header_ending: Ending,
+    lines: Vec<Line>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Line {
+    /// Comments, blank lines and anything else we don't understand, kept verbatim.
+    Raw(String, Ending),
+    Entry {
+        /// The key, including its locale suffix (`Name[sv]`) if any.
+        key: String,
+        value: String,
+        /// The line as it appeared in the source, if unchanged.
+        raw: Option<String>,
+        /// Kept when the value changes.
+        ending: Ending,
+    },
+}
+
+impl IndexDocument {
+    /// Creates an empty document.
+    pub fn new() -> Self {
+        Self {
+            preamble: Vec::new(),
+            groups: Vec::new(),
+            trailing_newline: true,
+            line_ending: "\n",
+        }
+    }
+
+    /// Parses a document. This never fails: lines that aren't entries or group headers are kept
+    /// as-is and written back unchanged.
+    ///
+    /// Every line is written back with the line ending it had. Lines that are added end like the
+    /// first line of the document, in `\r\n` or `\n`.
+    pub fn parse(input: &str) -> Self {
+        let first_line = input.split_inclusive('\n').next().unwrap_or_default();
+        let mut doc = Self {
+            preamble: Vec::new(),
+            groups: Vec::new(),
+            trailing_newline: input.ends_with('\n') || input.is_empty(),
+            line_ending: match first_line.ends_with("\r\n") {
+                true => "\r\n",
+                false => "\n",
+            },
+        };
+
+        for line in input.split_inclusive('\n') {
+            let (line, ending) = match line.strip_suffix('\n') {
+                Some(line) => match line.strip_suffix('\r') {
+                    Some(line) => (line, Some("\r\n")),
+                    None => (line, Some("\n")),
+                },
+                None => (line, None),
+            };
+            let trimmed = line.trim();
+
+            if let Some(name) = trimmed
+                .strip_prefix('[')
+                .and_then(|rest| rest.strip_suffix(']'))
+            {
+                doc.groups.push(Group {
+                    name: name.into(),
+                    raw_header: Some(line.into()),
+                    header_ending: ending,
+                    lines: Vec::new(),
+                });
+                continue;
+            }
+
+            match doc.groups.last_mut() {
+                Some(group) => group.lines.push(Line::parse(line, ending)),
+                // lines before any group can't be entries, so we keep them verbatim
+                None => doc.preamble.push((line.into(), ending)),
+            }
+        }
+
+        doc
+    }
+
+    /// Reads and parses a document from a file.
+    pub fn parse_from_file(path: &Path) -> std::io::Result<Self> {
+        let bytes = std::fs::read(path)?;
+        let input = String::from_utf8(bytes).map_err(std::io::Error::other)?;
+
+        Ok(Self::parse(&input))
+    }
+
+    /// Writes the document to a file, replacing it if it exists.
+    pub fn write_to_file(&self, path: &Path) -> std::io::Result<()> {
+        std::fs::write(path, self.to_string())
+    }
+
+    /// Parses this document into a [`ThemeIndex`].
+    pub fn theme_index(&self) -> Result<ThemeIndex, ThemeParseError> {
+        ThemeIndex::parse(self.to_string().as_bytes())
+    }
+
+    /// The names of all groups in this document, in order.
+    pub fn groups(&self) -> impl Iterator<Item = &str> {
+        self.groups.iter().map(|group| group.name.as_str())
+    }
+
+    pub fn has_group(&self, group: &str) -> bool {
+        self.group(group).is_some()
+    }
+
+    /// The keys and values of a group, in order. Locale-specific keys such as `Name[sv]` are
+    /// included verbatim.
+    pub fn entries(&self, group: &str) -> impl Iterator<Item = (&str, &str)> {
+        self.group(group)
+            .into_iter()
+            .flat_map(|group| &group.lines)
+            .filter_map(|line| match line {
+                Line::Entry { key, value, .. } => Some((key.as_str(), value.as_str())),
+                Line::Raw(..) => None,
+            })
+    }
+
+    /// Gets the value of a key in a group.
+    pub fn get(&self, group: &str, key: &str) -> Option<&str> {
+        self.entries(group)
+            .find(|(k, _)| *k == key)
+            .map(|(_, value)| value)
+    }
+
+    /// Sets the value of a key in a group.
+    ///
+    /// An existing key is updated in place. Otherwise, the key is appended after the last entry
+    /// of the group, which is created at the end of the document if it doesn't exist yet.
+    pub fn set(&mut self, group: &str, key: &str, value: &str) {
+        let group = self.group_mut_or_insert(group);
+
+        let existing = group.lines.iter_mut().find_map(|line| match line {
+            Line::Entry {
+                key: k, value, raw, ..
+            } if k == key => Some((value, raw)),
+            _ => None,
+        });
+
+        if let Some((old_value, raw)) = existing {
+            if old_value != value {
+                *old_value = value.into();
+                *raw = None;
+            }
+            return;
+        }
+
+        // insert after the last entry, so trailing blank lines and comments stay at the end
+        let position = group
+            .lines
+            .iter()
+            .rposition(|line| matches!(line, Line::Entry { .. }))
+            .map(|idx| idx + 1)
+            .unwrap_or(0);
+
+        group.lines.insert(
+            position,
+            Line::Entry {
+                key: key.into(),
+                value: value.into(),
+                raw: None,
+                ending: None,
+            },
+        );
+    }
+
+    /// Removes a key from a group, returning its value.
+    pub fn remove(&mut self, group: &str, key: &str) -> Option<String> {
+        let group = self.groups.iter_mut().find(|g| g.name == group)?;
+        let idx = group
+            .lines
+            .iter()
+            .position(|line| matches!(line, Line::Entry { key: k, .. } if k == key))?;
+
+        match group.lines.remove(idx) {
+            Line::Entry { value, .. } => Some(value),
+            Line::Raw(..) => unreachable!(),
+        }
+    }
+
+    /// Removes a group and all its entries. Returns whether the group existed.
+    pub fn remove_group(&mut self, group: &str) -> bool {
+        let len = self.groups.len();
+        self.groups.retain(|g| g.name != group);
+
+        len != self.groups.len()
+    }
+
+    /// Adds a directory to the theme: it is appended to the `Directories` list, or the
+    /// `ScaledDirectories` list if it [belongs there](DirectoryIndex::in_scaled_directories),
+    /// unless it is listed already. A group describing it is added or replaced.
+    pub fn add_directory(&mut self, directory: &DirectoryIndex) {
+        let name = directory.directory_name.as_str();
+        let list_key = match directory.in_scaled_directories {
+            true => "ScaledDirectories",
+            false => "Directories",
+        };
+
+        let listed = ["Directories", "ScaledDirectories"]
+            .into_iter()
+            .any(|list_key| {
+                self.get(ICON_THEME_GROUP, list_key)
+                    .is_some_and(|list| split_list(list).contains(&name))
+            });
+        if !listed {
+            let mut list = self
+                .get(ICON_THEME_GROUP, list_key)
+                .map(split_list)
+                .unwrap_or_default();
+            list.push(name);
+            let list = list.join(",");
+            self.set(ICON_THEME_GROUP, list_key, &list);
+        }
+
+        self.remove_group(name);
+        if self.groups.last().is_some_and(|g| !ends_with_blank(g)) {
+            // keep groups visually separated, like the rest of the file probably does
+            self.groups
+                .last_mut()
+                .unwrap()
+                .lines
+                .push(Line::Raw(String::new(), None));
+        }
+
+        self.set(name, "Size", &directory.size.to_string());
+        if directory.scale != 1 {
+            self.set(name, "Scale", &directory.scale.to_string());
+        }
+        if let Some(context) = &directory.context {
+            self.set(name, "Context", context);
+        }
+        self.set(name, "Type", directory.directory_type.as_str());
+        match directory.directory_type {
+            DirectoryType::Fixed => {}
+            DirectoryType::Scalable => {
+                self.set(name, "MinSize", &directory.min_size.to_string());
+                self.set(name, "MaxSize", &directory.max_size.to_string());
+            }
+            DirectoryType::Threshold => {
+                self.set(name, "Threshold", &directory.threshold.to_string())
+            }
+        }
+    }
+
+    /// Removes a directory from the `Directories` and `ScaledDirectories` lists, along with its
+    /// group. Returns whether the directory was listed.
+    pub fn remove_directory(&mut self, directory_name: &str) -> bool {
+        let mut found = false;
+
+        for list_key in ["Directories", "ScaledDirectories"] {
+            let Some(list) = self.get(ICON_THEME_GROUP, list_key) else {
+                continue;
+            };
+            let mut list = split_list(list);
+            let len = list.len();
+            list.retain(|d| *d != directory_name);
+
+            if list.len() != len {
+                found = true;
+                let list = list.join(",");
+                self.set(ICON_THEME_GROUP, list_key, &list);
+            }
+        }
+
+        self.remove_group(directory_name);
+
+        found
+    }
+
+    fn group(&self, name: &str) -> Option<&Group> {
+        self.groups.iter().find(|group| group.name == name)
+    }
+
+    fn group_mut_or_insert(&mut self, name: &str) -> &mut Group {
+        let idx = match self.groups.iter().position(|group| group.name == name) {
+            Some(idx) => idx,
+            None => {
+                self.groups.push(Group {
+                    name: name.into(),
+                    raw_header: None,
+                    header_ending: None,
+                    lines: Vec::new(),
+                });
+                self.groups.len() - 1
+            }
+        };
+
+        &mut self.groups[idx]
+    }
+}
+
+fn split_list(list: &str) -> Vec<&str> {
+    list.split(',').filter(|s| !s.is_empty()).collect()
+}
+
+fn ends_with_blank(group: &Group) -> bool {
+    matches!(group.lines.last(), Some(Line::Raw(raw, _)) if raw.trim().is_empty())
+}
+
+impl Default for IndexDocument {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Line {
+    fn parse(line: &str, ending: Ending) -> Self {
+        if line.trim_start().starts_with('#') {
+            return Line::Raw(line.into(), ending);
+        }
+
+        match line.split_once('=') {
+            Some((key, value)) => Line::Entry {
+                key: key.trim().into(),
+                value: value.trim_start().into(),
+                raw: Some(line.into()),
+                ending,
+            },
+            None => Line::Raw(line.into(), ending),
+        }
+    }
+
+    fn text(&self) -> (Cow<'_, str>, Ending) {
+        match self {
+            Line::Raw(raw, ending)
+            | Line::Entry {
+                raw: Some(raw),
+                ending,
+                ..
+            } => (Cow::Borrowed(raw), *ending),
+            Line::Entry {
+                key,
+                value,
+                raw: None,
+                ending,
+            } => (Cow::Owned(format!("{key}={value}")), *ending),
+        }
+    }
+}
+
+impl Group {
+    fn header(&self) -> (Cow<'_, str>, Ending) {
+        let header = match &self.raw_header {
+            Some(raw) => Cow::Borrowed(raw.as_str()),
+            None => Cow::Owned(format!("[{}]", self.name)),
+        };
+        (header, self.header_ending)
+    }
+}
+
+impl fmt::Display for IndexDocument {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let preamble = self
+            .preamble
+            .iter()
+            .map(|(line, ending)| (Cow::Borrowed(line.as_str()), *ending));
+        let groups = self
+            .groups
+            .iter()
+            .flat_map(|group| iter::once(group.header()).chain(group.lines.iter().map(Line::text)));
+
+        let mut lines = preamble.chain(groups).peekable();
+        while let Some((line, ending)) = lines.next() {
+            f.write_str(&line)?;
+
+            if lines.peek().is_some() || self.trailing_newline {
+                f.write_str(ending.unwrap_or(self.line_ending))?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl From<&ThemeIndex> for IndexDocument {
+    /// Generates a fresh document from a parsed index.
+    ///
+    /// Translations, unknown keys and vendor groups are taken from the index's
+    /// [`groups`](ThemeIndex::groups). Comments and the order of keys are not retained.
+    fn from(index: &ThemeIndex) -> Self {
+        let mut doc = IndexDocument::new();
+
+        doc.set(ICON_THEME_GROUP, "Name", &index.name);
+        doc.set(ICON_THEME_GROUP, "Comment", &index.comment);
+        if !index.inherits.is_empty() {
+            doc.set(ICON_THEME_GROUP, "Inherits", &index.inherits.join(","));
+        }
+        doc.set(ICON_THEME_GROUP, "Directories", "");
+        if index
+            .directories
+            .iter()
+            .any(|dir| dir.in_scaled_directories)
+        {
+            doc.set(ICON_THEME_GROUP, "ScaledDirectories", "");
+        }
+        if index.hidden {
+            doc.set(ICON_THEME_GROUP, "Hidden", "true");
+        }
+        if let Some(example) = &index.example {
+            doc.set(ICON_THEME_GROUP, "Example", example);
+        }
+
+        for directory in &index.directories {
+            doc.add_directory(directory);
+        }
+
+        // the typed keys were written above, from the fields that may have been changed since
+        let typed_keys = [
+            "Name",
+            "Comment",
+            "Inherits",
+            "Directories",
+            "ScaledDirectories",
+            "Hidden",
+            "Example",
+        ];
+        for (group, entries) in &index.groups {
+            for (key, value) in entries {
+                if group != ICON_THEME_GROUP || !typed_keys.contains(&key.as_str()) {
+                    doc.set(group, key, value);
+                }
+            }
+        }
+
+        doc
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::edit::{ICON_THEME_GROUP, IndexDocument};
+    use crate::theme::{DirectoryIndex, DirectoryType};
+
+    static EXAMPLE: &str = include_str!("../resources/example.index.theme");
+
+    #[test]
+    fn test_round_trip_is_lossless() {
+        let doc = IndexDocument::parse(EXAMPLE);
+        assert_eq!(doc.to_string(), EXAMPLE);
+
+        let messy = "# leading comment\n\n[Icon Theme]\nName = Messy \n  # indented\nweird line\n";
+        assert_eq!(IndexDocument::parse(messy).to_string(), messy);
+    }
+
+    #[test]
+    fn test_add_directory_minimal_diff() {
+        let mut doc = IndexDocument::parse(EXAMPLE);
+
+        doc.add_directory(&DirectoryIndex {
+            directory_name: "64x64/apps".into(),
+            is_scaled_dir: false,
+            in_scaled_directories: false,
+            size: 64,
+            scale: 1,
+            context: Some("Applications".into()),
+            directory_type: DirectoryType::Fixed,
+            max_size: 64,
+            min_size: 64,
+            threshold: 2,
+        });
+
+        let output = doc.to_string();
+        let changed = output
+            .lines()
+            .zip(EXAMPLE.lines())
+            .filter(|(a, b)| a != b)
+            .count();
+        // only the `Directories` line changed, the rest was appended
+        assert_eq!(changed, 1);
+        assert!(output.ends_with("\n\n[64x64/apps]\nSize=64\nContext=Applications\nType=Fixed"));
+
+        assert_eq!(doc.get(ICON_THEME_GROUP, "Name[sv]"), Some("Björk"));
+
+        let index = doc.theme_index().unwrap();
+        assert_eq!(index.directories.len(), 8);
+        assert_eq!(index.directories[7].size, 64);
+
+        assert!(doc.remove_directory("64x64/apps"));
+        assert_eq!(doc.theme_index().unwrap().directories.len(), 7);
+    }
+
+    #[test]
+    fn test_add_scaled_directory() {
+        let mut doc = IndexDocument::parse("[Icon Theme]\nName=Birch\nDirectories=16\n");
+        doc.add_directory(&DirectoryIndex {
+            directory_name: "16@2".into(),
+            is_scaled_dir: true,
+            in_scaled_directories: true,
+            size: 16,
+            scale: 2,
+            context: None,
+            directory_type: DirectoryType::Fixed,
+            max_size: 16,
+            min_size: 16,
+            threshold: 2,
+        });
+
+        assert_eq!(doc.get(ICON_THEME_GROUP, "Directories"), Some("16"));
+        assert_eq!(doc.get(ICON_THEME_GROUP, "ScaledDirectories"), Some("16@2"));
+        let index = doc.theme_index().unwrap();
+        assert!(index.directories.iter().any(|dir| dir.is_scaled_dir));
+
+        let regenerated = IndexDocument::from(&index);
+        assert_eq!(regenerated.get(ICON_THEME_GROUP, "Directories"), Some(""));
+        assert_eq!(
+            regenerated.get(ICON_THEME_GROUP, "ScaledDirectories"),
+            Some("16@2")
+        );
+    }
+
+    #[test]
+    fn test_regenerate_keeps_directory_lists() {
+        let input = "[Icon Theme]\nName=Birch\nDirectories=16,16@2\nScaledDirectories=32@2\n\n\
+                     [16]\nSize=16\n\n[16@2]\nSize=16\nScale=2\n\n[32@2]\nSize=32\nScale=2\n";
+        let index = IndexDocument::parse(input).theme_index().unwrap();
+
+        let regenerated = IndexDocument::from(&index);
+        assert_eq!(
+            regenerated.get(ICON_THEME_GROUP, "Directories"),
+            Some("16,16@2")
+        );
+        assert_eq!(
+            regenerated.get(ICON_THEME_GROUP, "ScaledDirectories"),
+            Some("32@2")
+        );
+    }
+
+    #[test]
+    fn test_crlf_line_endings() {
+        let input = "[Icon Theme]\r\nName=Birch\r\nDirectories=\r\n";
+        let mut doc = IndexDocument::parse(input);
+        assert_eq!(doc.to_string(), input);
+        assert_eq!(doc.get(ICON_THEME_GROUP, "Name"), Some("Birch"));
+
+        doc.set(ICON_THEME_GROUP, "Comment", "Wooden icons");
+        assert_eq!(
+            doc.to_string(),
+            "[Icon Theme]\r\nName=Birch\r\nDirectories=\r\nComment=Wooden icons\r\n"
+        );
+    }
+
+    #[test]
+    fn test_mixed_line_endings() {
+        let input = "[Icon Theme]\nName=Birch\r\nDirectories=\n# notes\r\n";
+        let mut doc = IndexDocument::parse(input);
+        assert_eq!(doc.to_string(), input);
+
+        doc.set(ICON_THEME_GROUP, "Name", "Oak");
+        doc.set(ICON_THEME_GROUP, "Comment", "Wooden icons");
+        assert_eq!(
+            doc.to_string(),
+            "[Icon Theme]\nName=Oak\r\nDirectories=\nComment=Wooden icons\n# notes\r\n"
+        );
+    }
+}
@@ -0,0 +1,166 @@
+use std::path::PathBuf;
+
+/// Everything `icon` would otherwise read from the process environment.
+///
+/// Use [`Environment::current`] to read the environment of the current process, or construct one
+/// yourself and pass it to [`IconSearch::from_environment`](crate::IconSearch::from_environment)
+/// for a search that is guaranteed not to read environment variables or other global state.
+/// This is useful for reproducible builds, sandboxed processes and deterministic tests.
+///
+/// # Example
+///
+/// ```
+/// use icon::{Environment, IconSearch};
+///
+/// let env = Environment {
+///     home: Some("/home/test".into()),
+///     data_dirs: vec!["/usr/share".into()],
+///     ..Environment::default()
+/// };
+///
+/// let search = IconSearch::from_environment(&env);
+/// assert_eq!(search.dirs[0], std::path::Path::new("/home/test/.icons"));
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Default)]
+pub struct Environment {
+    /// The user's home directory, `$HOME`.
+    pub home: Option<PathBuf>,
+    /// `$XDG_DATA_HOME`. If not set, `$HOME/.local/share` is used.
+    pub data_home: Option<PathBuf>,
+    /// `$XDG_DATA_DIRS`, in order of preference.
+    pub data_dirs: Vec<PathBuf>,
+    /// `$XDG_CONFIG_HOME`. If not set, `$HOME/.config` is used.
+    pub config_home: Option<PathBuf>,
+    /// `$XDG_CONFIG_DIRS`, in order of preference.
+    pub config_dirs: Vec<PathBuf>,
+    /// `$XDG_CURRENT_DESKTOP`, split into its components (e.g. `["KDE"]` or `["ubuntu", "GNOME"]`).
+    pub current_desktop: Vec<String>,
+    /// The user's locale for messages, in the POSIX format (`lang_COUNTRY.ENCODING@MODIFIER`).
+    pub locale: Option<String>,
+    /// `$DBUS_SESSION_BUS_ADDRESS`, the session bus that
+    /// [user preferences](Self::contrast) are queried on. If not set, the bus isn't consulted.
+    pub session_bus: Option<String>,
+}
+
+impl Environment {
+    /// Reads the environment of the current process.
+    pub fn current() -> Self {
+        let xdg = xdg::BaseDirectories::new();
+
+        // "If the LC_ALL environment variable is set, it overrides [...] LC_MESSAGES, which
+        //  overrides LANG"
+        let locale = ["LC_ALL", "LC_MESSAGES", "LANG"]
+            .into_iter()
+            .filter_map(|var| std::env::var(var).ok())
+            .find(|value| !value.is_empty());
+
+        let current_desktop = std::env::var("XDG_CURRENT_DESKTOP")
+            .unwrap_or_default()
+            .split(':')
+            .filter(|desktop| !desktop.is_empty())
+            .map(Into::into)
+            .collect();
+
+        let session_bus = std::env::var("DBUS_SESSION_BUS_ADDRESS")
+            .ok()
+            .filter(|address| !address.is_empty());
+
+        Self {
+            home: std::env::home_dir(),
+            data_home: xdg.data_home,
+            data_dirs: xdg.data_dirs,
+            config_home: xdg.config_home,
+            config_dirs: xdg.config_dirs,
+            current_desktop,
+            locale,
+            session_bus,
+        }
+    }
+
+    /// `$XDG_DATA_HOME`, falling back to `$HOME/.local/share` if it isn't set.
+    pub fn data_home(&self) -> Option<PathBuf> {
+        self.data_home
+            .clone()
+            .or_else(|| Some(self.home.as_ref()?.join(".local/share")))
+    }
+
+    /// `$XDG_CONFIG_HOME`, falling back to `$HOME/.config` if it isn't set.
+    pub fn config_home(&self) -> Option<PathBuf> {
+        self.config_home
+            .clone()
+            .or_else(|| Some(self.home.as_ref()?.join(".config")))
+    }
+
+    /// All XDG configuration directories (`$XDG_CONFIG_HOME` followed by `$XDG_CONFIG_DIRS`), in
+    /// order of preference.
+    pub fn all_config_dirs(&self) -> Vec<PathBuf> {
+        self.config_home()
+            .into_iter()
+            .chain(self.config_dirs.iter().cloned())
+            .collect()
+    }
+
+    /// Whether the current desktop is the given desktop, as named in `$XDG_CURRENT_DESKTOP`.
+    pub fn is_desktop(&self, desktop: &str) -> bool {
+        self.current_desktop
+            .iter()
+            .any(|d| d.eq_ignore_ascii_case(desktop))
+    }
+
+    /// All XDG data directories (`$XDG_DATA_HOME` followed by `$XDG_DATA_DIRS`), in order of
+    /// preference.
+    pub fn all_data_dirs(&self) -> Vec<PathBuf> {
+        self.data_home()
+            .into_iter()
+            .chain(self.data_dirs.iter().cloned())
+            .collect()
+    }
+
+    /// The directories icons and icon themes are searched in by default.
+    ///
+    /// That is `$HOME/.icons`, `$XDG_DATA_HOME/icons`, `$XDG_DATA_DIRS/icons` and
+    /// `/usr/share/pixmaps`.
+    pub fn icon_dirs(&self) -> Vec<PathBuf> {
+        // "By default, apps should look in $HOME/.icons (for backwards compatibility),
+        // in $XDG_DATA_DIRS/icons
+        // and in /usr/share/pixmaps (in that order)."
+
+        let mut directories = vec![];
+
+        if let Some(home) = &self.home {
+            directories.push(home.join(".icons"));
+        }
+
+        self.all_data_dirs()
+            .into_iter()
+            .map(|data_dir| data_dir.join("icons"))
+            .for_each(|dir| directories.push(dir));
+
+        directories.push("/usr/share/pixmaps".into());
+
+        directories
+    }
+
+    /// The directories desktop entries are installed in, in order of precedence.
+    ///
+    /// That is `$XDG_DATA_HOME/applications` followed by `$XDG_DATA_DIRS/applications`.
+    pub fn application_dirs(&self) -> Vec<PathBuf> {
+        self.all_data_dirs()
+            .into_iter()
+            .map(|data_dir| data_dir.join("applications"))
+            .collect()
+    }
+
+    /// The directories AppStream metadata caches icons in, in order of precedence.
+    ///
+    /// That is `swcatalog/icons` and the older `app-info/icons` in each of the XDG data
+    /// directories, followed by both in `/var/lib` and `/var/cache`.
+    pub fn appstream_icon_dirs(&self) -> Vec<PathBuf> {
+        let catalogs = ["swcatalog/icons", "app-info/icons"];
+        let roots =
+            (self.all_data_dirs().into_iter()).chain(["/var/lib".into(), "/var/cache".into()]);
+        roots
+            .flat_map(|root| catalogs.map(|catalog| root.join(catalog)))
+            .collect()
+    }
+}
@@ -0,0 +1,62 @@
+use crate::Icons;
+use crate::report::ThemeFailure;
+use std::path::PathBuf;
+
+/// Why [`Icons::try_new`] found nothing usable.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum Error {
+    /// The home directory couldn't be determined, so the user's icons can't be found.
+    #[error("the home directory could not be determined")]
+    NoHomeDirectory,
+    /// None of the search directories could be read, usually because none exist.
+    #[error("none of the icon directories {0:?} could be read")]
+    NoIconDirectories(Vec<PathBuf>),
+    /// Theme candidates were found, but all of them failed to parse.
+    #[error("all {} icon themes failed to parse", .0.len())]
+    AllThemesFailed(Vec<ThemeFailure>),
+}
+
+impl Error {
+    /// Checks whether a scan found anything usable.
+    pub(crate) fn check(icons: &Icons) -> Result<(), Self> {
+        let report = &icons.report;
+        if report.base_dirs.iter().all(|dir| dir.error.is_some()) {
+            let dirs = report.base_dirs.iter().map(|dir| dir.path.clone());
+            return Err(Error::NoIconDirectories(dirs.collect()));
+        }
+        if icons.themes.is_empty() && !report.theme_failures.is_empty() {
+            return Err(Error::AllThemesFailed(report.theme_failures.clone()));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{Error, IconSearch};
+
+    #[test]
+    fn test_startup_errors() {
+        let dir = tempfile::tempdir().unwrap();
+        let missing = dir.path().join("missing");
+        let search = |dirs| IconSearch::new_from(dirs).search().icons();
+
+        let icons = search(vec![missing.clone()]);
+        assert_eq!(
+            Error::check(&icons),
+            Err(Error::NoIconDirectories(vec![missing.clone()]))
+        );
+        assert!(Error::check(&search(Vec::new())).is_err());
+
+        // an empty directory is fine, there just aren't any icons yet
+        assert_eq!(Error::check(&search(vec![dir.path().into()])), Ok(()));
+
+        std::fs::create_dir(dir.path().join("Birch")).unwrap();
+        std::fs::write(dir.path().join("Birch/index.theme"), "[Icon Theme]\n").unwrap();
+        let icons = search(vec![dir.path().into(), missing]);
+        assert!(matches!(
+            Error::check(&icons),
+            Err(Error::AllThemesFailed(failures)) if failures[0].name == "Birch"
+        ));
+    }
+}
@@ -0,0 +1,184 @@
+//! Exporting the icons an application needs from a theme.
+//!
+//! Appliance images and Flatpak manifests should bundle exactly the icons an application uses,
+//! rather than a whole theme. [`Theme::export_subset`] copies the best renditions of a list of
+//! icons into a minimal theme, with an `index.theme` describing only the directories it uses.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use icon::Icons;
+//!
+//! let icons = Icons::new();
+//! let theme = icons.theme("Adwaita").unwrap();
+//! let export = theme
+//!     .export_subset(&["document-open", "edit-copy"], "app/share/icons/hicolor".as_ref())
+//!     .unwrap();
+//! assert!(export.missing.is_empty());
+//! ```
+
+use crate::edit::IndexDocument;
+use crate::theme::{DirectoryIndex, Theme, ThemeIndex};
+use std::collections::BTreeMap;
+use std::path::{Component, Path, PathBuf};
+use std::sync::Arc;
+
+/// What [`Theme::export_subset`] exported.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Default)]
+pub struct Export {
+    /// The icon files written, in the order they were exported.
+    pub files: Vec<PathBuf>,
+    /// The names that weren't found in the theme or its parents.
+    pub missing: Vec<String>,
+}
+
+impl Theme {
+    /// Copies the icons named `names` into a new theme at `out_dir`, which is created if needed.
+    ///
+    /// For every size and scale the theme or its parents have icons in, the icon that would be
+    /// looked up for it is copied into the directory it was found in, keeping the layout of the
+    /// theme (e.g. `48x48/apps`). The generated `index.theme` has the theme's name and comment, and
+    /// lists only the directories used. Of directories with the same name in the theme and its
+    /// parents, the first one describes the directory.
+    ///
+    /// Symbolic links are followed, so the export doesn't depend on the original theme. Names that
+    /// aren't plain file names are reported as missing.
+    pub fn export_subset<S: AsRef<str>>(
+        &self,
+        names: &[S],
+        out_dir: &Path,
+    ) -> std::io::Result<Export> {
+        let themes = std::iter::once(self)
+            .chain(self.inherits_from.iter().map(Arc::as_ref))
+            .collect::<Vec<_>>();
+        let mut sizes = (themes.iter())
+            .flat_map(|theme| theme.info.index.directories.iter())
+            .map(|directory| (directory.size, directory.scale))
+            .collect::<Vec<_>>();
+        sizes.sort_unstable();
+        sizes.dedup();
+
+        let mut export = Export::default();
+        let mut directories = Vec::<DirectoryIndex>::new();
+        std::fs::create_dir_all(out_dir)?;
+
+        for name in names {
+            let name = name.as_ref();
+            let mut components = Path::new(name).components();
+            let is_file_name = matches!(components.next(), Some(Component::Normal(_)))
+                && components.next().is_none();
+
+            let mut found = false;
+            for &(size, scale) in sizes.iter().filter(|_| is_file_name) {
+                let Some((directory, file)) = themes.iter().find_map(|theme| {
+                    let (_, directory, file) = theme.locate_icon_here(name, size, scale)?;
+                    Some((&theme.info.index.directories[directory], file))
+                }) else {
+                    continue;
+                };
+                found = true;
+
+                let Some(file_name) = file.path.file_name() else {
+                    continue;
+                };
+                let target = out_dir.join(&directory.directory_name).join(file_name);
+                // sizes without an exact match find the same file as a closer size
+                if export.files.contains(&target) {
+                    continue;
+                }
+
+                std::fs::create_dir_all(out_dir.join(&directory.directory_name))?;
+                std::fs::copy(&file.path, &target)?;
+                export.files.push(target);
+                if !(directories.iter()).any(|d| d.directory_name == directory.directory_name) {
+                    directories.push(directory.clone());
+                }
+            }
+
+            if !found {
+                export.missing.push(name.to_owned());
+            }
+        }
+
+        let index = ThemeIndex {
+            name: self.info.index.name.clone(),
+            comment: self.info.index.comment.clone(),
+            inherits: Box::new([]),
+            directories: directories.into(),
+            hidden: false,
+            example: None,
+            groups: BTreeMap::new(),
+        };
+        IndexDocument::from(&index).write_to_file(&out_dir.join("index.theme"))?;
+
+        Ok(export)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::IconSearch;
+    use crate::theme::ThemeIndex;
+    use std::fs;
+
+    #[test]
+    fn test_export_subset() {
+        let dir = tempfile::tempdir().unwrap();
+        for (name, inherits, files) in [
+            ("hicolor", "", &["16/htop.png", "48/htop.png"][..]),
+            (
+                "Oak",
+                "hicolor",
+                &["48/firefox.svg", "16/firefox.png", "48/gimp.png"],
+            ),
+        ] {
+            let theme = dir.path().join("icons").join(name);
+            for sub_dir in ["16", "48"] {
+                fs::create_dir_all(theme.join(sub_dir)).unwrap();
+            }
+            fs::write(
+                theme.join("index.theme"),
+                format!("[Icon Theme]\nName={name}\nComment=Trees\nInherits={inherits}\nDirectories=48,16\n\n[48]\nSize=48\n\n[16]\nSize=16\n"),
+            )
+            .unwrap();
+            for file in files {
+                fs::write(theme.join(file), file.as_bytes()).unwrap();
+            }
+        }
+
+        let icons = IconSearch::new_from(vec![dir.path().join("icons")])
+            .search()
+            .icons();
+        let out = dir.path().join("out");
+        let export = (icons.theme("Oak").unwrap())
+            .export_subset(&["firefox", "htop", "missing", "../Oak/48/gimp"], &out)
+            .unwrap();
+
+        assert_eq!(
+            export.files,
+            [
+                out.join("16/firefox.png"),
+                out.join("48/firefox.svg"),
+                out.join("16/htop.png"),
+                out.join("48/htop.png"),
+            ]
+        );
+        assert_eq!(export.missing, ["missing", "../Oak/48/gimp"]);
+        assert_eq!(fs::read(out.join("48/htop.png")).unwrap(), b"48/htop.png");
+
+        let index = ThemeIndex::parse_from_file(&out.join("index.theme")).unwrap();
+        assert_eq!(index.name, "Oak");
+        assert!(index.inherits.is_empty());
+        let directories = (index.directories.iter())
+            .map(|directory| (directory.directory_name.as_str(), directory.size))
+            .collect::<Vec<_>>();
+        assert_eq!(directories, [("16", 16), ("48", 48)]);
+
+        // the export is a theme of its own
+        let exported = IconSearch::new_from(vec![dir.path().into()])
+            .search()
+            .icons();
+        let firefox = exported.find_icon("firefox", 48, 1, "out").unwrap();
+        assert_eq!(firefox.path, out.join("48/firefox.svg"));
+    }
+}
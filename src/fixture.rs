@@ -0,0 +1,304 @@
+//! Writes icon themes to the file system for tests and benchmarks.
+//!
+//! [`ThemeFixture`] writes a single theme as a test describes it: its index, its directories and
+//! its icons. [`Fixture`] generates a whole set of themes in a temporary directory, mirroring what
+//! is commonly installed: a `hicolor` theme, a number of themes each inheriting from the previous
+//! one, fixed-size directories for a range of sizes plus a scalable directory, and a few
+//! standalone icons next to the themes.
+//!
+//! In a generated set, `hicolor` contains every icon, the other themes only the first half of
+//! them, so that looking up the second half has to walk the whole inheritance chain.
+//!
+//! The benchmarks include this file as a module of their own, so it doesn't depend on the crate.
+
+use std::io;
+use std::path::{Path, PathBuf};
+use tempfile::TempDir;
+
+/// A theme to write, with an index listing its directories.
+#[derive(Debug, Clone)]
+pub struct ThemeFixture {
+    /// The keys of the `[Icon Theme]` section besides `Directories`, in order.
+    keys: Vec<(String, String)>,
+    /// The directories with the keys of their sections.
+    directories: Vec<(String, String)>,
+    /// The files to write, relative to the theme directory, with their contents.
+    files: Vec<(PathBuf, Vec<u8>)>,
+    /// The symbolic links to create, relative to the theme directory, with their targets.
+    links: Vec<(PathBuf, PathBuf)>,
+}
+
+impl ThemeFixture {
+    /// A theme named `name` with an empty comment and no directories.
+    pub fn new(name: &str) -> Self {
+        Self {
+            keys: vec![("Name".into(), name.into()), ("Comment".into(), "".into())],
+            directories: Vec::new(),
+            files: Vec::new(),
+            links: Vec::new(),
+        }
+    }
+
+    pub fn with_comment(self, comment: &str) -> Self {
+        self.with_key("Comment", comment)
+    }
+
+    pub fn with_inherits(self, inherits: &str) -> Self {
+        self.with_key("Inherits", inherits)
+    }
+
+    /// Sets a key of the `[Icon Theme]` section, replacing it if already set.
+    pub fn with_key(mut self, key: &str, value: &str) -> Self {
+        match self.keys.iter_mut().find(|(k, _)| k == key) {
+            Some((_, v)) => *v = value.into(),
+            None => self.keys.push((key.into(), value.into())),
+        }
+        self
+    }
+
+    /// Adds a directory of the given size with the default type.
+    pub fn with_directory(self, directory: &str, size: u32) -> Self {
+        self.with_section(directory, &format!("Size={size}\n"))
+    }
+
+    /// Adds a directory whose section has the given lines.
+    pub fn with_section(mut self, directory: &str, keys: &str) -> Self {
+        self.directories.push((directory.into(), keys.into()));
+        self
+    }
+
+    /// Adds an empty file at `path`, relative to the theme directory.
+    pub fn with_icon(self, path: impl AsRef<Path>) -> Self {
+        self.with_file(path, "")
+    }
+
+    /// Adds a file at `path`, relative to the theme directory.
+    pub fn with_file(mut self, path: impl AsRef<Path>, contents: impl AsRef<[u8]>) -> Self {
+        (self.files).push((path.as_ref().into(), contents.as_ref().into()));
+        self
+    }
+
+    /// Adds a symbolic link at `path`, relative to the theme directory, pointing to `target`.
+    pub fn with_symlink(mut self, path: impl AsRef<Path>, target: impl AsRef<Path>) -> Self {
+        (self.links).push((path.as_ref().into(), target.as_ref().into()));
+        self
+    }
+
+    /// The contents of the theme's `index.theme`.
+    pub fn index(&self) -> String {
+        let mut index = String::from("[Icon Theme]\n");
+        for (key, value) in &self.keys {
+            index += &format!("{key}={value}\n");
+        }
+        let names = self.directories.iter().map(|(dir, _)| dir.as_str());
+        index += &format!("Directories={}\n", names.collect::<Vec<_>>().join(","));
+
+        for (dir, keys) in &self.directories {
+            index += &format!("\n[{dir}]\n{keys}");
+        }
+        index
+    }
+
+    /// Writes the theme into `theme_dir`, creating its directories.
+    pub fn write(&self, theme_dir: &Path) -> io::Result<()> {
+        std::fs::create_dir_all(theme_dir)?;
+        for (dir, _) in &self.directories {
+            std::fs::create_dir_all(theme_dir.join(dir))?;
+        }
+        for (path, contents) in &self.files {
+            let path = theme_dir.join(path);
+            std::fs::create_dir_all(path.parent().unwrap())?;
+            std::fs::write(path, contents)?;
+        }
+        for (path, target) in &self.links {
+            std::os::unix::fs::symlink(target, theme_dir.join(path))?;
+        }
+        std::fs::write(theme_dir.join("index.theme"), self.index())
+    }
+}
+
+/// The shape of a generated fixture.
+#[derive(Debug, Clone)]
+pub struct FixtureOptions {
+    /// The number of themes besides `hicolor`.
+    pub themes: usize,
+    /// The fixed icon sizes of every theme. Each theme also has a scalable directory.
+    pub sizes: Vec<u32>,
+    /// The number of icons in every directory of `hicolor`.
+    pub icons: usize,
+    /// The number of standalone icons.
+    pub standalone_icons: usize,
+}
+
+impl Default for FixtureOptions {
+    fn default() -> Self {
+        Self {
+            themes: 4,
+            sizes: vec![16, 22, 24, 32, 48, 64, 128, 256],
+            icons: 200,
+            standalone_icons: 20,
+        }
+    }
+}
+
+/// A set of generated themes in a temporary directory, removed again on drop.
+pub struct Fixture {
+    dir: TempDir,
+    /// The internal names of the generated themes, from least to most derived.
+    pub themes: Vec<String>,
+    /// The names of all generated icons.
+    pub icon_names: Vec<String>,
+}
+
+impl Fixture {
+    pub fn generate(options: &FixtureOptions) -> io::Result<Self> {
+        let dir = tempfile::tempdir()?;
+
+        let icon_names = (0..options.icons)
+            .map(|i| format!("icon-{i}"))
+            .collect::<Vec<_>>();
+
+        let mut themes = vec!["hicolor".to_string()];
+        themes.extend((0..options.themes).map(|i| format!("theme-{i}")));
+
+        for (i, theme) in themes.iter().enumerate() {
+            // every theme inherits from the one generated before it
+            let inherits = i.checked_sub(1).map(|parent| &themes[parent]);
+            let icons = match i {
+                0 => &icon_names[..],
+                _ => &icon_names[..icon_names.len() / 2],
+            };
+            generated_theme(theme, inherits, options, icons).write(&dir.path().join(theme))?;
+        }
+
+        for i in 0..options.standalone_icons {
+            std::fs::write(dir.path().join(format!("standalone-{i}.png")), b"")?;
+        }
+
+        Ok(Self {
+            dir,
+            themes,
+            icon_names,
+        })
+    }
+
+    /// The directory to search for icons in.
+    pub fn path(&self) -> &Path {
+        self.dir.path()
+    }
+
+    pub fn dirs(&self) -> Vec<PathBuf> {
+        vec![self.path().into()]
+    }
+
+    /// The most derived theme, which inherits from every other theme.
+    pub fn leaf_theme(&self) -> &str {
+        self.themes.last().unwrap()
+    }
+
+    /// An icon the leaf theme contains itself.
+    pub fn own_icon(&self) -> &str {
+        &self.icon_names[0]
+    }
+
+    /// An icon only `hicolor` contains.
+    pub fn inherited_icon(&self) -> &str {
+        self.icon_names.last().unwrap()
+    }
+}
+
+fn generated_theme(
+    name: &str,
+    inherits: Option<&String>,
+    options: &FixtureOptions,
+    icon_names: &[String],
+) -> ThemeFixture {
+    let mut theme = ThemeFixture::new(name).with_comment("Generated theme");
+    if let Some(inherits) = inherits {
+        theme = theme.with_inherits(inherits);
+    }
+
+    let mut directories = (options.sizes.iter())
+        .map(|size| {
+            (
+                format!("{size}x{size}/apps"),
+                format!("Size={size}\nType=Fixed\n"),
+                "png",
+            )
+        })
+        .collect::<Vec<_>>();
+    directories.push((
+        "scalable/apps".into(),
+        "Size=48\nType=Scalable\nMinSize=8\nMaxSize=512\n".into(),
+        "svg",
+    ));
+
+    for (dir, keys, ext) in directories {
+        theme = theme.with_section(&dir, &format!("{keys}Context=Applications\n"));
+        for icon in icon_names {
+            theme = theme.with_icon(format!("{dir}/{icon}.{ext}"));
+        }
+    }
+    theme
+}
+
+// the benchmarks compile this module without running its tests, so the tests import what they
+// need themselves.
+#[cfg(test)]
+mod test {
+    #[test]
+    fn test_theme_fixture() {
+        use crate::fixture::ThemeFixture;
+
+        let dir = tempfile::tempdir().unwrap();
+        let theme = ThemeFixture::new("Oak")
+            .with_inherits("hicolor")
+            .with_directory("16", 16)
+            .with_section("scalable", "Size=48\nType=Scalable\n")
+            .with_comment("Trees")
+            .with_icon("16/acorn.png")
+            .with_file("scalable/acorn.svg", "<svg/>")
+            .with_symlink("16/nut.png", "acorn.png");
+        assert_eq!(
+            theme.index(),
+            "[Icon Theme]\nName=Oak\nComment=Trees\nInherits=hicolor\nDirectories=16,scalable\n\n\
+             [16]\nSize=16\n\n[scalable]\nSize=48\nType=Scalable\n"
+        );
+
+        let oak = dir.path().join("Oak");
+        theme.write(&oak).unwrap();
+        assert_eq!(
+            std::fs::read_to_string(oak.join("index.theme")).unwrap(),
+            theme.index()
+        );
+        assert_eq!(
+            std::fs::read(oak.join("scalable/acorn.svg")).unwrap(),
+            b"<svg/>"
+        );
+        assert!(oak.join("16/nut.png").is_symlink());
+    }
+
+    #[test]
+    fn test_generated_fixture() {
+        use crate::IconSearch;
+        use crate::fixture::{Fixture, FixtureOptions};
+
+        let options = FixtureOptions {
+            themes: 2,
+            sizes: vec![16, 32],
+            icons: 4,
+            standalone_icons: 1,
+        };
+        let fixture = Fixture::generate(&options).unwrap();
+        let icons = IconSearch::new_from(fixture.dirs()).search().icons();
+
+        let theme = fixture.leaf_theme();
+        let own = icons.find_icon(fixture.own_icon(), 32, 1, theme).unwrap();
+        assert!(own.path.starts_with(fixture.path().join(theme)));
+        let inherited = icons
+            .find_icon(fixture.inherited_icon(), 32, 1, theme)
+            .unwrap();
+        assert!(inherited.path.starts_with(fixture.path().join("hicolor")));
+        assert!(icons.find_icon("standalone-0", 32, 1, theme).is_some());
+    }
+}
@@ -0,0 +1,152 @@
+//! Looking up icons for fractional scales.
+//!
+//! Compositors report scales like 1.25 or 1.5, but themes only have directories for integer
+//! scales. Like GTK and Qt, [`Icons::find_icon_for_scale`] looks the icon up at the next integer
+//! scale, so that it is never upscaled, and tells the size in device pixels the caller should
+//! render it at, or downscale it to.
+//!
+//! # Example
+//!
+//! ```
+//! use icon::Icons;
+//!
+//! let icons = Icons::new();
+//! if let Some(icon) = icons.find_icon_for_scale("firefox", 24, 1.25, "Adwaita") {
+//!     // the 48px icon for scale 2, to be drawn at 30px
+//!     println!("draw {:?} at {}px", icon.icon.file.path, icon.pixel_size);
+//! }
+//! ```
+
+use crate::IconMatch;
+use crate::theme::{DirectoryType, Icons};
+
+/// An icon found by [`Icons::find_icon_for_scale`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScaledIcon {
+    pub icon: IconMatch,
+    /// The integer scale the icon was looked up at: the fractional scale, rounded up.
+    pub scale: u32,
+    /// The size in device pixels to render the icon at: the logical size times the fractional
+    /// scale, rounded.
+    pub pixel_size: u32,
+}
+
+impl ScaledIcon {
+    /// The size in device pixels of the icon's file, or `None` for scalable icons and standalone
+    /// icons, whose size isn't known without reading them.
+    pub fn natural_size(&self) -> Option<u32> {
+        let directory = self.icon.directory()?;
+        match directory.directory_type {
+            DirectoryType::Scalable => None,
+            DirectoryType::Fixed | DirectoryType::Threshold => {
+                Some(directory.size * directory.scale)
+            }
+        }
+    }
+
+    /// Whether the icon's file is larger than [`pixel_size`](Self::pixel_size) and has to be
+    /// downscaled, as opposed to rendered at that size.
+    pub fn needs_downscale(&self) -> bool {
+        self.natural_size()
+            .is_some_and(|natural| natural > self.pixel_size)
+    }
+}
+
+/// The integer scale to look icons up at for a fractional `scale`, which is at least 1.
+///
+/// Scales that aren't finite or positive are treated as 1.
+pub fn integer_scale(scale: f64) -> u32 {
+    if !scale.is_finite() || scale <= 1.0 {
+        return 1;
+    }
+    // saturates for absurdly large scales
+    scale.ceil() as u32
+}
+
+impl Icons {
+    /// Like [`find_icon_match`](Self::find_icon_match), for a fractional `scale`, see
+    /// [`fractional`](crate::fractional).
+    ///
+    /// `logical_size` is the size in logical pixels, as for [`find_icon`](Self::find_icon).
+    pub fn find_icon_for_scale(
+        &self,
+        icon_name: &str,
+        logical_size: u32,
+        scale: f64,
+        theme: &str,
+    ) -> Option<ScaledIcon> {
+        let integer = integer_scale(scale);
+        let icon = self.find_icon_match(icon_name, logical_size, integer, theme)?;
+
+        let scale = match scale.is_finite() && scale > 0.0 {
+            true => scale,
+            false => 1.0,
+        };
+        let pixel_size = (logical_size as f64 * scale).round() as u32;
+
+        Some(ScaledIcon {
+            icon,
+            scale: integer,
+            pixel_size,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::IconSearch;
+    use crate::fractional::integer_scale;
+    use std::fs;
+
+    #[test]
+    fn test_integer_scale() {
+        assert_eq!(integer_scale(1.0), 1);
+        assert_eq!(integer_scale(1.25), 2);
+        assert_eq!(integer_scale(2.0), 2);
+        assert_eq!(integer_scale(2.5), 3);
+        assert_eq!(integer_scale(0.5), 1);
+        assert_eq!(integer_scale(f64::NAN), 1);
+        assert_eq!(integer_scale(-2.0), 1);
+    }
+
+    #[test]
+    fn test_find_icon_for_scale() {
+        let dir = tempfile::tempdir().unwrap();
+        let theme = dir.path().join("Oak");
+        for sub_dir in ["24", "24@2", "scalable"] {
+            fs::create_dir_all(theme.join(sub_dir)).unwrap();
+        }
+        fs::write(
+            theme.join("index.theme"),
+            "[Icon Theme]\nName=Oak\nComment=\nDirectories=24,24@2,scalable\n\n\
+             [24]\nSize=24\nType=Fixed\n\n\
+             [24@2]\nSize=24\nScale=2\nType=Fixed\n\n\
+             [scalable]\nSize=64\nType=Scalable\nMinSize=8\nMaxSize=512\n",
+        )
+        .unwrap();
+        fs::write(theme.join("24/oak.png"), "").unwrap();
+        fs::write(theme.join("24@2/oak.png"), "").unwrap();
+        fs::write(theme.join("scalable/leaf.svg"), "").unwrap();
+
+        let icons = IconSearch::new_from(vec![dir.path().to_owned()])
+            .search()
+            .icons();
+
+        let icon = icons.find_icon_for_scale("oak", 24, 1.25, "Oak").unwrap();
+        assert_eq!(icon.icon.file.path, theme.join("24@2/oak.png"));
+        assert_eq!(icon.scale, 2);
+        assert_eq!(icon.pixel_size, 30);
+        assert_eq!(icon.natural_size(), Some(48));
+        assert!(icon.needs_downscale());
+
+        let icon = icons.find_icon_for_scale("oak", 24, 1.0, "Oak").unwrap();
+        assert_eq!(icon.icon.file.path, theme.join("24/oak.png"));
+        assert_eq!(icon.pixel_size, 24);
+        assert!(!icon.needs_downscale());
+
+        let icon = icons.find_icon_for_scale("leaf", 24, 1.5, "Oak").unwrap();
+        assert_eq!(icon.pixel_size, 36);
+        assert_eq!(icon.natural_size(), None);
+        assert!(!icon.needs_downscale());
+    }
+}
@@ -0,0 +1,56 @@
+//! Showing icons with [GTK 4](https://gtk.org).
+//!
+//! GTK apps can look up icons with this crate, e.g. to benefit from
+//! [listing](crate::listing) or a custom [search](crate::IconSearch), and still leave loading and
+//! rendering them to GTK:
+//!
+//! - [`IconFile::gtk_paintable`] creates a [`gtk::IconPaintable`], which renders SVGs at the size
+//!   they are drawn at and recolors symbolic icons like GTK's own icon theme does.
+//! - [`IconFile::gdk_texture`] loads the file into a [`gdk::Texture`] with gdk's own loaders.
+//! - Decoded [`Raster`](crate::render::Raster)s convert into a [`gdk::Texture`] as well.
+//!
+//! This module requires the `gtk4` feature.
+//!
+//! # Example
+//!
+//! ```ignore
+//! let file = icons.find_icon("firefox", 48, scale, "Adwaita").unwrap();
+//! let image = gtk4::Image::from_paintable(Some(&file.gtk_paintable(48, scale)));
+//! ```
+
+use crate::IconFile;
+use crate::render::Raster;
+use ::gtk4 as gtk;
+use gtk::{gdk, gio, glib};
+
+impl IconFile {
+    /// Creates a paintable drawing this icon at `size`, for a display with the given `scale`.
+    pub fn gtk_paintable(&self, size: i32, scale: i32) -> gtk::IconPaintable {
+        gtk::IconPaintable::for_file(&gio::File::for_path(&self.path), size, scale)
+    }
+
+    /// Loads this icon into a texture, at its intrinsic size.
+    ///
+    /// SVGs are rasterized by gdk's loaders; prefer [`gtk_paintable`](Self::gtk_paintable) for
+    /// them to render at the size they are drawn at.
+    pub fn gdk_texture(&self) -> Result<gdk::Texture, glib::Error> {
+        gdk::Texture::from_file(&gio::File::for_path(&self.path))
+    }
+}
+
+impl From<&Raster> for gdk::Texture {
+    fn from(raster: &Raster) -> Self {
+        use gtk::prelude::Cast;
+
+        let bytes = glib::Bytes::from_owned(raster.pixels.clone());
+        let stride = raster.width as usize * 4;
+        gdk::MemoryTexture::new(
+            raster.width as i32,
+            raster.height as i32,
+            gdk::MemoryFormat::R8g8b8a8,
+            &bytes,
+            stride,
+        )
+        .upcast()
+    }
+}
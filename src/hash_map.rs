@@ -0,0 +1,21 @@
+//! The hash map used internally, e.g. for the icons of directory listings.
+//!
+//! With the `fxhash` feature, maps use the much faster `FxHasher` instead of the standard
+//! library's SipHash. Theme and icon names aren't attacker-controlled in any meaningful way, so the
+//! protection against collision attacks SipHash offers isn't needed.
+//!
+//! Construct maps with [`Default`] or [`collect`](Iterator::collect) to work with either hasher.
+//!
+//! Only private maps use it: features must be additive, so public fields and return values stay
+//! `std::collections::HashMap` with its default hasher, whichever crate enables `fxhash`.
+
+/// The hasher of [`HashMap`].
+#[cfg(feature = "fxhash")]
+pub type BuildHasher = rustc_hash::FxBuildHasher;
+
+/// The hasher of [`HashMap`].
+#[cfg(not(feature = "fxhash"))]
+pub type BuildHasher = std::collections::hash_map::RandomState;
+
+/// A [`std::collections::HashMap`] using this crate's [`BuildHasher`].
+pub type HashMap<K, V> = std::collections::HashMap<K, V, BuildHasher>;
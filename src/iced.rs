@@ -0,0 +1,80 @@
+//! Showing icons with [iced](https://iced.rs).
+//!
+//! Raster icons become an [`image::Handle`], SVG icons an [`svg::Handle`]. Both refer to the icon
+//! by its path, so iced loads the file when it is first drawn.
+//!
+//! This module requires the `iced` feature, and only depends on `iced_core`, whose handles are the
+//! ones iced's widgets use.
+//!
+//! # Example
+//!
+//! ```ignore
+//! use iced::widget::{image, svg};
+//! use icon::iced::Handle;
+//!
+//! let element: iced::Element<'_, Message> = match Handle::from(&file) {
+//!     Handle::Image(handle) => image(handle).into(),
+//!     Handle::Svg(handle) => svg(handle).into(),
+//! };
+//! ```
+
+use crate::{FileType, IconFile};
+pub use iced_core::{image, svg};
+
+/// A handle to an icon for the iced widget that can display it.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Handle {
+    /// For PNGs, and other [registered](crate::IconSearch::with_file_type) types. XPMs are
+    /// included, but iced can't decode them.
+    Image(image::Handle),
+    Svg(svg::Handle),
+}
+
+impl From<&IconFile> for Handle {
+    fn from(file: &IconFile) -> Self {
+        match file.file_type {
+            FileType::Svg => Handle::Svg(file.into()),
+            FileType::Png | FileType::Xpm | FileType::Other(_) => Handle::Image(file.into()),
+        }
+    }
+}
+
+impl From<&IconFile> for image::Handle {
+    fn from(file: &IconFile) -> Self {
+        image::Handle::from_path(&file.path)
+    }
+}
+
+impl From<&IconFile> for svg::Handle {
+    fn from(file: &IconFile) -> Self {
+        svg::Handle::from_path(&file.path)
+    }
+}
+
+impl IconFile {
+    /// Reads this icon into a handle, so that iced doesn't access the file system later.
+    pub fn iced_handle_from_memory(&self) -> Result<Handle, crate::ReadError> {
+        let bytes = self.read()?;
+
+        Ok(match self.file_type {
+            FileType::Svg => Handle::Svg(svg::Handle::from_memory(bytes)),
+            _ => Handle::Image(image::Handle::from_bytes(bytes)),
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::IconFile;
+    use crate::iced::Handle;
+    use std::path::Path;
+
+    #[test]
+    fn test_handle() {
+        let png = IconFile::from_path(Path::new("/icons/htop.png")).unwrap();
+        let svg = IconFile::from_path(Path::new("/icons/firefox.svg")).unwrap();
+
+        assert!(matches!(Handle::from(&png), Handle::Image(_)));
+        assert!(matches!(Handle::from(&svg), Handle::Svg(_)));
+    }
+}
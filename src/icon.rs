@@ -1,12 +1,17 @@
+use crate::theme::{DirectoryIndex, Theme};
 use std::path::{Path, PathBuf};
+use std::sync::Arc;
 
-#[derive(Debug, Clone, Eq, PartialEq)]
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
 pub struct IconFile {
     pub path: PathBuf,
     pub file_type: FileType,
 }
 
 impl IconFile {
+    /// Creates an `IconFile` if `path` has the extension of a built-in [`FileType`].
+    ///
+    /// Use [`FileTypes::icon_file`] to also accept registered types.
     pub fn from_path(path: &Path) -> Option<IconFile> {
         let file_type = FileType::from_path_ext(path)?;
 
@@ -15,26 +20,385 @@ impl IconFile {
             file_type,
         })
     }
+
+    /// The name of this icon, which is its file name without extension.
+    pub fn icon_name(&self) -> Option<&str> {
+        self.path.file_stem()?.to_str()
+    }
+
+    /// Whether this is a symbolic icon, a monochrome icon named `*-symbolic` that is recolored
+    /// to match the text around it.
+    pub fn is_symbolic(&self) -> bool {
+        (self.icon_name()).is_some_and(|name| name.ends_with(crate::naming::SYMBOLIC_SUFFIX))
+    }
+
+    /// Determines the type of this icon file from its contents, ignoring its extension.
+    ///
+    /// Returns `None` if the contents don't look like any supported type. See
+    /// [`FileType::sniff`].
+    pub fn sniff_file_type(&self) -> std::io::Result<Option<FileType>> {
+        use std::io::Read;
+
+        // an SVG's root element may come after an XML declaration, a doctype and comments
+        let mut header = Vec::with_capacity(SNIFF_LEN);
+        std::fs::File::open(&self.path)?
+            .take(SNIFF_LEN as u64)
+            .read_to_end(&mut header)?;
+
+        Ok(FileType::sniff(&header))
+    }
+
+    /// Corrects [`file_type`](Self::file_type) according to the contents of the file.
+    ///
+    /// Real systems contain icons with the wrong extension, such as SVGs named `.png`. If the
+    /// file can't be read or its type can't be determined, the type from the extension is kept.
+    ///
+    /// ```no_run
+    /// use icon::{IconFile, Icons};
+    ///
+    /// let icon = Icons::new()
+    ///     .find_icon("firefox", 32, 1, "hicolor")
+    ///     .map(IconFile::sniffed);
+    /// ```
+    pub fn sniffed(mut self) -> Self {
+        if let Ok(Some(file_type)) = self.sniff_file_type() {
+            self.file_type = file_type;
+        }
+        self
+    }
+
+    /// Whether this icon file is a symbolic link.
+    ///
+    /// Themes often link many icon names to the same file. This reads the file system, so it is
+    /// `false` if the file doesn't exist (anymore).
+    pub fn is_symlink(&self) -> bool {
+        self.path.is_symlink()
+    }
+
+    /// The path of the file this icon file refers to, with all symbolic links resolved.
+    ///
+    /// Icons with the same canonical path are the same file, which allows loading them only once.
+    pub fn canonical_path(&self) -> std::io::Result<PathBuf> {
+        self.path.canonicalize()
+    }
+
+    /// Hashes the contents of this icon file.
+    ///
+    /// This allows noticing that an icon changed on disk even though its path stayed the same,
+    /// e.g. to invalidate a texture cache. The hash is not cryptographically secure.
+    ///
+    /// This requires the `hash` feature.
+    #[cfg(feature = "hash")]
+    pub fn content_hash(&self) -> std::io::Result<u128> {
+        hash_file(&self.path)
+    }
+
+    /// Opens this icon file for reading.
+    pub fn open(&self) -> Result<std::fs::File, ReadError> {
+        std::fs::File::open(&self.path).map_err(|e| ReadError::from_io(&self.path, e))
+    }
+
+    /// Maps this icon file into memory, read-only.
+    ///
+    /// This hands the contents to decoders and GPU uploads without copying them, and lets
+    /// several surfaces decoding the same large SVG share its pages.
+    ///
+    /// This requires the `mmap` feature.
+    ///
+    /// # Safety
+    ///
+    /// The file must not be modified or truncated while the map exists, see
+    /// [`memmap2::Mmap`]. Replacing the file by renaming another one over it, as package managers
+    /// do, is fine.
+    #[cfg(feature = "mmap")]
+    pub unsafe fn mmap(&self) -> Result<memmap2::Mmap, ReadError> {
+        let file = self.open()?;
+        // SAFETY: upheld by the caller
+        unsafe { memmap2::Mmap::map(&file) }.map_err(|e| ReadError::from_io(&self.path, e))
+    }
+
+    /// Reads the contents of this icon file, refusing files larger than
+    /// [`DEFAULT_READ_LIMIT`].
+    pub fn read(&self) -> Result<Vec<u8>, ReadError> {
+        self.read_with_limit(DEFAULT_READ_LIMIT)
+    }
+
+    /// Reads the contents of this icon file, refusing files larger than `limit` bytes.
+    pub fn read_with_limit(&self, limit: u64) -> Result<Vec<u8>, ReadError> {
+        use std::io::Read;
+
+        let too_large = || ReadError::TooLarge {
+            path: self.path.clone(),
+            limit,
+        };
+
+        let file = self.open()?;
+        let len = file
+            .metadata()
+            .map_err(|e| ReadError::from_io(&self.path, e))?
+            .len();
+        if len > limit {
+            return Err(too_large());
+        }
+
+        // the file may have grown since, so read one byte more than allowed to notice
+        let mut contents = Vec::with_capacity(len as usize);
+        file.take(limit.saturating_add(1))
+            .read_to_end(&mut contents)
+            .map_err(|e| ReadError::from_io(&self.path, e))?;
+        if contents.len() as u64 > limit {
+            return Err(too_large());
+        }
+
+        Ok(contents)
+    }
+}
+
+/// The size limit of [`IconFile::read`], in bytes.
+///
+/// Icons are small: even large PNGs and SVGs rarely exceed a few megabytes.
+pub const DEFAULT_READ_LIMIT: u64 = 16 << 20;
+
+/// Why an icon file couldn't be [read](IconFile::read) or [opened](IconFile::open).
+#[derive(Debug, thiserror::Error)]
+pub enum ReadError {
+    /// The file was removed since the icons were searched.
+    #[error("icon file {0:?} no longer exists")]
+    Missing(PathBuf),
+    #[error("permission to read icon file {0:?} was denied")]
+    PermissionDenied(PathBuf),
+    #[error("icon file {path:?} is larger than {limit} bytes")]
+    TooLarge { path: PathBuf, limit: u64 },
+    #[error("failed to read icon file {0:?}")]
+    Io(PathBuf, #[source] std::io::Error),
+}
+
+impl ReadError {
+    pub(crate) fn from_io(path: &Path, error: std::io::Error) -> Self {
+        match error.kind() {
+            std::io::ErrorKind::NotFound => ReadError::Missing(path.into()),
+            std::io::ErrorKind::PermissionDenied => ReadError::PermissionDenied(path.into()),
+            _ => ReadError::Io(path.into(), error),
+        }
+    }
 }
 
-#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+/// Hashes the contents of the file at `path`, see [`IconFile::content_hash`].
+#[cfg(feature = "hash")]
+pub(crate) fn hash_file(path: &Path) -> std::io::Result<u128> {
+    use std::io::Read;
+
+    let mut file = std::fs::File::open(path)?;
+    let mut hasher = xxhash_rust::xxh3::Xxh3::new();
+    let mut buffer = [0; 8192];
+
+    loop {
+        match file.read(&mut buffer)? {
+            0 => return Ok(hasher.digest128()),
+            n => hasher.update(&buffer[..n]),
+        }
+    }
+}
+
+/// A file that looks like an icon, but was skipped because it can't be used.
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct SkippedFile {
+    pub path: PathBuf,
+    pub reason: SkipReason,
+}
+
+/// Why a [`SkippedFile`] was skipped.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub enum SkipReason {
+    /// The file is a symbolic link to a file that doesn't exist.
+    DanglingSymlink,
+    /// The file can't be read by the current user.
+    PermissionDenied,
+    /// The file can't be read for another reason.
+    Unreadable(std::io::ErrorKind),
+}
+
+impl SkipReason {
+    /// Checks whether the file at `path` can be opened for reading, by opening it. Used when
+    /// scanning directories, where the reason a file is skipped is reported.
+    pub(crate) fn check(path: &Path) -> Result<(), Option<SkipReason>> {
+        Self::check_opened(path, std::fs::File::open(path))
+    }
+
+    /// Like [`check`](Self::check), without following links out of `root` if given.
+    pub(crate) fn check_in(root: Option<&Path>, path: &Path) -> Result<(), Option<SkipReason>> {
+        match root {
+            #[cfg(feature = "openat2")]
+            Some(root) => Self::check_opened(path, crate::openat2::open_beneath(root, path)),
+            _ => Self::check(path),
+        }
+    }
+
+    /// Like [`check`](Self::check), without opening the file: a missing file costs a single
+    /// `lstat`, an existing one an `faccessat` more. Used when looking icons up.
+    pub(crate) fn probe(path: &Path) -> Result<(), Option<SkipReason>> {
+        let metadata = (path.symlink_metadata()).map_err(|e| Self::from_error(e, || false))?;
+        #[cfg(unix)]
+        let access = {
+            use rustix::fs::{Access, AtFlags, CWD};
+            rustix::fs::accessat(CWD, path, Access::READ_OK, AtFlags::EACCESS).map_err(Into::into)
+        };
+        // without `faccessat`, the file has to be opened to tell whether it's readable
+        #[cfg(not(unix))]
+        let access = std::fs::File::open(path).map(drop);
+        access.map_err(|e| Self::from_error(e, || metadata.is_symlink()))
+    }
+
+    /// Like [`probe`](Self::probe), without following links out of `root` if given.
+    pub(crate) fn probe_in(root: Option<&Path>, path: &Path) -> Result<(), Option<SkipReason>> {
+        Self::probe(path)?;
+        match root {
+            #[cfg(feature = "openat2")]
+            Some(root) => (crate::openat2::resolve_beneath(root, path))
+                .map_err(|e| Self::from_error(e, || path.is_symlink())),
+            _ => Ok(()),
+        }
+    }
+
+    fn check_opened(
+        path: &Path,
+        file: std::io::Result<std::fs::File>,
+    ) -> Result<(), Option<SkipReason>> {
+        match file {
+            Ok(_) => Ok(()),
+            Err(e) => Err(Self::from_error(e, || path.is_symlink())),
+        }
+    }
+
+    // the reason for an error accessing a file, if it is worth reporting.
+    fn from_error(error: std::io::Error, is_symlink: impl FnOnce() -> bool) -> Option<SkipReason> {
+        use std::io::ErrorKind;
+
+        match error.kind() {
+            // a missing file is not a problem, but a link to one is
+            ErrorKind::NotFound => is_symlink().then_some(SkipReason::DanglingSymlink),
+            ErrorKind::PermissionDenied => Some(SkipReason::PermissionDenied),
+            kind => Some(SkipReason::Unreadable(kind)),
+        }
+    }
+}
+
+/// An icon file along with where it was found, as returned by
+/// [`Icons::find_icon_match`](crate::Icons::find_icon_match).
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct IconMatch {
+    pub file: IconFile,
+    /// The theme the icon was found in, which may be a parent of the requested theme. `None` for
+    /// standalone icons.
+    pub theme: Option<Arc<Theme>>,
+    /// The directory the icon was found in: one of the theme's
+    /// [base directories](crate::theme::ThemeInfo::base_dirs), or the search directory of a
+    /// standalone icon.
+    pub base_dir: PathBuf,
+    /// The index of the icon's directory in the theme's index.
+    pub(crate) directory: Option<usize>,
+    /// How the icon matches the requested size and scale.
+    pub kind: MatchKind,
+    /// The [generation](crate::Icons::generation) of the icons this was found in, or 0 if it was
+    /// looked up in a [`Theme`] directly.
+    pub generation: u64,
+}
+
+impl IconMatch {
+    /// The internal name of the theme the icon was found in.
+    pub fn theme_name(&self) -> Option<&str> {
+        Some(&self.theme.as_ref()?.info.internal_name)
+    }
+
+    /// The description of the theme directory the icon was found in, which tells its size, scale,
+    /// context and type.
+    pub fn directory(&self) -> Option<&DirectoryIndex> {
+        let directories = &self.theme.as_ref()?.info.index.directories;
+        directories.get(self.directory?)
+    }
+
+    /// Whether the icon matches the requested size and scale exactly.
+    pub fn is_exact(&self) -> bool {
+        self.kind == MatchKind::Exact
+    }
+}
+
+/// How an [`IconMatch`] matches the requested size and scale.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub enum MatchKind {
+    /// The icon's directory matches the size and scale, see
+    /// [`DirectoryIndex::matches_size`].
+    Exact,
+    /// No directory matching the size and scale has the icon, so it was found in the directory
+    /// closest to them and should be scaled.
+    Nearest,
+    /// A standalone icon or a file named by its path, whose size isn't known.
+    Unsized,
+}
+
+/// How many bytes of a file are read to determine its type.
+const SNIFF_LEN: usize = 4096;
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
 pub enum FileType {
     Png,
-    Xmp,
     Svg,
+    /// The legacy X PixMap format, which only some old icons still use.
+    Xpm,
+    /// An additional type, registered with [`IconSearch::with_file_type`](crate::IconSearch::with_file_type).
+    ///
+    /// Holds the extension without the leading dot, such as `"webp"`.
+    Other(&'static str),
 }
 
 impl FileType {
+    /// Determines the built-in file type from the extension of `path`.
     pub fn from_path_ext(path: &Path) -> Option<Self> {
         let ext = path.extension()?;
         let ext = ext.to_str()?;
 
         if ext.eq_ignore_ascii_case("png") {
             Some(FileType::Png)
-        } else if ext.eq_ignore_ascii_case("xmp") {
-            Some(FileType::Xmp)
         } else if ext.eq_ignore_ascii_case("svg") {
             Some(FileType::Svg)
+        } else if ext.eq_ignore_ascii_case("xpm") {
+            Some(FileType::Xpm)
+        } else {
+            None
+        }
+    }
+
+    /// Determines the file type from the first bytes of a file.
+    ///
+    /// PNGs are recognized by their signature, XPMs by their `/* XPM */` header and SVGs by an
+    /// `<svg` root element. Compressed SVGs are not recognized. Of the
+    /// [extra types](Self::extra_types), WebP, AVIF and ICO files are recognized by their headers
+    /// too.
+    pub fn sniff(header: &[u8]) -> Option<Self> {
+        const PNG_SIGNATURE: &[u8] = b"\x89PNG\r\n\x1a\n";
+
+        if header.starts_with(PNG_SIGNATURE) {
+            return Some(FileType::Png);
+        }
+        if header.starts_with(b"RIFF") && header.get(8..12) == Some(b"WEBP") {
+            return Some(FileType::WEBP);
+        }
+        if header.get(4..8) == Some(b"ftyp") && header.get(8..12) == Some(b"avif") {
+            return Some(FileType::AVIF);
+        }
+        // a reserved zero, the type 1 for icons and a non-zero number of images
+        if header.starts_with(b"\0\0\x01\0") && header.get(4..6).is_some_and(|n| n != [0, 0]) {
+            return Some(FileType::ICO);
+        }
+
+        let header = header.strip_prefix(b"\xef\xbb\xbf").unwrap_or(header); // UTF-8 BOM
+        let start = header.iter().position(|b| !b.is_ascii_whitespace())?;
+        let header = &header[start..];
+
+        if header.starts_with(b"/* XPM */") {
+            Some(FileType::Xpm)
+        } else if header.starts_with(b"<") && header.windows(4).any(|w| w == b"<svg") {
+            Some(FileType::Svg)
         } else {
             None
         }
@@ -43,12 +407,283 @@ impl FileType {
     pub fn ext(&self) -> &str {
         match self {
             FileType::Png => "png",
-            FileType::Xmp => "xmp",
             FileType::Svg => "svg",
+            FileType::Xpm => "xpm",
+            FileType::Other(ext) => ext,
         }
     }
 
+    /// The built-in file types, in their default order of preference.
     pub const fn types() -> [FileType; 3] {
-        [FileType::Png, FileType::Xmp, FileType::Svg]
+        [FileType::Png, FileType::Svg, FileType::Xpm]
+    }
+
+    /// WebP images, used by some third-party themes.
+    pub const WEBP: FileType = FileType::Other("webp");
+    /// AVIF images, used by some third-party themes.
+    pub const AVIF: FileType = FileType::Other("avif");
+    /// Windows icons, which Wine installs for the applications it runs.
+    pub const ICO: FileType = FileType::Other("ico");
+
+    /// Types that aren't in the specification but occur on real systems, which are only looked
+    /// for if [registered](crate::IconSearch::with_extra_file_types).
+    pub const fn extra_types() -> [FileType; 3] {
+        [FileType::WEBP, FileType::AVIF, FileType::ICO]
+    }
+}
+
+/// The built-in file types, in their default order of preference.
+static BUILT_IN: [FileType; 3] = FileType::types();
+
+/// The built-in file types, in the order files in directories that don't match the size exactly
+/// are tried in: an SVG scales best, and an XPM is only used if nothing else is there.
+static BUILT_IN_FALLBACK: [FileType; 3] = [FileType::Svg, FileType::Png, FileType::Xpm];
+
+/// The file types icons are looked up with, in order of preference.
+///
+/// These are the built-in types, followed by those registered with
+/// [`IconSearch::with_file_type`](crate::IconSearch::with_file_type) in the order they were
+/// registered, unless [a priority](Self::with_priority) is set.
+#[derive(Debug, Clone, Eq, PartialEq, Hash, Default)]
+pub struct FileTypes {
+    registered: Option<Arc<[FileType]>>,
+    /// All types in order of preference, replacing the built-in and registered ones.
+    priority: Option<Arc<[FileType]>>,
+}
+
+impl FileTypes {
+    /// The most file types a `FileTypes` can hold, including the built-in ones.
+    pub const MAX: usize = 64;
+
+    /// Only the built-in file types.
+    pub const fn new() -> Self {
+        Self {
+            registered: None,
+            priority: None,
+        }
+    }
+
+    /// Adds a file type after all others. Types that are already present are ignored.
+    ///
+    /// # Panics
+    ///
+    /// If this would hold more than [`MAX`](Self::MAX) types.
+    pub fn with(self, file_type: FileType) -> Self {
+        if self.position(file_type).is_some() {
+            return self;
+        }
+        assert!(self.len() < Self::MAX, "too many file types");
+
+        match &self.priority {
+            Some(priority) => Self {
+                priority: Some(priority.iter().copied().chain([file_type]).collect()),
+                ..self
+            },
+            None => {
+                let registered = self.registered();
+                let registered = registered.iter().copied().chain([file_type]).collect();
+                Self {
+                    registered: Some(registered),
+                    ..self
+                }
+            }
+        }
+    }
+
+    /// Replaces the order of preference with `file_types`, e.g. `[Svg, Png]` to prefer vector
+    /// icons. Types that aren't listed, built-in or registered, are excluded: their files are
+    /// never found. Types registered afterwards with [`with`](Self::with) come last.
+    ///
+    /// Unlike the default order, this order also decides between files of the same icon in
+    /// directories that don't match the requested size exactly.
+    ///
+    /// # Panics
+    ///
+    /// If `file_types` holds more than [`MAX`](Self::MAX) distinct types.
+    pub fn with_priority(self, file_types: impl IntoIterator<Item = FileType>) -> Self {
+        let mut priority = Vec::<FileType>::new();
+        for file_type in file_types {
+            let ext = file_type.ext();
+            if !(priority.iter()).any(|other| other.ext().eq_ignore_ascii_case(ext)) {
+                priority.push(file_type);
+            }
+        }
+        assert!(priority.len() <= Self::MAX, "too many file types");
+
+        Self {
+            registered: None,
+            priority: Some(priority.into()),
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.iter().count()
+    }
+
+    // the types in order of preference, split into the built-in ones, ordered like `built_in`,
+    // and the rest.
+    fn parts(&self, built_in: &'static [FileType]) -> (&[FileType], &[FileType]) {
+        match &self.priority {
+            Some(priority) => (&[], priority),
+            None => (built_in, self.registered()),
+        }
+    }
+
+    /// All file types, in order of preference.
+    pub fn iter(&self) -> impl DoubleEndedIterator<Item = FileType> + Clone + '_ {
+        let (built_in, rest) = self.parts(&BUILT_IN);
+        built_in.iter().chain(rest).copied()
+    }
+
+    /// All file types, in the order files in directories that don't match the size exactly are
+    /// tried in.
+    ///
+    /// Of multiple files in the same directory, SVGs are preferred for those as they scale
+    /// without losing quality, then PNGs, and XPMs last. Registered types still come last. With
+    /// [a priority](Self::with_priority), this is the same as [`iter`](Self::iter).
+    pub fn fallback_order(&self) -> impl Iterator<Item = FileType> + Clone + '_ {
+        let (built_in, rest) = self.parts(&BUILT_IN_FALLBACK);
+        built_in.iter().chain(rest).copied()
+    }
+
+    /// The position of `file_type` in [`iter`](Self::iter).
+    pub fn position(&self, file_type: FileType) -> Option<usize> {
+        self.iter()
+            .position(|other| other.ext().eq_ignore_ascii_case(file_type.ext()))
+    }
+
+    /// Determines the file type from the extension of `path`, ignoring case.
+    pub fn from_path(&self, path: &Path) -> Option<FileType> {
+        let ext = path.extension()?.to_str()?;
+        self.iter()
+            .find(|file_type| file_type.ext().eq_ignore_ascii_case(ext))
+    }
+
+    /// Like [`IconFile::from_path`], also accepting registered types.
+    pub fn icon_file(&self, path: &Path) -> Option<IconFile> {
+        Some(IconFile {
+            path: path.to_owned(),
+            file_type: self.from_path(path)?,
+        })
+    }
+
+    fn registered(&self) -> &[FileType] {
+        self.registered.as_deref().unwrap_or(&[])
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{FileType, IconFile, ReadError};
+
+    #[test]
+    fn test_canonical_path() {
+        let dir = tempfile::tempdir().unwrap();
+        let target = dir.path().join("edit-copy.svg");
+        let link = dir.path().join("edit-duplicate.svg");
+        std::fs::write(&target, b"<svg/>").unwrap();
+        std::os::unix::fs::symlink("edit-copy.svg", &link).unwrap();
+
+        let target = IconFile::from_path(&target).unwrap();
+        let link = IconFile::from_path(&link).unwrap();
+
+        assert!(!target.is_symlink());
+        assert!(link.is_symlink());
+        assert_eq!(
+            link.canonical_path().unwrap(),
+            target.canonical_path().unwrap()
+        );
+    }
+
+    #[test]
+    fn test_sniff() {
+        assert_eq!(
+            FileType::sniff(b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR"),
+            Some(FileType::Png)
+        );
+        assert_eq!(
+            FileType::sniff(b"/* XPM */\nstatic char *icon[] = {"),
+            Some(FileType::Xpm)
+        );
+        assert_eq!(
+            FileType::sniff(b"<?xml version=\"1.0\"?>\n<!-- made by hand -->\n<svg width=\"16\">"),
+            Some(FileType::Svg)
+        );
+        assert_eq!(
+            FileType::sniff(b"RIFF\x24\0\0\0WEBPVP8 "),
+            Some(FileType::WEBP)
+        );
+        assert_eq!(
+            FileType::sniff(b"\0\0\0\x1cftypavif\0\0\0\0"),
+            Some(FileType::AVIF)
+        );
+        assert_eq!(
+            FileType::sniff(b"\0\0\x01\0\x02\0\x10\x10"),
+            Some(FileType::ICO)
+        );
+        assert_eq!(FileType::sniff(b"\0\0\x01\0\0\0"), None);
+        assert_eq!(FileType::sniff(b"GIF89a"), None);
+        assert_eq!(FileType::sniff(b""), None);
+
+        // an SVG named .png
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("firefox.png");
+        std::fs::write(&path, b"\n<svg xmlns=\"http://www.w3.org/2000/svg\"/>").unwrap();
+
+        let icon = IconFile::from_path(&path).unwrap();
+        assert_eq!(icon.file_type, FileType::Png);
+        assert_eq!(icon.sniffed().file_type, FileType::Svg);
+    }
+
+    #[cfg(feature = "hash")]
+    #[test]
+    fn test_content_hash() {
+        let dir = tempfile::tempdir().unwrap();
+        let write = |name: &str, contents: &[u8]| {
+            let path = dir.path().join(name);
+            std::fs::write(&path, contents).unwrap();
+            IconFile::from_path(&path).unwrap()
+        };
+
+        let a = write("a.svg", b"<svg/>");
+        let b = write("b.svg", b"<svg/>");
+        let c = write("c.svg", b"<svg></svg>");
+
+        assert_eq!(a.content_hash().unwrap(), b.content_hash().unwrap());
+        assert_ne!(a.content_hash().unwrap(), c.content_hash().unwrap());
+
+        std::fs::remove_file(&a.path).unwrap();
+        assert!(a.content_hash().is_err());
+    }
+
+    #[test]
+    fn test_read() {
+        let dir = tempfile::tempdir().unwrap();
+        let icon = IconFile::from_path(&dir.path().join("htop.svg")).unwrap();
+        std::fs::write(&icon.path, b"<svg/>").unwrap();
+
+        assert_eq!(icon.read().unwrap(), b"<svg/>");
+        assert_eq!(icon.read_with_limit(6).unwrap(), b"<svg/>");
+        assert!(matches!(
+            icon.read_with_limit(5),
+            Err(ReadError::TooLarge { limit: 5, .. })
+        ));
+        assert!(icon.open().is_ok());
+
+        std::fs::remove_file(&icon.path).unwrap();
+        assert!(matches!(icon.read(), Err(ReadError::Missing(path)) if path == icon.path));
+        assert!(matches!(icon.open(), Err(ReadError::Missing(_))));
+    }
+
+    #[test]
+    #[cfg(feature = "mmap")]
+    fn test_mmap() {
+        let dir = tempfile::tempdir().unwrap();
+        let icon = IconFile::from_path(&dir.path().join("htop.svg")).unwrap();
+        std::fs::write(&icon.path, b"<svg/>").unwrap();
+
+        // SAFETY: the file isn't modified during the test
+        let map = unsafe { icon.mmap() }.unwrap();
+        assert_eq!(&map[..], b"<svg/>");
     }
 }
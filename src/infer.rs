@@ -0,0 +1,347 @@
+//! Inferring the index of themes that don't have one.
+//!
+//! Some icon directories, especially in `~/.icons` or in icons dropped in by vendors, lack an
+//! `index.theme`, which makes them invisible to lookups. Most of them still follow the usual
+//! layout of `48x48/apps`, `scalable/apps` or `apps/48` directories. With
+//! [`IconSearch::with_inferred_indexes`](crate::IconSearch::with_inferred_indexes), a
+//! [`ThemeIndex`] is synthesized from the names of those directories instead.
+//!
+//! Directory names are recognized as sizes if they are
+//!
+//! - `48x48` or `48`, for icons of size 48,
+//! - `48x48@2` or `48x48@2x`, for icons of size 48 at scale 2,
+//! - `scalable`, for scalable icons of any size.
+//!
+//! Either the size or the context may come first, and a size directory may hold icons directly.
+//!
+//! hicolor's directories are fixed, so an installation of it without an index, e.g. on systems
+//! without the hicolor package, gets [its usual index](ThemeIndex::hicolor) unless
+//! [disabled](crate::IconSearch::with_hicolor_bootstrap).
+
+use crate::audit::{AccessKind, Audit, allows};
+use crate::theme::{
+    DirectoryIndex, DirectoryType, MAX_DIRECTORY_SCALE, MAX_DIRECTORY_SIZE, ThemeIndex,
+};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+/// The size, scale and type of icons in a directory named `name`, e.g. `(48, 2, Threshold)` for
+/// `48x48@2`.
+pub fn parse_directory_name(name: &str) -> Option<(u32, u32, DirectoryType)> {
+    if name == "scalable" {
+        return Some((128, 1, DirectoryType::Scalable));
+    }
+
+    let (size, scale) = match name.split_once('@') {
+        Some((size, scale)) => (size, scale.strip_suffix('x').unwrap_or(scale).parse().ok()?),
+        None => (name, 1),
+    };
+    let size = match size.split_once('x') {
+        Some((width, height)) if width == height => width,
+        Some(_) => return None,
+        None => size,
+    };
+    // `parse` accepts a leading `+`, which no size directory has
+    let size: u32 = Some(size)
+        .filter(|size| size.bytes().all(|b| b.is_ascii_digit()))?
+        .parse()
+        .ok()?;
+
+    let in_range =
+        (1..=MAX_DIRECTORY_SIZE).contains(&size) && (1..=MAX_DIRECTORY_SCALE).contains(&scale);
+    in_range.then_some((size, scale, DirectoryType::Threshold))
+}
+
+/// The `Context` of icons in a directory named `name`, e.g. `Applications` for `apps`.
+pub fn context_of(name: &str) -> Option<&'static str> {
+    let context = match name {
+        "actions" => "Actions",
+        "animations" => "Animations",
+        "apps" => "Applications",
+        "categories" => "Categories",
+        "devices" => "Devices",
+        "emblems" => "Emblems",
+        "emotes" => "Emotes",
+        "filesystems" => "FileSystems",
+        "intl" => "International",
+        "mimetypes" => "MimeTypes",
+        "places" => "Places",
+        "status" => "Status",
+        _ => return None,
+    };
+    Some(context)
+}
+
+impl DirectoryIndex {
+    /// Describes a directory from its name alone, see [`infer`](crate::infer). Returns `None` if
+    /// no component of the name is a size.
+    pub fn infer(directory_name: &str) -> Option<Self> {
+        let components = directory_name.split('/').collect::<Vec<_>>();
+        let (size, scale, directory_type, context) = match components[..] {
+            [size] => {
+                let (size, scale, directory_type) = parse_directory_name(size)?;
+                (size, scale, directory_type, None)
+            }
+            [first, second] => match parse_directory_name(first) {
+                Some((size, scale, directory_type)) => (size, scale, directory_type, Some(second)),
+                None => {
+                    let (size, scale, directory_type) = parse_directory_name(second)?;
+                    (size, scale, directory_type, Some(first))
+                }
+            },
+            _ => return None,
+        };
+
+        let (min_size, max_size) = match directory_type {
+            DirectoryType::Scalable => (16, 512),
+            _ => (size, size),
+        };
+        Some(Self {
+            directory_name: directory_name.into(),
+            is_scaled_dir: scale != 1,
+            // like hicolor's own index
+            in_scaled_directories: scale != 1,
+            size,
+            scale,
+            context: context.and_then(context_of).map(Into::into),
+            directory_type,
+            max_size,
+            min_size,
+            threshold: 2,
+        })
+    }
+}
+
+/// The sizes hicolor has directories for, at scales 1 and 2.
+pub const HICOLOR_SIZES: &[u32] = &[16, 22, 24, 32, 36, 48, 64, 72, 96, 128, 192, 256, 512];
+
+/// The contexts hicolor has directories for, by directory name.
+pub const HICOLOR_CONTEXTS: &[&str] = &[
+    "actions",
+    "animations",
+    "apps",
+    "categories",
+    "devices",
+    "emblems",
+    "emotes",
+    "filesystems",
+    "intl",
+    "mimetypes",
+    "places",
+    "status",
+];
+
+impl ThemeIndex {
+    /// The index of hicolor as installed by the hicolor package, with directories for every
+    /// [size](HICOLOR_SIZES) and [context](HICOLOR_CONTEXTS), `scalable` and `symbolic`.
+    pub fn hicolor() -> Self {
+        let sizes = HICOLOR_SIZES
+            .iter()
+            .flat_map(|size| [format!("{size}x{size}"), format!("{size}x{size}@2")]);
+        let directories = sizes
+            .chain(["scalable".into()])
+            .flat_map(|size| {
+                HICOLOR_CONTEXTS
+                    .iter()
+                    .map(move |context| format!("{size}/{context}"))
+            })
+            .filter_map(|name| DirectoryIndex::infer(&name));
+
+        // symbolic icons are scalable, but meant for small sizes
+        let symbolic = HICOLOR_CONTEXTS.iter().map(|context| DirectoryIndex {
+            directory_name: format!("symbolic/{context}"),
+            size: 16,
+            min_size: 8,
+            ..DirectoryIndex::infer(&format!("scalable/{context}")).expect("scalable is a size")
+        });
+
+        Self {
+            name: "Hicolor".into(),
+            comment: "Fallback icon theme".into(),
+            inherits: Box::new([]),
+            directories: directories.chain(symbolic).collect(),
+            hidden: true,
+            example: None,
+            groups: BTreeMap::new(),
+        }
+    }
+
+    /// Synthesizes the index of a theme without an `index.theme` from the names of the
+    /// directories in `base_dirs`, see [`infer`](crate::infer).
+    ///
+    /// Returns `None` if none of the directories look like they contain icons.
+    pub fn infer(name: &str, base_dirs: &[PathBuf]) -> Option<Self> {
+        Self::infer_with(name, base_dirs, None)
+    }
+
+    // like `infer`, only listing the directories `audit` allows.
+    pub(crate) fn infer_with(
+        name: &str,
+        base_dirs: &[PathBuf],
+        audit: Option<&Audit>,
+    ) -> Option<Self> {
+        let sub_dirs = |dir: &Path| {
+            let entries = (allows(audit, dir, AccessKind::ListDirectory))
+                .then(|| dir.read_dir().ok())
+                .flatten();
+            let mut names = entries
+                .into_iter()
+                .flatten()
+                .flatten()
+                .filter(|entry| entry.path().is_dir())
+                .filter_map(|entry| entry.file_name().into_string().ok())
+                .collect::<Vec<_>>();
+            names.sort_unstable();
+            names
+        };
+
+        let mut directories = Vec::<DirectoryIndex>::new();
+        for base_dir in base_dirs {
+            for first in sub_dirs(base_dir) {
+                // a size directory only holds icons itself if it isn't split up by context
+                let mut found = (sub_dirs(&base_dir.join(&first)).into_iter())
+                    .filter_map(|second| DirectoryIndex::infer(&format!("{first}/{second}")))
+                    .collect::<Vec<_>>();
+                if found.is_empty() {
+                    found.extend(DirectoryIndex::infer(&first));
+                }
+
+                for directory in found {
+                    if !(directories.iter()).any(|d| d.directory_name == directory.directory_name) {
+                        directories.push(directory);
+                    }
+                }
+            }
+        }
+
+        if directories.is_empty() {
+            return None;
+        }
+        directories.sort_by(|a, b| a.directory_name.cmp(&b.directory_name));
+
+        Some(Self {
+            name: name.into(),
+            comment: String::new(),
+            inherits: Box::new([]),
+            directories: directories.into(),
+            hidden: false,
+            example: None,
+            groups: BTreeMap::new(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::IconSearch;
+    use crate::infer::parse_directory_name;
+    use crate::spec::{Compliance, Deviation};
+    use crate::theme::{DirectoryType, IndexOrigin, ThemeIndex};
+    use std::fs;
+
+    #[test]
+    fn test_parse_directory_name() {
+        assert_eq!(
+            parse_directory_name("48x48"),
+            Some((48, 1, DirectoryType::Threshold))
+        );
+        assert_eq!(
+            parse_directory_name("24x24@2x"),
+            Some((24, 2, DirectoryType::Threshold))
+        );
+        assert_eq!(
+            parse_directory_name("16@3"),
+            Some((16, 3, DirectoryType::Threshold))
+        );
+        assert_eq!(
+            parse_directory_name("scalable"),
+            Some((128, 1, DirectoryType::Scalable))
+        );
+        for name in ["apps", "48x32", "+48", "0x0", "48x48@0", ""] {
+            assert_eq!(parse_directory_name(name), None, "{name}");
+        }
+    }
+
+    #[test]
+    fn test_hicolor_bootstrap() {
+        let dir = tempfile::tempdir().unwrap();
+        let hicolor = dir.path().join("hicolor");
+        for file in [
+            "48x48/apps/firefox.png",
+            "symbolic/apps/firefox-symbolic.svg",
+        ] {
+            fs::create_dir_all(hicolor.join(file).parent().unwrap()).unwrap();
+            fs::write(hicolor.join(file), b"").unwrap();
+        }
+
+        let index = ThemeIndex::hicolor();
+        assert!((index.directories.iter()).any(|d| d.directory_name == "256x256@2/apps"));
+        assert!((index.directories.iter()).all(|d| d.context.is_some()));
+
+        let search = IconSearch::new_from(vec![dir.path().into()]);
+        let icons = search.clone().search().icons();
+        let firefox = icons.find_icon("firefox", 48, 1, "Missing").unwrap();
+        assert_eq!(firefox.path, hicolor.join("48x48/apps/firefox.png"));
+        let symbolic = icons.find_default_icon("firefox-symbolic", 16, 1).unwrap();
+        assert_eq!(
+            symbolic.path,
+            hicolor.join("symbolic/apps/firefox-symbolic.svg")
+        );
+
+        let hicolor_theme = icons.theme("hicolor").unwrap();
+        assert_eq!(hicolor_theme.info.index_origin, IndexOrigin::Builtin);
+        assert_eq!(hicolor_theme.spec_notes(), [Deviation::BuiltinHicolorIndex]);
+
+        let strict = search.clone().with_compliance(Compliance::Strict);
+        assert!(strict.search().icons().theme("hicolor").is_none());
+        let icons = search.with_hicolor_bootstrap(false).search().icons();
+        assert!(icons.theme("hicolor").is_none());
+    }
+
+    #[test]
+    fn test_inferred_index() {
+        let dir = tempfile::tempdir().unwrap();
+        let theme = dir.path().join("Vendor");
+        for file in [
+            "48x48/apps/firefox.png",
+            "scalable/apps/gimp.svg",
+            "places/32/folder.png",
+            "16x16/htop.png",
+            "cursors/left_ptr",
+        ] {
+            fs::create_dir_all(theme.join(file).parent().unwrap()).unwrap();
+            fs::write(theme.join(file), b"").unwrap();
+        }
+
+        let index = ThemeIndex::infer("Vendor", std::slice::from_ref(&theme)).unwrap();
+        let directories = (index.directories.iter())
+            .map(|d| (d.directory_name.as_str(), d.size, d.context.as_deref()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            directories,
+            [
+                ("16x16", 16, None),
+                ("48x48/apps", 48, Some("Applications")),
+                ("places/32", 32, Some("Places")),
+                ("scalable/apps", 128, Some("Applications")),
+            ]
+        );
+        assert_eq!(ThemeIndex::infer("Cursors", &[theme.join("cursors")]), None);
+
+        let search = IconSearch::new_from(vec![dir.path().into()]);
+        let icons = search.clone().search().icons();
+        assert!(icons.theme("Vendor").is_none());
+
+        let search = search.with_inferred_indexes(true);
+        let strict = search.clone().with_compliance(Compliance::Strict);
+        assert!(strict.search().icons().theme("Vendor").is_none());
+
+        let icons = search.search().icons();
+        let vendor = icons.theme("Vendor").unwrap();
+        assert!(vendor.spec_notes().contains(&Deviation::InferredIndexes));
+        for (name, size) in [("firefox", 48), ("gimp", 64), ("folder", 32), ("htop", 16)] {
+            let icon = icons.find_icon(name, size, 1, "Vendor").unwrap();
+            assert!(icon.path.starts_with(&theme), "{name}");
+        }
+    }
+}
@@ -0,0 +1,797 @@
+//! Installing icon themes from directories and archives.
+//!
+//! Theme managers install themes the user downloaded, usually as a `.tar.gz` archive, into their
+//! icons directory. [`install_theme`] copies a theme directory, or unpacks an archive with the
+//! `archive` feature, into the directory of an [`InstallScope`]. The theme is validated first,
+//! and an existing theme by the same name is only replaced if
+//! [asked to](InstallOptions::replace).
+//!
+//! The theme is assembled next to its final location and moved there at once, so lookups never
+//! see a partially installed theme. [`uninstall_theme`] removes it the same way, and
+//! [`collect_garbage`] cleans up what is left behind by other means: broken links, icons outside
+//! of any directory of their theme and the remains of interrupted installations.
+//!
+//! To decide which themes to offer removing, [`Icons::theme_locations`] tells for every
+//! directory of every theme whether it belongs to the user or the system, and whether this
+//! process could modify it.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use icon::install::{InstallScope, install_theme};
+//!
+//! let installed = install_theme("Downloads/Papirus.tar.gz", &InstallScope::User).unwrap();
+//! println!("installed {} to {:?}", installed.index.name, installed.theme_dir);
+//! ```
+
+use crate::FileType;
+use crate::environment::Environment;
+use crate::l10n::L10N_DIR;
+use crate::theme::{Icons, ThemeIndex};
+use std::collections::HashSet;
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use tempfile::TempDir;
+use thiserror::Error;
+
+/// Where a theme is installed.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum InstallScope {
+    /// The user's icons, `$XDG_DATA_HOME/icons`.
+    User,
+    /// The icons of all users, `/usr/share/icons`. Installing there usually requires root.
+    System,
+    /// A custom icons directory.
+    Directory(PathBuf),
+}
+
+impl InstallScope {
+    /// The icons directory of this scope, or `None` if the user's data directory isn't known.
+    ///
+    /// See [`icons_dir_in`](Self::icons_dir_in) to avoid reading the environment of the current
+    /// process.
+    pub fn icons_dir(&self) -> Option<PathBuf> {
+        self.icons_dir_in(&Environment::current())
+    }
+
+    /// Like [`icons_dir`](Self::icons_dir), in the given environment.
+    pub fn icons_dir_in(&self, env: &Environment) -> Option<PathBuf> {
+        match self {
+            InstallScope::User => Some(env.data_home()?.join("icons")),
+            InstallScope::System => Some("/usr/share/icons".into()),
+            InstallScope::Directory(dir) => Some(dir.clone()),
+        }
+    }
+}
+
+/// How [`install_theme_with`] installs a theme.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Default)]
+pub struct InstallOptions {
+    /// Replace a theme that is already installed by the same name, instead of failing with
+    /// [`InstallError::Conflict`].
+    pub replace: bool,
+    /// Generate the theme's `icon-theme.cache` with `gtk-update-icon-cache` after installing it,
+    /// if that is installed.
+    pub update_icon_cache: bool,
+}
+
+/// A theme installed by [`install_theme`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InstalledTheme {
+    /// The internal name of the theme, the name of its directory.
+    pub internal_name: String,
+    /// Where the theme was installed.
+    pub theme_dir: PathBuf,
+    pub index: ThemeIndex,
+    /// The amount of files installed, including the index.
+    pub files: usize,
+    /// Whether a theme by the same name was replaced.
+    pub replaced: bool,
+    /// Whether the icon cache was generated.
+    pub cache_updated: bool,
+}
+
+/// Why a theme couldn't be installed.
+#[derive(Debug, Error)]
+pub enum InstallError {
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+    /// The icons directory of the scope isn't known.
+    #[error("the icons directory of {0:?} is not known")]
+    NoIconsDir(InstallScope),
+    /// The source is neither a directory nor an archive this build can unpack.
+    #[error("{0:?} is not a supported archive")]
+    UnsupportedArchive(PathBuf),
+    /// An entry of the archive would be unpacked outside of it.
+    #[error("the archive entry {0:?} is outside of the archive")]
+    UnsafePath(PathBuf),
+    /// Neither the source nor any directory directly in it has an `index.theme`.
+    #[error("{0:?} does not contain an icon theme")]
+    NoTheme(PathBuf),
+    /// The source contains several themes, by their internal names.
+    #[error("{0:?} contains more than one icon theme: {1:?}")]
+    MultipleThemes(PathBuf, Vec<String>),
+    /// The theme's index doesn't parse.
+    #[error("the index of {name} is invalid: {source}")]
+    InvalidIndex {
+        name: String,
+        source: std::io::Error,
+    },
+    /// A theme by the same name is installed already.
+    #[error("the theme {name} is already installed at {path:?}")]
+    Conflict { name: String, path: PathBuf },
+    /// No theme by the name is installed, for [`uninstall_theme`].
+    #[error("the theme {0} is not installed")]
+    NotInstalled(String),
+    /// [`collect_garbage`] was asked to remove files from [`InstallScope::System`].
+    #[error("garbage is not removed from the system's icons directory")]
+    SystemScope,
+}
+
+/// The prefix of the directories themes are assembled and removed in.
+const STAGING_PREFIX: &str = ".icon-install-";
+
+/// Installs the theme in `source` into the icons directory of `scope`, see
+/// [`install_theme_with`].
+pub fn install_theme(
+    source: impl AsRef<Path>,
+    scope: &InstallScope,
+) -> Result<InstalledTheme, InstallError> {
+    install_theme_with(source, scope, InstallOptions::default())
+}
+
+/// Installs the theme in `source` into the icons directory of `scope`.
+///
+/// `source` is either a directory or, with the `archive` feature, a `.tar`, `.tar.gz` or `.tgz`
+/// archive. It is the theme itself if it has an `index.theme`, and otherwise must contain
+/// exactly one directory that has one, as archives usually do. Symbolic links are kept.
+pub fn install_theme_with(
+    source: impl AsRef<Path>,
+    scope: &InstallScope,
+    options: InstallOptions,
+) -> Result<InstalledTheme, InstallError> {
+    let source = source.as_ref();
+    let icons_dir = (scope.icons_dir()).ok_or_else(|| InstallError::NoIconsDir(scope.clone()))?;
+    fs::create_dir_all(&icons_dir)?;
+
+    // everything is assembled in the icons directory, so that it can be renamed into place. The
+    // staging directory may become the theme itself, so it gets the usual permissions.
+    let staging = staging_dir(&icons_dir, fs::Permissions::from_mode(0o755))?;
+    install_staged(source, &icons_dir, staging.path(), options)
+}
+
+// a directory with a unique name in `icons_dir`, removed with everything in it when dropped.
+fn staging_dir(icons_dir: &Path, permissions: fs::Permissions) -> std::io::Result<TempDir> {
+    (tempfile::Builder::new())
+        .prefix(STAGING_PREFIX)
+        .permissions(permissions)
+        .tempdir_in(icons_dir)
+}
+
+fn install_staged(
+    source: &Path,
+    icons_dir: &Path,
+    staging: &Path,
+    options: InstallOptions,
+) -> Result<InstalledTheme, InstallError> {
+    let (theme_root, internal_name) = match source.is_dir() {
+        true => {
+            let (root, internal_name) = find_theme(source)?;
+            let copy = staging.join(&internal_name);
+            copy_dir(&root, &copy)?;
+            (copy, internal_name)
+        }
+        false => {
+            unpack(source, staging)?;
+            let (root, internal_name) = find_theme(staging)?;
+            // an archive of a theme's contents is named after it
+            let internal_name = match root == staging {
+                true => archive_stem(source).ok_or(InstallError::NoTheme(source.into()))?,
+                false => internal_name,
+            };
+            (root, internal_name)
+        }
+    };
+
+    if !is_file_name(&internal_name) {
+        return Err(InstallError::NoTheme(source.into()));
+    }
+    let index = ThemeIndex::parse_from_file(&theme_root.join("index.theme")).map_err(|source| {
+        InstallError::InvalidIndex {
+            name: internal_name.clone(),
+            source,
+        }
+    })?;
+
+    let theme_dir = icons_dir.join(&internal_name);
+    let replaced = theme_dir.symlink_metadata().is_ok();
+    if replaced {
+        if !options.replace {
+            return Err(InstallError::Conflict {
+                name: internal_name,
+                path: theme_dir,
+            });
+        }
+        // the old theme is moved out of the way first, so it isn't lost if the rename fails
+        let old = staging.join(".replaced");
+        fs::rename(&theme_dir, &old)?;
+        if let Err(error) = fs::rename(&theme_root, &theme_dir) {
+            fs::rename(&old, &theme_dir)?;
+            return Err(error.into());
+        }
+    } else {
+        fs::rename(&theme_root, &theme_dir)?;
+    }
+
+    let files = count_files(&theme_dir)?;
+    let cache_updated = options.update_icon_cache
+        && Command::new("gtk-update-icon-cache")
+            .args(["--quiet", "--force", "--ignore-theme-index"])
+            .arg(&theme_dir)
+            .status()
+            .is_ok_and(|status| status.success());
+
+    Ok(InstalledTheme {
+        internal_name,
+        theme_dir,
+        index,
+        files,
+        replaced,
+        cache_updated,
+    })
+}
+
+/// Removes the theme with the internal name `name` from the icons directory of `scope`, returning
+/// the directory it was installed in.
+///
+/// Only directories with an `index.theme` are removed. Like installing, the theme disappears at
+/// once, and is then deleted.
+pub fn uninstall_theme(name: &str, scope: &InstallScope) -> Result<PathBuf, InstallError> {
+    let icons_dir = (scope.icons_dir()).ok_or_else(|| InstallError::NoIconsDir(scope.clone()))?;
+    let theme_dir = icons_dir.join(name);
+    let is_theme = theme_dir.symlink_metadata().is_ok_and(|meta| meta.is_dir())
+        && theme_dir.join("index.theme").is_file();
+    if !is_file_name(name) || !is_theme {
+        return Err(InstallError::NotInstalled(name.into()));
+    }
+
+    let removed = staging_dir(&icons_dir, fs::Permissions::from_mode(0o700))?;
+    fs::rename(&theme_dir, removed.path().join(name))?;
+    removed.close()?;
+    Ok(theme_dir)
+}
+
+/// Whom a theme directory belongs to, see [`Icons::theme_locations`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum ThemeScope {
+    /// In the user's home or data directory, e.g. `~/.icons` or `~/.local/share/icons`.
+    User,
+    /// Anywhere else, e.g. `/usr/share/icons` or a Flatpak runtime.
+    System,
+}
+
+impl ThemeScope {
+    /// The scope of the theme directory `theme_dir` in the environment `env`.
+    pub fn of(theme_dir: &Path, env: &Environment) -> Self {
+        let home = env.home.iter().cloned().chain(env.data_home());
+        match home.into_iter().any(|home| theme_dir.starts_with(home)) {
+            true => ThemeScope::User,
+            false => ThemeScope::System,
+        }
+    }
+}
+
+/// One directory of an installed theme, see [`Icons::theme_locations`].
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ThemeLocation {
+    pub internal_name: String,
+    /// The theme directory, e.g. `/usr/share/icons/hicolor`.
+    pub dir: PathBuf,
+    pub scope: ThemeScope,
+    /// Whether this process could [uninstall](uninstall_theme) the theme from this directory.
+    pub writable: bool,
+}
+
+impl ThemeLocation {
+    /// The scope to pass to [`uninstall_theme`] to remove the theme from this directory.
+    pub fn install_scope(&self) -> InstallScope {
+        let icons_dir = self.dir.parent().unwrap_or(&self.dir);
+        InstallScope::Directory(icons_dir.to_owned())
+    }
+}
+
+impl Icons {
+    /// Every directory of every theme, with whom it belongs to and whether this process could
+    /// modify it. Themes are in the order of [`iter_themes`](Self::iter_themes), and their
+    /// directories in the order they are searched in.
+    ///
+    /// A theme is often spread over several directories, e.g. hicolor with icons of the system
+    /// and of applications the user installed. Only directories with an `index.theme` can be
+    /// [uninstalled](uninstall_theme).
+    ///
+    /// Whether a directory is writable is found out without writing to it: this process needs
+    /// write and search permission for it and the icons directory around it, and their file
+    /// system mustn't be mounted read-only.
+    pub fn theme_locations(&self) -> Vec<ThemeLocation> {
+        self.theme_locations_in(&Environment::current())
+    }
+
+    /// Like [`theme_locations`](Self::theme_locations), in the given environment.
+    pub fn theme_locations_in(&self, env: &Environment) -> Vec<ThemeLocation> {
+        let mut locations = Vec::new();
+        for (_, theme) in self.iter_themes() {
+            for dir in &theme.info.base_dirs {
+                let icons_dir = dir.parent().unwrap_or(dir);
+                locations.push(ThemeLocation {
+                    internal_name: theme.info.internal_name.clone(),
+                    dir: dir.clone(),
+                    scope: ThemeScope::of(dir, env),
+                    writable: can_modify(icons_dir) && can_modify(dir),
+                });
+            }
+        }
+        locations
+    }
+}
+
+// whether entries can be created and removed in `dir`.
+#[cfg(unix)]
+fn can_modify(dir: &Path) -> bool {
+    use rustix::fs::{Access, StatVfs, StatVfsMountFlags};
+
+    let read_only = |fs: StatVfs| fs.f_flag.contains(StatVfsMountFlags::RDONLY);
+    rustix::fs::access(dir, Access::WRITE_OK | Access::EXEC_OK).is_ok()
+        && rustix::fs::statvfs(dir).is_ok_and(|fs| !read_only(fs))
+}
+
+// elsewhere, only the permissions of `dir` itself can be checked.
+#[cfg(not(unix))]
+fn can_modify(dir: &Path) -> bool {
+    fs::metadata(dir).is_ok_and(|metadata| !metadata.permissions().readonly())
+}
+
+/// What [`collect_garbage`] found in an icons directory.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Default)]
+pub struct Garbage {
+    /// Symbolic links whose target doesn't exist.
+    pub broken_links: Vec<PathBuf>,
+    /// Icon files in a theme that are in none of the directories of its index, and so are never
+    /// found. Files at the top of a theme, such as previews, are kept.
+    pub orphaned_files: Vec<PathBuf>,
+    /// Directories left behind by interrupted calls to [`install_theme`] and [`uninstall_theme`].
+    pub leftovers: Vec<PathBuf>,
+}
+
+impl Garbage {
+    pub fn is_empty(&self) -> bool {
+        self.broken_links.is_empty() && self.orphaned_files.is_empty() && self.leftovers.is_empty()
+    }
+
+    /// All paths that were found, or removed.
+    pub fn paths(&self) -> impl Iterator<Item = &Path> {
+        (self.broken_links.iter())
+            .chain(&self.orphaned_files)
+            .chain(&self.leftovers)
+            .map(PathBuf::as_path)
+    }
+}
+
+/// Finds broken links, orphaned icon files and leftovers of interrupted installations in the
+/// icons directory of `scope`, and removes them unless `dry_run` is set. Returns what was removed,
+/// or would have been with `dry_run`.
+///
+/// The leftovers of installations still in progress are removed too, so this shouldn't run
+/// concurrently with [`install_theme`] in the same directory.
+///
+/// The system's icons directory belongs to the package manager, so [`InstallScope::System`] is
+/// refused with [`InstallError::SystemScope`] unless `dry_run` is set. To clean it up anyway,
+/// pass it explicitly as an [`InstallScope::Directory`].
+pub fn collect_garbage(scope: &InstallScope, dry_run: bool) -> Result<Garbage, InstallError> {
+    if *scope == InstallScope::System && !dry_run {
+        return Err(InstallError::SystemScope);
+    }
+    let icons_dir = (scope.icons_dir()).ok_or_else(|| InstallError::NoIconsDir(scope.clone()))?;
+    let mut garbage = Garbage::default();
+    let entries = match fs::read_dir(&icons_dir) {
+        Ok(entries) => entries,
+        Err(error) if error.kind() == std::io::ErrorKind::NotFound => return Ok(garbage),
+        Err(error) => return Err(error.into()),
+    };
+
+    for entry in entries {
+        let entry = entry?;
+        let path = entry.path();
+        let file_type = entry.file_type()?;
+        if file_type.is_dir()
+            && entry
+                .file_name()
+                .to_string_lossy()
+                .starts_with(STAGING_PREFIX)
+        {
+            garbage.leftovers.push(path);
+        } else if file_type.is_dir() {
+            // themes that don't parse are left alone, their directories aren't known
+            let index = ThemeIndex::parse_from_file(&path.join("index.theme")).ok();
+            let directories = index.as_ref().map(|index| {
+                (index.directories.iter())
+                    .map(|dir| Path::new(&dir.directory_name))
+                    .collect::<HashSet<_>>()
+            });
+            find_garbage(&path, Path::new(""), directories.as_ref(), &mut garbage)?;
+        } else if file_type.is_symlink() && fs::metadata(&path).is_err() {
+            garbage.broken_links.push(path);
+        }
+    }
+
+    garbage.broken_links.sort();
+    garbage.orphaned_files.sort();
+    garbage.leftovers.sort();
+    if !dry_run {
+        for path in garbage.broken_links.iter().chain(&garbage.orphaned_files) {
+            fs::remove_file(path)?;
+        }
+        for path in &garbage.leftovers {
+            fs::remove_dir_all(path)?;
+        }
+    }
+    Ok(garbage)
+}
+
+// collects the garbage in `relative` of the theme at `theme_dir`, whose index lists
+// `directories`.
+fn find_garbage(
+    theme_dir: &Path,
+    relative: &Path,
+    directories: Option<&HashSet<&Path>>,
+    garbage: &mut Garbage,
+) -> std::io::Result<()> {
+    // files at the top are previews and caches, localized variants and cursors are used too
+    let is_used = |relative: &Path| {
+        let parent = relative.parent().unwrap_or(relative);
+        parent == Path::new("")
+            || relative.starts_with("cursors")
+            || relative.ancestors().any(|ancestor| {
+                ancestor.file_name() == Some(L10N_DIR.as_ref())
+                    && directories.is_some_and(|dirs| dirs.contains(ancestor.parent().unwrap()))
+            })
+            || directories.is_none_or(|dirs| dirs.contains(parent))
+    };
+
+    for entry in fs::read_dir(theme_dir.join(relative))? {
+        let entry = entry?;
+        let relative = relative.join(entry.file_name());
+        let path = theme_dir.join(&relative);
+        let file_type = entry.file_type()?;
+        if file_type.is_dir() {
+            find_garbage(theme_dir, &relative, directories, garbage)?;
+        } else if file_type.is_symlink() && fs::metadata(&path).is_err() {
+            garbage.broken_links.push(path);
+        } else if FileType::from_path_ext(&path).is_some() && !is_used(&relative) {
+            garbage.orphaned_files.push(path);
+        }
+    }
+    Ok(())
+}
+
+fn is_file_name(name: &str) -> bool {
+    !name.is_empty() && name != "." && name != ".." && !name.contains('/')
+}
+
+// the directory of the theme in `dir` and its internal name: `dir` itself, or the only directory
+// in it with an index.
+fn find_theme(dir: &Path) -> Result<(PathBuf, String), InstallError> {
+    let name_of = |path: &Path| {
+        let name = path.file_name().unwrap_or_default();
+        name.to_string_lossy().into_owned()
+    };
+    if dir.join("index.theme").is_file() {
+        return Ok((dir.to_owned(), name_of(dir)));
+    }
+
+    let mut themes = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() && path.join("index.theme").is_file() {
+            themes.push(path);
+        }
+    }
+    themes.sort();
+    match &themes[..] {
+        [] => Err(InstallError::NoTheme(dir.into())),
+        [theme] => Ok((theme.clone(), name_of(theme))),
+        _ => {
+            let names = themes.iter().map(|theme| name_of(theme)).collect();
+            Err(InstallError::MultipleThemes(dir.into(), names))
+        }
+    }
+}
+
+// `from` copied to `to`, keeping symbolic links.
+fn copy_dir(from: &Path, to: &Path) -> std::io::Result<()> {
+    fs::create_dir(to)?;
+    for entry in fs::read_dir(from)? {
+        let entry = entry?;
+        let (from, to) = (entry.path(), to.join(entry.file_name()));
+        let file_type = entry.file_type()?;
+        if file_type.is_symlink() {
+            std::os::unix::fs::symlink(fs::read_link(&from)?, &to)?;
+        } else if file_type.is_dir() {
+            copy_dir(&from, &to)?;
+        } else {
+            fs::copy(&from, &to)?;
+        }
+    }
+    Ok(())
+}
+
+fn count_files(dir: &Path) -> std::io::Result<usize> {
+    let mut files = 0;
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        files += match entry.file_type()?.is_dir() {
+            true => count_files(&entry.path())?,
+            false => 1,
+        };
+    }
+    Ok(files)
+}
+
+const ARCHIVE_EXTENSIONS: &[&str] = &[".tar.gz", ".tgz", ".tar"];
+
+// the file name of an archive without its extension, e.g. `Papirus` for `Papirus.tar.gz`.
+fn archive_stem(archive: &Path) -> Option<String> {
+    let name = archive.file_name()?.to_str()?;
+    let lowercase = name.to_ascii_lowercase();
+    let extension =
+        (ARCHIVE_EXTENSIONS.iter()).find(|extension| lowercase.ends_with(**extension))?;
+    Some(name[..name.len() - extension.len()].to_owned())
+}
+
+#[cfg(feature = "archive")]
+fn unpack(archive: &Path, into: &Path) -> Result<(), InstallError> {
+    let unsupported = || InstallError::UnsupportedArchive(archive.into());
+    let name = (archive.file_name())
+        .ok_or_else(unsupported)?
+        .to_string_lossy()
+        .to_ascii_lowercase();
+
+    let file = fs::File::open(archive)?;
+    let reader: Box<dyn std::io::Read> = if name.ends_with(".tar.gz") || name.ends_with(".tgz") {
+        Box::new(flate2::read::GzDecoder::new(file))
+    } else if name.ends_with(".tar") {
+        Box::new(file)
+    } else {
+        return Err(unsupported());
+    };
+
+    let mut archive = tar::Archive::new(reader);
+    archive.set_preserve_permissions(false);
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        // `unpack_in` refuses entries that would end up outside of `into`
+        if !entry.unpack_in(into)? {
+            return Err(InstallError::UnsafePath(entry.path()?.into_owned()));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(not(feature = "archive"))]
+fn unpack(archive: &Path, _into: &Path) -> Result<(), InstallError> {
+    Err(InstallError::UnsupportedArchive(archive.into()))
+}
+
+#[cfg(test)]
+mod test {
+    use crate::fixture::ThemeFixture;
+    use crate::install::{
+        InstallError, InstallOptions, InstallScope, ThemeScope, collect_garbage,
+        install_theme_with, uninstall_theme,
+    };
+    use std::fs;
+
+    fn theme_fixture(name: &str) -> ThemeFixture {
+        ThemeFixture::new(name)
+            .with_directory("48", 48)
+            .with_icon("48/firefox.png")
+            .with_symlink("48/web-browser.png", "firefox.png")
+    }
+
+    #[test]
+    fn test_install_theme() {
+        let dir = tempfile::tempdir().unwrap();
+        let download = dir.path().join("download");
+        theme_fixture("Oak").write(&download.join("Oak")).unwrap();
+        let scope = InstallScope::Directory(dir.path().join("icons"));
+
+        let installed = install_theme_with(&download, &scope, InstallOptions::default()).unwrap();
+        assert_eq!(installed.internal_name, "Oak");
+        assert_eq!(installed.theme_dir, dir.path().join("icons/Oak"));
+        assert_eq!(installed.index.name, "Oak");
+        assert_eq!(installed.files, 3);
+        assert!(!installed.replaced);
+        assert!(installed.theme_dir.join("48/web-browser.png").is_symlink());
+        // nothing is left behind
+        assert_eq!(fs::read_dir(dir.path().join("icons")).unwrap().count(), 1);
+
+        let conflict = install_theme_with(download.join("Oak"), &scope, InstallOptions::default());
+        assert!(matches!(conflict, Err(InstallError::Conflict { name, .. }) if name == "Oak"));
+
+        fs::write(download.join("Oak/48/htop.png"), b"").unwrap();
+        let options = InstallOptions {
+            replace: true,
+            ..InstallOptions::default()
+        };
+        let installed = install_theme_with(download.join("Oak"), &scope, options).unwrap();
+        assert!(installed.replaced);
+        assert!(installed.theme_dir.join("48/htop.png").exists());
+        assert_eq!(fs::read_dir(dir.path().join("icons")).unwrap().count(), 1);
+
+        theme_fixture("Birch")
+            .write(&download.join("Birch"))
+            .unwrap();
+        let multiple = install_theme_with(&download, &scope, options);
+        assert!(
+            matches!(multiple, Err(InstallError::MultipleThemes(_, names)) if names == ["Birch", "Oak"])
+        );
+
+        fs::write(download.join("Birch/index.theme"), "[Icon Theme]\n").unwrap();
+        let invalid = install_theme_with(download.join("Birch"), &scope, options);
+        assert!(matches!(invalid, Err(InstallError::InvalidIndex { .. })));
+
+        let empty = install_theme_with(dir.path().join("icons/Oak/48"), &scope, options);
+        assert!(matches!(empty, Err(InstallError::NoTheme(_))));
+    }
+
+    #[test]
+    fn test_uninstall_theme() {
+        let dir = tempfile::tempdir().unwrap();
+        let icons = dir.path().join("icons");
+        theme_fixture("Oak").write(&icons.join("Oak")).unwrap();
+        fs::create_dir_all(icons.join("cursors-only")).unwrap();
+        let scope = InstallScope::Directory(icons.clone());
+
+        assert_eq!(uninstall_theme("Oak", &scope).unwrap(), icons.join("Oak"));
+        assert_eq!(fs::read_dir(&icons).unwrap().count(), 1);
+
+        for name in ["Oak", "cursors-only", "..", ""] {
+            let missing = uninstall_theme(name, &scope);
+            assert!(matches!(missing, Err(InstallError::NotInstalled(_))));
+        }
+        assert!(icons.join("cursors-only").exists());
+    }
+
+    #[test]
+    fn test_theme_locations() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let (system, user) = (dir.path().join("usr/icons"), dir.path().join("home/.icons"));
+        theme_fixture("Oak").write(&system.join("Oak")).unwrap();
+        theme_fixture("Oak").write(&user.join("Oak")).unwrap();
+        theme_fixture("Birch").write(&system.join("Birch")).unwrap();
+        fs::set_permissions(system.join("Birch"), fs::Permissions::from_mode(0o555)).unwrap();
+
+        let env = crate::Environment {
+            home: Some(dir.path().join("home")),
+            ..crate::Environment::default()
+        };
+        let icons = crate::IconSearch::new_from(vec![user.clone(), system.clone()])
+            .search()
+            .icons();
+        let locations = icons.theme_locations_in(&env);
+        let summary = (locations.iter())
+            .map(|location| (&location.internal_name[..], &location.dir, location.scope))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            summary,
+            [
+                ("Oak", &user.join("Oak"), ThemeScope::User),
+                ("Oak", &system.join("Oak"), ThemeScope::System),
+                ("Birch", &system.join("Birch"), ThemeScope::System),
+            ]
+        );
+        assert!(locations[0].writable);
+        assert_eq!(
+            locations[1].install_scope(),
+            InstallScope::Directory(system.clone())
+        );
+        // root may write anywhere
+        let is_root = fs::create_dir(system.join("Birch/probe")).is_ok();
+        assert_eq!(locations[2].writable, is_root);
+        // nothing is written
+        assert_eq!(fs::read_dir(&system).unwrap().count(), 2);
+
+        fs::set_permissions(system.join("Birch"), fs::Permissions::from_mode(0o755)).unwrap();
+    }
+
+    #[test]
+    fn test_collect_garbage() {
+        let dir = tempfile::tempdir().unwrap();
+        let icons = dir.path().join("icons");
+        let theme = icons.join("Oak");
+        theme_fixture("Oak").write(&theme).unwrap();
+        for dir in ["96", "48/l10n/de", "cursors"] {
+            fs::create_dir_all(theme.join(dir)).unwrap();
+        }
+        fs::create_dir_all(icons.join(".icon-install-1/Birch")).unwrap();
+        for file in [
+            "96/htop.png",
+            "48/l10n/de/firefox.png",
+            "cursors/left_ptr",
+            "preview.png",
+        ] {
+            fs::write(theme.join(file), b"").unwrap();
+        }
+        std::os::unix::fs::symlink("missing.png", theme.join("48/htop.png")).unwrap();
+        std::os::unix::fs::symlink("missing.svg", icons.join("btop.svg")).unwrap();
+        fs::write(icons.join("gimp.png"), b"").unwrap();
+        let scope = InstallScope::Directory(icons.clone());
+
+        let dry_run = collect_garbage(&scope, true).unwrap();
+        assert_eq!(
+            dry_run.broken_links,
+            [theme.join("48/htop.png"), icons.join("btop.svg")]
+        );
+        assert_eq!(dry_run.orphaned_files, [theme.join("96/htop.png")]);
+        assert_eq!(dry_run.leftovers, [icons.join(".icon-install-1")]);
+        assert!(dry_run.paths().all(|path| path.symlink_metadata().is_ok()));
+
+        assert_eq!(collect_garbage(&scope, false).unwrap(), dry_run);
+        assert!(dry_run.paths().all(|path| path.symlink_metadata().is_err()));
+        assert!(collect_garbage(&scope, true).unwrap().is_empty());
+
+        assert!(collect_garbage(&InstallScope::System, true).is_ok());
+        assert!(matches!(
+            collect_garbage(&InstallScope::System, false),
+            Err(InstallError::SystemScope)
+        ));
+        assert!(theme.join("48/l10n/de/firefox.png").exists());
+        assert!(theme.join("preview.png").exists());
+        assert!(icons.join("gimp.png").exists());
+    }
+
+    #[cfg(feature = "archive")]
+    #[test]
+    fn test_install_archive() {
+        let dir = tempfile::tempdir().unwrap();
+        let theme = dir.path().join("Oak");
+        theme_fixture("Oak").write(&theme).unwrap();
+
+        let archive = dir.path().join("Oak-1.0.tar.gz");
+        let encoder = flate2::write::GzEncoder::new(
+            fs::File::create(&archive).unwrap(),
+            flate2::Compression::fast(),
+        );
+        let mut builder = tar::Builder::new(encoder);
+        builder.follow_symlinks(false);
+        builder.append_dir_all("Oak", &theme).unwrap();
+        builder.into_inner().unwrap().finish().unwrap();
+
+        let scope = InstallScope::Directory(dir.path().join("icons"));
+        let installed = install_theme_with(&archive, &scope, InstallOptions::default()).unwrap();
+        assert_eq!(installed.theme_dir, dir.path().join("icons/Oak"));
+        assert_eq!(installed.files, 3);
+        assert!(installed.theme_dir.join("48/web-browser.png").is_symlink());
+
+        // an archive of the theme's contents is named after the archive
+        let archive = dir.path().join("Birch.tar");
+        let mut builder = tar::Builder::new(fs::File::create(&archive).unwrap());
+        builder.append_dir_all(".", &theme).unwrap();
+        builder.finish().unwrap();
+        let installed = install_theme_with(&archive, &scope, InstallOptions::default()).unwrap();
+        assert_eq!(installed.internal_name, "Birch");
+
+        let unsupported = install_theme_with(
+            dir.path().join("Oak.zip"),
+            &scope,
+            InstallOptions::default(),
+        );
+        assert!(matches!(
+            unsupported,
+            Err(InstallError::UnsupportedArchive(_)) | Err(InstallError::Io(_))
+        ));
+    }
+}
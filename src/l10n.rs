@@ -0,0 +1,164 @@
+//! Localized variants of icons.
+//!
+//! Some icons show text or symbols that differ between languages, e.g. the letters of
+//! `format-text-bold` or the currency of `accessories-calculator`. Themes such as KDE's ship
+//! variants of them in an `l10n` directory next to the icon, by locale:
+//!
+//! ```text
+//! 22x22/actions/format-text-bold.svg
+//! 22x22/actions/l10n/de/format-text-bold.svg
+//! 22x22/actions/l10n/pt_BR/format-text-bold.svg
+//! ```
+//!
+//! [`Icons::find_icon_localized`] looks an icon up as usual and picks the variant for a locale if
+//! there is one.
+//!
+//! # Example
+//!
+//! ```
+//! use icon::{Environment, Icons};
+//!
+//! let icons = Icons::new();
+//! let locale = Environment::current().locale.unwrap_or_default();
+//! let bold = icons.find_icon_localized("format-text-bold", &locale, 22, 1, "breeze");
+//! ```
+
+use crate::audit::{AccessKind, allows};
+use crate::theme::Icons;
+use crate::{IconFile, IconQuery};
+
+/// The name of the directories localized variants are in.
+pub const L10N_DIR: &str = "l10n";
+
+/// The locales to look up variants for with a POSIX `locale`, from the most to the least
+/// specific, e.g. `sr_RS@latin`, `sr_RS`, `sr@latin` and `sr` for `sr_RS.UTF-8@latin`.
+///
+/// The encoding is ignored, as are the `C` and `POSIX` locales.
+pub fn locale_variants(locale: &str) -> Vec<String> {
+    let (rest, modifier) = match locale.split_once('@') {
+        Some((rest, modifier)) => (rest, Some(modifier)),
+        None => (locale, None),
+    };
+    let rest = rest.split_once('.').map_or(rest, |(rest, _)| rest);
+    let (language, country) = match rest.split_once('_') {
+        Some((language, country)) => (language, Some(country)),
+        None => (rest, None),
+    };
+    if language.is_empty() || language == "C" || language == "POSIX" {
+        return Vec::new();
+    }
+
+    // the same order the desktop entry specification matches localized keys in
+    let mut variants = Vec::new();
+    if let (Some(country), Some(modifier)) = (country, modifier) {
+        variants.push(format!("{language}_{country}@{modifier}"));
+    }
+    if let Some(country) = country {
+        variants.push(format!("{language}_{country}"));
+    }
+    if let Some(modifier) = modifier {
+        variants.push(format!("{language}@{modifier}"));
+    }
+    variants.push(language.into());
+    variants
+}
+
+impl Icons {
+    /// Looks up an icon like [`find_icon`](Self::find_icon), preferring its variant for `locale`
+    /// if the theme has one, see [`l10n`](crate::l10n).
+    ///
+    /// Only variants of the icon that is found are considered, so an icon that only exists in an
+    /// `l10n` directory isn't found.
+    pub fn find_icon_localized(
+        &self,
+        icon_name: &str,
+        locale: &str,
+        size: u32,
+        scale: u32,
+        theme: &str,
+    ) -> Option<IconFile> {
+        self.lookup(
+            &IconQuery::new(icon_name, size)
+                .with_scale(scale)
+                .with_theme(theme)
+                .with_locale(locale),
+        )
+    }
+
+    /// The variant of `icon` for `locale`, or `icon` itself if there is none.
+    pub(crate) fn localize(&self, icon: IconFile, locale: &str) -> IconFile {
+        let (Some(dir), Some(file_name)) = (icon.path.parent(), icon.path.file_name()) else {
+            return icon;
+        };
+
+        let localized = locale_variants(locale)
+            .into_iter()
+            .map(|variant| dir.join(L10N_DIR).join(variant).join(file_name))
+            .find(|path| allows(self.audit.as_ref(), path, AccessKind::Probe) && path.is_file());
+        match localized {
+            Some(path) => IconFile { path, ..icon },
+            None => icon,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::IconSearch;
+    use crate::l10n::locale_variants;
+    use std::fs;
+
+    #[test]
+    fn test_locale_variants() {
+        assert_eq!(
+            locale_variants("sr_RS.UTF-8@latin"),
+            ["sr_RS@latin", "sr_RS", "sr@latin", "sr"]
+        );
+        assert_eq!(locale_variants("pt_BR"), ["pt_BR", "pt"]);
+        assert_eq!(locale_variants("de"), ["de"]);
+        assert!(locale_variants("C.UTF-8").is_empty());
+        assert!(locale_variants("").is_empty());
+    }
+
+    #[test]
+    fn test_find_icon_localized() {
+        let dir = tempfile::tempdir().unwrap();
+        let theme = dir.path().join("hicolor");
+        fs::create_dir_all(theme.join("22/l10n/de")).unwrap();
+        fs::create_dir_all(theme.join("22/l10n/pt_BR")).unwrap();
+        fs::write(
+            theme.join("index.theme"),
+            "[Icon Theme]\nName=Hicolor\nComment=\nDirectories=22\n\n[22]\nSize=22\n",
+        )
+        .unwrap();
+        for file in [
+            "format-text-bold.svg",
+            "l10n/de/format-text-bold.svg",
+            "l10n/pt_BR/format-text-bold.svg",
+            "l10n/de/format-text-italic.svg",
+        ] {
+            fs::write(theme.join("22").join(file), b"").unwrap();
+        }
+
+        let icons = IconSearch::new_from(vec![dir.path().into()])
+            .search()
+            .icons();
+        let find = |name, locale| {
+            let icon = icons.find_icon_localized(name, locale, 22, 1, "hicolor")?;
+            Some(icon.path.strip_prefix(&theme).ok()?.to_owned())
+        };
+        assert_eq!(
+            find("format-text-bold", "de_AT.UTF-8"),
+            Some("22/l10n/de/format-text-bold.svg".into())
+        );
+        assert_eq!(
+            find("format-text-bold", "pt_BR"),
+            Some("22/l10n/pt_BR/format-text-bold.svg".into())
+        );
+        assert_eq!(
+            find("format-text-bold", "fr_FR"),
+            Some("22/format-text-bold.svg".into())
+        );
+        assert_eq!(find("format-text-italic", "de"), None);
+    }
+}
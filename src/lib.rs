@@ -55,10 +55,77 @@
 //!   - it only supports a rust-native icon cache, which you cannot opt out of.
 //!   - it provides only icon loading—you cannot use it to obtain information about Icon Themes.
 
+pub mod animation;
+pub mod appstream;
+pub mod audit;
+pub mod borrowed;
+pub mod cancel;
+pub mod change;
+#[cfg(feature = "config")]
+pub mod config;
+pub mod desktop;
+pub mod diff;
+pub mod duplicates;
+pub mod edit;
+mod environment;
+mod error;
+pub mod export;
+#[cfg(test)]
+mod fixture;
+pub mod fractional;
+#[cfg(feature = "gtk4")]
+pub mod gtk4;
+mod hash_map;
+#[cfg(feature = "iced")]
+pub mod iced;
 mod icon;
+pub mod infer;
+pub mod install;
+pub mod l10n;
+pub mod listing;
+pub mod memory;
+pub mod merge;
+pub mod mime;
+pub mod mpris;
+pub mod naming;
+#[cfg(feature = "openat2")]
+pub mod openat2;
+#[cfg(feature = "rayon")]
+pub mod parallel;
+pub mod places;
+pub mod query;
+#[cfg(feature = "raster-cache")]
+pub mod raster_cache;
+pub mod render;
+pub mod report;
+pub mod resolver;
 mod search;
+pub mod settings;
+#[cfg(feature = "slint")]
+pub mod slint;
+pub mod snapshot;
+pub mod spec;
+pub mod statistics;
+pub mod status;
+#[cfg(feature = "svg")]
+pub mod svg;
+pub mod testing;
 pub mod theme;
+#[cfg(feature = "watch")]
+pub mod watch;
+#[cfg(feature = "wayland")]
+pub mod wayland;
+#[cfg(feature = "xpm")]
+pub mod xpm;
+#[cfg(feature = "x11")]
+pub mod xsettings;
 
+#[cfg(all(feature = "openat2", not(target_os = "linux")))]
+compile_error!("the `openat2` feature is only available on Linux");
+
+pub use environment::*;
+pub use error::Error;
 pub use icon::*;
+pub use query::IconQuery;
 pub use search::*;
 pub use theme::Icons;
@@ -0,0 +1,249 @@
+//! Listings of the files in a theme's directories.
+//!
+//! Looking up an icon normally probes the file system for every candidate directory and
+//! extension. For applications that look up many icons, [`IconSearch::with_directory_listing`]
+//! reads every directory of every theme once instead, recording which extensions exist for each
+//! icon name, so that lookups don't touch the file system at all.
+//!
+//! Listings are a snapshot: icons added after the search are not found until the theme is
+//! [rescanned](crate::Icons::rescan_theme) or the icons are [refreshed](crate::Icons::refresh).
+//!
+//! [`IconSearch::with_directory_listing`]: crate::IconSearch::with_directory_listing
+
+use crate::audit::{AccessKind, Audit, allows};
+use crate::hash_map::HashMap;
+use crate::icon::{FileTypes, SkipReason};
+use crate::report::UnreadableDirs;
+use crate::theme::ThemeInfo;
+use std::hash::{Hash, Hasher};
+use std::path::Path;
+
+/// The icon files in the directories of a theme.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct DirectoryListing {
+    icons: HashMap<String, Vec<IconLocation>>,
+}
+
+/// A directory an icon exists in, with the extensions it exists with.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct IconLocation {
+    /// The index of the base directory in the theme's [`base_dirs`](ThemeInfo::base_dirs).
+    pub base_dir: usize,
+    /// The index of the directory in the theme's
+    /// [`directories`](crate::theme::ThemeIndex::directories).
+    pub directory: usize,
+    pub extensions: ExtensionSet,
+}
+
+/// A set of [`FileType`](crate::FileType)s, stored as a bitset of their positions in the theme's
+/// [`FileTypes`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Default)]
+pub struct ExtensionSet(u64);
+
+impl ExtensionSet {
+    pub fn insert(&mut self, position: usize) {
+        self.0 |= Self::bit(position);
+    }
+
+    pub fn contains(&self, position: usize) -> bool {
+        self.0 & Self::bit(position) != 0
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0 == 0
+    }
+
+    fn bit(position: usize) -> u64 {
+        // `FileTypes` holds at most 64 types
+        1 << position
+    }
+}
+
+impl DirectoryListing {
+    /// Lists the icon files in all directories of a theme.
+    ///
+    /// Files that aren't of one of `file_types`, unreadable directories and dangling symbolic
+    /// links are skipped.
+    pub fn build(info: &ThemeInfo, file_types: &FileTypes) -> Self {
+        Self::build_recording(info, file_types, &UnreadableDirs::default(), None, false)
+    }
+
+    /// Like [`build`](Self::build), recording the directories that can't be read in `unreadable`,
+    /// only reading the directories `audit` allows and, if `resolve_beneath`, not following links
+    /// out of the search directories.
+    pub(crate) fn build_recording(
+        info: &ThemeInfo,
+        file_types: &FileTypes,
+        unreadable: &UnreadableDirs,
+        audit: Option<&Audit>,
+        resolve_beneath: bool,
+    ) -> Self {
+        let mut listing = Self::default();
+
+        for (base_idx, base_dir) in info.base_dirs.iter().enumerate() {
+            for (dir_idx, directory) in info.index.directories.iter().enumerate() {
+                let dir = base_dir.join(&directory.directory_name);
+                if !allows(audit, &dir, AccessKind::ListDirectory) {
+                    continue;
+                }
+                let entries = match dir.read_dir() {
+                    Ok(entries) => entries,
+                    Err(e) => {
+                        unreadable.insert(&dir, e.kind());
+                        continue;
+                    }
+                };
+
+                for entry in entries.flatten() {
+                    let path = entry.path();
+                    let Some(file_type) = file_types.from_path(&path) else {
+                        continue;
+                    };
+                    let position = file_types.position(file_type).expect("found in file_types");
+                    let Some(name) = path.file_stem().and_then(|stem| stem.to_str()) else {
+                        continue;
+                    };
+
+                    let is_symlink = entry.file_type().is_ok_and(|ft| ft.is_symlink());
+                    let root = resolve_beneath.then(|| base_dir.parent()).flatten();
+                    if is_symlink
+                        && !(allows(audit, &path, AccessKind::Probe)
+                            && SkipReason::check_in(root, &path).is_ok())
+                    {
+                        continue;
+                    }
+
+                    listing.insert(name, base_idx, dir_idx, position);
+                }
+            }
+        }
+
+        listing
+    }
+
+    /// Lists the icon files of a theme with a single base directory, given their paths relative
+    /// to it, rather than reading the directories.
+    pub(crate) fn from_files<'a>(
+        info: &ThemeInfo,
+        file_types: &FileTypes,
+        files: impl IntoIterator<Item = &'a str> + Clone,
+    ) -> Self {
+        let mut listing = Self::default();
+
+        for (dir_idx, directory) in info.index.directories.iter().enumerate() {
+            for file in files.clone() {
+                let path = Path::new(file);
+                if path.parent() != Some(Path::new(&directory.directory_name)) {
+                    continue;
+                }
+                let Some(file_type) = file_types.from_path(path) else {
+                    continue;
+                };
+                let position = file_types.position(file_type).expect("found in file_types");
+                let Some(name) = path.file_stem().and_then(|stem| stem.to_str()) else {
+                    continue;
+                };
+                listing.insert(name, 0, dir_idx, position);
+            }
+        }
+
+        listing
+    }
+
+    // files must be inserted in the order of base directories and directories.
+    fn insert(&mut self, name: &str, base_idx: usize, dir_idx: usize, position: usize) {
+        let locations = self.icons.entry(name.into()).or_default();
+        match locations.last_mut() {
+            Some(last) if (last.base_dir, last.directory) == (base_idx, dir_idx) => {
+                last.extensions.insert(position);
+            }
+            _ => {
+                let mut extensions = ExtensionSet::default();
+                extensions.insert(position);
+                locations.push(IconLocation {
+                    base_dir: base_idx,
+                    directory: dir_idx,
+                    extensions,
+                });
+            }
+        }
+    }
+
+    /// The directories an icon exists in, in the order of base directories and directories.
+    pub fn locations(&self, icon_name: &str) -> &[IconLocation] {
+        self.icons.get(icon_name).map_or(&[], Vec::as_slice)
+    }
+
+    /// The names of all icons in the listing.
+    pub fn icon_names(&self) -> impl Iterator<Item = &str> {
+        self.icons.keys().map(String::as_str)
+    }
+}
+
+// `HashMap` doesn't implement `Hash`; equal listings always have the same number of icons.
+impl Hash for DirectoryListing {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.icons.len().hash(state);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::listing::DirectoryListing;
+    use crate::theme::ThemeInfo;
+    use crate::{FileType, FileTypes, IconSearch};
+    use std::fs;
+
+    #[test]
+    fn test_listing() {
+        let dir = tempfile::tempdir().unwrap();
+        let theme = dir.path().join("Oak");
+        fs::create_dir_all(theme.join("48x48/apps")).unwrap();
+        fs::create_dir_all(theme.join("scalable/apps")).unwrap();
+        fs::write(
+            theme.join("index.theme"),
+            "[Icon Theme]\nName=Oak\nComment=\nDirectories=48x48/apps,scalable/apps\n\n\
+             [48x48/apps]\nSize=48\n\n[scalable/apps]\nSize=48\nType=Scalable\n",
+        )
+        .unwrap();
+        fs::write(theme.join("48x48/apps/firefox.png"), b"").unwrap();
+        fs::write(theme.join("48x48/apps/firefox.svg"), b"").unwrap();
+        fs::write(theme.join("scalable/apps/firefox.svg"), b"").unwrap();
+        fs::write(theme.join("scalable/apps/README"), b"").unwrap();
+        std::os::unix::fs::symlink("missing.svg", theme.join("scalable/apps/broken.svg")).unwrap();
+
+        let info = ThemeInfo::new_from_folders("Oak".into(), vec![theme]).unwrap();
+        let file_types = FileTypes::new();
+        let listing = DirectoryListing::build(&info, &file_types);
+
+        let position = |file_type| file_types.position(file_type).unwrap();
+        let locations = listing.locations("firefox");
+        assert_eq!(locations.len(), 2);
+        assert_eq!(locations[0].directory, 0);
+        assert!(locations[0].extensions.contains(position(FileType::Png)));
+        assert!(locations[0].extensions.contains(position(FileType::Svg)));
+        assert!(!locations[1].extensions.contains(position(FileType::Png)));
+
+        assert!(listing.locations("broken").is_empty());
+        assert_eq!(listing.icon_names().count(), 1);
+
+        // lookups give the same results with and without listing
+        let probed = IconSearch::new_from(vec![dir.path().into()])
+            .search()
+            .icons();
+        let listed = IconSearch::new_from(vec![dir.path().into()])
+            .with_directory_listing(true)
+            .search()
+            .icons();
+        assert!(listed.theme("Oak").unwrap().listing.is_some());
+
+        for size in [16, 48, 64] {
+            for name in ["firefox", "broken", "missing"] {
+                assert_eq!(
+                    listed.find_icon(name, size, 1, "Oak"),
+                    probed.find_icon(name, size, 1, "Oak")
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,235 @@
+//! Themes compiled into the application.
+//!
+//! Applications that ship their icons inside the binary, e.g. with `include_bytes!` or their own
+//! resource system, can register them as a [`MemoryTheme`] with [`Icons::add_memory_theme`].
+//! Icons are then looked up in it exactly like in themes on disk, including inheritance and size
+//! matching.
+//!
+//! The files of a memory theme have virtual paths below [`MEMORY_ROOT`], which don't exist on
+//! disk. Read them with [`Icons::read_icon`], which reads other icons from disk.
+//!
+//! # Example
+//!
+//! ```
+//! use icon::IconSearch;
+//! use icon::memory::MemoryTheme;
+//!
+//! let theme = MemoryTheme::parse(
+//!     "app-icons",
+//!     b"[Icon Theme]\nName=App\nComment=\nInherits=hicolor\nDirectories=48\n\n[48]\nSize=48\n",
+//! )
+//! .unwrap()
+//! .with_file("48/app-logo.svg", &b"<svg/>"[..]);
+//!
+//! let mut icons = IconSearch::new().search().icons();
+//! icons.add_memory_theme(theme);
+//!
+//! let logo = icons.find_icon("app-logo", 48, 1, "app-icons").unwrap();
+//! assert_eq!(&*icons.read_icon(&logo).unwrap(), b"<svg/>");
+//! ```
+
+use crate::listing::DirectoryListing;
+use crate::report::UnreadableDirs;
+use crate::spec::Compliance;
+use crate::statistics::HitStatistics;
+use crate::theme::{
+    Icons, IndexOrigin, SubDirSelections, Theme, ThemeIndex, ThemeInfo, ThemeParseError,
+};
+use crate::{IconFile, ReadError};
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// The virtual directory memory themes are placed in, e.g. `memory:/app-icons/48/app-logo.svg`.
+pub const MEMORY_ROOT: &str = "memory:";
+
+/// A theme whose index and files are held in memory.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct MemoryTheme {
+    internal_name: String,
+    index: ThemeIndex,
+    /// The files by their path relative to the theme, e.g. `48/app-logo.svg`.
+    files: BTreeMap<String, Arc<[u8]>>,
+}
+
+impl MemoryTheme {
+    pub fn new(internal_name: impl Into<String>, index: ThemeIndex) -> Self {
+        Self {
+            internal_name: internal_name.into(),
+            index,
+            files: BTreeMap::new(),
+        }
+    }
+
+    /// Creates a theme from the contents of its `index.theme`.
+    pub fn parse(internal_name: impl Into<String>, index: &[u8]) -> Result<Self, ThemeParseError> {
+        Ok(Self::new(internal_name, ThemeIndex::parse(index)?))
+    }
+
+    /// Adds a file, by its path relative to the theme, e.g. `48x48/apps/app-logo.png`. Files
+    /// outside the directories of the index are never found.
+    pub fn with_file(mut self, path: impl Into<String>, bytes: impl Into<Arc<[u8]>>) -> Self {
+        self.files.insert(path.into(), bytes.into());
+        self
+    }
+
+    pub fn internal_name(&self) -> &str {
+        &self.internal_name
+    }
+
+    pub fn index(&self) -> &ThemeIndex {
+        &self.index
+    }
+
+    /// The virtual directory this theme's files are in.
+    pub fn base_dir(&self) -> PathBuf {
+        Path::new(MEMORY_ROOT).join(&self.internal_name)
+    }
+
+    /// The contents of a file, by its virtual path.
+    pub fn file(&self, path: &Path) -> Option<&[u8]> {
+        let path = path.strip_prefix(self.base_dir()).ok()?.to_str()?;
+        self.files.get(path).map(|bytes| &**bytes)
+    }
+}
+
+impl Icons {
+    /// Registers a theme held in memory, replacing any theme by the same internal name.
+    ///
+    /// The theme inherits from the themes already present, in the order of its `Inherits`.
+    /// Themes inheriting from it, other than memory themes registered later, aren't affected.
+    /// Memory themes are kept across [refreshes](Self::refresh), but can't be
+    /// [rescanned](Self::rescan_theme).
+    pub fn add_memory_theme(&mut self, theme: MemoryTheme) -> Arc<Theme> {
+        self.memory_themes
+            .retain(|other| other.internal_name != theme.internal_name);
+        self.generation = crate::change::next_generation();
+        self.insert_memory_theme(Arc::new(theme))
+    }
+
+    pub(crate) fn insert_memory_theme(&mut self, memory: Arc<MemoryTheme>) -> Arc<Theme> {
+        let name = memory.internal_name.as_str();
+        let index = &memory.index;
+
+        // like the themes on disk, parents are searched breadth-first, with hicolor last
+        let mut parents = Vec::<Arc<Theme>>::new();
+        let mut add = |theme: &Arc<Theme>| {
+            if theme.info.internal_name != name && !parents.iter().any(|p| Arc::ptr_eq(p, theme)) {
+                parents.push(theme.clone());
+            }
+        };
+        let direct = (index.inherits.iter())
+            .filter_map(|parent| self.theme(parent))
+            .collect::<Vec<_>>();
+        direct.iter().for_each(&mut add);
+        direct
+            .iter()
+            .flat_map(|parent| parent.inherits_from.iter())
+            .for_each(&mut add);
+        if self.compliance == Compliance::Lenient || index.inherits.is_empty() {
+            self.theme("hicolor").iter().for_each(&mut add);
+        }
+        if let Some(position) = (parents.iter()).position(|p| p.info.internal_name == "hicolor") {
+            let hicolor = parents.remove(position);
+            parents.push(hicolor);
+        }
+
+        let info = ThemeInfo {
+            internal_name: name.into(),
+            base_dirs: vec![memory.base_dir()],
+            index_location: memory.base_dir().join("index.theme"),
+            index: index.clone(),
+            index_origin: IndexOrigin::Memory,
+        };
+        let files = memory.files.keys().map(String::as_str);
+        let listing = DirectoryListing::from_files(&info, &self.file_types, files);
+        let statistics = HitStatistics::new(
+            index.directories.len(),
+            self.adaptive_ordering && self.compliance == Compliance::Lenient,
+        );
+
+        let theme = Arc::new(Theme {
+            info,
+            inherits_from: parents.into(),
+            listing: Some(Arc::new(listing)),
+            statistics,
+            unreadable: UnreadableDirs::default(),
+            selections: SubDirSelections::default(),
+            file_types: self.file_types.clone(),
+            compliance: self.compliance,
+            audit: self.audit.clone(),
+            resolve_beneath: self.resolve_beneath,
+            scale_suffixes: self.scale_suffixes,
+        });
+
+        self.themes.insert(name.into(), theme.clone());
+        self.memory_themes.push(memory);
+        theme
+    }
+
+    /// Reads an icon found by this `Icons`, from memory if it belongs to a
+    /// [memory theme](crate::memory), and from disk otherwise.
+    pub fn read_icon(&self, file: &IconFile) -> Result<Cow<'_, [u8]>, ReadError> {
+        let memory = (self.memory_themes.iter()).find_map(|theme| theme.file(&file.path));
+        match memory {
+            Some(bytes) => Ok(Cow::Borrowed(bytes)),
+            None => file.read().map(Cow::Owned),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::IconSearch;
+    use crate::memory::MemoryTheme;
+    use std::fs;
+
+    #[test]
+    fn test_memory_theme() {
+        let dir = tempfile::tempdir().unwrap();
+        let hicolor = dir.path().join("hicolor");
+        fs::create_dir_all(hicolor.join("48")).unwrap();
+        fs::write(
+            hicolor.join("index.theme"),
+            "[Icon Theme]\nName=Hicolor\nComment=Fallback\nDirectories=48\n\n[48]\nSize=48\n",
+        )
+        .unwrap();
+        fs::write(hicolor.join("48/htop.png"), b"htop").unwrap();
+
+        let theme = MemoryTheme::parse(
+            "App",
+            b"[Icon Theme]\nName=App\nComment=\nDirectories=16,48\n\n[16]\nSize=16\n\n[48]\nSize=48\n",
+        )
+        .unwrap()
+        .with_file("16/logo.png", &b"small"[..])
+        .with_file("48/logo.png", &b"large"[..])
+        .with_file("48/logo.svg", &b"<svg/>"[..])
+        .with_file("96/elsewhere.png", &b""[..]);
+
+        let mut icons = IconSearch::new_from(vec![dir.path().into()])
+            .search()
+            .icons();
+        let app = icons.add_memory_theme(theme.clone());
+        assert_eq!(app.inherits_from.len(), 1);
+
+        let read = |icons: &crate::Icons, size| {
+            let icon = icons.find_icon("logo", size, 1, "App").unwrap();
+            icons.read_icon(&icon).unwrap().into_owned()
+        };
+        assert_eq!(read(&icons, 16), b"small");
+        // the file types are preferred as on disk
+        assert_eq!(read(&icons, 48), b"large");
+        assert_eq!(read(&icons, 40), b"<svg/>");
+        assert!(icons.find_icon("elsewhere", 96, 1, "App").is_none());
+
+        // parents on disk are still searched
+        let htop = icons.find_icon("htop", 48, 1, "App").unwrap();
+        assert_eq!(&*icons.read_icon(&htop).unwrap(), b"htop");
+
+        icons.refresh();
+        assert_eq!(read(&icons, 16), b"small");
+        icons.add_memory_theme(theme);
+        assert_eq!(icons.memory_themes.len(), 1);
+    }
+}
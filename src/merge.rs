@@ -0,0 +1,322 @@
+//! Materializing merged ("overlay") themes on disk.
+//!
+//! A merged theme layers the icons of several themes over each other: where more than one layer
+//! provides an icon by the same name in the same directory, the first layer wins. This is useful
+//! for building a personal theme that overrides a handful of icons of a bigger theme.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use icon::Icons;
+//! use icon::merge::ThemeMerge;
+//!
+//! let icons = Icons::new();
+//! let custom = icons.theme("my-custom-icons").unwrap();
+//! let papirus = icons.theme("Papirus").unwrap();
+//!
+//! let home = std::env::home_dir().unwrap();
+//! ThemeMerge::new("Papirus-custom")
+//!     .layer(&custom)
+//!     .layer(&papirus)
+//!     .write_to(&home.join(".local/share/icons"))
+//!     .unwrap();
+//! ```
+
+use crate::edit::IndexDocument;
+use crate::theme::{DirectoryIndex, Theme, ThemeIndex};
+use std::collections::btree_map::Entry;
+use std::collections::{BTreeMap, HashSet};
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+
+/// How icons of the source themes end up in the merged theme.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash, Default)]
+pub enum LinkMode {
+    /// Create symbolic links to the original files.
+    #[default]
+    Symlink,
+    /// Copy the original files.
+    Copy,
+}
+
+/// Builder for a merged theme.
+#[derive(Debug, Clone)]
+pub struct ThemeMerge<'a> {
+    internal_name: String,
+    name: Option<String>,
+    comment: Option<String>,
+    inherits: Option<Vec<String>>,
+    layers: Vec<&'a Theme>,
+    link_mode: LinkMode,
+}
+
+/// Summary of a merged theme written to disk.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MergeReport {
+    /// The directory of the merged theme.
+    pub theme_dir: PathBuf,
+    /// The amount of icon files linked or copied into the merged theme.
+    pub icons: usize,
+    /// The amount of icon files that were hidden by a layer with a higher priority.
+    pub shadowed: usize,
+}
+
+impl<'a> ThemeMerge<'a> {
+    /// Starts a merged theme with the given internal name, which will be the name of its directory.
+    pub fn new(internal_name: impl Into<String>) -> Self {
+        Self {
+            internal_name: internal_name.into(),
+            name: None,
+            comment: None,
+            inherits: None,
+            layers: Vec::new(),
+            link_mode: LinkMode::default(),
+        }
+    }
+
+    /// Sets the display name of the merged theme. Defaults to the internal name.
+    pub fn name(mut self, name: impl Into<String>) -> Self {
+        self.name = Some(name.into());
+        self
+    }
+
+    /// Sets the comment of the merged theme.
+    pub fn comment(mut self, comment: impl Into<String>) -> Self {
+        self.comment = Some(comment.into());
+        self
+    }
+
+    /// Sets the themes the merged theme inherits from.
+    ///
+    /// By default, the merged theme inherits from everything its layers inherit from (except for
+    /// the layers themselves).
+    pub fn inherits<I, S>(mut self, inherits: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.inherits = Some(inherits.into_iter().map(Into::into).collect());
+        self
+    }
+
+    /// Adds a layer. Layers added first take precedence over layers added later. Of several
+    /// files of an icon in the same directory of a layer, e.g. a PNG and an SVG, only the one of
+    /// the theme's preferred [file type](crate::IconSearch::with_file_type) is used.
+    ///
+    /// Only the icons of the theme itself are used, not those of the themes it inherits from.
+    pub fn layer(mut self, theme: &'a Theme) -> Self {
+        self.layers.push(theme);
+        self
+    }
+
+    /// Sets whether icons are symlinked (the default) or copied.
+    pub fn link_mode(mut self, link_mode: LinkMode) -> Self {
+        self.link_mode = link_mode;
+        self
+    }
+
+    /// Writes the merged theme into `icons_dir`, e.g. `~/.local/share/icons`.
+    ///
+    /// Files in an existing theme directory of the same name are replaced, but files not part of
+    /// the merged theme are left alone.
+    pub fn write_to(&self, icons_dir: &Path) -> std::io::Result<MergeReport> {
+        let theme_dir = icons_dir.join(&self.internal_name);
+        std::fs::create_dir_all(&theme_dir)?;
+
+        let mut directories: Vec<DirectoryIndex> = Vec::new();
+        // (directory name, icon name) pairs that have been claimed by a layer
+        let mut claimed: HashSet<(String, OsString)> = HashSet::new();
+        let mut icons = 0;
+        let mut shadowed = 0;
+
+        for layer in &self.layers {
+            // of the files of an icon in a directory, the layer's preferred file type wins,
+            // whatever order they are listed in
+            let mut files = BTreeMap::new();
+            for (sub_dir, file) in layer.iter_files() {
+                let Some(stem) = file.path.file_stem() else {
+                    continue;
+                };
+                let key = (sub_dir.directory_name.clone(), stem.to_owned());
+                let rank = (layer.file_types.position(file.file_type)).unwrap_or(usize::MAX);
+                match files.entry(key) {
+                    Entry::Vacant(entry) => {
+                        entry.insert((rank, sub_dir, file));
+                    }
+                    Entry::Occupied(mut entry) => {
+                        shadowed += 1;
+                        if rank < entry.get().0 {
+                            entry.insert((rank, sub_dir, file));
+                        }
+                    }
+                }
+            }
+
+            for ((name, stem), (_, sub_dir, file)) in files {
+                if !claimed.insert((name.clone(), stem)) {
+                    shadowed += 1;
+                    continue;
+                }
+                let name = name.as_str();
+
+                if !directories.iter().any(|d| d.directory_name == name) {
+                    std::fs::create_dir_all(theme_dir.join(name))?;
+                    directories.push(sub_dir.clone());
+                }
+
+                // unwrap OK: `from_path` requires an extension, so there is a file name
+                let target = theme_dir.join(name).join(file.path.file_name().unwrap());
+                self.place(&file.path, &target)?;
+                icons += 1;
+            }
+        }
+
+        let inherits = match &self.inherits {
+            Some(inherits) => inherits.clone(),
+            None => self.default_inherits(),
+        };
+
+        let index = ThemeIndex {
+            name: self
+                .name
+                .clone()
+                .unwrap_or_else(|| self.internal_name.clone()),
+            comment: self.comment.clone().unwrap_or_default(),
+            inherits: inherits.into(),
+            directories: directories.into(),
+            hidden: false,
+            example: None,
+            groups: BTreeMap::new(),
+        };
+
+        IndexDocument::from(&index).write_to_file(&theme_dir.join("index.theme"))?;
+
+        Ok(MergeReport {
+            theme_dir,
+            icons,
+            shadowed,
+        })
+    }
+
+    fn default_inherits(&self) -> Vec<String> {
+        let mut inherits: Vec<String> = Vec::new();
+
+        for parent in self.layers.iter().flat_map(|l| &l.info.index.inherits) {
+            let is_layer = self
+                .layers
+                .iter()
+                .any(|layer| layer.info.internal_name == *parent);
+
+            if !is_layer && !inherits.contains(parent) {
+                inherits.push(parent.clone());
+            }
+        }
+
+        inherits
+    }
+
+    fn place(&self, source: &Path, target: &Path) -> std::io::Result<()> {
+        if target.symlink_metadata().is_ok() {
+            std::fs::remove_file(target)?;
+        }
+        // links relative to the working directory would dangle in the merged theme
+        let source = std::path::absolute(source)?;
+
+        match self.link_mode {
+            #[cfg(unix)]
+            LinkMode::Symlink => std::os::unix::fs::symlink(source, target),
+            #[cfg(not(unix))]
+            LinkMode::Symlink => std::fs::copy(source, target).map(drop),
+            LinkMode::Copy => std::fs::copy(source, target).map(drop),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::IconSearch;
+    use crate::fixture::ThemeFixture;
+    use crate::merge::{LinkMode, ThemeMerge};
+    use std::fs;
+    use std::path::Path;
+
+    fn write_layer(icons_dir: &Path, name: &str, inherits: &str, icons: &[&str]) {
+        let mut theme = ThemeFixture::new(name)
+            .with_inherits(inherits)
+            .with_section("16x16/apps", "Size=16\nType=Fixed\n");
+        for icon in icons {
+            theme = theme.with_file(format!("16x16/apps/{icon}"), name);
+        }
+        theme.write(&icons_dir.join(name)).unwrap();
+    }
+
+    #[test]
+    fn test_merge_layers() {
+        let sources = tempfile::tempdir().unwrap();
+        let output = tempfile::tempdir().unwrap();
+
+        write_layer(sources.path(), "custom", "base", &["firefox.svg"]);
+        write_layer(
+            sources.path(),
+            "base",
+            "hicolor",
+            &["firefox.png", "htop.png"],
+        );
+
+        let icons = IconSearch::new_from(vec![sources.path().into()])
+            .search()
+            .icons();
+
+        let report = ThemeMerge::new("merged")
+            .layer(&icons.theme("custom").unwrap())
+            .layer(&icons.theme("base").unwrap())
+            .link_mode(LinkMode::Copy)
+            .write_to(output.path())
+            .unwrap();
+
+        assert_eq!(report.icons, 2);
+        assert_eq!(report.shadowed, 1);
+
+        let merged = IconSearch::new_from(vec![output.path().into()])
+            .search()
+            .icons();
+        let theme = merged.theme("merged").unwrap();
+        assert_eq!(*theme.info.index.inherits, ["hicolor"]);
+
+        let firefox = theme.find_icon("firefox", 16, 1).unwrap();
+        assert_eq!(fs::read_to_string(firefox.path).unwrap(), "custom");
+        let htop = theme.find_icon("htop", 16, 1).unwrap();
+        assert_eq!(fs::read_to_string(htop.path).unwrap(), "base");
+    }
+
+    #[test]
+    fn test_merge_prefers_file_type() {
+        let sources = tempfile::tempdir().unwrap();
+        let output = tempfile::tempdir().unwrap();
+
+        write_layer(
+            sources.path(),
+            "base",
+            "hicolor",
+            &["gimp.xpm", "gimp.svg", "gimp.png", "htop.svg"],
+        );
+        let icons = IconSearch::new_from(vec![sources.path().into()])
+            .search()
+            .icons();
+
+        let report = ThemeMerge::new("merged")
+            .layer(&icons.theme("base").unwrap())
+            .write_to(output.path())
+            .unwrap();
+        assert_eq!((report.icons, report.shadowed), (2, 2));
+
+        let apps = output.path().join("merged/16x16/apps");
+        let mut files = fs::read_dir(&apps)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name())
+            .collect::<Vec<_>>();
+        files.sort();
+        assert_eq!(files, ["gimp.png", "htop.svg"]);
+        assert!(fs::read_link(apps.join("gimp.png")).unwrap().is_absolute());
+    }
+}
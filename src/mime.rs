@@ -0,0 +1,388 @@
+//! Icons of files, as shown by file managers.
+//!
+//! A file is shown with the icon of its MIME type, e.g. `text-x-python` for `text/x-python`,
+//! falling back to the generic icon of its media type, e.g. `text-x-generic`. On top of it,
+//! emblems tell whether the file is a symbolic link, read-only, unreadable or shared.
+//!
+//! Detecting MIME types is left to a [`MimeDetector`]. [`GlobDetector`] matches file names
+//! against the globs of the shared MIME-info database, which is enough for most files; callers
+//! that sniff contents can plug in their own detector.
+//!
+//! # Example
+//!
+//! ```
+//! use icon::Icons;
+//! use icon::mime::GlobDetector;
+//!
+//! let icons = Icons::new();
+//! let detector = GlobDetector::load();
+//! if let Some(file) = icons.icon_for_file("/etc/hostname", &detector, 48, 1, "Adwaita") {
+//!     println!("{} with {} emblems", file.mime_type, file.emblems.len());
+//! }
+//! ```
+
+use crate::IconFile;
+use crate::audit::{AccessKind, allows};
+use crate::environment::Environment;
+use crate::theme::Icons;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+
+/// The MIME type of files no detector recognizes.
+pub const UNKNOWN_MIME_TYPE: &str = "application/octet-stream";
+
+/// The MIME type of directories.
+pub const DIRECTORY_MIME_TYPE: &str = "inode/directory";
+
+/// The directories of the shared MIME-info database, `mime` in each XDG data directory, in order of
+/// precedence.
+///
+/// See [`Environment::all_data_dirs`] to avoid reading the environment of the current process.
+pub fn mime_dirs() -> Vec<PathBuf> {
+    let dirs = Environment::current().all_data_dirs();
+    dirs.into_iter().map(|dir| dir.join("mime")).collect()
+}
+
+/// Detects the MIME type of files.
+///
+/// This is implemented for closures, so `&|_: &Path| None` treats every file as
+/// [unknown](UNKNOWN_MIME_TYPE).
+pub trait MimeDetector {
+    /// The MIME type of the file at `path`, or `None` if it isn't known.
+    fn mime_type(&self, path: &Path) -> Option<String>;
+
+    /// Whether the file at `path` is shared with other users, e.g. as a network share. No file is
+    /// by default.
+    fn is_shared(&self, _path: &Path) -> bool {
+        false
+    }
+}
+
+impl<F: Fn(&Path) -> Option<String>> MimeDetector for F {
+    fn mime_type(&self, path: &Path) -> Option<String> {
+        self(path)
+    }
+}
+
+/// A glob of the shared MIME-info database.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct Glob {
+    weight: u32,
+    mime_type: String,
+    pattern: String,
+    case_sensitive: bool,
+}
+
+impl Glob {
+    fn matches(&self, file_name: &str) -> bool {
+        let (pattern, file_name) = match self.case_sensitive {
+            true => (self.pattern.clone(), file_name.to_owned()),
+            false => (self.pattern.to_lowercase(), file_name.to_lowercase()),
+        };
+        match pattern.strip_prefix('*') {
+            Some(suffix) => file_name.ends_with(suffix),
+            None => file_name == pattern,
+        }
+    }
+}
+
+/// Detects MIME types by matching file names against the `globs2` files of the shared MIME-info
+/// database.
+///
+/// Only the globs of the form `*.ext` and literal file names are supported, which are nearly all
+/// of them. Of the globs matching a file, the one with the highest weight wins, then the longest.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Default)]
+pub struct GlobDetector {
+    globs: Vec<Glob>,
+}
+
+impl GlobDetector {
+    /// Loads the globs of the MIME databases in [`mime_dirs`].
+    pub fn load() -> Self {
+        Self::load_from(mime_dirs())
+    }
+
+    /// Loads the `globs2` files of the MIME databases in the given directories, e.g.
+    /// `/usr/share/mime`. Directories without one are skipped.
+    pub fn load_from<I, P>(dirs: I) -> Self
+    where
+        I: IntoIterator<Item = P>,
+        P: AsRef<Path>,
+    {
+        let mut detector = Self::default();
+        for dir in dirs {
+            if let Ok(globs) = std::fs::read_to_string(dir.as_ref().join("globs2")) {
+                detector.add_globs(&globs);
+            }
+        }
+        detector
+    }
+
+    /// Parses the contents of a `globs2` file, which has a `weight:type:glob[:flags]` per line.
+    pub fn parse(globs: &str) -> Self {
+        let mut detector = Self::default();
+        detector.add_globs(globs);
+        detector
+    }
+
+    fn add_globs(&mut self, globs: &str) {
+        for line in globs.lines().filter(|line| !line.starts_with('#')) {
+            let mut fields = line.split(':');
+            let (Some(weight), Some(mime_type), Some(pattern)) =
+                (fields.next(), fields.next(), fields.next())
+            else {
+                continue;
+            };
+            let Ok(weight) = weight.parse() else {
+                continue;
+            };
+            let supported = match pattern.strip_prefix('*') {
+                Some(suffix) => !suffix.contains(['*', '?', '[']),
+                None => !pattern.is_empty() && !pattern.contains(['*', '?', '[']),
+            };
+            if !supported {
+                continue;
+            }
+
+            let case_sensitive = fields
+                .next()
+                .is_some_and(|flags| flags.split(',').any(|flag| flag == "cs"));
+            self.globs.push(Glob {
+                weight,
+                mime_type: mime_type.into(),
+                pattern: pattern.into(),
+                case_sensitive,
+            });
+        }
+    }
+}
+
+impl MimeDetector for GlobDetector {
+    fn mime_type(&self, path: &Path) -> Option<String> {
+        let file_name = path.file_name()?.to_str()?;
+        let glob = (self.globs.iter())
+            .filter(|glob| glob.matches(file_name))
+            // `max_by_key` picks the last of equal globs, so the earlier ones are reversed first
+            .rev()
+            .max_by_key(|glob| (glob.weight, glob.pattern.len()))?;
+        Some(glob.mime_type.clone())
+    }
+}
+
+/// The names of the icons for a MIME type, from the most to the least specific.
+///
+/// That is the type with the `/` replaced by a `-`, e.g. `text-x-python`, the generic icon of its
+/// media type, e.g. `text-x-generic`, and `application-x-generic`. Directories fall back to
+/// `folder`.
+pub fn mime_icon_names(mime_type: &str) -> Vec<String> {
+    let mut names = vec![mime_type.replace('/', "-")];
+    match mime_type.split_once('/') {
+        Some(("inode", "directory")) => names.push("folder".into()),
+        Some(("inode", _)) | None => {}
+        Some((media, _)) => names.push(format!("{media}-x-generic")),
+    }
+    names.push("application-x-generic".into());
+    names.dedup();
+    names
+}
+
+/// An emblem shown on top of the icon of a file.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum Emblem {
+    SymbolicLink,
+    /// Nobody may write to the file.
+    ReadOnly,
+    /// Nobody may read the file.
+    Unreadable,
+    /// The file is [shared](MimeDetector::is_shared).
+    Shared,
+}
+
+impl Emblem {
+    /// The name of the emblem's icon, e.g. `emblem-symbolic-link`.
+    pub fn icon_name(&self) -> &'static str {
+        match self {
+            Emblem::SymbolicLink => "emblem-symbolic-link",
+            Emblem::ReadOnly => "emblem-readonly",
+            Emblem::Unreadable => "emblem-unreadable",
+            Emblem::Shared => "emblem-shared",
+        }
+    }
+}
+
+/// The icon of a file found by [`Icons::icon_for_file`].
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct FileIcon {
+    /// The detected MIME type of the file.
+    pub mime_type: String,
+    /// The icon of the MIME type, if the theme has one.
+    pub icon: Option<IconFile>,
+    /// The emblems that apply to the file and their icons, leaving out emblems without one.
+    pub emblems: Vec<(Emblem, IconFile)>,
+}
+
+impl Icons {
+    /// Looks up the icon of the file at `path` as a file manager would show it at `size` and
+    /// `scale`, with its MIME type detected by `detector`.
+    ///
+    /// The icon is the first of the [names of its MIME type](mime_icon_names) found in the
+    /// nearest theme of `theme`'s chain, as [`choose_icon`](Self::choose_icon) looks them up.
+    /// Files the detector doesn't recognize are [unknown](UNKNOWN_MIME_TYPE), or
+    /// [directories](DIRECTORY_MIME_TYPE). Emblems are looked up at half the size, as they cover
+    /// a corner of the icon.
+    ///
+    /// Returns `None` if the file doesn't exist or the [audit](crate::audit) doesn't allow
+    /// probing it.
+    pub fn icon_for_file(
+        &self,
+        path: impl AsRef<Path>,
+        detector: &dyn MimeDetector,
+        size: u32,
+        scale: u32,
+        theme: &str,
+    ) -> Option<FileIcon> {
+        let path = path.as_ref();
+        if !allows(self.audit.as_ref(), path, AccessKind::Probe) {
+            return None;
+        }
+        let link = path.symlink_metadata().ok()?;
+        // a dangling link is still shown, as the link itself
+        let metadata = path.metadata().unwrap_or_else(|_| link.clone());
+
+        let mime_type = detector.mime_type(path).unwrap_or_else(|| {
+            let fallback = match metadata.is_dir() {
+                true => DIRECTORY_MIME_TYPE,
+                false => UNKNOWN_MIME_TYPE,
+            };
+            fallback.into()
+        });
+        let icon = self.find_first_icon(&mime_icon_names(&mime_type), size, scale, theme);
+
+        let mode = metadata.permissions().mode();
+        let emblems = [
+            (Emblem::SymbolicLink, link.is_symlink()),
+            (Emblem::ReadOnly, metadata.permissions().readonly()),
+            (Emblem::Unreadable, mode & 0o444 == 0),
+            (Emblem::Shared, detector.is_shared(path)),
+        ];
+        let emblem_size = (size / 2).max(1);
+        let emblems = emblems
+            .into_iter()
+            .filter(|(_, applies)| *applies)
+            .filter_map(|(emblem, _)| {
+                let icon = self.find_icon(emblem.icon_name(), emblem_size, scale, theme)?;
+                Some((emblem, icon))
+            })
+            .collect();
+
+        Some(FileIcon {
+            mime_type,
+            icon,
+            emblems,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::IconSearch;
+    use crate::mime::{Emblem, GlobDetector, MimeDetector, mime_icon_names};
+    use std::fs;
+    use std::os::unix::fs::PermissionsExt;
+    use std::path::Path;
+
+    #[test]
+    fn test_glob_detector() {
+        let detector = GlobDetector::parse(
+            "# comment\n\
+             50:text/x-python:*.py\n\
+             50:text/x-csrc:*.c:cs\n\
+             50:text/x-c++src:*.C:cs\n\
+             10:text/x-makefile:makefile\n\
+             50:application/x-compressed-tar:*.tar.gz\n\
+             50:application/gzip:*.gz\n\
+             50:text/x-readme:README*\n",
+        );
+        let detect = |name: &str| detector.mime_type(Path::new(name));
+        assert_eq!(detect("/src/main.PY").as_deref(), Some("text/x-python"));
+        assert_eq!(detect("main.c").as_deref(), Some("text/x-csrc"));
+        assert_eq!(detect("main.C").as_deref(), Some("text/x-c++src"));
+        assert_eq!(detect("Makefile").as_deref(), Some("text/x-makefile"));
+        assert_eq!(
+            detect("a.tar.gz").as_deref(),
+            Some("application/x-compressed-tar")
+        );
+        assert_eq!(detect("README.md"), None);
+
+        assert_eq!(
+            mime_icon_names("text/x-python"),
+            ["text-x-python", "text-x-generic", "application-x-generic"]
+        );
+        assert_eq!(
+            mime_icon_names("application/x-generic"),
+            ["application-x-generic"]
+        );
+        assert_eq!(mime_icon_names("inode/directory")[1], "folder");
+    }
+
+    #[test]
+    fn test_icon_for_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let theme = dir.path().join("icons/hicolor");
+        fs::create_dir_all(theme.join("48")).unwrap();
+        fs::create_dir_all(theme.join("24")).unwrap();
+        fs::write(
+            theme.join("index.theme"),
+            "[Icon Theme]\nName=Hicolor\nDirectories=24,48\n\n[24]\nSize=24\n\n[48]\nSize=48\n",
+        )
+        .unwrap();
+        for icon in ["48/text-x-generic", "48/folder", "24/emblem-symbolic-link"] {
+            fs::write(theme.join(format!("{icon}.svg")), "").unwrap();
+        }
+        let files = dir.path().join("files");
+        fs::create_dir_all(files.join("src")).unwrap();
+        fs::write(files.join("notes.txt"), "").unwrap();
+        fs::set_permissions(files.join("notes.txt"), fs::Permissions::from_mode(0o444)).unwrap();
+        std::os::unix::fs::symlink(files.join("notes.txt"), files.join("link.txt")).unwrap();
+
+        let icons = IconSearch::new_from(vec![dir.path().join("icons")])
+            .search()
+            .icons();
+        let detector = GlobDetector::parse("50:text/plain:*.txt\n");
+
+        let notes = icons
+            .icon_for_file(files.join("notes.txt"), &detector, 48, 1, "hicolor")
+            .unwrap();
+        assert_eq!(notes.mime_type, "text/plain");
+        assert_eq!(
+            notes.icon.unwrap().path,
+            theme.join("48/text-x-generic.svg")
+        );
+        // the theme has no read-only emblem
+        assert!(notes.emblems.is_empty());
+
+        let link = icons
+            .icon_for_file(files.join("link.txt"), &detector, 48, 1, "hicolor")
+            .unwrap();
+        let emblems = (link.emblems.iter())
+            .map(|(emblem, icon)| (*emblem, icon.path.clone()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            emblems,
+            [(
+                Emblem::SymbolicLink,
+                theme.join("24/emblem-symbolic-link.svg")
+            )]
+        );
+
+        let src = icons
+            .icon_for_file(files.join("src"), &|_: &Path| None, 48, 1, "hicolor")
+            .unwrap();
+        assert_eq!(src.mime_type, "inode/directory");
+        assert_eq!(src.icon.unwrap().path, theme.join("48/folder.svg"));
+
+        assert!(
+            (icons.icon_for_file(files.join("missing"), &detector, 48, 1, "hicolor")).is_none()
+        );
+    }
+}
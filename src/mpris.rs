@@ -0,0 +1,261 @@
+//! Resolving the artwork media applets show for MPRIS players.
+//!
+//! Media players announce the art of the current track in the `mpris:artUrl` metadata, which is a
+//! `file://` URL, a web URL, or missing. Applets fall back to the player's icon, and finally to a
+//! generic one. [`Icons::find_player_art`] implements that ladder, leaving downloading to an
+//! [`ArtFetcher`] supplied by the caller.
+//!
+//! # Example
+//!
+//! ```
+//! use icon::Icons;
+//! use icon::mpris::PlayerMetadata;
+//!
+//! let icons = Icons::new();
+//! let player = PlayerMetadata {
+//!     art_url: Some("https://example.com/cover.jpg".into()),
+//!     desktop_entry: Some("org.gnome.Rhythmbox3".into()),
+//! };
+//! // without network access, the player's icon is used
+//! let art = icons.find_player_art(&player, &|_: &str| None, 64, 1, "Adwaita");
+//! ```
+
+use crate::IconMatch;
+use crate::audit::{AccessKind, allows};
+use crate::desktop::{application_dirs, desktop_entries};
+use crate::theme::Icons;
+use std::ffi::OsString;
+use std::os::unix::ffi::OsStringExt;
+use std::path::{Path, PathBuf};
+
+/// The icon shown when neither the track nor the player has one.
+pub const GENERIC_PLAYER_ICON: &str = "multimedia-player";
+
+/// Downloads remote art, e.g. into a cache directory, returning where it was saved.
+///
+/// This is implemented for closures, so `&|_: &str| None` skips remote art.
+pub trait ArtFetcher {
+    fn fetch(&self, url: &str) -> Option<PathBuf>;
+}
+
+impl<F: Fn(&str) -> Option<PathBuf>> ArtFetcher for F {
+    fn fetch(&self, url: &str) -> Option<PathBuf> {
+        self(url)
+    }
+}
+
+/// What an MPRIS player tells about itself and its current track.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Default)]
+pub struct PlayerMetadata {
+    /// The `mpris:artUrl` of the current track.
+    pub art_url: Option<String>,
+    /// The player's `DesktopEntry` property: its desktop file ID without `.desktop`.
+    pub desktop_entry: Option<String>,
+}
+
+/// The art found by [`Icons::find_player_art`].
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum PlayerArt {
+    /// The track's art, as a local file.
+    Track(PathBuf),
+    /// The player's icon.
+    Player(IconMatch),
+    /// The [generic icon](GENERIC_PLAYER_ICON), as the player doesn't have one.
+    Generic(IconMatch),
+}
+
+impl PlayerArt {
+    /// The path of the art, whichever kind it is.
+    pub fn path(&self) -> &Path {
+        match self {
+            PlayerArt::Track(path) => path,
+            PlayerArt::Player(icon) | PlayerArt::Generic(icon) => &icon.file.path,
+        }
+    }
+}
+
+/// The local path a `file://` URL refers to.
+pub fn file_url_path(url: &str) -> Option<PathBuf> {
+    let path = url.strip_prefix("file://")?;
+    // only local files can be used, the host is either empty or `localhost`
+    let path = path.strip_prefix("localhost").unwrap_or(path);
+    if !path.starts_with('/') {
+        return None;
+    }
+
+    let mut bytes = Vec::with_capacity(path.len());
+    let mut rest = path.as_bytes();
+    while let Some((&byte, tail)) = rest.split_first() {
+        let escaped = (byte == b'%')
+            .then(|| tail.get(..2))
+            .flatten()
+            .and_then(|hex| u8::from_str_radix(std::str::from_utf8(hex).ok()?, 16).ok());
+        match escaped {
+            Some(byte) => {
+                bytes.push(byte);
+                rest = &tail[2..];
+            }
+            None => {
+                bytes.push(byte);
+                rest = tail;
+            }
+        }
+    }
+
+    Some(OsString::from_vec(bytes).into())
+}
+
+impl Icons {
+    /// Resolves the art a media applet should show for a player, trying in order
+    ///
+    /// 1. the track's art, if it is a `file://` URL of an existing file,
+    /// 2. the track's art fetched by `fetcher`, if it is any other URL,
+    /// 3. the [icon of the player's desktop entry](Self::icon_for_desktop_file), or the desktop
+    ///    entry ID as an icon name if the entry isn't installed,
+    /// 4. the [generic player icon](GENERIC_PLAYER_ICON).
+    ///
+    /// Icons are looked up in `theme`.
+    pub fn find_player_art(
+        &self,
+        player: &PlayerMetadata,
+        fetcher: &dyn ArtFetcher,
+        size: u32,
+        scale: u32,
+        theme: &str,
+    ) -> Option<PlayerArt> {
+        self.find_player_art_in(application_dirs(), player, fetcher, size, scale, theme)
+    }
+
+    /// Like [`find_player_art`](Self::find_player_art), but with the desktop entries in the given
+    /// directories.
+    pub fn find_player_art_in<I, P>(
+        &self,
+        dirs: I,
+        player: &PlayerMetadata,
+        fetcher: &dyn ArtFetcher,
+        size: u32,
+        scale: u32,
+        theme: &str,
+    ) -> Option<PlayerArt>
+    where
+        I: IntoIterator<Item = P>,
+        P: AsRef<Path>,
+    {
+        let exists =
+            |path: &Path| allows(self.audit.as_ref(), path, AccessKind::Probe) && path.is_file();
+        let track = (player.art_url.as_deref())
+            .filter(|url| !url.is_empty())
+            .and_then(|url| match url.starts_with("file:") {
+                true => file_url_path(url),
+                false => fetcher.fetch(url),
+            })
+            .filter(|path| exists(path));
+        if let Some(path) = track {
+            return Some(PlayerArt::Track(path));
+        }
+
+        let desktop_id = player.desktop_entry.as_deref().filter(|id| !id.is_empty());
+        let icon = desktop_id.and_then(|id| {
+            let file_name = format!("{id}.desktop");
+            let entry = desktop_entries(dirs)
+                .into_iter()
+                .find(|(desktop_id, _)| *desktop_id == file_name);
+            match entry {
+                Some((_, path)) => {
+                    Some(self.icon_for_desktop_file(&path, size, scale, theme)?.icon)
+                }
+                None => self.find_icon_match(id, size, scale, theme),
+            }
+        });
+        if let Some(icon) = icon {
+            return Some(PlayerArt::Player(icon));
+        }
+
+        let generic = self.find_icon_match(GENERIC_PLAYER_ICON, size, scale, theme)?;
+        Some(PlayerArt::Generic(generic))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::IconSearch;
+    use crate::mpris::{PlayerArt, PlayerMetadata, file_url_path};
+    use std::fs;
+    use std::path::{Path, PathBuf};
+
+    #[test]
+    fn test_file_url_path() {
+        assert_eq!(
+            file_url_path("file:///home/user/My%20Music/cover.jpg"),
+            Some("/home/user/My Music/cover.jpg".into())
+        );
+        assert_eq!(
+            file_url_path("file://localhost/tmp/a%2"),
+            Some("/tmp/a%2".into())
+        );
+        assert_eq!(file_url_path("file://example.com/cover.jpg"), None);
+        assert_eq!(file_url_path("https://example.com/cover.jpg"), None);
+    }
+
+    #[test]
+    fn test_find_player_art() {
+        let dir = tempfile::tempdir().unwrap();
+        let apps = dir.path().join("applications");
+        let theme = dir.path().join("icons/hicolor");
+        fs::create_dir_all(&apps).unwrap();
+        fs::create_dir_all(theme.join("64x64/apps")).unwrap();
+        fs::write(
+            theme.join("index.theme"),
+            "[Icon Theme]\nName=Hicolor\nDirectories=64x64/apps\n\n[64x64/apps]\nSize=64\n",
+        )
+        .unwrap();
+        for icon in ["rhythmbox", "vlc", "multimedia-player"] {
+            fs::write(theme.join(format!("64x64/apps/{icon}.png")), "").unwrap();
+        }
+        fs::write(
+            apps.join("org.gnome.Rhythmbox3.desktop"),
+            "[Desktop Entry]\nType=Application\nName=Rhythmbox\nIcon=rhythmbox\n",
+        )
+        .unwrap();
+        let cover = dir.path().join("my cover.png");
+        fs::write(&cover, "").unwrap();
+
+        let icons = IconSearch::new_from(vec![dir.path().join("icons")])
+            .search()
+            .icons();
+        let cached = cover.clone();
+        let fetcher = move |url: &str| (url == "https://example.com/a.png").then(|| cached.clone());
+        let find = |art_url: Option<&str>, desktop_entry: Option<&str>| {
+            let player = PlayerMetadata {
+                art_url: art_url.map(Into::into),
+                desktop_entry: desktop_entry.map(Into::into),
+            };
+            let art = icons.find_player_art_in([&apps], &player, &fetcher, 64, 1, "hicolor");
+            art.map(|art| (art.path().to_owned(), matches!(art, PlayerArt::Track(_))))
+        };
+        let icon = |name: &str| -> PathBuf { theme.join(format!("64x64/apps/{name}.png")) };
+
+        let url = format!("file://{}", cover.display()).replace(' ', "%20");
+        assert_eq!(find(Some(&url), None), Some((cover.clone(), true)));
+        assert_eq!(
+            find(Some("https://example.com/a.png"), None),
+            Some((cover.clone(), true))
+        );
+        assert_eq!(
+            find(
+                Some("https://example.com/b.png"),
+                Some("org.gnome.Rhythmbox3")
+            ),
+            Some((icon("rhythmbox"), false))
+        );
+        assert_eq!(
+            find(Some("file:///missing.png"), Some("vlc")),
+            Some((icon("vlc"), false))
+        );
+        assert_eq!(
+            find(None, Some("unknown")),
+            Some((icon("multimedia-player"), false))
+        );
+        assert!(Path::new(&find(None, None).unwrap().0).ends_with("multimedia-player.png"));
+    }
+}
@@ -0,0 +1,351 @@
+//! Icon names as laid out by the
+//! [icon naming specification](https://specifications.freedesktop.org/icon-naming-spec/latest/).
+//!
+//! Icon names are made of `-` separated segments, from the most generic to the most specific,
+//! e.g. `network-wireless-signal-excellent`. When a theme lacks a specific icon, the
+//! specification has applications fall back to the more generic ones by removing segments from
+//! the end, which [`IconSearch::with_name_fallback`](crate::IconSearch::with_name_fallback) turns
+//! on for [`Icons::find_icon`](crate::Icons::find_icon).
+//!
+//! Icons that point in a direction, such as `go-next` or `media-playback-start`, may have
+//! variants for left-to-right and right-to-left text, e.g. `go-next-rtl`.
+//! [`Icons::find_icon_directional`] prefers the variant for a [`TextDirection`].
+//!
+//! Symbolic icons, named `*-symbolic`, are monochrome variants for panels and toolbars.
+//! [`Icons::find_icon_symbolic`] finds them or their full-color counterparts as requested by
+//! [`Symbolic`].
+//!
+//! # Example
+//!
+//! ```
+//! use icon::naming::fallback_names;
+//!
+//! assert_eq!(
+//!     fallback_names("network-wireless-signal-excellent"),
+//!     [
+//!         "network-wireless-signal-excellent",
+//!         "network-wireless-signal",
+//!         "network-wireless",
+//!         "network",
+//!     ]
+//! );
+//! ```
+
+use crate::theme::Icons;
+use crate::{Environment, IconFile, IconQuery};
+
+/// The suffix of symbolic icons, which is kept when falling back.
+pub const SYMBOLIC_SUFFIX: &str = "-symbolic";
+
+/// The names to look up for `icon_name`, starting with the name itself and removing one segment
+/// from the end at a time.
+///
+/// As GTK does, `-symbolic` isn't a segment: `network-wireless-symbolic` falls back to
+/// `network-symbolic`.
+pub fn fallback_names(icon_name: &str) -> Vec<String> {
+    let (mut base, suffix) = match icon_name.strip_suffix(SYMBOLIC_SUFFIX) {
+        Some(base) if !base.is_empty() => (base, SYMBOLIC_SUFFIX),
+        _ => (icon_name, ""),
+    };
+
+    let mut names = vec![icon_name.to_owned()];
+    while let Some((generic, _)) = base.rsplit_once('-')
+        && !generic.is_empty()
+    {
+        names.push(format!("{generic}{suffix}"));
+        base = generic;
+    }
+    names
+}
+
+/// The direction text is written in, which some icons are mirrored for, e.g. `go-next-rtl`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Default)]
+pub enum TextDirection {
+    /// Left to right, as in English.
+    #[default]
+    Ltr,
+    /// Right to left, as in Arabic or Hebrew.
+    Rtl,
+}
+
+/// The languages written right to left, by their ISO 639 codes.
+const RTL_LANGUAGES: &[&str] = &[
+    "ar", "arc", "ckb", "dv", "fa", "ha", "he", "iw", "khw", "ks", "ps", "sd", "ug", "ur", "yi",
+];
+
+impl TextDirection {
+    /// The direction of the language of a POSIX locale, e.g. `Rtl` for `he_IL.UTF-8`.
+    pub fn from_locale(locale: &str) -> Self {
+        let language = locale
+            .split(['_', '.', '@'])
+            .next()
+            .unwrap_or_default()
+            .to_ascii_lowercase();
+        match RTL_LANGUAGES.contains(&language.as_str()) {
+            true => TextDirection::Rtl,
+            false => TextDirection::Ltr,
+        }
+    }
+
+    /// The direction of the [locale](Environment::locale) of the current process, or `Ltr` if it
+    /// isn't set.
+    pub fn current() -> Self {
+        let locale = Environment::current().locale;
+        locale.map_or_else(Self::default, |locale| Self::from_locale(&locale))
+    }
+
+    /// The suffix of icons for this direction, `-ltr` or `-rtl`.
+    pub fn suffix(&self) -> &'static str {
+        match self {
+            TextDirection::Ltr => "-ltr",
+            TextDirection::Rtl => "-rtl",
+        }
+    }
+}
+
+/// The names to look up for `icon_name` in text of the given direction: the variant for the
+/// direction, e.g. `go-next-rtl`, followed by the neutral name.
+///
+/// Symbolic icons keep their suffix last, e.g. `go-next-rtl-symbolic`.
+pub fn directional_names(icon_name: &str, direction: TextDirection) -> Vec<String> {
+    let suffix = direction.suffix();
+    let variant = match icon_name.strip_suffix(SYMBOLIC_SUFFIX) {
+        Some(base) if !base.is_empty() => format!("{base}{suffix}{SYMBOLIC_SUFFIX}"),
+        _ => format!("{icon_name}{suffix}"),
+    };
+    vec![variant, icon_name.to_owned()]
+}
+
+impl Icons {
+    /// Looks up an icon for text of the given direction, preferring the variant for the
+    /// direction in the whole theme chain over the neutral icon, see [`directional_names`].
+    ///
+    /// Use [`TextDirection::current`] to follow the user's locale.
+    pub fn find_icon_directional(
+        &self,
+        icon_name: &str,
+        direction: TextDirection,
+        size: u32,
+        scale: u32,
+        theme: &str,
+    ) -> Option<IconFile> {
+        self.lookup(
+            &IconQuery::new(icon_name, size)
+                .with_scale(scale)
+                .with_theme(theme)
+                .with_direction(direction),
+        )
+    }
+}
+
+/// Whether a lookup looks for [symbolic](IconFile::is_symbolic) icons.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Default)]
+pub enum Symbolic {
+    /// Looks up the name as given. A symbolic name, e.g. `audio-volume-high-symbolic`, falls
+    /// back to the full-color icon, `audio-volume-high`.
+    #[default]
+    AsNamed,
+    /// Prefers the symbolic variant of the name, falling back to the full-color icon.
+    Prefer,
+    /// Only finds the symbolic variant of the name.
+    Require,
+    /// Prefers the full-color variant of the name, falling back to the symbolic icon.
+    Avoid,
+}
+
+/// The names to look up for `icon_name` given whether a [`Symbolic`] icon is wanted.
+pub fn symbolic_names(icon_name: &str, symbolic: Symbolic) -> Vec<String> {
+    let base = match icon_name.strip_suffix(SYMBOLIC_SUFFIX) {
+        Some(base) if !base.is_empty() => base,
+        _ => icon_name,
+    };
+    let symbolic_name = format!("{base}{SYMBOLIC_SUFFIX}");
+
+    match symbolic {
+        Symbolic::AsNamed if base == icon_name => vec![icon_name.to_owned()],
+        Symbolic::AsNamed | Symbolic::Prefer => vec![symbolic_name, base.to_owned()],
+        Symbolic::Require => vec![symbolic_name],
+        Symbolic::Avoid => vec![base.to_owned(), symbolic_name],
+    }
+}
+
+impl Icons {
+    /// Looks up an icon, finding its symbolic or full-color variant as requested, see
+    /// [`symbolic_names`]. Use [`IconFile::is_symbolic`] to tell which one was found.
+    pub fn find_icon_symbolic(
+        &self,
+        icon_name: &str,
+        symbolic: Symbolic,
+        size: u32,
+        scale: u32,
+        theme: &str,
+    ) -> Option<IconFile> {
+        self.lookup(
+            &IconQuery::new(icon_name, size)
+                .with_scale(scale)
+                .with_theme(theme)
+                .with_symbolic(symbolic),
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::IconSearch;
+    use crate::naming::{
+        Symbolic, TextDirection, directional_names, fallback_names, symbolic_names,
+    };
+    use std::fs;
+
+    #[test]
+    fn test_fallback_names() {
+        assert_eq!(
+            fallback_names("audio-volume-high-symbolic"),
+            [
+                "audio-volume-high-symbolic",
+                "audio-volume-symbolic",
+                "audio-symbolic"
+            ]
+        );
+        assert_eq!(fallback_names("firefox"), ["firefox"]);
+        assert_eq!(fallback_names("-symbolic"), ["-symbolic"]);
+        assert_eq!(fallback_names("-a-b"), ["-a-b", "-a"]);
+        assert_eq!(fallback_names(""), [""]);
+    }
+
+    #[test]
+    fn test_name_fallback() {
+        let dir = tempfile::tempdir().unwrap();
+        let theme = dir.path().join("hicolor");
+        fs::create_dir_all(theme.join("16")).unwrap();
+        fs::write(
+            theme.join("index.theme"),
+            "[Icon Theme]\nName=Hicolor\nComment=\nDirectories=16\n\n[16]\nSize=16\n",
+        )
+        .unwrap();
+        for icon in ["network-wireless", "network-wireless-signal-ok"] {
+            fs::write(theme.join(format!("16/{icon}.svg")), b"").unwrap();
+        }
+        fs::write(dir.path().join("network.png"), b"").unwrap();
+
+        let search = IconSearch::new_from(vec![dir.path().into()]);
+        let icons = search.clone().search().icons();
+        assert!((icons.find_icon("network-wireless-signal-excellent", 16, 1, "hicolor")).is_none());
+
+        let icons = search.with_name_fallback(true).search().icons();
+        let find = |name| {
+            let icon = icons.find_icon(name, 16, 1, "hicolor")?;
+            Some(icon.path.file_name()?.to_str()?.to_owned())
+        };
+        assert_eq!(
+            find("network-wireless-signal-excellent").as_deref(),
+            Some("network-wireless.svg")
+        );
+        assert_eq!(
+            find("network-wireless-signal-ok").as_deref(),
+            Some("network-wireless-signal-ok.svg")
+        );
+        // standalone icons are fallen back to as well
+        assert_eq!(find("network-wired").as_deref(), Some("network.png"));
+        assert_eq!(find("audio-volume-high"), None);
+    }
+
+    #[test]
+    fn test_directional_names() {
+        assert_eq!(
+            TextDirection::from_locale("he_IL.UTF-8"),
+            TextDirection::Rtl
+        );
+        assert_eq!(TextDirection::from_locale("ar"), TextDirection::Rtl);
+        assert_eq!(TextDirection::from_locale("en_US@euro"), TextDirection::Ltr);
+        assert_eq!(TextDirection::from_locale(""), TextDirection::Ltr);
+
+        assert_eq!(
+            directional_names("go-next-symbolic", TextDirection::Rtl),
+            ["go-next-rtl-symbolic", "go-next-symbolic"]
+        );
+        assert_eq!(
+            directional_names("media-playback-start", TextDirection::Ltr),
+            ["media-playback-start-ltr", "media-playback-start"]
+        );
+
+        let dir = tempfile::tempdir().unwrap();
+        let theme = dir.path().join("hicolor");
+        fs::create_dir_all(theme.join("16")).unwrap();
+        fs::write(
+            theme.join("index.theme"),
+            "[Icon Theme]\nName=Hicolor\nComment=\nDirectories=16\n\n[16]\nSize=16\n",
+        )
+        .unwrap();
+        for icon in ["go-next", "go-next-rtl"] {
+            fs::write(theme.join(format!("16/{icon}.svg")), b"").unwrap();
+        }
+
+        let icons = IconSearch::new_from(vec![dir.path().into()])
+            .search()
+            .icons();
+        let find = |direction| {
+            let icon = icons.find_icon_directional("go-next", direction, 16, 1, "hicolor")?;
+            Some(icon.icon_name()?.to_owned())
+        };
+        assert_eq!(find(TextDirection::Rtl).as_deref(), Some("go-next-rtl"));
+        assert_eq!(find(TextDirection::Ltr).as_deref(), Some("go-next"));
+    }
+
+    #[test]
+    fn test_symbolic_names() {
+        assert_eq!(
+            symbolic_names("edit-copy-symbolic", Symbolic::AsNamed),
+            ["edit-copy-symbolic", "edit-copy"]
+        );
+        assert_eq!(
+            symbolic_names("edit-copy", Symbolic::AsNamed),
+            ["edit-copy"]
+        );
+        assert_eq!(
+            symbolic_names("edit-copy", Symbolic::Prefer),
+            ["edit-copy-symbolic", "edit-copy"]
+        );
+        assert_eq!(
+            symbolic_names("edit-copy-symbolic", Symbolic::Require),
+            ["edit-copy-symbolic"]
+        );
+        assert_eq!(
+            symbolic_names("edit-copy-symbolic", Symbolic::Avoid),
+            ["edit-copy", "edit-copy-symbolic"]
+        );
+
+        let dir = tempfile::tempdir().unwrap();
+        let theme = dir.path().join("hicolor");
+        fs::create_dir_all(theme.join("16")).unwrap();
+        fs::write(
+            theme.join("index.theme"),
+            "[Icon Theme]\nName=Hicolor\nComment=\nDirectories=16\n\n[16]\nSize=16\n",
+        )
+        .unwrap();
+        for icon in ["edit-copy", "edit-copy-symbolic", "edit-paste"] {
+            fs::write(theme.join(format!("16/{icon}.svg")), b"").unwrap();
+        }
+
+        let icons = IconSearch::new_from(vec![dir.path().into()])
+            .search()
+            .icons();
+        let find = |name, symbolic| {
+            let icon = icons.find_icon_symbolic(name, symbolic, 16, 1, "hicolor")?;
+            Some((icon.icon_name()?.to_owned(), icon.is_symbolic()))
+        };
+        assert_eq!(
+            find("edit-copy", Symbolic::Prefer),
+            Some(("edit-copy-symbolic".into(), true))
+        );
+        assert_eq!(
+            find("edit-paste-symbolic", Symbolic::AsNamed),
+            Some(("edit-paste".into(), false))
+        );
+        assert_eq!(find("edit-paste", Symbolic::Require), None);
+        assert_eq!(
+            find("edit-copy-symbolic", Symbolic::Avoid),
+            Some(("edit-copy".into(), false))
+        );
+    }
+}
@@ -0,0 +1,139 @@
+//! Opening icon files without following links out of the search directories.
+//!
+//! Icon directories are often writable by users, e.g. `~/.local/share/icons`. A privileged
+//! process looking up icons in them could be tricked into opening other files by symbolic links
+//! planted there. With [`IconSearch::with_resolve_beneath`](crate::IconSearch::with_resolve_beneath),
+//! icon files are opened with Linux's `openat2` relative to the search directory they were found
+//! in, with `RESOLVE_BENEATH` and `RESOLVE_NO_MAGICLINKS`: links out of the search directory, as
+//! well as "magic" links like `/proc/self/fd/*`, fail to resolve and the icon is skipped.
+//! [`IconFile::open_beneath`] opens the icons found the same way, so that the file read is the one
+//! that was checked.
+//!
+//! This module requires the `openat2` feature and Linux 5.6 or later. Where `openat2` isn't
+//! available, such as on older kernels or behind a seccomp filter, no icons are found.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use icon::IconSearch;
+//!
+//! let icons = IconSearch::new()
+//!     .with_resolve_beneath(true)
+//!     .search()
+//!     .icons();
+//!
+//! let icon = icons.find_icon_match("firefox", 48, 1, "hicolor").unwrap();
+//! let file = icon.file.open_beneath(icons.base_dir_of(&icon.file).unwrap()).unwrap();
+//! ```
+
+use crate::{IconFile, ReadError};
+use rustix::fs::{Mode, OFlags, ResolveFlags};
+use std::fs::File;
+use std::io::{Error, ErrorKind};
+use std::path::Path;
+
+/// Opens the file at `path` for reading, resolving it relative to `root` without leaving it.
+pub(crate) fn open_beneath(root: &Path, path: &Path) -> std::io::Result<File> {
+    let relative = path
+        .strip_prefix(root)
+        .map_err(|_| Error::from(ErrorKind::InvalidInput))?;
+
+    let root = rustix::fs::open(
+        root,
+        OFlags::PATH | OFlags::DIRECTORY | OFlags::CLOEXEC,
+        Mode::empty(),
+    )?;
+    let file = rustix::fs::openat2(
+        &root,
+        relative,
+        OFlags::RDONLY | OFlags::CLOEXEC,
+        Mode::empty(),
+        ResolveFlags::BENEATH | ResolveFlags::NO_MAGICLINKS,
+    )?;
+
+    Ok(File::from(file))
+}
+
+/// Checks that `path` resolves relative to `root` without leaving it, without opening the file
+/// for reading.
+pub(crate) fn resolve_beneath(root: &Path, path: &Path) -> std::io::Result<()> {
+    let relative = path
+        .strip_prefix(root)
+        .map_err(|_| Error::from(ErrorKind::InvalidInput))?;
+
+    let root = rustix::fs::open(
+        root,
+        OFlags::PATH | OFlags::DIRECTORY | OFlags::CLOEXEC,
+        Mode::empty(),
+    )?;
+    rustix::fs::openat2(
+        &root,
+        relative,
+        OFlags::PATH | OFlags::CLOEXEC,
+        Mode::empty(),
+        ResolveFlags::BENEATH | ResolveFlags::NO_MAGICLINKS,
+    )?;
+
+    Ok(())
+}
+
+impl IconFile {
+    /// Opens this icon file for reading, without following links out of `root`, which must
+    /// contain it. Pass the search directory the icon was found in, see
+    /// [`Icons::base_dir_of`](crate::Icons::base_dir_of).
+    ///
+    /// This requires the `openat2` feature.
+    pub fn open_beneath(&self, root: &Path) -> Result<File, ReadError> {
+        open_beneath(root, &self.path).map_err(|e| ReadError::from_io(&self.path, e))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::IconSearch;
+    use std::fs;
+    use std::os::unix::fs::symlink;
+
+    #[test]
+    fn test_resolve_beneath() {
+        let dir = tempfile::tempdir().unwrap();
+        let icons_dir = dir.path().join("icons");
+        let theme = icons_dir.join("hicolor");
+        fs::create_dir_all(theme.join("48")).unwrap();
+        fs::write(
+            theme.join("index.theme"),
+            "[Icon Theme]\nName=Hicolor\nComment=Fallback\nDirectories=48\n\n[48]\nSize=48\n",
+        )
+        .unwrap();
+        fs::write(theme.join("48/firefox.png"), b"").unwrap();
+        fs::write(dir.path().join("secret.png"), b"").unwrap();
+        // links within the search directory are fine, links out of it are not
+        symlink("firefox.png", theme.join("48/browser.png")).unwrap();
+        symlink(dir.path().join("secret.png"), theme.join("48/htop.png")).unwrap();
+        symlink("../secret.png", icons_dir.join("btop.png")).unwrap();
+
+        let search = |resolve_beneath| {
+            IconSearch::new_from(vec![icons_dir.clone()])
+                .with_resolve_beneath(resolve_beneath)
+                .search()
+                .icons()
+        };
+
+        let icons = search(false);
+        assert!(icons.find_icon("htop", 48, 1, "hicolor").is_some());
+        assert!(icons.find_standalone_icon("btop").is_some());
+
+        let icons = search(true);
+        let browser = icons.find_icon("browser", 48, 1, "hicolor").unwrap();
+        assert!(icons.find_icon("htop", 48, 1, "hicolor").is_none());
+        assert!(icons.find_standalone_icon("btop").is_none());
+
+        assert!(browser.open_beneath(&icons_dir).is_ok());
+        assert!(browser.open_beneath(&theme.join("48")).is_ok());
+        let htop = crate::IconFile {
+            path: theme.join("48/htop.png"),
+            file_type: crate::FileType::Png,
+        };
+        assert!(htop.open_beneath(&icons_dir).is_err());
+    }
+}
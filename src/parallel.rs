@@ -0,0 +1,146 @@
+//! Looking up many icons at once on all cores.
+//!
+//! Launchers resolve the icons of every application they show at once, which is hundreds of
+//! lookups that are independent of each other. [`Icons::find_icons_par`] spreads them over
+//! [rayon](https://docs.rs/rayon)'s thread pool. Themes are shared between the threads as they
+//! are, including their [listings](crate::listing) and [statistics](crate::statistics).
+//!
+//! By default, rayon's global thread pool is used, which has a thread per core. Applications
+//! that manage their CPU budget themselves can pass their own pool, or limit the number of
+//! threads, with [`IconSearch::with_parallelism`](crate::IconSearch::with_parallelism).
+//!
+//! This module requires the `rayon` feature.
+
+use crate::{IconFile, Icons};
+use rayon::prelude::*;
+use rayon::{ThreadPool, ThreadPoolBuildError, ThreadPoolBuilder};
+use std::fmt::{Debug, Formatter};
+use std::sync::Arc;
+
+/// The threads parallel lookups run on.
+#[derive(Clone, Default)]
+pub enum Parallelism {
+    /// rayon's global thread pool, or the pool the lookup is started from.
+    #[default]
+    Global,
+    Pool(Arc<ThreadPool>),
+}
+
+impl Parallelism {
+    /// Creates a pool of `threads` threads, used by nothing else.
+    pub fn with_threads(threads: usize) -> Result<Self, ThreadPoolBuildError> {
+        let pool = ThreadPoolBuilder::new()
+            .num_threads(threads)
+            .thread_name(|i| format!("icon-{i}"))
+            .build()?;
+        Ok(Self::Pool(Arc::new(pool)))
+    }
+
+    /// Runs `op` on these threads.
+    pub fn install<R: Send>(&self, op: impl FnOnce() -> R + Send) -> R {
+        match self {
+            Parallelism::Global => op(),
+            Parallelism::Pool(pool) => pool.install(op),
+        }
+    }
+}
+
+impl Debug for Parallelism {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Parallelism::Global => f.write_str("Global"),
+            Parallelism::Pool(pool) => f
+                .debug_struct("Pool")
+                .field("threads", &pool.current_num_threads())
+                .finish(),
+        }
+    }
+}
+
+/// Pools are equal if they are the same pool.
+impl PartialEq for Parallelism {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Parallelism::Global, Parallelism::Global) => true,
+            (Parallelism::Pool(a), Parallelism::Pool(b)) => Arc::ptr_eq(a, b),
+            _ => false,
+        }
+    }
+}
+
+impl Eq for Parallelism {}
+
+impl Icons {
+    /// Like [`find_icon`](Self::find_icon) for each of `icon_names`, looking them up in parallel.
+    ///
+    /// The lookups run on the threads of [`parallelism`](Self::parallelism). The results are in
+    /// the order of `icon_names`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use icon::Icons;
+    ///
+    /// let icons = Icons::new();
+    /// let found = icons.find_icons_par(&["firefox", "htop", "gimp"], 48, 1, "hicolor");
+    /// assert_eq!(found.len(), 3);
+    /// ```
+    pub fn find_icons_par<S>(
+        &self,
+        icon_names: &[S],
+        size: u32,
+        scale: u32,
+        theme: &str,
+    ) -> Vec<Option<IconFile>>
+    where
+        S: AsRef<str> + Sync,
+    {
+        self.parallelism.install(|| {
+            icon_names
+                .par_iter()
+                .map(|icon_name| self.find_icon(icon_name.as_ref(), size, scale, theme))
+                .collect()
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::IconSearch;
+    use crate::parallel::Parallelism;
+
+    #[test]
+    fn test_find_icons_par() {
+        let dir = tempfile::tempdir().unwrap();
+        let theme = dir.path().join("hicolor");
+        std::fs::create_dir_all(theme.join("apps")).unwrap();
+        std::fs::write(
+            theme.join("index.theme"),
+            "[Icon Theme]\nName=Hicolor\nDirectories=apps\n\n[apps]\nSize=48\n",
+        )
+        .unwrap();
+
+        let names = (0..200).map(|i| format!("app-{i}")).collect::<Vec<_>>();
+        for name in names.iter().step_by(2) {
+            std::fs::write(theme.join(format!("apps/{name}.png")), b"").unwrap();
+        }
+
+        for parallelism in [Parallelism::Global, Parallelism::with_threads(2).unwrap()] {
+            let icons = IconSearch::new_from(vec![dir.path().into()])
+                .with_directory_listing(true)
+                .with_parallelism(parallelism)
+                .search()
+                .icons();
+            let found = icons.find_icons_par(&names, 48, 1, "hicolor");
+
+            for (i, (name, icon)) in names.iter().zip(found).enumerate() {
+                assert_eq!(icon.is_some(), i % 2 == 0);
+                assert!(icon.is_none_or(|icon| icon.icon_name() == Some(name)));
+            }
+        }
+
+        let pool = Parallelism::with_threads(1).unwrap();
+        let thread_name = pool.install(|| std::thread::current().name().map(String::from));
+        assert_eq!(thread_name.as_deref(), Some("icon-0"));
+    }
+}
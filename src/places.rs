@@ -0,0 +1,178 @@
+//! Icons for the places shown in the sidebars of file managers and file choosers.
+//!
+//! Sidebars list the user's folders, the trash, mounted media and network shares, some of which
+//! have icons that change with their state: the trash looks different when it has something in
+//! it, and removable media are shown by what kind of medium they are. Themes implement the names
+//! of the [icon naming specification](https://specifications.freedesktop.org/icon-naming-spec/latest/)
+//! to varying extents, so [`place_icon_names`] produces the names for a place from the most to the
+//! least specific, and [`Icons::find_place_icon`] looks them up in that order.
+//!
+//! # Example
+//!
+//! ```
+//! use icon::Icons;
+//! use icon::places::{Place, place_icon_names};
+//!
+//! assert_eq!(place_icon_names(Place::Trash { full: true })[0], "user-trash-full");
+//!
+//! let icons = Icons::new();
+//! let trash = icons.find_place_icon(Place::Trash { full: false }, 16, 1, "Adwaita");
+//! ```
+
+use crate::IconFile;
+use crate::theme::Icons;
+
+/// A place shown in a sidebar, see [`place_icon_names`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum Place {
+    Home,
+    Desktop,
+    Documents,
+    Downloads,
+    Music,
+    Pictures,
+    Videos,
+    Templates,
+    PublicShare,
+    /// The trash, which has a different icon when `full`.
+    Trash {
+        full: bool,
+    },
+    /// The whole computer, e.g. "Other Locations".
+    Computer,
+    /// The root file system.
+    FileSystem,
+    /// The local network, e.g. "Browse Network".
+    Network,
+    /// A mounted network share, e.g. over SMB or NFS.
+    NetworkShare,
+    /// A server on the network.
+    NetworkServer,
+    /// Removable media of the given kind.
+    Removable(Medium),
+}
+
+/// The kind of a removable medium.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum Medium {
+    /// A USB stick.
+    Usb,
+    /// A CD, DVD or Blu-ray disc.
+    Optical,
+    /// A memory card, such as an SD card.
+    Flash,
+    Floppy,
+    /// An external hard disk.
+    HardDisk,
+    Phone,
+    /// A camera, as mounted over PTP.
+    Camera,
+    /// A portable media player, as mounted over MTP.
+    MediaPlayer,
+    /// Any other kind, or an unknown one.
+    Other,
+}
+
+/// The names of the icons for a place.
+pub fn place_icon_names(place: Place) -> Vec<String> {
+    let names: &[&str] = match place {
+        Place::Home => &["user-home", "folder-home", "folder"],
+        Place::Desktop => &["user-desktop", "folder-desktop", "folder"],
+        Place::Documents => &["folder-documents", "folder"],
+        Place::Downloads => &["folder-download", "folder"],
+        Place::Music => &["folder-music", "folder"],
+        Place::Pictures => &["folder-pictures", "folder"],
+        Place::Videos => &["folder-videos", "folder"],
+        Place::Templates => &["folder-templates", "folder"],
+        Place::PublicShare => &["folder-publicshare", "folder"],
+        Place::Trash { full: true } => &["user-trash-full", "user-trash"],
+        Place::Trash { full: false } => &["user-trash"],
+        Place::Computer => &["computer"],
+        Place::FileSystem => &["drive-harddisk-system", "drive-harddisk"],
+        Place::Network => &["network-workgroup", "network-server"],
+        Place::NetworkShare => &["folder-remote", "network-workgroup", "folder"],
+        Place::NetworkServer => &["network-server", "network-workgroup"],
+        Place::Removable(medium) => match medium {
+            Medium::Usb => &[
+                "drive-removable-media-usb",
+                "drive-removable-media",
+                "media-removable",
+            ],
+            Medium::Optical => &["media-optical", "drive-optical", "drive-removable-media"],
+            Medium::Flash => &["media-flash", "drive-removable-media", "media-removable"],
+            Medium::Floppy => &["media-floppy", "drive-removable-media", "media-removable"],
+            Medium::HardDisk => &["drive-harddisk-usb", "drive-harddisk"],
+            Medium::Phone => &["phone", "drive-removable-media", "media-removable"],
+            Medium::Camera => &["camera-photo", "drive-removable-media", "media-removable"],
+            Medium::MediaPlayer => &[
+                "multimedia-player",
+                "drive-removable-media",
+                "media-removable",
+            ],
+            Medium::Other => &["drive-removable-media", "media-removable"],
+        },
+    };
+    names.iter().map(|&name| name.to_owned()).collect()
+}
+
+impl Icons {
+    /// Looks up the icon of a place, see [`place_icon_names`].
+    pub fn find_place_icon(
+        &self,
+        place: Place,
+        size: u32,
+        scale: u32,
+        theme: &str,
+    ) -> Option<IconFile> {
+        self.find_first_icon(&place_icon_names(place), size, scale, theme)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::IconSearch;
+    use crate::places::{Medium, Place, place_icon_names};
+    use std::fs;
+
+    #[test]
+    fn test_find_place_icon() {
+        let dir = tempfile::tempdir().unwrap();
+        let theme = dir.path().join("hicolor");
+        fs::create_dir_all(theme.join("16")).unwrap();
+        fs::write(
+            theme.join("index.theme"),
+            "[Icon Theme]\nName=Hicolor\nComment=\nDirectories=16\n\n[16]\nSize=16\n",
+        )
+        .unwrap();
+        for icon in ["user-trash", "folder", "media-optical", "media-removable"] {
+            fs::write(theme.join(format!("16/{icon}.svg")), b"").unwrap();
+        }
+
+        let icons = IconSearch::new_from(vec![dir.path().into()])
+            .search()
+            .icons();
+        let find = |place| {
+            let icon = icons.find_place_icon(place, 16, 1, "hicolor")?;
+            Some(icon.icon_name()?.to_owned())
+        };
+        assert_eq!(
+            find(Place::Trash { full: true }).as_deref(),
+            Some("user-trash")
+        );
+        assert_eq!(find(Place::Music).as_deref(), Some("folder"));
+        assert_eq!(
+            find(Place::Removable(Medium::Optical)).as_deref(),
+            Some("media-optical")
+        );
+        assert_eq!(
+            find(Place::Removable(Medium::Usb)).as_deref(),
+            Some("media-removable")
+        );
+        assert_eq!(find(Place::Computer), None);
+
+        assert_eq!(
+            place_icon_names(Place::Trash { full: true }),
+            ["user-trash-full", "user-trash"]
+        );
+    }
+}
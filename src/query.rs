@@ -0,0 +1,227 @@
+//! Describing a lookup with all of its options.
+//!
+//! The positional lookups, like [`Icons::find_icon`], take one argument per option, so every new
+//! option needs a new method. An [`IconQuery`] collects the options instead, and
+//! [`Icons::lookup`] finds the icon it describes. Options that aren't set behave like
+//! [`find_icon`](Icons::find_icon).
+//!
+//! # Example
+//!
+//! ```
+//! use icon::naming::{Symbolic, TextDirection};
+//! use icon::{IconQuery, Icons};
+//!
+//! let icons = Icons::new();
+//! let query = IconQuery::new("go-next", 16)
+//!     .with_scale(2)
+//!     .with_theme("Adwaita")
+//!     .with_direction(TextDirection::Rtl)
+//!     .with_symbolic(Symbolic::Prefer);
+//! let icon = icons.lookup(&query);
+//! ```
+
+use crate::IconFile;
+use crate::naming::{Symbolic, TextDirection, directional_names, fallback_names, symbolic_names};
+use crate::theme::Icons;
+
+/// The options of a lookup, see [`query`](crate::query).
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct IconQuery<'a> {
+    pub name: &'a str,
+    pub size: u32,
+    /// 1 unless [set](Self::with_scale).
+    pub scale: u32,
+    /// The theme to look the icon up in, or `None` for the
+    /// [fallback theme](Icons::fallback_theme).
+    pub theme: Option<&'a str>,
+    /// See [`with_direction`](Self::with_direction).
+    pub direction: Option<TextDirection>,
+    /// See [`with_symbolic`](Self::with_symbolic).
+    pub symbolic: Option<Symbolic>,
+    /// See [`with_locale`](Self::with_locale).
+    pub locale: Option<&'a str>,
+    /// See [`with_name_fallback`](Self::with_name_fallback).
+    pub name_fallback: Option<bool>,
+}
+
+impl<'a> IconQuery<'a> {
+    /// A query for the icon `name` at `size` and scale 1 in the fallback theme.
+    pub fn new(name: &'a str, size: u32) -> Self {
+        Self {
+            name,
+            size,
+            scale: 1,
+            theme: None,
+            direction: None,
+            symbolic: None,
+            locale: None,
+            name_fallback: None,
+        }
+    }
+
+    pub fn with_scale(mut self, scale: u32) -> Self {
+        self.scale = scale;
+        self
+    }
+
+    pub fn with_theme(mut self, theme: &'a str) -> Self {
+        self.theme = Some(theme);
+        self
+    }
+
+    /// Prefers the variant of the icon for text of the given direction, as
+    /// [`find_icon_directional`](Icons::find_icon_directional) does.
+    pub fn with_direction(mut self, direction: TextDirection) -> Self {
+        self.direction = Some(direction);
+        self
+    }
+
+    /// Finds the symbolic or full-color variant of the icon, as
+    /// [`find_icon_symbolic`](Icons::find_icon_symbolic) does. Without this, the name is looked
+    /// up as given.
+    pub fn with_symbolic(mut self, symbolic: Symbolic) -> Self {
+        self.symbolic = Some(symbolic);
+        self
+    }
+
+    /// Prefers the variant of the icon for `locale`, as
+    /// [`find_icon_localized`](Icons::find_icon_localized) does.
+    pub fn with_locale(mut self, locale: &'a str) -> Self {
+        self.locale = Some(locale);
+        self
+    }
+
+    /// Overrides [`IconSearch::with_name_fallback`](crate::IconSearch::with_name_fallback) for
+    /// this lookup.
+    pub fn with_name_fallback(mut self, name_fallback: bool) -> Self {
+        self.name_fallback = Some(name_fallback);
+        self
+    }
+
+    /// The names to look up, in order: the variants for the direction, each followed by its
+    /// symbolic or full-color counterpart as requested.
+    fn names(&self) -> Vec<String> {
+        let names = match self.direction {
+            Some(direction) => directional_names(self.name, direction),
+            None => vec![self.name.to_owned()],
+        };
+        let Some(symbolic) = self.symbolic else {
+            return names;
+        };
+
+        let mut unique = Vec::<String>::new();
+        for name in names.iter().flat_map(|name| symbolic_names(name, symbolic)) {
+            if !unique.contains(&name) {
+                unique.push(name);
+            }
+        }
+        unique
+    }
+}
+
+impl Icons {
+    /// Looks up the icon described by `query`, see [`query`](crate::query).
+    ///
+    /// The icon is looked up as by [`find_icon`](Self::find_icon). With a
+    /// [direction](IconQuery::with_direction) or [symbolic](IconQuery::with_symbolic) preference,
+    /// each of the names it asks for is looked up that way in turn, and the first icon found
+    /// wins. The [localized](IconQuery::with_locale) variant of that icon is returned if the
+    /// theme has one.
+    pub fn lookup(&self, query: &IconQuery) -> Option<IconFile> {
+        if query.name.is_empty() {
+            return None;
+        }
+
+        let theme = query.theme.unwrap_or(&self.fallback_theme);
+        let name_fallback = query.name_fallback.unwrap_or(self.name_fallback);
+        let chain = self.lookup_chain(theme);
+        let lookup = |icon_name: &str| {
+            let find = |icon_name: &str| {
+                (chain.iter())
+                    .find_map(|theme| theme.find_icon_here(icon_name, query.size, query.scale))
+                    .or_else(|| self.find_standalone_scaled(icon_name, query.scale))
+            };
+
+            let icon = find(icon_name);
+            if icon.is_some() || !name_fallback {
+                return icon;
+            }
+            (fallback_names(icon_name).iter().skip(1)).find_map(|name| find(name))
+        };
+
+        let icon = match query.direction.is_none() && query.symbolic.is_none() {
+            // the common case, without allocating
+            true => lookup(query.name),
+            false => query.names().iter().find_map(|name| lookup(name)),
+        }?;
+
+        match query.locale {
+            Some(locale) => Some(self.localize(icon, locale)),
+            None => Some(icon),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::naming::{Symbolic, TextDirection};
+    use crate::{IconQuery, IconSearch};
+    use std::fs;
+
+    #[test]
+    fn test_lookup() {
+        let dir = tempfile::tempdir().unwrap();
+        let hicolor = dir.path().join("hicolor");
+        fs::create_dir_all(hicolor.join("16/l10n/de")).unwrap();
+        fs::write(
+            hicolor.join("index.theme"),
+            "[Icon Theme]\nName=Hicolor\nComment=\nDirectories=16\n\n[16]\nSize=16\n",
+        )
+        .unwrap();
+        for name in [
+            "go-next",
+            "go-next-rtl-symbolic",
+            "format-text-bold",
+            "edit",
+        ] {
+            fs::write(hicolor.join(format!("16/{name}.png")), "").unwrap();
+        }
+        fs::write(hicolor.join("16/l10n/de/format-text-bold.png"), "").unwrap();
+
+        let icons = IconSearch::new_from(vec![dir.path().to_owned()])
+            .search()
+            .icons();
+        let lookup = |query: IconQuery| {
+            let icon = icons.lookup(&query)?;
+            Some(icon.path.strip_prefix(&hicolor).unwrap().to_owned())
+        };
+
+        assert_eq!(
+            lookup(IconQuery::new("go-next", 16)),
+            Some("16/go-next.png".into())
+        );
+        assert_eq!(
+            lookup(IconQuery::new("go-next", 16).with_direction(TextDirection::Rtl)),
+            Some("16/go-next.png".into())
+        );
+        assert_eq!(
+            lookup(
+                IconQuery::new("go-next", 16)
+                    .with_theme("hicolor")
+                    .with_direction(TextDirection::Rtl)
+                    .with_symbolic(Symbolic::Prefer)
+            ),
+            Some("16/go-next-rtl-symbolic.png".into())
+        );
+        assert_eq!(
+            lookup(IconQuery::new("format-text-bold", 16).with_locale("de_DE.UTF-8")),
+            Some("16/l10n/de/format-text-bold.png".into())
+        );
+
+        assert_eq!(lookup(IconQuery::new("edit-copy", 16)), None);
+        assert_eq!(
+            lookup(IconQuery::new("edit-copy", 16).with_name_fallback(true)),
+            Some("16/edit.png".into())
+        );
+    }
+}
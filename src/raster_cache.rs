@@ -0,0 +1,350 @@
+//! A bounded cache of decoded icons.
+//!
+//! Applications displaying icons decode them into RGBA buffers at the size they are shown at, and
+//! usually show the same icons over and over again. A [`RasterCache`] keeps those buffers around,
+//! keyed by the canonical path of the file and the size, scale and recoloring they were decoded
+//! with, evicting the least recently used ones once it exceeds its capacity.
+//!
+//! The cache doesn't decode icons itself: pass your decoder to
+//! [`get_or_try_insert_with`](RasterCache::get_or_try_insert_with).
+//!
+//! Entries of themes that are changed or removed by [`Icons::refresh`], including refreshes by an
+//! [`IconWatcher`](crate::watch::IconWatcher), are dropped once the cache is connected with
+//! [`invalidate_on_change`](RasterCache::invalidate_on_change). Files modified in place are not
+//! noticed, see [`IconFile::content_hash`](crate::IconFile::content_hash) for that.
+//!
+//! This module requires the `raster-cache` feature.
+//!
+//! # Example
+//!
+//! ```
+//! use icon::Icons;
+//! use icon::raster_cache::{Raster, RasterCache, RasterKey};
+//!
+//! let icons = Icons::new();
+//! let cache = RasterCache::new(64 << 20);
+//! cache.invalidate_on_change(&icons);
+//!
+//! if let Some(file) = icons.find_icon("firefox", 32, 1, "hicolor") {
+//!     let key = RasterKey::new(&file, 32, 1, None).unwrap();
+//!     let raster = cache.get_or_try_insert_with(key, || {
+//!         // decode `file` here
+//!         Ok::<_, std::io::Error>(Raster::new(32, 32, vec![0; 32 * 32 * 4]))
+//!     });
+//! }
+//! ```
+
+use crate::IconFile;
+use crate::hash_map::HashMap;
+pub use crate::render::Raster;
+use crate::theme::Icons;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+/// What a [`Raster`] was decoded from.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct RasterKey {
+    /// The canonical path of the icon file, so that icons linked under several names share
+    /// entries.
+    pub path: PathBuf,
+    pub size: u32,
+    pub scale: u32,
+    /// The RGBA color symbolic icons were recolored with, if any.
+    pub recolor: Option<[u8; 4]>,
+}
+
+impl RasterKey {
+    /// Creates the key of `file`, resolving its path.
+    pub fn new(
+        file: &IconFile,
+        size: u32,
+        scale: u32,
+        recolor: Option<[u8; 4]>,
+    ) -> std::io::Result<Self> {
+        Ok(Self {
+            path: file.canonical_path()?,
+            size,
+            scale,
+            recolor,
+        })
+    }
+}
+
+/// A cache of decoded icons, holding at most a fixed number of bytes of pixels.
+///
+/// Clones share the same cache.
+#[derive(Debug, Clone)]
+pub struct RasterCache(Arc<Mutex<CacheState>>);
+
+#[derive(Debug)]
+struct CacheState {
+    capacity: usize,
+    len_bytes: usize,
+    /// Incremented on every access, to order the entries by when they were last used.
+    tick: u64,
+    entries: HashMap<RasterKey, Entry>,
+    by_last_use: BTreeMap<u64, RasterKey>,
+}
+
+#[derive(Debug)]
+struct Entry {
+    raster: Arc<Raster>,
+    last_use: u64,
+}
+
+impl RasterCache {
+    /// Creates an empty cache holding at most `capacity` bytes of pixels.
+    pub fn new(capacity: usize) -> Self {
+        Self(Arc::new(Mutex::new(CacheState {
+            capacity,
+            len_bytes: 0,
+            tick: 0,
+            entries: HashMap::default(),
+            by_last_use: BTreeMap::new(),
+        })))
+    }
+
+    fn state(&self) -> std::sync::MutexGuard<'_, CacheState> {
+        // a panic can't leave the state inconsistent, as no user code runs while it is locked
+        self.0.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    pub fn get(&self, key: &RasterKey) -> Option<Arc<Raster>> {
+        let mut state = self.state();
+        let tick = state.next_tick();
+
+        let entry = state.entries.get_mut(key)?;
+        let last_use = std::mem::replace(&mut entry.last_use, tick);
+        let raster = entry.raster.clone();
+
+        state.by_last_use.remove(&last_use);
+        state.by_last_use.insert(tick, key.clone());
+
+        Some(raster)
+    }
+
+    /// Adds a raster, evicting the least recently used ones if the cache gets too large.
+    ///
+    /// A raster larger than the whole cache is not added.
+    pub fn insert(&self, key: RasterKey, raster: Raster) -> Arc<Raster> {
+        let raster = Arc::new(raster);
+        let mut state = self.state();
+
+        if raster.pixels.len() > state.capacity {
+            return raster;
+        }
+
+        state.remove(&key);
+        state.len_bytes += raster.pixels.len();
+        let last_use = state.next_tick();
+        state.by_last_use.insert(last_use, key.clone());
+        let entry = Entry {
+            raster: raster.clone(),
+            last_use,
+        };
+        state.entries.insert(key, entry);
+
+        while state.len_bytes > state.capacity {
+            let Some((_, key)) = state.by_last_use.pop_first() else {
+                break;
+            };
+            state.remove(&key);
+        }
+
+        raster
+    }
+
+    /// Returns the cached raster, or decodes and adds it.
+    ///
+    /// The cache isn't locked while decoding, so two threads may decode the same icon at once.
+    pub fn get_or_try_insert_with<E>(
+        &self,
+        key: RasterKey,
+        decode: impl FnOnce() -> Result<Raster, E>,
+    ) -> Result<Arc<Raster>, E> {
+        if let Some(raster) = self.get(&key) {
+            return Ok(raster);
+        }
+
+        Ok(self.insert(key, decode()?))
+    }
+
+    /// Removes all entries decoded from the file at the canonical `path`.
+    pub fn invalidate(&self, path: &Path) {
+        self.retain(|key| key.path != path);
+    }
+
+    /// Removes all entries decoded from files inside the directory `dir`.
+    pub fn invalidate_dir(&self, dir: &Path) {
+        // the keys are canonical, the directory may not be
+        let canonical = dir.canonicalize().ok();
+        self.retain(|key| {
+            !key.path.starts_with(dir)
+                && !canonical.as_ref().is_some_and(|d| key.path.starts_with(d))
+        });
+    }
+
+    pub fn clear(&self) {
+        self.retain(|_| false);
+    }
+
+    fn retain(&self, mut keep: impl FnMut(&RasterKey) -> bool) {
+        let mut state = self.state();
+        let removed = (state.entries.keys())
+            .filter(|key| !keep(key))
+            .cloned()
+            .collect::<Vec<_>>();
+
+        for key in removed {
+            state.remove(&key);
+        }
+    }
+
+    /// Removes the entries of themes and standalone icons whenever a [refresh](Icons::refresh) of
+    /// `icons` (or its clones) finds them changed or removed.
+    pub fn invalidate_on_change(&self, icons: &Icons) {
+        let cache = self.clone();
+        let base_dirs = icons.base_dirs.clone();
+
+        icons.on_change(move |diff| {
+            let themes = diff.changed_themes.iter().chain(&diff.removed_themes);
+            for theme in themes {
+                for base_dir in &base_dirs {
+                    cache.invalidate_dir(&base_dir.join(theme));
+                }
+            }
+
+            if !diff.removed_standalone_icons.is_empty() {
+                cache.retain(|key| {
+                    let name = key.path.file_stem().and_then(|stem| stem.to_str());
+                    let in_base_dir = key
+                        .path
+                        .parent()
+                        .is_some_and(|parent| base_dirs.iter().any(|dir| dir == parent));
+                    !(in_base_dir
+                        && name.is_some_and(|n| diff.removed_standalone_icons.contains(n)))
+                });
+            }
+        });
+    }
+
+    /// The number of cached rasters.
+    pub fn len(&self) -> usize {
+        self.state().entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The number of bytes of pixels cached.
+    pub fn len_bytes(&self) -> usize {
+        self.state().len_bytes
+    }
+}
+
+impl CacheState {
+    fn next_tick(&mut self) -> u64 {
+        self.tick += 1;
+        self.tick
+    }
+
+    fn remove(&mut self, key: &RasterKey) {
+        if let Some(entry) = self.entries.remove(key) {
+            self.len_bytes -= entry.raster.pixels.len();
+            self.by_last_use.remove(&entry.last_use);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::IconSearch;
+    use crate::raster_cache::{Raster, RasterCache, RasterKey};
+    use std::convert::Infallible;
+    use std::path::PathBuf;
+
+    fn key(path: &str, size: u32) -> RasterKey {
+        RasterKey {
+            path: PathBuf::from(path),
+            size,
+            scale: 1,
+            recolor: None,
+        }
+    }
+
+    fn raster(size: u32) -> Raster {
+        Raster::new(size, size, vec![0; (size * size * 4) as usize])
+    }
+
+    #[test]
+    fn test_eviction() {
+        // room for two 16x16 rasters
+        let cache = RasterCache::new(2 * 16 * 16 * 4);
+
+        cache.insert(key("/a.png", 16), raster(16));
+        cache.insert(key("/b.png", 16), raster(16));
+        assert!(cache.get(&key("/a.png", 16)).is_some());
+
+        // `b` is the least recently used
+        cache.insert(key("/c.png", 16), raster(16));
+        assert_eq!(cache.len(), 2);
+        assert!(cache.get(&key("/b.png", 16)).is_none());
+        assert!(cache.get(&key("/a.png", 16)).is_some());
+
+        // too large to be cached at all
+        cache.insert(key("/d.png", 64), raster(64));
+        assert_eq!(cache.len(), 2);
+
+        let mut decoded = 0;
+        for _ in 0..2 {
+            cache
+                .get_or_try_insert_with(key("/a.png", 8), || {
+                    decoded += 1;
+                    Ok::<_, Infallible>(raster(8))
+                })
+                .unwrap();
+        }
+        assert_eq!(decoded, 1);
+        assert_eq!(cache.len_bytes(), 16 * 16 * 4 + 8 * 8 * 4);
+
+        cache.invalidate("/a.png".as_ref());
+        assert!(cache.is_empty());
+    }
+
+    #[test]
+    fn test_invalidate_on_change() {
+        let dir = tempfile::tempdir().unwrap();
+        let theme = dir.path().join("hicolor");
+        std::fs::create_dir_all(theme.join("apps")).unwrap();
+        let write_index = |size: u32| {
+            std::fs::write(
+                theme.join("index.theme"),
+                format!("[Icon Theme]\nName=Hicolor\nDirectories=apps\n\n[apps]\nSize={size}\n"),
+            )
+            .unwrap();
+        };
+        write_index(48);
+        std::fs::write(theme.join("apps/firefox.png"), b"").unwrap();
+        std::fs::write(dir.path().join("htop.png"), b"").unwrap();
+
+        let mut icons = IconSearch::new_from(vec![dir.path().canonicalize().unwrap()])
+            .search()
+            .icons();
+        let cache = RasterCache::new(1 << 20);
+        cache.invalidate_on_change(&icons);
+
+        let firefox = icons.find_default_icon("firefox", 48, 1).unwrap();
+        let htop = icons.find_standalone_icon("htop").unwrap();
+        for file in [&firefox, &htop] {
+            cache.insert(RasterKey::new(file, 48, 1, None).unwrap(), raster(48));
+        }
+
+        write_index(32);
+        std::fs::remove_file(&htop.path).unwrap();
+        icons.refresh();
+
+        assert!(cache.is_empty());
+    }
+}
@@ -0,0 +1,656 @@
+//! Decoded icons, resizing them and drawing overlays on them.
+//!
+//! Icon themes rarely provide every size an application needs, so icons found through
+//! [`Icons::find_icon`](crate::Icons::find_icon) often have to be resized after decoding. Which
+//! [`Filter`] to use is a trade-off between speed and quality: [`Filter::Nearest`] and
+//! [`Filter::Triangle`] are cheap but blurry or jagged when shrinking icons a lot, while
+//! [`Filter::CatmullRom`] and [`Filter::Lanczos3`] keep small icons sharp.
+//!
+//! [`Raster::composite`] draws [`Overlay`]s such as badges or status dots onto an icon, and
+//! [`Raster::to_pixels`] converts the pixels into the [`PixelFormat`] a toolkit or compositor
+//! expects.
+//!
+//! # Example
+//!
+//! ```
+//! use icon::render::{Filter, Raster, ResizeOptions};
+//!
+//! let raster = Raster::new(64, 64, vec![255; 64 * 64 * 4]);
+//! let options = ResizeOptions::new(Filter::Lanczos3).downscale_only(true);
+//!
+//! let small = raster.resize(24, 24, options);
+//! assert_eq!((small.width, small.height), (24, 24));
+//! ```
+
+/// A decoded icon.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Raster {
+    pub width: u32,
+    pub height: u32,
+    /// The pixels as RGBA with 8 bits per channel, row by row without padding. The color channels
+    /// are not premultiplied with alpha.
+    pub pixels: Box<[u8]>,
+}
+
+impl Raster {
+    /// # Panics
+    ///
+    /// If `pixels` doesn't hold exactly `width * height` pixels.
+    pub fn new(width: u32, height: u32, pixels: impl Into<Box<[u8]>>) -> Self {
+        let pixels = pixels.into();
+        assert_eq!(
+            pixels.len() as u64,
+            u64::from(width) * u64::from(height) * 4,
+            "wrong number of pixels"
+        );
+
+        Self {
+            width,
+            height,
+            pixels,
+        }
+    }
+
+    /// Resizes this raster to `width` x `height` pixels.
+    ///
+    /// The colors are blended premultiplied with alpha, so transparent pixels don't bleed into the
+    /// edges of the icon.
+    ///
+    /// With [`downscale_only`](ResizeOptions::downscale_only), a raster smaller than the requested
+    /// size isn't enlarged, but centered on a transparent raster of that size instead.
+    pub fn resize(&self, width: u32, height: u32, options: ResizeOptions) -> Raster {
+        if options.downscale_only && (width > self.width || height > self.height) {
+            let inner = self.resize(
+                width.min(self.width),
+                height.min(self.height),
+                options.downscale_only(false),
+            );
+            return inner.centered(width, height);
+        }
+
+        if (width, height) == (self.width, self.height) {
+            return self.clone();
+        }
+        if width == 0 || height == 0 || self.width == 0 || self.height == 0 {
+            return Raster::new(width, height, vec![0; width as usize * height as usize * 4]);
+        }
+
+        let premultiplied = self
+            .pixels
+            .chunks_exact(4)
+            .flat_map(|pixel| {
+                let alpha = f32::from(pixel[3]) / 255.0;
+                [
+                    f32::from(pixel[0]) * alpha,
+                    f32::from(pixel[1]) * alpha,
+                    f32::from(pixel[2]) * alpha,
+                    f32::from(pixel[3]),
+                ]
+            })
+            .collect::<Vec<_>>();
+
+        // resize the rows first, then the columns of the result
+        let horizontal = Weights::new(self.width, width, options.filter);
+        let vertical = Weights::new(self.height, height, options.filter);
+
+        let mut rows = vec![0.0; width as usize * self.height as usize * 4];
+        for y in 0..self.height as usize {
+            let src = &premultiplied[y * self.width as usize * 4..][..self.width as usize * 4];
+            let dst = &mut rows[y * width as usize * 4..][..width as usize * 4];
+            for (x, pixel) in dst.chunks_exact_mut(4).enumerate() {
+                for (i, weight) in horizontal.taps(x) {
+                    for c in 0..4 {
+                        pixel[c] += src[i * 4 + c] * weight;
+                    }
+                }
+            }
+        }
+
+        let mut pixels = vec![0; width as usize * height as usize * 4];
+        for y in 0..height as usize {
+            for x in 0..width as usize {
+                let mut pixel = [0.0f32; 4];
+                for (i, weight) in vertical.taps(y) {
+                    let src = &rows[(i * width as usize + x) * 4..][..4];
+                    for c in 0..4 {
+                        pixel[c] += src[c] * weight;
+                    }
+                }
+
+                // filters with negative lobes overshoot, clamp to what's representable
+                let alpha = pixel[3].clamp(0.0, 255.0);
+                let dst = &mut pixels[(y * width as usize + x) * 4..][..4];
+                if alpha > 0.0 {
+                    let unpremultiply = 255.0 / alpha;
+                    for c in 0..3 {
+                        dst[c] = (pixel[c] * unpremultiply).clamp(0.0, 255.0).round() as u8;
+                    }
+                }
+                dst[3] = alpha.round() as u8;
+            }
+        }
+
+        Raster::new(width, height, pixels)
+    }
+
+    /// Places this raster in the middle of a transparent raster of the given size, which must not
+    /// be smaller.
+    fn centered(&self, width: u32, height: u32) -> Raster {
+        let mut pixels = vec![0; width as usize * height as usize * 4];
+        let left = (width - self.width) as usize / 2;
+        let top = (height - self.height) as usize / 2;
+
+        let row_len = self.width as usize * 4;
+        for (y, row) in self.pixels.chunks_exact(row_len.max(1)).enumerate() {
+            let start = ((top + y) * width as usize + left) * 4;
+            pixels[start..][..row_len].copy_from_slice(row);
+        }
+
+        Raster::new(width, height, pixels)
+    }
+}
+
+/// How to compute resized pixels from the source pixels around them.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Default)]
+pub enum Filter {
+    /// Takes the closest source pixel. Fast, but jagged.
+    Nearest,
+    /// Blends the source pixels linearly, also known as bilinear filtering.
+    Triangle,
+    /// A cubic filter that stays sharp without much ringing. A good default for icons.
+    #[default]
+    CatmullRom,
+    /// The sharpest filter, at the cost of some ringing around hard edges and of speed.
+    Lanczos3,
+}
+
+impl Filter {
+    /// How many source pixels around the center the filter reaches, when not shrinking.
+    fn support(self) -> f32 {
+        match self {
+            Filter::Nearest => 0.5,
+            Filter::Triangle => 1.0,
+            Filter::CatmullRom => 2.0,
+            Filter::Lanczos3 => 3.0,
+        }
+    }
+
+    fn weight(self, x: f32) -> f32 {
+        let x = x.abs();
+        match self {
+            Filter::Nearest => {
+                if x < 0.5 {
+                    1.0
+                } else {
+                    0.0
+                }
+            }
+            Filter::Triangle => (1.0 - x).max(0.0),
+            Filter::CatmullRom => {
+                if x < 1.0 {
+                    1.5 * x * x * x - 2.5 * x * x + 1.0
+                } else if x < 2.0 {
+                    -0.5 * x * x * x + 2.5 * x * x - 4.0 * x + 2.0
+                } else {
+                    0.0
+                }
+            }
+            Filter::Lanczos3 => {
+                if x < 3.0 {
+                    sinc(x) * sinc(x / 3.0)
+                } else {
+                    0.0
+                }
+            }
+        }
+    }
+}
+
+fn sinc(x: f32) -> f32 {
+    if x == 0.0 {
+        1.0
+    } else {
+        let x = x * std::f32::consts::PI;
+        x.sin() / x
+    }
+}
+
+/// How to [resize](Raster::resize) a raster.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Default)]
+pub struct ResizeOptions {
+    pub filter: Filter,
+    /// Never enlarge the source, as enlarged icons look blurry. Off by default.
+    pub downscale_only: bool,
+}
+
+impl ResizeOptions {
+    pub const fn new(filter: Filter) -> Self {
+        Self {
+            filter,
+            downscale_only: false,
+        }
+    }
+
+    pub const fn downscale_only(mut self, downscale_only: bool) -> Self {
+        self.downscale_only = downscale_only;
+        self
+    }
+}
+
+/// The source pixels, and their weights, each resized pixel along one axis is computed from.
+struct Weights {
+    /// Per resized pixel, the first source pixel and the range in `weights`.
+    ranges: Vec<(usize, std::ops::Range<usize>)>,
+    weights: Vec<f32>,
+}
+
+impl Weights {
+    fn new(src_len: u32, dst_len: u32, filter: Filter) -> Self {
+        let ratio = src_len as f32 / dst_len as f32;
+        // when shrinking, stretch the filter to cover all source pixels
+        let stretch = match filter {
+            Filter::Nearest => 1.0,
+            _ => ratio.max(1.0),
+        };
+        let support = filter.support() * stretch;
+
+        let mut ranges = Vec::with_capacity(dst_len as usize);
+        let mut weights = Vec::new();
+
+        for i in 0..dst_len {
+            let center = (i as f32 + 0.5) * ratio;
+            let first = ((center - support).floor().max(0.0) as usize).min(src_len as usize - 1);
+            let last = ((center + support).ceil() as usize).clamp(first + 1, src_len as usize);
+
+            let start = weights.len();
+            weights
+                .extend((first..last).map(|j| filter.weight((j as f32 + 0.5 - center) / stretch)));
+
+            let sum = weights[start..].iter().sum::<f32>();
+            if sum != 0.0 {
+                weights[start..].iter_mut().for_each(|w| *w /= sum);
+            } else {
+                // a nearest filter exactly between two pixels
+                weights[start] = 1.0;
+            }
+
+            ranges.push((first, start..weights.len()));
+        }
+
+        Self { ranges, weights }
+    }
+
+    fn taps(&self, i: usize) -> impl Iterator<Item = (usize, f32)> + '_ {
+        let (first, range) = self.ranges[i].clone();
+        (first..).zip(self.weights[range].iter().copied())
+    }
+}
+
+/// The order of the channels of a pixel in memory.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Default)]
+pub enum ByteOrder {
+    #[default]
+    Rgba,
+    Bgra,
+    Argb,
+}
+
+/// Whether the color channels of a pixel are multiplied with its alpha.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Default)]
+pub enum AlphaMode {
+    #[default]
+    Straight,
+    Premultiplied,
+}
+
+/// The layout of pixels in memory, for [`Raster::to_pixels`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Default)]
+pub struct PixelFormat {
+    pub order: ByteOrder,
+    pub alpha: AlphaMode,
+}
+
+impl PixelFormat {
+    /// The layout of [`Raster::pixels`].
+    pub const RGBA: Self = Self::new(ByteOrder::Rgba, AlphaMode::Straight);
+    /// Cairo's `FORMAT_ARGB32`: 32 bit integers of premultiplied ARGB in native endianness.
+    pub const ARGB32: Self = Self::new(
+        if cfg!(target_endian = "little") {
+            ByteOrder::Bgra
+        } else {
+            ByteOrder::Argb
+        },
+        AlphaMode::Premultiplied,
+    );
+
+    pub const fn new(order: ByteOrder, alpha: AlphaMode) -> Self {
+        Self { order, alpha }
+    }
+}
+
+impl Raster {
+    /// Copies the pixels of this raster, converted to `format`.
+    pub fn to_pixels(&self, format: PixelFormat) -> Vec<u8> {
+        let mut pixels = vec![0; self.pixels.len()];
+        self.write_pixels(format, &mut pixels);
+        pixels
+    }
+
+    /// Writes the pixels of this raster, converted to `format`, into `out`, such as a mapped
+    /// shared memory buffer.
+    ///
+    /// # Panics
+    ///
+    /// If `out` isn't as long as [`pixels`](Raster::pixels).
+    pub fn write_pixels(&self, format: PixelFormat, out: &mut [u8]) {
+        assert_eq!(out.len(), self.pixels.len(), "wrong buffer length");
+
+        for (src, dst) in self.pixels.chunks_exact(4).zip(out.chunks_exact_mut(4)) {
+            let [mut r, mut g, mut b, a] = [src[0], src[1], src[2], src[3]];
+            if format.alpha == AlphaMode::Premultiplied {
+                // rounded, like cairo and pixman do
+                let premultiply = |c: u8| ((u16::from(c) * u16::from(a) + 127) / 255) as u8;
+                [r, g, b] = [premultiply(r), premultiply(g), premultiply(b)];
+            }
+
+            dst.copy_from_slice(&match format.order {
+                ByteOrder::Rgba => [r, g, b, a],
+                ByteOrder::Bgra => [b, g, r, a],
+                ByteOrder::Argb => [a, r, g, b],
+            });
+        }
+    }
+}
+
+/// Where to place an [`Overlay`] on an icon.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Default)]
+pub enum Anchor {
+    TopLeft,
+    Top,
+    TopRight,
+    Left,
+    Center,
+    Right,
+    BottomLeft,
+    Bottom,
+    #[default]
+    BottomRight,
+}
+
+impl Anchor {
+    /// The position of the top left corner of an overlay of `size` on an icon of `base`.
+    fn position(self, base: (u32, u32), size: (u32, u32)) -> (i64, i64) {
+        let free = (
+            i64::from(base.0) - i64::from(size.0),
+            i64::from(base.1) - i64::from(size.1),
+        );
+        let (x, y) = match self {
+            Anchor::TopLeft => (0, 0),
+            Anchor::Top => (1, 0),
+            Anchor::TopRight => (2, 0),
+            Anchor::Left => (0, 1),
+            Anchor::Center => (1, 1),
+            Anchor::Right => (2, 1),
+            Anchor::BottomLeft => (0, 2),
+            Anchor::Bottom => (1, 2),
+            Anchor::BottomRight => (2, 2),
+        };
+        (free.0 * x / 2, free.1 * y / 2)
+    }
+}
+
+/// What an [`Overlay`] draws.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum OverlaySource<'a> {
+    /// A decoded image, such as a rendered unread count or progress arc, resized to the overlay.
+    Raster(&'a Raster),
+    /// A filled circle of a RGBA color, such as a recording indicator.
+    Dot([u8; 4]),
+}
+
+/// An image or color drawn on top of an icon by [`Raster::composite`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct Overlay<'a> {
+    pub source: OverlaySource<'a>,
+    pub anchor: Anchor,
+    pub width: u32,
+    pub height: u32,
+    /// Moves the overlay from its anchor, in pixels. Parts outside the icon are cut off.
+    pub offset: (i32, i32),
+}
+
+impl<'a> Overlay<'a> {
+    /// Draws `raster` at its own size.
+    pub fn raster(raster: &'a Raster, anchor: Anchor) -> Self {
+        Self {
+            source: OverlaySource::Raster(raster),
+            anchor,
+            width: raster.width,
+            height: raster.height,
+            offset: (0, 0),
+        }
+    }
+
+    /// Draws a circle of `color` with the given diameter.
+    pub fn dot(color: [u8; 4], diameter: u32, anchor: Anchor) -> Self {
+        Self {
+            source: OverlaySource::Dot(color),
+            anchor,
+            width: diameter,
+            height: diameter,
+            offset: (0, 0),
+        }
+    }
+
+    pub fn with_size(mut self, width: u32, height: u32) -> Self {
+        self.width = width;
+        self.height = height;
+        self
+    }
+
+    pub fn with_offset(mut self, x: i32, y: i32) -> Self {
+        self.offset = (x, y);
+        self
+    }
+
+    /// Draws this overlay into a raster of its size.
+    fn draw(&self) -> Raster {
+        match self.source {
+            OverlaySource::Raster(raster) => {
+                raster.resize(self.width, self.height, ResizeOptions::default())
+            }
+            OverlaySource::Dot(color) => {
+                let center = (self.width as f32 / 2.0, self.height as f32 / 2.0);
+                let radius = center.0.min(center.1);
+
+                let mut pixels = Vec::with_capacity(self.width as usize * self.height as usize * 4);
+                for y in 0..self.height {
+                    for x in 0..self.width {
+                        let distance = (x as f32 + 0.5 - center.0).hypot(y as f32 + 0.5 - center.1);
+                        // anti-alias the edge over one pixel
+                        let coverage = (radius - distance + 0.5).clamp(0.0, 1.0);
+                        let alpha = (f32::from(color[3]) * coverage).round() as u8;
+                        pixels.extend([color[0], color[1], color[2], alpha]);
+                    }
+                }
+
+                Raster::new(self.width, self.height, pixels)
+            }
+        }
+    }
+}
+
+impl Raster {
+    /// Draws `overlays` on top of this icon, in order, such as the unread count badges and
+    /// progress indicators of docks and task bars.
+    pub fn composite(&self, overlays: &[Overlay<'_>]) -> Raster {
+        let mut result = self.clone();
+
+        for overlay in overlays {
+            let drawn = overlay.draw();
+            let (left, top) = overlay
+                .anchor
+                .position((self.width, self.height), (overlay.width, overlay.height));
+            let left = left + i64::from(overlay.offset.0);
+            let top = top + i64::from(overlay.offset.1);
+
+            for (y, row) in drawn
+                .pixels
+                .chunks_exact(drawn.width as usize * 4)
+                .enumerate()
+            {
+                let Some(dst_y) = clip(top + y as i64, self.height) else {
+                    continue;
+                };
+                for (x, src) in row.chunks_exact(4).enumerate() {
+                    let Some(dst_x) = clip(left + x as i64, self.width) else {
+                        continue;
+                    };
+                    let dst = &mut result.pixels[(dst_y * self.width as usize + dst_x) * 4..][..4];
+                    blend(dst, src);
+                }
+            }
+        }
+
+        result
+    }
+}
+
+/// The index of `position` along an axis of `len` pixels, if it is inside.
+fn clip(position: i64, len: u32) -> Option<usize> {
+    (0..i64::from(len))
+        .contains(&position)
+        .then_some(position as usize)
+}
+
+/// Draws the pixel `src` over `dst`.
+fn blend(dst: &mut [u8], src: &[u8]) {
+    let src_alpha = f32::from(src[3]) / 255.0;
+    let dst_alpha = f32::from(dst[3]) / 255.0 * (1.0 - src_alpha);
+    let alpha = src_alpha + dst_alpha;
+    if alpha <= 0.0 {
+        return;
+    }
+
+    for c in 0..3 {
+        let color = (f32::from(src[c]) * src_alpha + f32::from(dst[c]) * dst_alpha) / alpha;
+        dst[c] = color.round() as u8;
+    }
+    dst[3] = (alpha * 255.0).round() as u8;
+}
+
+#[cfg(test)]
+mod test {
+    use crate::render::{
+        AlphaMode, Anchor, ByteOrder, Filter, Overlay, PixelFormat, Raster, ResizeOptions,
+    };
+
+    fn solid(size: u32, pixel: [u8; 4]) -> Raster {
+        Raster::new(size, size, pixel.repeat((size * size) as usize))
+    }
+
+    #[test]
+    fn test_resize() {
+        let filters = [
+            Filter::Nearest,
+            Filter::Triangle,
+            Filter::CatmullRom,
+            Filter::Lanczos3,
+        ];
+
+        // a solid color stays the same with every filter
+        let red = solid(48, [255, 0, 0, 255]);
+        for filter in filters {
+            for size in [16, 24, 64] {
+                let resized = red.resize(size, size, ResizeOptions::new(filter));
+                assert_eq!(
+                    resized,
+                    solid(size, [255, 0, 0, 255]),
+                    "{filter:?} to {size}"
+                );
+            }
+        }
+
+        // transparent pixels don't darken the edges
+        let mut half = solid(4, [0, 0, 0, 0]);
+        for pixel in half.pixels.chunks_exact_mut(8) {
+            pixel[..4].copy_from_slice(&[0, 255, 0, 255]);
+        }
+        let resized = half.resize(2, 2, ResizeOptions::new(Filter::Triangle));
+        for pixel in resized.pixels.chunks_exact(4) {
+            assert_eq!(pixel[..3], [0, 255, 0]);
+        }
+
+        // a checkerboard shrinks to gray, instead of to every other pixel
+        let checkerboard = Raster::new(
+            8,
+            8,
+            (0..64)
+                .flat_map(|i| match (i % 8 + i / 8) % 2 {
+                    0 => [0, 0, 0, 255],
+                    _ => [255, 255, 255, 255],
+                })
+                .collect::<Vec<_>>(),
+        );
+        let resized = checkerboard.resize(2, 2, ResizeOptions::new(Filter::Lanczos3));
+        for pixel in resized.pixels.chunks_exact(4) {
+            assert!((120..=135).contains(&pixel[0]), "{pixel:?}");
+        }
+
+        // small icons aren't enlarged
+        let options = ResizeOptions::new(Filter::CatmullRom).downscale_only(true);
+        let centered = solid(16, [0, 0, 255, 255]).resize(32, 24, options);
+        assert_eq!((centered.width, centered.height), (32, 24));
+        let pixel = |x: usize, y: usize| &centered.pixels[(y * 32 + x) * 4..][..4];
+        assert_eq!(pixel(0, 0), [0, 0, 0, 0]);
+        assert_eq!(pixel(8, 4), [0, 0, 255, 255]);
+        assert_eq!(pixel(23, 19), [0, 0, 255, 255]);
+        assert_eq!(pixel(24, 20), [0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn test_composite() {
+        let base = solid(16, [255, 255, 255, 255]);
+        let badge = solid(4, [255, 0, 0, 128]);
+        let composited = base.composite(&[
+            Overlay::raster(&badge, Anchor::TopRight),
+            Overlay::dot([0, 0, 255, 255], 6, Anchor::BottomLeft).with_offset(-2, 0),
+            // entirely outside
+            Overlay::raster(&badge, Anchor::Center).with_offset(100, 0),
+        ]);
+        let pixel = |x: usize, y: usize| &composited.pixels[(y * 16 + x) * 4..][..4];
+
+        // half transparent red over white
+        assert_eq!(pixel(12, 0), [255, 127, 127, 255]);
+        assert_eq!(pixel(15, 3), [255, 127, 127, 255]);
+        assert_eq!(pixel(11, 0), [255, 255, 255, 255]);
+        // the dot's center, moved left by two pixels, and its corner
+        assert_eq!(pixel(1, 13), [0, 0, 255, 255]);
+        assert_eq!(pixel(3, 10), [255, 255, 255, 255]);
+        assert_eq!(pixel(8, 8), [255, 255, 255, 255]);
+
+        // overlays are resized to their size
+        let large = Overlay::raster(&badge, Anchor::Center).with_size(16, 16);
+        let covered = solid(16, [0, 0, 0, 0]).composite(&[large]);
+        assert_eq!(covered, solid(16, [255, 0, 0, 128]));
+    }
+
+    #[test]
+    fn test_pixel_format() {
+        let raster = Raster::new(2, 1, vec![255, 128, 0, 128, 10, 20, 30, 255]);
+
+        assert_eq!(&raster.to_pixels(PixelFormat::RGBA)[..], &raster.pixels[..]);
+        assert_eq!(
+            raster.to_pixels(PixelFormat::new(ByteOrder::Argb, AlphaMode::Straight)),
+            [128, 255, 128, 0, 255, 10, 20, 30]
+        );
+        assert_eq!(
+            raster.to_pixels(PixelFormat::new(ByteOrder::Bgra, AlphaMode::Premultiplied)),
+            [0, 64, 128, 128, 30, 20, 10, 255]
+        );
+
+        let argb32 = raster.to_pixels(PixelFormat::ARGB32);
+        let first = u32::from_ne_bytes(argb32[..4].try_into().unwrap());
+        assert_eq!(first, 0x80_80_40_00);
+    }
+}
@@ -0,0 +1,334 @@
+//! What happened during a scan.
+//!
+//! Scanning skips whatever it can't use: base directories that don't exist, entries that can't be
+//! read, and theme candidates whose `index.theme` is missing or invalid. A [`ScanReport`] records
+//! all of these, to answer questions like "why is my theme missing".
+//!
+//! [`IconLocations::report`](crate::IconLocations::report) covers finding the theme candidates,
+//! [`Icons::report`](crate::Icons::report) also covers resolving them.
+//!
+//! Directories that can't be read, e.g. because of restrictive permissions or SELinux, don't stop
+//! a scan or lookup. The themes they belong to are [partial](crate::theme::Theme::is_partial)
+//! instead: icons are still found in the rest of the theme, and the directories are listed by
+//! [`Theme::unreadable_dirs`](crate::theme::Theme::unreadable_dirs). Directories are found to be
+//! unreadable while scanning, or when a lookup first fails to read them.
+//!
+//! When a theme is installed in more than one base directory, e.g. to override some of its icons
+//! locally, [`Theme::shadowing_report`] tells which of its files lookups never reach.
+//!
+//! # Example
+//!
+//! ```
+//! use icon::Icons;
+//!
+//! let icons = Icons::new();
+//! for failure in &icons.report.theme_failures {
+//!     eprintln!("skipped theme {:?}: {}", failure.name, failure.message);
+//! }
+//! ```
+
+use crate::audit::{AccessKind, Audit, allows};
+use crate::theme::{Theme, ThemeInfo};
+use std::collections::{BTreeMap, BTreeSet};
+use std::ffi::OsString;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::Duration;
+
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct ScanReport {
+    /// What was found in each base directory, in the order they were searched.
+    pub base_dirs: Vec<BaseDirReport>,
+    /// Theme candidates that were skipped because they couldn't be parsed.
+    pub theme_failures: Vec<ThemeFailure>,
+    /// Entries of base directories that couldn't be read.
+    pub skipped_entries: Vec<SkippedEntry>,
+    /// The themes with directories that couldn't be read during the scan, by internal name.
+    pub partial_themes: Vec<OsString>,
+    pub timings: ScanTimings,
+}
+
+/// What was found in a single base directory.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BaseDirReport {
+    pub path: PathBuf,
+    /// Why the directory couldn't be read, if it couldn't. Missing directories are reported as
+    /// [`ErrorKind::NotFound`].
+    pub error: Option<ErrorKind>,
+    /// The amount of directories that may be (part of) an icon theme.
+    pub theme_candidates: usize,
+    /// The amount of standalone icons found.
+    pub standalone_icons: usize,
+    /// The amount of files that aren't icons, or that were skipped because they can't be read.
+    pub ignored_files: usize,
+}
+
+impl BaseDirReport {
+    pub(crate) fn new(path: PathBuf) -> Self {
+        Self {
+            path,
+            error: None,
+            theme_candidates: 0,
+            standalone_icons: 0,
+            ignored_files: 0,
+        }
+    }
+}
+
+/// A theme candidate that was skipped, usually because of its `index.theme`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ThemeFailure {
+    /// The internal name of the theme.
+    pub name: OsString,
+    /// The directories of the theme, in all base directories.
+    pub paths: Vec<PathBuf>,
+    pub kind: ErrorKind,
+    /// A description of the error.
+    pub message: String,
+}
+
+impl ThemeFailure {
+    pub(crate) fn new(name: OsString, paths: Vec<PathBuf>, error: &std::io::Error) -> Self {
+        Self {
+            name,
+            paths,
+            kind: error.kind(),
+            message: error.to_string(),
+        }
+    }
+}
+
+/// An entry of a base directory that couldn't be read.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct SkippedEntry {
+    /// The path of the entry, or of the base directory if the entry's name couldn't be read.
+    pub path: PathBuf,
+    pub kind: ErrorKind,
+}
+
+/// How long the stages of a scan took.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Default)]
+pub struct ScanTimings {
+    /// Finding theme candidates and standalone icons in the base directories.
+    pub locate: Duration,
+    /// Parsing and resolving themes, including listing their directories.
+    pub resolve: Duration,
+}
+
+/// The files of a theme that lookups never reach, see [`Theme::shadowing_report`].
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Default)]
+pub struct ShadowingReport {
+    /// Directories with icon files that aren't listed in the theme's index, so they are never
+    /// looked in. The index used is the first one found, which may not be the one in the same base
+    /// directory as these.
+    pub unlisted: Vec<PathBuf>,
+    /// Directories with icons that also exist in the same directory of an earlier base directory.
+    pub shadowed: Vec<ShadowedDir>,
+}
+
+/// Icons of a directory that are hidden by another copy of the directory.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ShadowedDir {
+    pub path: PathBuf,
+    /// The directory with the same name in the earliest base directory having these icons.
+    pub by: PathBuf,
+    /// The names of the icons that are hidden, in alphabetical order.
+    pub icons: Vec<String>,
+}
+
+impl Theme {
+    /// Lists which of the directories and files this theme has on disk lookups never reach.
+    ///
+    /// A theme installed in several base directories is looked up in them in order, so an icon in
+    /// a directory of one base directory hides the icon of the same name in the same directory of
+    /// every later one. This helps to find out why a locally overridden icon isn't picked up:
+    /// either its directory isn't in the index, or another copy of the icon comes first.
+    ///
+    /// Reading the directories may take a while, as all of them are listed.
+    pub fn shadowing_report(&self) -> ShadowingReport {
+        let audit = self.audit.as_ref();
+        let mut report = ShadowingReport::default();
+
+        // the icon names in a directory, if it can be read
+        let icon_names = |dir: &Path| {
+            let entries = (allows(audit, dir, AccessKind::ListDirectory))
+                .then(|| dir.read_dir().ok())
+                .flatten();
+            (entries.into_iter().flatten().flatten())
+                .map(|entry| entry.path())
+                .filter(|path| self.file_types.from_path(path).is_some())
+                .filter_map(|path| Some(path.file_stem()?.to_str()?.to_owned()))
+                .collect::<BTreeSet<_>>()
+        };
+
+        let listed = (self.info.index.directories.iter())
+            .map(|directory| Path::new(&directory.directory_name))
+            .collect::<BTreeSet<_>>();
+        for base_dir in &self.info.base_dirs {
+            let mut pending = vec![base_dir.clone()];
+            while let Some(dir) = pending.pop() {
+                let entries = (allows(audit, &dir, AccessKind::ListDirectory))
+                    .then(|| dir.read_dir().ok())
+                    .flatten();
+                let mut has_icons = false;
+                for entry in entries.into_iter().flatten().flatten() {
+                    // links to directories aren't followed, they may form cycles
+                    if entry.file_type().is_ok_and(|ft| ft.is_dir()) {
+                        pending.push(entry.path());
+                    } else if self.file_types.from_path(&entry.path()).is_some() {
+                        has_icons = true;
+                    }
+                }
+
+                let relative = dir.strip_prefix(base_dir).unwrap_or(&dir);
+                if has_icons && dir != *base_dir && !listed.contains(relative) {
+                    report.unlisted.push(dir);
+                }
+            }
+        }
+        report.unlisted.sort();
+
+        for directory in &self.info.index.directories {
+            let mut seen = BTreeMap::<String, usize>::new();
+            let dirs = (self.info.base_dirs.iter())
+                .map(|base_dir| base_dir.join(&directory.directory_name))
+                .collect::<Vec<_>>();
+
+            for (idx, dir) in dirs.iter().enumerate() {
+                let mut shadowed = BTreeMap::<usize, Vec<String>>::new();
+                for name in icon_names(dir) {
+                    match seen.get(&name) {
+                        Some(&by) => shadowed.entry(by).or_default().push(name),
+                        None => {
+                            seen.insert(name, idx);
+                        }
+                    }
+                }
+
+                report
+                    .shadowed
+                    .extend(shadowed.into_iter().map(|(by, icons)| ShadowedDir {
+                        path: dir.clone(),
+                        by: dirs[by].clone(),
+                        icons,
+                    }));
+            }
+        }
+
+        report
+    }
+}
+
+/// The directories of a theme that couldn't be read.
+#[derive(Debug, Default)]
+pub struct UnreadableDirs(Mutex<BTreeSet<PathBuf>>);
+
+impl UnreadableDirs {
+    /// Checks which of the directories a theme is installed in can't be read.
+    pub(crate) fn scan(info: &ThemeInfo, audit: Option<&Audit>) -> Self {
+        let dirs = Self::default();
+        for base_dir in &info.base_dirs {
+            if allows(audit, base_dir, AccessKind::ListDirectory) {
+                dirs.check(base_dir);
+            }
+        }
+        dirs
+    }
+
+    /// Records `dir` if it exists, but can't be read.
+    pub(crate) fn check(&self, dir: &Path) {
+        if let Err(e) = dir.read_dir() {
+            self.insert(dir, e.kind());
+        }
+    }
+
+    /// Records `dir` after reading it failed with `kind`.
+    pub(crate) fn insert(&self, dir: &Path, kind: ErrorKind) {
+        if kind == ErrorKind::NotFound {
+            return;
+        }
+
+        #[cfg(feature = "log")]
+        log::debug!("theme directory {dir:?} is unreadable: {kind}");
+
+        let mut dirs = self.0.lock().unwrap_or_else(|e| e.into_inner());
+        if !dirs.contains(dir) {
+            dirs.insert(dir.to_owned());
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.lock().unwrap_or_else(|e| e.into_inner()).is_empty()
+    }
+
+    /// The unreadable directories, in alphabetical order.
+    pub fn to_vec(&self) -> Vec<PathBuf> {
+        let dirs = self.0.lock().unwrap_or_else(|e| e.into_inner());
+        dirs.iter().cloned().collect()
+    }
+}
+
+impl Clone for UnreadableDirs {
+    fn clone(&self) -> Self {
+        Self(Mutex::new(
+            self.0.lock().unwrap_or_else(|e| e.into_inner()).clone(),
+        ))
+    }
+}
+
+// like statistics, what lookups found out doesn't affect what a theme is
+impl PartialEq for UnreadableDirs {
+    fn eq(&self, _other: &Self) -> bool {
+        true
+    }
+}
+
+impl Eq for UnreadableDirs {}
+
+impl std::hash::Hash for UnreadableDirs {
+    fn hash<H: std::hash::Hasher>(&self, _state: &mut H) {}
+}
+
+#[cfg(test)]
+mod test {
+    use crate::IconSearch;
+    use crate::report::ShadowedDir;
+    use std::fs;
+
+    #[test]
+    fn test_shadowing_report() {
+        let dir = tempfile::tempdir().unwrap();
+        let (local, system) = (dir.path().join("local"), dir.path().join("system"));
+        for (base_dir, files) in [
+            (&local, &["48/firefox.svg", "48x48/apps/gimp.png"][..]),
+            (&system, &["48/firefox.png", "48/gimp.png", "48/README"]),
+        ] {
+            let theme = base_dir.join("Oak");
+            for file in files {
+                fs::create_dir_all(theme.join(file).parent().unwrap()).unwrap();
+                fs::write(theme.join(file), b"").unwrap();
+            }
+            fs::write(
+                theme.join("index.theme"),
+                "[Icon Theme]\nName=Oak\nComment=\nDirectories=48\n\n[48]\nSize=48\n",
+            )
+            .unwrap();
+        }
+
+        let icons = IconSearch::new_from(vec![local.clone(), system.clone()])
+            .search()
+            .icons();
+        let report = icons.theme("Oak").unwrap().shadowing_report();
+
+        assert_eq!(report.unlisted, [local.join("Oak/48x48/apps")]);
+        assert_eq!(
+            report.shadowed,
+            [ShadowedDir {
+                path: system.join("Oak/48"),
+                by: local.join("Oak/48"),
+                icons: vec!["firefox".into()],
+            }]
+        );
+    }
+}
@@ -0,0 +1,134 @@
+//! Abstracting over where icons come from.
+//!
+//! Code that only looks icons up can take an [`IconResolver`] instead of [`Icons`], so that its
+//! tests can pass a [`MockIcons`](crate::testing::MockIcons) rather than depend on the
+//! icons installed on the machine running them.
+//!
+//! # Example
+//!
+//! ```
+//! use icon::resolver::IconResolver;
+//! use icon::testing::MockIcons;
+//!
+//! fn toolbar_icons(icons: &impl IconResolver, theme: &str) -> Vec<bool> {
+//!     ["document-open", "document-save"]
+//!         .into_iter()
+//!         .map(|name| icons.has_icon(name, theme))
+//!         .collect()
+//! }
+//!
+//! let icons = MockIcons::new().with_icon("Adwaita", "document-open", "/icons/open.svg");
+//! assert_eq!(toolbar_icons(&icons, "Adwaita"), [true, false]);
+//! ```
+
+use crate::IconFile;
+use crate::theme::Icons;
+use std::collections::BTreeSet;
+
+/// Looks up icons by name, size, scale and theme, see [`resolver`](crate::resolver).
+pub trait IconResolver {
+    /// Looks up an icon, see [`Icons::find_icon`].
+    fn find_icon(&self, icon_name: &str, size: u32, scale: u32, theme: &str) -> Option<IconFile>;
+
+    /// Whether there is a theme `theme` an icon would be found in at any size, possibly through
+    /// the fallbacks of [`find_icon`](Self::find_icon). Unknown themes have no icons, even if
+    /// looking icons up in them falls back to other themes.
+    fn has_icon(&self, icon_name: &str, theme: &str) -> bool {
+        self.list_themes().iter().any(|name| name == theme)
+            && self.find_icon(icon_name, 48, 1, theme).is_some()
+    }
+
+    /// The internal names of all themes, in a stable order.
+    fn list_themes(&self) -> Vec<String>;
+
+    /// The names of all icons in `theme` (but not its parents), in alphabetical order. Empty if
+    /// there is no such theme.
+    fn list_icons(&self, theme: &str) -> BTreeSet<String>;
+}
+
+impl<T: IconResolver + ?Sized> IconResolver for &T {
+    fn find_icon(&self, icon_name: &str, size: u32, scale: u32, theme: &str) -> Option<IconFile> {
+        (**self).find_icon(icon_name, size, scale, theme)
+    }
+
+    fn has_icon(&self, icon_name: &str, theme: &str) -> bool {
+        (**self).has_icon(icon_name, theme)
+    }
+
+    fn list_themes(&self) -> Vec<String> {
+        (**self).list_themes()
+    }
+
+    fn list_icons(&self, theme: &str) -> BTreeSet<String> {
+        (**self).list_icons(theme)
+    }
+}
+
+impl IconResolver for Icons {
+    fn find_icon(&self, icon_name: &str, size: u32, scale: u32, theme: &str) -> Option<IconFile> {
+        Icons::find_icon(self, icon_name, size, scale, theme)
+    }
+
+    fn has_icon(&self, icon_name: &str, theme: &str) -> bool {
+        self.theme(theme).is_some() && Icons::find_icon(self, icon_name, 48, 1, theme).is_some()
+    }
+
+    /// The themes in the order of [`iter_themes`](Icons::iter_themes).
+    fn list_themes(&self) -> Vec<String> {
+        (self.iter_themes())
+            .map(|(name, _)| name.to_string_lossy().into_owned())
+            .collect()
+    }
+
+    /// See [`Theme::list_icons`](crate::theme::Theme::list_icons), which reads the theme's
+    /// directories.
+    fn list_icons(&self, theme: &str) -> BTreeSet<String> {
+        (self.theme(theme))
+            .map(|theme| theme.list_icons())
+            .unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::IconSearch;
+    use crate::resolver::IconResolver;
+    use std::fs;
+
+    #[test]
+    fn test_icons_resolver() {
+        let dir = tempfile::tempdir().unwrap();
+        let theme = dir.path().join("Oak");
+        fs::create_dir_all(theme.join("16")).unwrap();
+        fs::write(
+            theme.join("index.theme"),
+            "[Icon Theme]\nName=Oak\nComment=\nDirectories=16\n\n[16]\nSize=16\n",
+        )
+        .unwrap();
+        fs::write(theme.join("16/acorn.png"), "").unwrap();
+        fs::write(theme.join("16/leaf.svg"), "").unwrap();
+        fs::write(dir.path().join("bark.png"), "").unwrap();
+
+        let icons = IconSearch::new_from(vec![dir.path().to_owned()])
+            .search()
+            .icons();
+        let resolver: &dyn IconResolver = &icons;
+
+        assert_eq!(resolver.list_themes(), ["Oak"]);
+        assert_eq!(
+            resolver.list_icons("Oak").into_iter().collect::<Vec<_>>(),
+            ["acorn", "leaf"]
+        );
+        assert!(resolver.list_icons("Birch").is_empty());
+        assert!(resolver.has_icon("acorn", "Oak"));
+        assert!(resolver.has_icon("bark", "Oak"));
+        assert!(!resolver.has_icon("trunk", "Oak"));
+        // looking icons up in unknown themes falls back to standalone icons
+        assert!(resolver.find_icon("bark", 16, 1, "Birch").is_some());
+        assert!(!resolver.has_icon("bark", "Birch"));
+        assert_eq!(
+            resolver.find_icon("leaf", 16, 1, "Oak").unwrap().path,
+            theme.join("16/leaf.svg")
+        );
+    }
+}
@@ -1,11 +1,23 @@
-use crate::icon::IconFile;
-use crate::theme::{Icons, Theme, ThemeInfo, ThemeParseError};
+use crate::audit::{AccessKind, Audit, allows};
+use crate::cancel::{CancellationToken, Cancelled};
+use crate::change::Listeners;
+use crate::environment::Environment;
+use crate::hash_map::HashMap as FastHashMap;
+use crate::icon::{FileType, FileTypes, IconFile, SkipReason, SkippedFile};
+use crate::listing::DirectoryListing;
+use crate::report::{
+    BaseDirReport, ScanReport, ScanTimings, SkippedEntry, ThemeFailure, UnreadableDirs,
+};
+use crate::spec::Compliance;
+use crate::statistics::HitStatistics;
+use crate::theme::{Icons, SubDirSelections, Theme, ThemeInfo, ThemeParseError, search_order};
 use states::*;
 use std::collections::HashMap;
 use std::ffi::{OsStr, OsString};
 use std::marker::PhantomData;
-use std::path::PathBuf;
-use std::sync::Arc;
+use std::path::{Component, Path, PathBuf};
+use std::sync::{Arc, OnceLock};
+use std::time::Instant;
 
 macro_rules! states {
     ($($(#[$($attr:tt)*])* $id:ident),*) => {
@@ -17,6 +29,7 @@ macro_rules! states {
 
         $(
             $(#[$($attr)*])*
+            #[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
             pub struct $id;
 
             impl sealed::Sealed for $id {}
@@ -72,10 +85,46 @@ pub mod states {
 ///     // resolve all icon themes and return an Icons struct which you can use for icon finding!
 ///     .icons();
 /// ```
-// #[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct IconSearch<State = Initial> {
     /// The list of directories to search for standalone icons and icon themes
     pub dirs: Vec<PathBuf>,
+    /// If set, all directories in [`dirs`](Self::dirs) are resolved relative to this directory
+    /// instead of `/`.
+    pub sysroot: Option<PathBuf>,
+    /// The theme to use when a requested theme doesn't exist. If unset, `hicolor` is used.
+    pub fallback_theme: Option<String>,
+    /// Themes to look up icons in after the requested theme and before hicolor.
+    pub preferred_fallbacks: Vec<String>,
+    /// Whether missing icons [fall back](Self::with_name_fallback) to more generic names.
+    pub name_fallback: bool,
+    /// Which copy of a theme wins when it is installed in more than one directory.
+    pub precedence: Precedence,
+    /// Whether to [list the directories](Self::with_directory_listing) of all themes up front.
+    pub list_directories: bool,
+    /// Whether lookups [adapt](Self::with_adaptive_ordering) the order of directories.
+    pub adaptive_ordering: bool,
+    /// Whether lookups find [`@2x` files](Self::with_scale_suffixes).
+    pub scale_suffixes: bool,
+    /// Whether themes without an index [get one](Self::with_inferred_indexes).
+    pub infer_indexes: bool,
+    /// Whether hicolor without an index [gets the built-in one](Self::with_hicolor_bootstrap).
+    pub bootstrap_hicolor: bool,
+    /// The file types to look for, see [`with_file_type`](Self::with_file_type).
+    pub file_types: FileTypes,
+    /// How closely to follow the specification, see [`with_compliance`](Self::with_compliance).
+    pub compliance: Compliance,
+    /// Where file system access is confined to and recorded, see [`with_audit`](Self::with_audit).
+    pub audit: Option<Audit>,
+    /// Whether icon files are opened without leaving the search directories, see
+    /// `with_resolve_beneath`. Only set with the `openat2` feature.
+    pub(crate) resolve_beneath: bool,
+    /// The environment given to [`from_environment`](Self::from_environment), which the user's
+    /// current theme is read from. If unset, the environment of the current process is used.
+    pub(crate) environment: Option<Environment>,
+    /// The threads of parallel lookups, see [`with_parallelism`](Self::with_parallelism).
+    #[cfg(feature = "rayon")]
+    pub parallelism: crate::parallel::Parallelism,
     icon_locations: Option<IconLocations>,
     icons: Option<Icons>,
     // in fn() so that the compiler doesn't see State as part of this struct,
@@ -83,6 +132,34 @@ pub struct IconSearch<State = Initial> {
     _state: PhantomData<fn() -> State>,
 }
 
+impl<State> IconSearch<State> {
+    fn into_state<Next>(self) -> IconSearch<Next> {
+        IconSearch {
+            dirs: self.dirs,
+            sysroot: self.sysroot,
+            fallback_theme: self.fallback_theme,
+            preferred_fallbacks: self.preferred_fallbacks,
+            name_fallback: self.name_fallback,
+            precedence: self.precedence,
+            list_directories: self.list_directories,
+            adaptive_ordering: self.adaptive_ordering,
+            scale_suffixes: self.scale_suffixes,
+            infer_indexes: self.infer_indexes,
+            bootstrap_hicolor: self.bootstrap_hicolor,
+            file_types: self.file_types,
+            compliance: self.compliance,
+            audit: self.audit,
+            resolve_beneath: self.resolve_beneath,
+            environment: self.environment,
+            #[cfg(feature = "rayon")]
+            parallelism: self.parallelism,
+            icon_locations: self.icon_locations,
+            icons: self.icons,
+            _state: PhantomData,
+        }
+    }
+}
+
 impl IconSearch<Initial> {
     // -- STAGE 1: Establish directories wherein to find icons
 
@@ -96,6 +173,21 @@ impl IconSearch<Initial> {
         <Self as Default>::default()
     }
 
+    /// Constructs a new `IconSearch` from the default directories of the given [`Environment`].
+    ///
+    /// Unlike [`new`](Self::new), this does not read the environment of the current process. The
+    /// user's [current theme](Environment::current_theme) is read from `env` as well.
+    pub fn from_environment(env: &Environment) -> Self {
+        let mut search: Self = env.icon_dirs().into();
+        search.environment = Some(env.clone());
+        search
+    }
+
+    /// Constructs a new `IconSearch` by applying [`SearchOptions`] to the default directories.
+    pub fn from_options(options: &SearchOptions) -> Self {
+        options.apply(Self::new())
+    }
+
     /// Constructs a new `IconSearch` without any directories to search.
     pub const fn new_empty() -> Self {
         Self::new_from(Vec::new())
@@ -105,6 +197,23 @@ impl IconSearch<Initial> {
     pub const fn new_from(dirs: Vec<PathBuf>) -> Self {
         Self {
             dirs,
+            sysroot: None,
+            fallback_theme: None,
+            preferred_fallbacks: Vec::new(),
+            name_fallback: false,
+            precedence: Precedence::UserFirst,
+            list_directories: false,
+            adaptive_ordering: false,
+            scale_suffixes: false,
+            infer_indexes: false,
+            bootstrap_hicolor: true,
+            file_types: FileTypes::new(),
+            compliance: Compliance::Lenient,
+            audit: None,
+            resolve_beneath: false,
+            environment: None,
+            #[cfg(feature = "rayon")]
+            parallelism: crate::parallel::Parallelism::Global,
             icon_locations: None,
             icons: None,
             _state: PhantomData,
@@ -128,41 +237,336 @@ impl IconSearch<Initial> {
         I: IntoIterator<Item = P>,
         P: Into<PathBuf>,
     {
-        let mut extra_dirs = directories.into_iter().map(Into::into).collect();
-        self.dirs.append(&mut extra_dirs);
+        self.dirs.extend(directories.into_iter().map(Into::into));
+        self
+    }
+
+    /// Resolves all search directories relative to `sysroot`, as if it were the root directory.
+    ///
+    /// This allows finding the icons of a system other than the one currently running, such as
+    /// a mounted disk image, a chroot or an OS build tree. The paths of the icons found will be
+    /// inside `sysroot`.
+    ///
+    /// Note that the default directories are still derived from the environment of the _current_
+    /// process. Symbolic links are followed as usual, so absolute links inside the sysroot are
+    /// resolved against the real root directory.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use icon::IconSearch;
+    ///
+    /// let icons = IconSearch::new_from(vec!["/usr/share/icons".into(), "/usr/share/pixmaps".into()])
+    ///     .with_sysroot("/mnt/image")
+    ///     .search()
+    ///     .icons();
+    /// ```
+    pub fn with_sysroot(mut self, sysroot: impl Into<PathBuf>) -> Self {
+        self.sysroot = Some(sysroot.into());
+        self
+    }
+
+    /// Sets the theme to use when a requested theme doesn't exist, instead of `hicolor`.
+    ///
+    /// If the fallback theme doesn't exist either, `hicolor` is still used.
+    pub fn with_fallback_theme(mut self, theme: impl Into<String>) -> Self {
+        self.fallback_theme = Some(theme.into());
+        self
+    }
+
+    /// Sets themes to look up icons in after the requested theme and its parents, but before
+    /// hicolor, in all lookups of the resulting [`Icons`].
+    ///
+    /// This lets applications fill gaps in the user's theme with themes they know to be complete,
+    /// e.g. `["breeze", "Adwaita"]`. Themes that don't exist are skipped. With
+    /// [`Compliance::Strict`], preferred fallbacks aren't used.
+    pub fn with_preferred_fallbacks<I, S>(mut self, themes: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.preferred_fallbacks = themes.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Makes [`Icons::find_icon`] fall back to more generic names when an icon doesn't exist, by
+    /// stripping `-` separated segments off its end, as GTK and Qt do. See
+    /// [`fallback_names`](crate::naming::fallback_names).
+    pub fn with_name_fallback(mut self, name_fallback: bool) -> Self {
+        self.name_fallback = name_fallback;
+        self
+    }
+
+    /// Sets which copy of a theme wins when it is installed in more than one search directory.
+    ///
+    /// See [`Precedence`].
+    pub fn with_precedence(mut self, precedence: Precedence) -> Self {
+        self.precedence = precedence;
+        self
+    }
+
+    /// Reads the directories of all themes up front, so that looking up icons doesn't need to
+    /// access the file system.
+    ///
+    /// This makes resolving the themes much slower, but pays off when looking up many icons.
+    /// See [`listing`](crate::listing).
+    pub fn with_directory_listing(mut self, list_directories: bool) -> Self {
+        self.list_directories = list_directories;
+        self
+    }
+
+    /// Tries the directories of a theme that match the requested size in order of how often
+    /// icons were found in them, instead of the order of the theme's index.
+    ///
+    /// This shortens the search for applications that repeatedly request similar sizes. If an
+    /// icon exists in more than one directory that matches the size exactly, a different one of
+    /// them may be returned than without this option. See [`statistics`](crate::statistics).
+    pub fn with_adaptive_ordering(mut self, adaptive_ordering: bool) -> Self {
+        self.adaptive_ordering = adaptive_ordering;
+        self
+    }
+
+    /// Also recognizes the `name@2x.png` convention for icons of a scale above 1, which some
+    /// themes and applications use instead of directories with a `Scale`.
+    ///
+    /// For a request at scale `N`, `{name}@{N}x` files in the directories for the requested size
+    /// at scale 1 are tried after the directories for scale `N` and before any other size. The
+    /// same goes for standalone icons, where `{name}@{N}x` is preferred over `{name}`.
+    pub fn with_scale_suffixes(mut self, scale_suffixes: bool) -> Self {
+        self.scale_suffixes = scale_suffixes;
+        self
+    }
+
+    /// Synthesizes an index for theme directories without an `index.theme` from the names of
+    /// their directories, e.g. `48x48/apps`, instead of skipping them. See [`infer`](crate::infer).
+    ///
+    /// This is a [deviation](crate::spec::Deviation::InferredIndexes) from the specification, so
+    /// it has no effect with [`Compliance::Strict`].
+    pub fn with_inferred_indexes(mut self, infer_indexes: bool) -> Self {
+        self.infer_indexes = infer_indexes;
+        self
+    }
+
+    /// Whether to use a built-in index for hicolor if it is installed without one, which is on by
+    /// default.
+    ///
+    /// On minimal systems without the hicolor package, applications still install their icons
+    /// into `hicolor` directories, e.g. in `~/.local/share/icons/hicolor`. As hicolor's
+    /// directories are fixed, its index can be [synthesized](crate::theme::ThemeIndex::hicolor),
+    /// so that looking up icons in it keeps working.
+    ///
+    /// This is a [deviation](crate::spec::Deviation::BuiltinHicolorIndex) from the specification,
+    /// so it has no effect with [`Compliance::Strict`].
+    pub fn with_hicolor_bootstrap(mut self, bootstrap_hicolor: bool) -> Self {
+        self.bootstrap_hicolor = bootstrap_hicolor;
+        self
+    }
+
+    /// Also looks for icon files of another type, such as `FileType::Other("webp")`.
+    ///
+    /// Registered types are found in themes and as standalone icons like the built-in PNG, SVG
+    /// and XPM files, but are tried after them: of two files in the same directory, a built-in
+    /// type always wins. Registered types are tried in the order they were registered in.
+    ///
+    /// # Panics
+    ///
+    /// If more than [`FileTypes::MAX`] types are registered.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use icon::{FileType, IconSearch};
+    ///
+    /// let icons = IconSearch::new()
+    ///     .with_file_type(FileType::Other("webp"))
+    ///     .search()
+    ///     .icons();
+    /// ```
+    pub fn with_file_type(mut self, file_type: FileType) -> Self {
+        self.file_types = self.file_types.with(file_type);
+        self
+    }
+
+    /// Also looks for the [extra file types](FileType::extra_types) that aren't in the
+    /// specification but occur on real systems: WebP and AVIF icons of third-party themes, and
+    /// the ICO files Wine installs. They are tried after the built-in types, see
+    /// [`with_file_type`](Self::with_file_type).
+    pub fn with_extra_file_types(mut self) -> Self {
+        for file_type in FileType::extra_types() {
+            self.file_types = self.file_types.with(file_type);
+        }
+        self
+    }
+
+    /// Sets the file types to look for, in order of preference, e.g. `[Svg, Png]` for user
+    /// interfaces that prefer vector icons. Files of other types are never found.
+    ///
+    /// See [`FileTypes::with_priority`].
+    pub fn with_file_type_priority(
+        mut self,
+        file_types: impl IntoIterator<Item = FileType>,
+    ) -> Self {
+        self.file_types = self.file_types.with_priority(file_types);
+        self
+    }
+
+    /// Sets how closely to follow the icon theme specification when reading themes and looking
+    /// up icons.
+    ///
+    /// [`Compliance::Lenient`] is the default, and what desktop applications should use.
+    /// [`Compliance::Strict`] suits tools that validate themes, see [`spec`](crate::spec).
+    pub fn with_compliance(mut self, compliance: Compliance) -> Self {
+        self.compliance = compliance;
+        self
+    }
+
+    /// Confines all file system access to the search directories, and records it in `audit`.
+    ///
+    /// This also stops consulting the environment and the user's settings. See
+    /// [`audit`](crate::audit).
+    pub fn with_audit(mut self, audit: Audit) -> Self {
+        self.audit = Some(audit);
+        self
+    }
+
+    /// Opens icon files with `openat2` relative to the search directory they are in, so that
+    /// symbolic links can't lead lookups out of the search directories. See
+    /// [`openat2`](crate::openat2).
+    ///
+    /// This requires the `openat2` feature.
+    #[cfg(feature = "openat2")]
+    pub fn with_resolve_beneath(mut self, resolve_beneath: bool) -> Self {
+        self.resolve_beneath = resolve_beneath;
+        self
+    }
 
-        extra_dirs.into()
+    /// Sets the threads [parallel lookups](Icons::find_icons_par) run on, instead of rayon's
+    /// global thread pool.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use icon::IconSearch;
+    /// use icon::parallel::Parallelism;
+    ///
+    /// let icons = IconSearch::new()
+    ///     .with_parallelism(Parallelism::with_threads(2).unwrap())
+    ///     .search()
+    ///     .icons();
+    /// ```
+    #[cfg(feature = "rayon")]
+    pub fn with_parallelism(mut self, parallelism: crate::parallel::Parallelism) -> Self {
+        self.parallelism = parallelism;
+        self
+    }
+
+    /// The search directories, resolved relative to the [sysroot](Self::with_sysroot) if set.
+    pub fn resolved_dirs(&self) -> Vec<PathBuf> {
+        match &self.sysroot {
+            Some(sysroot) => self
+                .dirs
+                .iter()
+                .map(|dir| in_sysroot(sysroot, dir))
+                .collect(),
+            None => self.dirs.clone(),
+        }
     }
 
     // -- STAGE 2: In search dirs, find standalone icons and directories that may be icon themes
 
-    fn find_icon_locations(&self) -> IconLocations {
+    fn find_icon_locations(&self, token: &CancellationToken) -> Result<IconLocations, Cancelled> {
+        let started = Instant::now();
+        let mut report = ScanReport::default();
+        let audit = self.audit.as_ref();
+        if let Some(audit) = audit {
+            audit.confine_to(&self.resolved_dirs());
+        }
+
         // "Each theme is stored as subdirectories of the base directories"
 
-        let (dirs, files) = self
-            .dirs
-            .iter()
-            .flat_map(|base_dir| base_dir.read_dir()) // read the entries in each base dir
-            .flatten() // merge all the iterators
-            .flatten() // remove Err entries
-            .filter_map(|entry| Some((entry.file_type().ok()?, entry))) // get file type for each entry and skip if fail
-            .partition::<Vec<_>, _>(|(ft, entry)| {
-                ft.is_dir() || (!entry.path().extension().is_some() && ft.is_symlink())
-            });
+        let mut dirs = Vec::new();
+        let mut files = Vec::new();
+        let mut skipped_files = Vec::new();
+        for base_dir in self.resolved_dirs() {
+            token.check()?;
+            let mut counts = BaseDirReport::new(base_dir.clone());
+            if !allows(audit, &base_dir, AccessKind::ListDirectory) {
+                report.base_dirs.push(counts);
+                continue;
+            }
 
-        // icons at the top-level in a base_dir don't belong to a theme, but must still be able to be found!
-        let files = files
-            .into_iter()
-            .flat_map(|(_, entry)| IconFile::from_path(&entry.path()))
-            .collect::<Vec<_>>();
+            let entries = match base_dir.read_dir() {
+                Ok(entries) => entries,
+                Err(e) => {
+                    counts.error = Some(e.kind());
+                    report.base_dirs.push(counts);
+                    continue;
+                }
+            };
+
+            for entry in entries {
+                token.check()?;
+
+                // get the file type of each entry, and skip it if either fails
+                let (ft, entry) = match entry.map(|entry| (entry.file_type(), entry)) {
+                    Ok((Ok(ft), entry)) => (ft, entry),
+                    Ok((Err(e), entry)) => {
+                        report.skipped_entries.push(SkippedEntry {
+                            path: entry.path(),
+                            kind: e.kind(),
+                        });
+                        continue;
+                    }
+                    Err(e) => {
+                        report.skipped_entries.push(SkippedEntry {
+                            path: base_dir.clone(),
+                            kind: e.kind(),
+                        });
+                        continue;
+                    }
+                };
+
+                if ft.is_dir() || (entry.path().extension().is_none() && ft.is_symlink()) {
+                    counts.theme_candidates += 1;
+                    dirs.push(entry);
+                    continue;
+                }
+
+                // icons at the top-level in a base_dir don't belong to a theme, but must still be able to be found!
+                let Some(file) = self.file_types.icon_file(&entry.path()) else {
+                    counts.ignored_files += 1;
+                    continue;
+                };
+                if !allows(audit, &file.path, AccessKind::Probe) {
+                    counts.ignored_files += 1;
+                    continue;
+                }
+                let root = self.resolve_beneath.then_some(base_dir.as_path());
+                match SkipReason::check_in(root, &file.path) {
+                    Ok(()) => {
+                        counts.standalone_icons += 1;
+                        files.push(file);
+                    }
+                    Err(reason) => {
+                        counts.ignored_files += 1;
+                        skipped_files.extend(reason.map(|reason| SkippedFile {
+                            path: file.path.clone(),
+                            reason,
+                        }));
+                    }
+                }
+            }
+
+            report.base_dirs.push(counts);
+        }
 
         // "In at least one of the theme directories there must be a file called
         // index.theme that describes the theme. The first index.theme found while
         // searching the base directories in order is used"
 
         // For each theme name, create a list of directories where it may be found:
-        let mut themes_directories: HashMap<OsString, Vec<PathBuf>> = HashMap::new();
-        for (_, dir) in dirs {
+        let mut themes_directories: HashMap<OsString, Vec<PathBuf>> = HashMap::default();
+        for dir in dirs {
             let theme_name = dir.file_name();
 
             themes_directories
@@ -171,24 +575,64 @@ impl IconSearch<Initial> {
                 .push(dir.path());
         }
 
-        IconLocations {
+        if self.precedence == Precedence::SystemFirst {
+            for dirs in themes_directories.values_mut() {
+                dirs.reverse();
+            }
+        }
+
+        Ok(IconLocations {
+            base_dirs: self.resolved_dirs(),
             standalone_icons: files,
             themes_directories,
-        }
+            skipped_files,
+            list_directories: self.list_directories,
+            adaptive_ordering: self.adaptive_ordering,
+            scale_suffixes: self.scale_suffixes,
+            infer_indexes: self.infer_indexes,
+            bootstrap_hicolor: self.bootstrap_hicolor,
+            file_types: self.file_types.clone(),
+            compliance: self.compliance,
+            audit: self.audit.clone(),
+            resolve_beneath: self.resolve_beneath,
+            environment: self.environment.clone(),
+            report: ScanReport {
+                timings: ScanTimings {
+                    locate: started.elapsed(),
+                    ..ScanTimings::default()
+                },
+                ..report
+            },
+        })
     }
 
     /// Find icons and icon themes in the configured search directories.
     ///
     /// This function proceeds the [`IconSearch`] to the [next stage](LocationsFound).
     pub fn search(self) -> IconSearch<LocationsFound> {
-        let icon_locations = self.find_icon_locations();
+        self.try_search(&CancellationToken::new())
+            .expect("the token is never cancelled")
+    }
 
-        IconSearch::<LocationsFound> {
-            dirs: self.dirs,
-            icon_locations: Some(icon_locations),
-            icons: None,
-            _state: PhantomData,
-        }
+    /// Like [`search`](Self::search), but aborting once `token` is
+    /// [cancelled](CancellationToken::cancel).
+    pub fn try_search(
+        self,
+        token: &CancellationToken,
+    ) -> Result<IconSearch<LocationsFound>, Cancelled> {
+        let icon_locations = self.find_icon_locations(token)?;
+        Ok(self.with_icon_locations(icon_locations))
+    }
+
+    /// Proceeds to the next stage with locations found otherwise, e.g. from a
+    /// [snapshot](crate::snapshot).
+    pub(crate) fn with_icon_locations(
+        self,
+        icon_locations: IconLocations,
+    ) -> IconSearch<LocationsFound> {
+        let mut search = self.into_state::<LocationsFound>();
+        search.icon_locations = Some(icon_locations);
+        search
     }
 }
 
@@ -209,22 +653,37 @@ impl IconSearch<LocationsFound> {
 
     // -- STAGE 3: We have icon theme candidates, so it's time to resolve them.
 
-    fn finish(self) -> IconSearch<Finished> {
-        let icons = self.icon_locations.expect("guaranteed by type-state");
-        let icons = icons.icons();
+    fn finish(self, token: &CancellationToken) -> Result<IconSearch<Finished>, Cancelled> {
+        let mut search = self.into_state::<Finished>();
 
-        IconSearch {
-            dirs: self.dirs,
-            icon_locations: None, // consumed!
-            icons: Some(icons),
-            _state: PhantomData,
+        let icons = search.icon_locations.take(); // consumed!
+        let mut icons = icons.expect("guaranteed by type-state").try_icons(token)?;
+        if let Some(fallback_theme) = &search.fallback_theme {
+            icons.fallback_theme = fallback_theme.clone();
         }
+        icons.preferred_fallbacks = search.preferred_fallbacks.clone();
+        icons.name_fallback = search.name_fallback;
+        icons.precedence = search.precedence;
+        #[cfg(feature = "rayon")]
+        {
+            icons.parallelism = search.parallelism.clone();
+        }
+
+        search.icons = Some(icons);
+        Ok(search)
     }
 
     /// Finish icon finding by parsing, validating, and resolving (parents of) all icon themes
     /// found.
     pub fn icons(self) -> Icons {
-        self.finish().icons()
+        self.try_icons(&CancellationToken::new())
+            .expect("the token is never cancelled")
+    }
+
+    /// Like [`icons`](Self::icons), but aborting once `token` is
+    /// [cancelled](CancellationToken::cancel).
+    pub fn try_icons(self, token: &CancellationToken) -> Result<Icons, Cancelled> {
+        Ok(self.finish(token)?.icons())
     }
 }
 
@@ -237,10 +696,36 @@ impl IconSearch<Finished> {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct IconLocations {
+    /// The directories that were searched, in order.
+    pub base_dirs: Vec<PathBuf>,
     pub standalone_icons: Vec<IconFile>,
     pub themes_directories: HashMap<OsString, Vec<PathBuf>>,
+    /// Standalone icons that were skipped because they can't be read, such as dangling symbolic
+    /// links.
+    pub skipped_files: Vec<SkippedFile>,
+    /// Whether to [list the directories](IconSearch::with_directory_listing) of resolved themes.
+    pub list_directories: bool,
+    /// Whether resolved themes [adapt](IconSearch::with_adaptive_ordering) the order of their
+    /// directories.
+    pub adaptive_ordering: bool,
+    /// See [`IconSearch::with_scale_suffixes`].
+    pub scale_suffixes: bool,
+    /// See [`IconSearch::with_inferred_indexes`].
+    pub infer_indexes: bool,
+    /// See [`IconSearch::with_hicolor_bootstrap`].
+    pub bootstrap_hicolor: bool,
+    /// The file types to look for, see [`IconSearch::with_file_type`].
+    pub file_types: FileTypes,
+    /// See [`IconSearch::with_compliance`].
+    pub compliance: Compliance,
+    /// See [`IconSearch::with_audit`].
+    pub audit: Option<Audit>,
+    pub(crate) resolve_beneath: bool,
+    pub(crate) environment: Option<Environment>,
+    /// What happened while finding these locations.
+    pub report: ScanReport,
 }
 
 impl IconLocations {
@@ -256,36 +741,146 @@ impl IconLocations {
     /// let locations = search.into_icon_locations();
     /// ```
     pub fn from_icon_search(dirs: &IconSearch<Initial>) -> Self {
-        dirs.find_icon_locations()
+        dirs.find_icon_locations(&CancellationToken::new())
+            .expect("the token is never cancelled")
     }
 
     pub fn icons(self) -> Icons {
-        let themes = self.resolve();
+        self.try_icons(&CancellationToken::new())
+            .expect("the token is never cancelled")
+    }
 
-        let standalone_icons = self
-            .standalone_icons
-            .into_iter()
-            .map(|file| {
-                let key = file
-                    .path
-                    .file_stem()
-                    .map(|s| s.to_string_lossy().to_string())
-                    .unwrap_or(String::new());
-                (key, file)
-            })
+    /// Like [`icons`](Self::icons), but aborting once `token` is
+    /// [cancelled](CancellationToken::cancel).
+    pub fn try_icons(self, token: &CancellationToken) -> Result<Icons, Cancelled> {
+        let started = Instant::now();
+        let mut report = self.report.clone();
+        let themes = self.resolve_reporting(
+            self.themes_directories.keys(),
+            token,
+            &mut report.theme_failures,
+        )?;
+        // in a stable order, as the themes are resolved in the order of a hash map
+        report.theme_failures.sort_by_key(|failure| {
+            let position = (failure.paths.iter())
+                .map(|path| search_order(&self.base_dirs, path))
+                .min();
+            (position, failure.name.clone())
+        });
+        report.partial_themes = (themes.iter())
+            .filter(|(_, theme)| theme.is_partial())
+            .map(|(name, _)| name.clone())
             .collect();
+        report.partial_themes.sort();
+        report.timings.resolve = started.elapsed();
+
+        let mut standalone_icons = HashMap::<String, Vec<IconFile>>::default();
+        for file in self.standalone_icons {
+            let key = file
+                .path
+                .file_stem()
+                .map(|s| s.to_string_lossy().to_string())
+                .unwrap_or(String::new());
+            standalone_icons.entry(key).or_default().push(file);
+        }
+        // the first search directory wins, then the most preferred file type
+        for files in standalone_icons.values_mut() {
+            files.sort_by_key(|file| {
+                (
+                    search_order(&self.base_dirs, &file.path),
+                    self.file_types.position(file.file_type),
+                )
+            });
+        }
 
-        Icons {
+        Ok(Icons {
+            base_dirs: self.base_dirs.clone(),
             standalone_icons,
             themes,
-        }
+            fallback_theme: "hicolor".into(),
+            preferred_fallbacks: Vec::new(),
+            name_fallback: false,
+            precedence: Precedence::UserFirst,
+            list_directories: self.list_directories,
+            adaptive_ordering: self.adaptive_ordering,
+            scale_suffixes: self.scale_suffixes,
+            infer_indexes: self.infer_indexes,
+            bootstrap_hicolor: self.bootstrap_hicolor,
+            file_types: self.file_types,
+            compliance: self.compliance,
+            audit: self.audit,
+            resolve_beneath: self.resolve_beneath,
+            #[cfg(feature = "rayon")]
+            parallelism: crate::parallel::Parallelism::Global,
+            report,
+            generation: crate::change::next_generation(),
+            environment: self.environment,
+            current_theme: OnceLock::new(),
+            memory_themes: Vec::new(),
+            listeners: Listeners::default(),
+        })
+    }
+
+    /// The internal names of all theme candidates, in a stable order: by the first search
+    /// directory each candidate is in, then by name.
+    pub fn theme_names(&self) -> Vec<&OsStr> {
+        let mut names = (self.themes_directories.iter())
+            .map(|(name, dirs)| {
+                let position = (dirs.iter())
+                    .map(|dir| search_order(&self.base_dirs, dir))
+                    .min();
+                (position, name.as_os_str())
+            })
+            .collect::<Vec<_>>();
+        names.sort_unstable();
+
+        names.into_iter().map(|(_, name)| name).collect()
     }
 
     pub fn resolve(&self) -> HashMap<OsString, Arc<Theme>> {
         self.resolve_only(self.themes_directories.keys())
     }
 
+    /// Like [`resolve`](Self::resolve), but aborting once `token` is
+    /// [cancelled](CancellationToken::cancel).
+    pub fn try_resolve(
+        &self,
+        token: &CancellationToken,
+    ) -> Result<HashMap<OsString, Arc<Theme>>, Cancelled> {
+        self.try_resolve_only(self.themes_directories.keys(), token)
+    }
+
     pub fn resolve_only<I, S>(&self, theme_names: I) -> HashMap<OsString, Arc<Theme>>
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<OsStr>,
+    {
+        self.try_resolve_only(theme_names, &CancellationToken::new())
+            .expect("the token is never cancelled")
+    }
+
+    /// Like [`resolve_only`](Self::resolve_only), but aborting once `token` is
+    /// [cancelled](CancellationToken::cancel).
+    pub fn try_resolve_only<I, S>(
+        &self,
+        theme_names: I,
+        token: &CancellationToken,
+    ) -> Result<HashMap<OsString, Arc<Theme>>, Cancelled>
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<OsStr>,
+    {
+        self.resolve_reporting(theme_names, token, &mut Vec::new())
+    }
+
+    /// Resolves themes like [`try_resolve_only`](Self::try_resolve_only), adding the candidates
+    /// that were skipped to `failures`.
+    fn resolve_reporting<I, S>(
+        &self,
+        theme_names: I,
+        token: &CancellationToken,
+        failures: &mut Vec<ThemeFailure>,
+    ) -> Result<HashMap<OsString, Arc<Theme>>, Cancelled>
     where
         I: IntoIterator<Item = S>,
         S: AsRef<OsStr>,
@@ -303,7 +898,8 @@ impl IconLocations {
         fn collect_themes(
             name: &OsStr,
             locations: &IconLocations,
-            themes: &mut HashMap<OsString, Option<ThemeInfo>>,
+            themes: &mut FastHashMap<OsString, Option<ThemeInfo>>,
+            failures: &mut Vec<ThemeFailure>,
         ) {
             // Skip if we already have this theme.
             if themes.contains_key(name) {
@@ -313,10 +909,14 @@ impl IconLocations {
             #[allow(clippy::manual_ok_err)] // clippy doesn't see the #[cfg]
             let info = match locations.load_single_theme(name) {
                 Ok(d) => Some(d),
-                Err(_e) => {
+                Err(e) => {
                     #[cfg(feature = "log")]
-                    log::debug!("skipping theme candidate {name:?} because {_e}");
+                    log::debug!("skipping theme candidate {name:?} because {e}");
 
+                    // parents that don't exist at all aren't candidates
+                    if let Some(paths) = locations.themes_directories.get(name) {
+                        failures.push(ThemeFailure::new(name.into(), paths.clone(), &e));
+                    }
                     None
                 }
             };
@@ -330,22 +930,55 @@ impl IconLocations {
 
             // Collect all parents of this theme:
             for parent in parents {
-                collect_themes(parent.as_ref(), locations, themes);
+                collect_themes(parent.as_ref(), locations, themes, failures);
+            }
+        }
+
+        // removes the edges of `parents` pointing back to a theme currently being visited, leaving
+        // a graph without cycles.
+        fn remove_back_edges(parents: &mut [Vec<usize>], order: Vec<usize>) {
+            #[derive(Clone, Copy, PartialEq)]
+            enum Visit {
+                NotYet,
+                InProgress,
+                Done,
+            }
+
+            fn visit(idx: usize, parents: &mut [Vec<usize>], state: &mut [Visit]) {
+                if state[idx] != Visit::NotYet {
+                    return;
+                }
+                state[idx] = Visit::InProgress;
+
+                let mut own_parents = std::mem::take(&mut parents[idx]);
+                own_parents.retain(|&parent| {
+                    visit(parent, parents, state);
+                    state[parent] == Visit::Done
+                });
+                parents[idx] = own_parents;
+
+                state[idx] = Visit::Done;
+            }
+
+            let mut state = vec![Visit::NotYet; parents.len()];
+            for idx in order {
+                visit(idx, parents, &mut state);
             }
         }
 
         // Map from theme names to their info:
-        let mut themes = HashMap::new();
+        let mut themes = FastHashMap::default();
 
         // collect all required themes:
         for theme_name in theme_names {
+            token.check()?;
             let theme_name = theme_name.as_ref();
-            collect_themes(theme_name, self, &mut themes);
+            collect_themes(theme_name, self, &mut themes, failures);
         }
 
         // make 100% sure we have `hicolor`, for the half-impossible edge-case of only collecting
         // themes that does not have hicolor in their inheritance tree
-        collect_themes("hicolor".as_ref(), self, &mut themes);
+        collect_themes("hicolor".as_ref(), self, &mut themes, failures);
         // of course, the user might be cursed and not have `hicolor` installed at all!
         // that is troubling, but we'll see that it is handled correctly below.
 
@@ -373,28 +1006,75 @@ impl IconLocations {
 
         // indexed by the position in our theme_names/theme_info vecs
         let number_of_themes = theme_names.len();
+
+        // the direct parents of each theme. Invalid parents are skipped, as are those that would
+        // make a theme (indirectly) inherit from itself: indexes are untrusted input, and a cycle
+        // can't be represented by `Theme`s.
+        let mut parents = theme_info
+            .iter()
+            .map(|info| {
+                let info = info.as_ref().expect("all infos are present");
+                (info.index.inherits.iter())
+                    .filter_map(|parent| {
+                        theme_names
+                            .iter()
+                            .position(|name| *name.as_os_str() == **parent)
+                    })
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+
+        // visit themes in name order, so the same edge of a cycle is always the one removed.
+        let mut by_name = (0..number_of_themes).collect::<Vec<_>>();
+        by_name.sort_by_key(|&idx| &theme_names[idx]);
+        // hicolor is added to every inheritance tree below, so it can't have parents itself.
+        if let Some(hicolor_idx) = hicolor_idx {
+            parents[hicolor_idx].clear();
+        }
+        remove_back_edges(&mut parents, by_name);
+
+        if self.compliance == Compliance::Strict {
+            // From the spec: "If no theme is specified, implementations are required to add the
+            //                 "hicolor" theme to the inheritance tree."
+            if let Some(hicolor_idx) = hicolor_idx {
+                for (idx, info) in theme_info.iter().enumerate() {
+                    let info = info.as_ref().expect("all infos are present");
+                    if idx != hicolor_idx && info.index.inherits.is_empty() {
+                        parents[idx].push(hicolor_idx);
+                    }
+                }
+            }
+        }
+
+        // the spec's lookup algorithm searches parents depth-first, which is only followed with
+        // strict compliance.
+        fn depth_first(idx: usize, parents: &[Vec<usize>], chain: &mut Vec<usize>) {
+            if chain.contains(&idx) {
+                return;
+            }
+            chain.push(idx);
+            for &parent_idx in &parents[idx] {
+                depth_first(parent_idx, parents, chain);
+            }
+        }
+
         let mut theme_chains = Vec::<Vec<usize>>::with_capacity(number_of_themes);
 
         for theme_idx in 0..number_of_themes {
             let mut chain = Vec::from([theme_idx]);
 
+            if self.compliance == Compliance::Strict {
+                chain.clear();
+                depth_first(theme_idx, &parents, &mut chain);
+                theme_chains.push(chain);
+                continue;
+            }
+
             let mut cursor = 0;
             while let Some(node_idx) = chain.get(cursor).copied() {
                 cursor += 1;
 
-                let Some(Some(info)) = theme_info.get(node_idx) else {
-                    continue;
-                };
-
-                for parent in &info.index.inherits {
-                    let Some(parent_idx) = theme_names
-                        .iter()
-                        .position(|name| *name.as_os_str() == **parent)
-                    else {
-                        // this parent was invalid
-                        continue;
-                    };
-
+                for &parent_idx in &parents[node_idx] {
                     // add this parent, removing any previous occurrences
                     chain.retain(|idx| *idx != parent_idx);
                     chain.push(parent_idx);
@@ -411,43 +1091,83 @@ impl IconLocations {
             theme_chains.push(chain);
         }
 
-        // at this point `theme_chains` contains a _topological order_ for each theme's parents,
-        // meaning we can easily iterate over it, constructing `Theme`s, assuming at every point
-        // that each parent already has a `Theme` created for it :)
-
-        // again indexed by theme indices, None values mean the theme hasn't been processed yet.
+        // indexed by theme indices, None values mean the theme hasn't been constructed yet.
         // the goal is that, by the end of the for loop, that this only contains `Some`s.
-        // we rely on the topological order of chains to always have all the prerequisite themes
-        // present already in this map!
         let mut full_themes = vec![None::<Arc<Theme>>; number_of_themes];
 
-        for chain in &theme_chains {
-            // go from last theme to first, as all dependencies are "forward" in the chain:
-            for theme_idx in chain.iter().copied().rev() {
-                let theme_info = theme_info[theme_idx].take();
+        // construct parents before the themes inheriting from them, so that every theme in a
+        // chain is present already when the chain is used. hicolor, at the end of every chain,
+        // has no parents of its own and comes first.
+        fn post_order(
+            idx: usize,
+            parents: &[Vec<usize>],
+            visited: &mut [bool],
+            order: &mut Vec<usize>,
+        ) {
+            if std::mem::replace(&mut visited[idx], true) {
+                return;
+            }
+            for &parent_idx in &parents[idx] {
+                post_order(parent_idx, parents, visited, order);
+            }
+            order.push(idx);
+        }
+
+        let mut construction_order = Vec::with_capacity(number_of_themes);
+        let mut visited = vec![false; number_of_themes];
+        for theme_idx in hicolor_idx.into_iter().chain(0..number_of_themes) {
+            post_order(theme_idx, &parents, &mut visited, &mut construction_order);
+        }
 
-                let Some(theme_info) = theme_info else {
-                    // the option was None, meaning this theme was processed already :-)
-                    continue;
-                };
+        for theme_idx in construction_order {
+            // listing directories takes most of the time
+            token.check()?;
+
+            let theme_info = theme_info[theme_idx]
+                .take()
+                .expect("every theme is constructed once");
+
+            let parents = &theme_chains[theme_idx];
+            let parents = parents
+                .iter()
+                .skip(1) // the first in the chain is the theme itself, which we'll ignore—it's not a parent.
+                .copied()
+                // unwrap OK because, by the construction order, all of these parents
+                // should already be present in the array:
+                .map(|parent_idx| Arc::clone(full_themes[parent_idx].as_ref().unwrap()))
+                .collect();
+
+            let unreadable = UnreadableDirs::scan(&theme_info, self.audit.as_ref());
+            let listing = self.list_directories.then(|| {
+                Arc::new(DirectoryListing::build_recording(
+                    &theme_info,
+                    &self.file_types,
+                    &unreadable,
+                    self.audit.as_ref(),
+                    self.resolve_beneath,
+                ))
+            });
 
-                let parents = &theme_chains[theme_idx];
-                let parents = parents
-                    .iter()
-                    .skip(1) // the first in the chain is the theme itself, which we'll ignore—it's not a parent.
-                    .copied()
-                    // unwrap OK because, by the topological order, all of these parents
-                    // should already be present in the array:
-                    .map(|parent_idx| Arc::clone(full_themes[parent_idx].as_ref().unwrap()))
-                    .collect();
-
-                let theme = Theme {
-                    info: theme_info,
-                    inherits_from: parents,
-                };
+            let statistics = HitStatistics::new(
+                theme_info.index.directories.len(),
+                self.adaptive_ordering && self.compliance == Compliance::Lenient,
+            );
+
+            let theme = Theme {
+                info: theme_info,
+                inherits_from: parents,
+                listing,
+                statistics,
+                unreadable,
+                selections: SubDirSelections::default(),
+                file_types: self.file_types.clone(),
+                compliance: self.compliance,
+                audit: self.audit.clone(),
+                resolve_beneath: self.resolve_beneath,
+                scale_suffixes: self.scale_suffixes,
+            };
 
-                full_themes[theme_idx] = Some(Arc::new(theme));
-            }
+            full_themes[theme_idx] = Some(Arc::new(theme));
         }
 
         debug_assert!(full_themes.iter().all(Option::is_some));
@@ -461,10 +1181,10 @@ impl IconLocations {
         // - and an optimal chain (inheritance tree search order) for each theme.
 
         // and to wrap things up, let's zip the themes back up with their names
-        theme_names
+        Ok(theme_names
             .into_iter()
             .zip(full_themes)
-            .collect::<HashMap<_, _>>()
+            .collect::<HashMap<_, _>>())
     }
 
     /// Parse a single theme, returning its info.
@@ -484,7 +1204,14 @@ impl IconLocations {
             .get(internal_name)
             .ok_or_else(|| std::io::Error::other(ThemeParseError::NotAnIconTheme))?;
 
-        ThemeInfo::new_from_folders(internal_name.to_string_lossy().into_owned(), theme.clone())
+        ThemeInfo::load(
+            internal_name.to_string_lossy().into_owned(),
+            theme.clone(),
+            self.compliance,
+            self.audit.as_ref(),
+            self.infer_indexes,
+            self.bootstrap_hicolor,
+        )
     }
 
     pub fn standalone_icon<S>(&self, icon_name: S) -> Option<&IconFile>
@@ -499,6 +1226,122 @@ impl IconLocations {
     }
 }
 
+/// Which copy of a theme wins when the same theme is installed in more than one search directory,
+/// e.g. in both `~/.local/share/icons` and `/usr/share/icons`.
+///
+/// The copies are merged: icons are looked up in all of them, and the first `index.theme` found
+/// describes the theme. Precedence determines the order in which the copies are consulted.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Default)]
+pub enum Precedence {
+    /// Copies are consulted in the order of the search directories. As the user's directories
+    /// come first by default, the user's files win, which allows overriding system themes.
+    #[default]
+    UserFirst,
+    /// Copies are consulted in the reverse order of the search directories, so that the system's
+    /// files win over stale or partial copies in the user's directories.
+    SystemFirst,
+}
+
+/// Options for an [`IconSearch`], in a form that can be embedded in an application's own
+/// configuration.
+///
+/// With the `serde` feature, this implements `Deserialize`, so that applications can hand an
+/// `[icons]` section of their configuration file straight to `icon`:
+///
+/// ```toml
+/// [icons]
+/// # replaces the default directories if set
+/// directories = ["/usr/share/icons"]
+/// add_directories = ["/opt/my-app/icons"]
+/// remove_directories = ["/usr/share/pixmaps"]
+/// sysroot = "/mnt/image"
+/// fallback_theme = "Adwaita"
+/// ```
+///
+/// Paths are used as given: neither `~` nor environment variables such as `$HOME` are expanded,
+/// so directories should be absolute.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default, deny_unknown_fields))]
+pub struct SearchOptions {
+    /// The directories to search. If `None`, the default directories are used.
+    pub directories: Option<Vec<PathBuf>>,
+    /// Directories to search in addition to [`directories`](Self::directories).
+    pub add_directories: Vec<PathBuf>,
+    /// Directories not to search, even if they are default directories.
+    pub remove_directories: Vec<PathBuf>,
+    /// See [`IconSearch::with_sysroot`].
+    pub sysroot: Option<PathBuf>,
+    /// See [`IconSearch::with_fallback_theme`].
+    pub fallback_theme: Option<String>,
+}
+
+impl SearchOptions {
+    /// Merges other options into these, with `other` taking precedence.
+    pub fn merge(&mut self, other: SearchOptions) {
+        if other.directories.is_some() {
+            self.directories = other.directories;
+        }
+
+        self.add_directories
+            .retain(|dir| !other.remove_directories.contains(dir));
+        self.add_directories.extend(other.add_directories);
+        self.remove_directories.extend(other.remove_directories);
+
+        if other.sysroot.is_some() {
+            self.sysroot = other.sysroot;
+        }
+        if other.fallback_theme.is_some() {
+            self.fallback_theme = other.fallback_theme;
+        }
+    }
+
+    /// Applies these options to an [`IconSearch`].
+    ///
+    /// If [`directories`](Self::directories) is set, the directories of `search` are replaced.
+    pub fn apply(&self, mut search: IconSearch) -> IconSearch {
+        if let Some(directories) = &self.directories {
+            search.dirs = directories.clone();
+        }
+
+        search
+            .dirs
+            .retain(|dir| !self.remove_directories.contains(dir));
+
+        for dir in &self.add_directories {
+            if !search.dirs.contains(dir) {
+                search.dirs.push(dir.clone());
+            }
+        }
+
+        if let Some(sysroot) = &self.sysroot {
+            search = search.with_sysroot(sysroot);
+        }
+        if let Some(fallback_theme) = &self.fallback_theme {
+            search = search.with_fallback_theme(fallback_theme);
+        }
+
+        search
+    }
+}
+
+/// Resolves `path` relative to `sysroot`, as if `sysroot` were the root directory.
+///
+/// ```
+/// use icon::in_sysroot;
+/// use std::path::Path;
+///
+/// let path = in_sysroot(Path::new("/mnt/image"), Path::new("/usr/share/icons"));
+/// assert_eq!(path, Path::new("/mnt/image/usr/share/icons"));
+/// ```
+pub fn in_sysroot(sysroot: &Path, path: &Path) -> PathBuf {
+    let relative = path
+        .components()
+        .filter(|c| !matches!(c, Component::RootDir | Component::Prefix(_)));
+
+    sysroot.join(relative.collect::<PathBuf>())
+}
+
 /// Anything that turns into an iterator of things that can become paths can be turned into an [`IconSearch`].
 impl<I, P> From<I> for IconSearch
 where
@@ -513,34 +1356,32 @@ where
 }
 
 impl Default for IconSearch {
+    /// The default directories of the current environment.
+    ///
+    /// With the `config` feature, the [configuration files](crate::config) are applied as well.
     fn default() -> Self {
-        // "By default, apps should look in $HOME/.icons (for backwards compatibility),
-        // in $XDG_DATA_DIRS/icons
-        // and in /usr/share/pixmaps (in that order)."
+        let search = Self::from_environment(&Environment::current());
 
-        let xdg = xdg::BaseDirectories::new();
+        #[cfg(feature = "config")]
+        let search = match crate::config::load() {
+            Ok(options) => options.apply(search),
+            Err(_e) => {
+                #[cfg(feature = "log")]
+                log::warn!("ignoring icon configuration because {_e}");
 
-        let mut directories = vec![];
-
-        if let Some(home) = std::env::home_dir() {
-            directories.push(home.join(".icons"));
-        }
-
-        xdg.data_home
-            .into_iter()
-            .chain(xdg.data_dirs)
-            .map(|data_dir| data_dir.join("icons"))
-            .for_each(|dir| directories.push(dir));
-
-        directories.push("/usr/share/pixmaps".into());
+                search
+            }
+        };
 
-        directories.into()
+        search
     }
 }
 
 #[cfg(test)]
 mod test {
-    use crate::search::IconSearch;
+    use crate::search::{IconSearch, Precedence};
+    use crate::{Environment, FileType, SkipReason, SkippedFile};
+    use std::path::{Path, PathBuf};
 
     // these tests assume certain applications are installed on the system they are run on.
 
@@ -554,11 +1395,36 @@ mod test {
         // no panic
     }
 
+    #[test]
+    fn test_explicit_environment() {
+        let env = Environment {
+            home: Some("/home/test".into()),
+            data_dirs: vec!["/usr/local/share".into(), "/usr/share".into()],
+            ..Environment::default()
+        };
+
+        let search = IconSearch::from_environment(&env).add_directories(["/opt/icons"]);
+        let dirs: Vec<&Path> = search.dirs.iter().map(PathBuf::as_path).collect();
+
+        assert_eq!(
+            dirs,
+            [
+                "/home/test/.icons",
+                "/home/test/.local/share/icons",
+                "/usr/local/share/icons",
+                "/usr/share/icons",
+                "/usr/share/pixmaps",
+                "/opt/icons",
+            ]
+            .map(Path::new)
+        );
+    }
+
     #[test]
     fn test_find_standard_theme_and_icon() {
         let dirs = IconSearch::new();
 
-        let locations = dirs.find_icon_locations();
+        let locations = dirs.search().into_icon_locations();
 
         let info = locations.load_single_theme("Adwaita").unwrap();
         assert_eq!(info.index.name, "Adwaita");
@@ -566,4 +1432,359 @@ mod test {
         let icon = locations.standalone_icon("htop").unwrap();
         assert_eq!(icon.path.file_name(), Some("htop.png".as_ref()))
     }
+
+    #[test]
+    fn test_current_theme_from_environment() {
+        let dir = tempfile::tempdir().unwrap();
+        let home = dir.path().join("home");
+        for name in ["Oak", "Pine"] {
+            let theme = home.join(".icons").join(name);
+            std::fs::create_dir_all(theme.join("apps")).unwrap();
+            std::fs::write(
+                theme.join("index.theme"),
+                format!(
+                    "[Icon Theme]\nName={name}\nComment=\nDirectories=apps\n\n[apps]\nSize=48\n"
+                ),
+            )
+            .unwrap();
+            std::fs::write(theme.join("apps/acorn-of-the-test.png"), b"").unwrap();
+        }
+        let settings = home.join(".config/gtk-3.0");
+        std::fs::create_dir_all(&settings).unwrap();
+        std::fs::write(
+            settings.join("settings.ini"),
+            "[Settings]\ngtk-icon-theme-name=Pine\n",
+        )
+        .unwrap();
+
+        let env = Environment {
+            home: Some(home.clone()),
+            ..Environment::default()
+        };
+        let icons = IconSearch::from_environment(&env).search().icons();
+        assert_eq!(icons.current_theme().as_deref(), Some("Pine"));
+        let icon = icons.find_icon_any("acorn-of-the-test", 48, 1).unwrap();
+        assert!(icon.path.starts_with(home.join(".icons/Pine")));
+
+        // neither the settings of $HOME nor of $XDG_CONFIG_HOME of this process are read
+        let icons = IconSearch::from_environment(&Environment::default())
+            .add_directories([home.join(".icons")])
+            .search()
+            .icons();
+        assert_eq!(icons.current_theme(), None);
+        let icon = icons.find_icon_any("acorn-of-the-test", 48, 1).unwrap();
+        assert!(icon.path.starts_with(home.join(".icons/Oak")));
+    }
+
+    #[test]
+    fn test_precedence() {
+        let dir = tempfile::tempdir().unwrap();
+        let user = dir.path().join("user");
+        let system = dir.path().join("system");
+
+        for base_dir in [&user, &system] {
+            let theme = base_dir.join("hicolor");
+            std::fs::create_dir_all(theme.join("apps")).unwrap();
+            std::fs::write(
+                theme.join("index.theme"),
+                "[Icon Theme]\nName=Hicolor\nComment=\nDirectories=apps\n\n[apps]\nSize=48\n",
+            )
+            .unwrap();
+            std::fs::write(theme.join("apps/firefox.png"), b"").unwrap();
+        }
+
+        let search = IconSearch::new_from(vec![user.clone(), system.clone()]);
+
+        let icons = search.clone().search().icons();
+        let icon = icons.find_default_icon("firefox", 48, 1).unwrap();
+        assert_eq!(icons.base_dir_of(&icon), Some(user.as_path()));
+
+        let icons = search
+            .with_precedence(Precedence::SystemFirst)
+            .search()
+            .icons();
+        let icon = icons.find_default_icon("firefox", 48, 1).unwrap();
+        assert_eq!(icons.base_dir_of(&icon), Some(system.as_path()));
+    }
+
+    #[test]
+    fn test_cyclic_inheritance() {
+        let dir = tempfile::tempdir().unwrap();
+        for (name, inherits) in [
+            ("hicolor", "Ash"),
+            ("Ash", "Birch"),
+            ("Birch", "Ash"),
+            ("Cedar", "Cedar"),
+            ("Dogwood", "Birch"),
+        ] {
+            let theme = dir.path().join(name);
+            std::fs::create_dir_all(theme.join("apps")).unwrap();
+            std::fs::write(
+                theme.join("index.theme"),
+                format!("[Icon Theme]\nName={name}\nInherits={inherits}\nDirectories=apps\n\n[apps]\nSize=48\n"),
+            )
+            .unwrap();
+        }
+        std::fs::write(dir.path().join("Birch/apps/firefox.png"), b"").unwrap();
+
+        let icons = IconSearch::new_from(vec![dir.path().into()])
+            .search()
+            .icons();
+
+        let parents = |name: &str| {
+            icons
+                .theme(name)
+                .unwrap()
+                .inherits_from
+                .iter()
+                .map(|theme| theme.info.internal_name.clone())
+                .collect::<Vec<_>>()
+        };
+        // of the cycle between Ash and Birch, the edge visited last is removed.
+        assert_eq!(parents("Ash"), ["Birch", "hicolor"]);
+        assert_eq!(parents("Birch"), ["hicolor"]);
+        assert_eq!(parents("Cedar"), ["hicolor"]);
+        assert_eq!(parents("Dogwood"), ["Birch", "hicolor"]);
+        assert!(parents("hicolor").is_empty());
+
+        assert!(icons.find_icon("firefox", 48, 1, "Ash").is_some());
+    }
+
+    #[test]
+    fn test_registered_file_type() {
+        let dir = tempfile::tempdir().unwrap();
+        let theme = dir.path().join("hicolor");
+        std::fs::create_dir_all(theme.join("apps")).unwrap();
+        std::fs::write(
+            theme.join("index.theme"),
+            "[Icon Theme]\nName=Hicolor\nComment=\nDirectories=apps\n\n[apps]\nSize=48\n",
+        )
+        .unwrap();
+        std::fs::write(theme.join("apps/firefox.webp"), b"").unwrap();
+        std::fs::write(theme.join("apps/htop.webp"), b"").unwrap();
+        std::fs::write(theme.join("apps/htop.png"), b"").unwrap();
+        std::fs::write(dir.path().join("btop.WEBP"), b"").unwrap();
+
+        let search = IconSearch::new_from(vec![dir.path().into()]);
+        let icons = search.clone().search().icons();
+        assert_eq!(icons.find_default_icon("firefox", 48, 1), None);
+        assert_eq!(icons.find_standalone_icon("btop"), None);
+
+        for list_directories in [false, true] {
+            let icons = search
+                .clone()
+                .with_file_type(FileType::Other("webp"))
+                .with_directory_listing(list_directories)
+                .search()
+                .icons();
+
+            for size in [48, 16] {
+                let icon = icons.find_default_icon("firefox", size, 1).unwrap();
+                assert_eq!(icon.file_type, FileType::Other("webp"));
+                // built-in types win
+                let icon = icons.find_default_icon("htop", size, 1).unwrap();
+                assert_eq!(icon.file_type, FileType::Png);
+            }
+            assert!(icons.find_standalone_icon("btop").is_some());
+        }
+    }
+
+    #[test]
+    fn test_extra_file_types() {
+        let dir = tempfile::tempdir().unwrap();
+        let theme = dir.path().join("hicolor");
+        std::fs::create_dir_all(theme.join("48")).unwrap();
+        std::fs::write(
+            theme.join("index.theme"),
+            "[Icon Theme]\nName=Hicolor\nComment=\nDirectories=48\n\n[48]\nSize=48\n",
+        )
+        .unwrap();
+        std::fs::write(theme.join("48/spotify.webp"), b"").unwrap();
+        std::fs::write(theme.join("48/krita.avif"), b"").unwrap();
+        std::fs::write(dir.path().join("notepad.ico"), b"").unwrap();
+
+        let search = IconSearch::new_from(vec![dir.path().into()]);
+        let icons = search.clone().search().icons();
+        assert_eq!(icons.find_default_icon("spotify", 48, 1), None);
+        assert_eq!(icons.find_standalone_icon("notepad"), None);
+
+        let icons = search.with_extra_file_types().search().icons();
+        let file_type = |name| Some(icons.find_default_icon(name, 48, 1)?.file_type);
+        assert_eq!(file_type("spotify"), Some(FileType::WEBP));
+        assert_eq!(file_type("krita"), Some(FileType::AVIF));
+        assert_eq!(file_type("notepad"), Some(FileType::ICO));
+    }
+
+    #[test]
+    fn test_file_type_priority() {
+        let dir = tempfile::tempdir().unwrap();
+        let theme = dir.path().join("hicolor");
+        std::fs::create_dir_all(theme.join("16")).unwrap();
+        std::fs::create_dir_all(theme.join("48")).unwrap();
+        std::fs::write(
+            theme.join("index.theme"),
+            "[Icon Theme]\nName=Hicolor\nComment=\nDirectories=16,48\n\n[16]\nSize=16\n\n[48]\nSize=48\n",
+        )
+        .unwrap();
+        for file in ["48/htop.png", "48/htop.svg", "16/gimp.png", "48/vlc.xpm"] {
+            std::fs::write(theme.join(file), b"").unwrap();
+        }
+        std::fs::write(dir.path().join("btop.png"), b"").unwrap();
+        std::fs::write(dir.path().join("btop.svg"), b"").unwrap();
+
+        let search = IconSearch::new_from(vec![dir.path().into()]);
+        for list_directories in [false, true] {
+            let icons = search
+                .clone()
+                .with_file_type_priority([FileType::Svg, FileType::Png])
+                .with_directory_listing(list_directories)
+                .search()
+                .icons();
+
+            // the priority applies to exact and fallback matches alike
+            for size in [48, 32] {
+                let icon = icons.find_default_icon("htop", size, 1).unwrap();
+                assert_eq!(icon.file_type, FileType::Svg);
+            }
+            assert!(icons.find_default_icon("gimp", 16, 1).is_some());
+            // XPMs are excluded
+            assert_eq!(icons.find_default_icon("vlc", 48, 1), None);
+            assert_eq!(
+                icons.find_standalone_icon("btop").unwrap().file_type,
+                FileType::Svg
+            );
+        }
+
+        let file_types = crate::FileTypes::new()
+            .with_priority([FileType::Svg, FileType::Other("SVG"), FileType::Png])
+            .with(FileType::Other("webp"));
+        assert_eq!(
+            file_types.iter().collect::<Vec<_>>(),
+            [FileType::Svg, FileType::Png, FileType::Other("webp")]
+        );
+    }
+
+    #[test]
+    fn test_xpm_ranked_last() {
+        let dir = tempfile::tempdir().unwrap();
+        let theme = dir.path().join("hicolor");
+        std::fs::create_dir_all(theme.join("16")).unwrap();
+        std::fs::create_dir_all(theme.join("48")).unwrap();
+        std::fs::write(
+            theme.join("index.theme"),
+            "[Icon Theme]\nName=Hicolor\nComment=\nDirectories=16,48\n\n[16]\nSize=16\n\n[48]\nSize=48\n",
+        )
+        .unwrap();
+        for file in [
+            "48/xterm.xpm",
+            "48/xterm.svg",
+            "16/xterm.png",
+            "16/xeyes.xpm",
+        ] {
+            std::fs::write(theme.join(file), b"").unwrap();
+        }
+        std::fs::write(dir.path().join("xclock.xpm"), b"").unwrap();
+        std::fs::write(dir.path().join("xclock.png"), b"").unwrap();
+
+        let search = IconSearch::new_from(vec![dir.path().into()]);
+        for list_directories in [false, true] {
+            let icons = search
+                .clone()
+                .with_directory_listing(list_directories)
+                .search()
+                .icons();
+
+            let file_type = |name, size| Some(icons.find_default_icon(name, size, 1)?.file_type);
+            assert_eq!(file_type("xterm", 48), Some(FileType::Svg));
+            assert_eq!(file_type("xterm", 40), Some(FileType::Svg));
+            assert_eq!(file_type("xeyes", 24), Some(FileType::Xpm));
+            assert_eq!(
+                icons.find_standalone_icon("xclock").unwrap().file_type,
+                FileType::Png
+            );
+        }
+    }
+
+    #[test]
+    fn test_skip_broken_files() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("htop.png"), b"").unwrap();
+        std::os::unix::fs::symlink("missing.png", dir.path().join("broken.png")).unwrap();
+
+        let locations = IconSearch::new_from(vec![dir.path().into()])
+            .search()
+            .into_icon_locations();
+
+        assert!(locations.standalone_icon("htop").is_some());
+        assert!(locations.standalone_icon("broken").is_none());
+        assert_eq!(
+            locations.skipped_files,
+            [SkippedFile {
+                path: dir.path().join("broken.png"),
+                reason: SkipReason::DanglingSymlink,
+            }]
+        );
+    }
+
+    #[test]
+    fn test_sysroot() {
+        let sysroot = tempfile::tempdir().unwrap();
+        let theme = sysroot.path().join("usr/share/icons/hicolor");
+        std::fs::create_dir_all(theme.join("16x16/apps")).unwrap();
+        std::fs::write(
+            theme.join("index.theme"),
+            "[Icon Theme]\nName=Hicolor\nDirectories=16x16/apps\n\n[16x16/apps]\nSize=16\n",
+        )
+        .unwrap();
+        std::fs::write(theme.join("16x16/apps/firefox.png"), "").unwrap();
+
+        let icons = IconSearch::new_from(vec!["/usr/share/icons".into()])
+            .with_sysroot(sysroot.path())
+            .search()
+            .icons();
+
+        let icon = icons.find_default_icon("firefox", 16, 1).unwrap();
+        assert_eq!(icon.path, theme.join("16x16/apps/firefox.png"));
+    }
+
+    #[test]
+    fn test_scan_report() {
+        let dir = tempfile::tempdir().unwrap();
+        let missing = dir.path().join("missing");
+        std::fs::write(dir.path().join("htop.png"), b"").unwrap();
+        std::fs::write(dir.path().join("notes.txt"), b"").unwrap();
+        for (name, index) in [
+            (
+                "hicolor",
+                "[Icon Theme]\nName=Hicolor\nComment=Fallback\nDirectories=\n",
+            ),
+            ("Birch", "[Icon Theme]\nComment=Wooden\n"),
+        ] {
+            std::fs::create_dir(dir.path().join(name)).unwrap();
+            std::fs::write(dir.path().join(name).join("index.theme"), index).unwrap();
+        }
+
+        let icons = IconSearch::new_from(vec![dir.path().into(), missing.clone()])
+            .search()
+            .icons();
+        let report = &icons.report;
+
+        assert_eq!(report.base_dirs.len(), 2);
+        assert_eq!(report.base_dirs[0].error, None);
+        assert_eq!(report.base_dirs[0].theme_candidates, 2);
+        assert_eq!(report.base_dirs[0].standalone_icons, 1);
+        assert_eq!(report.base_dirs[0].ignored_files, 1);
+        assert_eq!(report.base_dirs[1].path, missing);
+        assert_eq!(
+            report.base_dirs[1].error,
+            Some(std::io::ErrorKind::NotFound)
+        );
+
+        // Birch has no name
+        assert_eq!(report.theme_failures.len(), 1);
+        assert_eq!(report.theme_failures[0].name, "Birch");
+        assert_eq!(report.theme_failures[0].paths, [dir.path().join("Birch")]);
+        assert!(report.theme_failures[0].message.contains("Name"));
+        assert!(report.skipped_entries.is_empty());
+    }
 }
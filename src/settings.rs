@@ -0,0 +1,506 @@
+//! Detecting the icon theme selected by the user.
+//!
+//! There is no standard way for applications to find out which icon theme the user has chosen, so
+//! every desktop environment stores it in its own place. This module knows about:
+//!
+//! - KDE Plasma, which stores the theme in `kdeglobals` (`[Icons] Theme=`) and implicitly falls
+//!   back to `breeze` for icons the theme lacks.
+//! - GTK's `settings.ini` (`gtk-icon-theme-name`), used by GTK applications on most other desktops.
+//! - The X11 XSETTINGS manager (`Net/IconThemeName`), with the `x11` feature. See
+//!   [`xsettings`](crate::xsettings).
+//!
+//! The user's [contrast preference](Contrast) is read from the `contrast` key of the settings
+//! portal (`org.freedesktop.appearance`), or else from GNOME's `high-contrast` setting in
+//! GSettings. Both are only reachable over D-Bus, so they are queried with the `gdbus` and
+//! `gsettings` tools, and only if the environment has a
+//! [session bus](crate::Environment::session_bus). As a last resort, the contrast is high if the
+//! name of the GTK theme (`gtk-theme-name`) or of KDE's color scheme contains `highcontrast`,
+//! ignoring case.
+//!
+//! The [color scheme](ColorScheme) is guessed from the same files: it is dark or light if the
+//! name of the theme or color scheme contains `dark` or `light`, or GTK's
+//! `gtk-application-prefer-dark-theme` is set.
+
+use crate::edit::IndexDocument;
+use crate::environment::Environment;
+use crate::icon::IconFile;
+use crate::theme::{Icons, Theme};
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::sync::Arc;
+
+/// The theme KDE uses when none is configured, and falls back to for icons missing in a theme.
+pub const KDE_DEFAULT_THEME: &str = "breeze";
+
+/// The high-contrast icon theme shipped by GNOME.
+pub const HIGH_CONTRAST_THEME: &str = "HighContrast";
+
+/// The user's contrast preference.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum Contrast {
+    #[default]
+    NoPreference,
+    High,
+}
+
+impl Contrast {
+    /// The themes to search for this preference, in order, given the theme the user selected.
+    ///
+    /// With [`Contrast::High`], [`HIGH_CONTRAST_THEME`] is preferred over the user's theme.
+    pub fn themes(self, user_theme: &str) -> Vec<String> {
+        match self {
+            Contrast::NoPreference => vec![user_theme.into()],
+            Contrast::High => vec![HIGH_CONTRAST_THEME.into(), user_theme.into()],
+        }
+    }
+}
+
+/// The icon theme selected by the user in the current environment, if it can be determined.
+///
+/// See [`Environment::current_theme`]. With the `x11` feature, the
+/// [XSETTINGS manager](crate::xsettings) is consulted first outside of KDE.
+pub fn current_theme() -> Option<String> {
+    let env = Environment::current();
+
+    #[cfg(feature = "x11")]
+    if !env.is_desktop("KDE")
+        && let Some(theme) = crate::xsettings::icon_theme_name()
+    {
+        return Some(theme);
+    }
+
+    env.current_theme()
+}
+
+/// The user's preferred color scheme.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum ColorScheme {
+    #[default]
+    NoPreference,
+    Dark,
+    Light,
+}
+
+impl ColorScheme {
+    /// The suffix of sibling themes for this color scheme, e.g. `-dark` for `Papirus-Dark`.
+    pub fn theme_suffix(self) -> Option<&'static str> {
+        match self {
+            ColorScheme::NoPreference => None,
+            ColorScheme::Dark => Some("-dark"),
+            ColorScheme::Light => Some("-light"),
+        }
+    }
+}
+
+/// Themes that should be searched after the current theme (and its parents) but before
+/// `hicolor`, as the current desktop environment would.
+///
+/// See [`Environment::fallback_themes`].
+pub fn fallback_themes() -> Vec<String> {
+    Environment::current().fallback_themes()
+}
+
+/// The user's contrast preference in the current environment.
+///
+/// See [`Environment::contrast`].
+pub fn contrast() -> Contrast {
+    Environment::current().contrast()
+}
+
+/// The user's preferred color scheme in the current environment.
+///
+/// See [`Environment::color_scheme`].
+pub fn color_scheme() -> ColorScheme {
+    Environment::current().color_scheme()
+}
+
+impl Environment {
+    /// The icon theme selected by the user in this environment, if it can be determined.
+    ///
+    /// On KDE (according to `$XDG_CURRENT_DESKTOP`), this is the theme configured in `kdeglobals`,
+    /// or `breeze` if none is configured. Elsewhere, GTK's `settings.ini` is consulted.
+    pub fn current_theme(&self) -> Option<String> {
+        if self.is_desktop("KDE") {
+            return Some(self.kde_theme().unwrap_or_else(|| KDE_DEFAULT_THEME.into()));
+        }
+
+        self.gtk_theme().or_else(|| self.kde_theme())
+    }
+
+    /// Themes that should be searched after the current theme (and its parents) but before
+    /// `hicolor`, as the current desktop environment would.
+    ///
+    /// KDE always consults `breeze` before `hicolor`, regardless of what the theme inherits from.
+    pub fn fallback_themes(&self) -> Vec<String> {
+        if self.is_desktop("KDE") {
+            vec![KDE_DEFAULT_THEME.into()]
+        } else {
+            Vec::new()
+        }
+    }
+
+    /// The icon theme configured in KDE's `kdeglobals`, if any.
+    pub fn kde_theme(&self) -> Option<String> {
+        // KDE's configuration cascades: the user's file overrides the system-wide ones.
+        self.find_config_value("kdeglobals", "Icons", "Theme")
+    }
+
+    /// The icon theme configured in GTK's `settings.ini`, if any.
+    pub fn gtk_theme(&self) -> Option<String> {
+        self.find_gtk_value("gtk-icon-theme-name")
+    }
+
+    /// The user's contrast preference in this environment.
+    ///
+    /// If the environment has a [session bus](Self::session_bus), the settings portal's
+    /// `contrast` key is read, and outside of KDE GNOME's `high-contrast` setting after that.
+    /// Otherwise, or if neither is available, this is [`Contrast::High`] if the name of the GTK
+    /// theme (`gtk-theme-name` in `settings.ini`) or of KDE's color scheme (`[General]
+    /// ColorScheme=` in `kdeglobals`) contains `highcontrast`, ignoring case, e.g.
+    /// `HighContrastInverse`.
+    pub fn contrast(&self) -> Contrast {
+        let preference = self.portal_contrast().or_else(|| {
+            // KDE leaves GNOME's setting at its default
+            (!self.is_desktop("KDE")).then(|| self.gsettings_contrast())?
+        });
+        if let Some(contrast) = preference {
+            return contrast;
+        }
+
+        let is_high_contrast = |name: String| name.to_lowercase().contains("highcontrast");
+
+        let gtk_theme = self.find_gtk_value("gtk-theme-name");
+        let kde_scheme = self.find_config_value("kdeglobals", "General", "ColorScheme");
+
+        if gtk_theme.is_some_and(is_high_contrast) || kde_scheme.is_some_and(is_high_contrast) {
+            Contrast::High
+        } else {
+            Contrast::NoPreference
+        }
+    }
+
+    /// The `contrast` key of the settings portal's `org.freedesktop.appearance` namespace, if
+    /// the portal provides it.
+    fn portal_contrast(&self) -> Option<Contrast> {
+        let output = (self.session_command("gdbus")?)
+            .args(["call", "--session", "--timeout", "1"])
+            .args(["--dest", "org.freedesktop.portal.Desktop"])
+            .args(["--object-path", "/org/freedesktop/portal/desktop"])
+            .args(["--method", "org.freedesktop.portal.Settings.Read"])
+            .args(["org.freedesktop.appearance", "contrast"])
+            .output()
+            .ok()?;
+        if !output.status.success() {
+            return None;
+        }
+        parse_portal_contrast(&String::from_utf8_lossy(&output.stdout))
+    }
+
+    /// GNOME's `high-contrast` accessibility setting, if its schema is installed.
+    fn gsettings_contrast(&self) -> Option<Contrast> {
+        let output = (self.session_command("gsettings")?)
+            .args(["get", "org.gnome.desktop.a11y.interface", "high-contrast"])
+            .output()
+            .ok()?;
+        if !output.status.success() {
+            return None;
+        }
+        match String::from_utf8_lossy(&output.stdout).trim() {
+            "true" => Some(Contrast::High),
+            "false" => Some(Contrast::NoPreference),
+            _ => None,
+        }
+    }
+
+    // a command talking to the session bus of this environment, if it has one.
+    fn session_command(&self, program: &str) -> Option<Command> {
+        let mut command = Command::new(program);
+        command
+            .env("DBUS_SESSION_BUS_ADDRESS", self.session_bus.as_ref()?)
+            .stdin(Stdio::null())
+            .stderr(Stdio::null());
+        Some(command)
+    }
+
+    /// The user's preferred color scheme in this environment.
+    ///
+    /// On KDE, this is derived from the name of the color scheme in `kdeglobals` (e.g.
+    /// `BreezeDark`). Elsewhere, GTK's `gtk-application-prefer-dark-theme` setting and the name of
+    /// the GTK theme (e.g. `Adwaita-dark`) are consulted.
+    pub fn color_scheme(&self) -> ColorScheme {
+        let scheme_of = |name: String| {
+            let name = name.to_lowercase();
+            if name.contains("dark") {
+                ColorScheme::Dark
+            } else if name.contains("light") {
+                ColorScheme::Light
+            } else {
+                ColorScheme::NoPreference
+            }
+        };
+
+        if self.is_desktop("KDE") {
+            return self
+                .find_config_value("kdeglobals", "General", "ColorScheme")
+                .map_or(ColorScheme::NoPreference, scheme_of);
+        }
+
+        let prefer_dark = self.find_gtk_value("gtk-application-prefer-dark-theme");
+        if matches!(prefer_dark.as_deref(), Some("1" | "true")) {
+            return ColorScheme::Dark;
+        }
+
+        self.find_gtk_value("gtk-theme-name")
+            .map_or(ColorScheme::NoPreference, scheme_of)
+    }
+
+    /// Finds a value in GTK's `settings.ini`, preferring GTK 4's.
+    fn find_gtk_value(&self, key: &str) -> Option<String> {
+        ["gtk-4.0/settings.ini", "gtk-3.0/settings.ini"]
+            .into_iter()
+            .find_map(|file| self.find_config_value(file, "Settings", key))
+    }
+
+    /// Finds the first value of `key` in `group` of `file` in the configuration directories.
+    fn find_config_value(&self, file: &str, group: &str, key: &str) -> Option<String> {
+        self.all_config_dirs()
+            .iter()
+            .find_map(|dir| read_value(&dir.join(file), group, key))
+    }
+}
+
+impl Icons {
+    /// Like [`find_icon`](Self::find_icon), but honoring the user's contrast preference.
+    ///
+    /// With [`Contrast::High`], icons of the [`HIGH_CONTRAST_THEME`] (if installed) are preferred.
+    /// Only that theme itself is consulted, so icons it lacks still come from `theme` rather than
+    /// the colorful ones in `hicolor`.
+    pub fn find_icon_with_contrast(
+        &self,
+        icon_name: &str,
+        size: u32,
+        scale: u32,
+        theme: &str,
+        contrast: Contrast,
+    ) -> Option<IconFile> {
+        contrast
+            .themes(theme)
+            .iter()
+            .filter(|name| *name != theme)
+            .filter_map(|name| self.theme(name))
+            .find_map(|theme| theme.find_icon_here(icon_name, size, scale))
+            .or_else(|| self.find_icon(icon_name, size, scale, theme))
+    }
+
+    /// Resolves `base` to its sibling theme for the given color scheme, if one is installed.
+    ///
+    /// Many themes come in variants such as `Papirus`, `Papirus-Dark` and `Papirus-Light`. Given
+    /// any of them, this returns the variant matching `scheme` (compared case-insensitively), or
+    /// `base` itself if there is no such variant or no preference. Without a `-light` variant, the
+    /// unsuffixed theme is used for [`ColorScheme::Light`].
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use icon::Icons;
+    /// use icon::settings::ColorScheme;
+    ///
+    /// let icons = Icons::new();
+    /// let theme = icons.themed_for_color_scheme("Papirus", ColorScheme::Dark);
+    /// ```
+    pub fn themed_for_color_scheme(&self, base: &str, scheme: ColorScheme) -> Option<Arc<Theme>> {
+        let sibling = scheme.theme_suffix().and_then(|suffix| {
+            let lower = base.to_lowercase();
+            let stem = ["-dark", "-light"]
+                .into_iter()
+                .find_map(|variant| lower.strip_suffix(variant))
+                .unwrap_or(&lower);
+            let find = |wanted: &str| {
+                self.themes
+                    .iter()
+                    .find(|(name, _)| name.to_string_lossy().to_lowercase() == wanted)
+                    .map(|(_, theme)| theme.clone())
+            };
+
+            // the unsuffixed theme is usually the light one
+            find(&format!("{stem}{suffix}"))
+                .or_else(|| (scheme == ColorScheme::Light).then(|| find(stem))?)
+        });
+
+        sibling.or_else(|| self.theme(base))
+    }
+}
+
+// parses the reply of `gdbus call` to the portal, e.g. `(<<uint32 1>>,)`. "0: No preference,
+// 1: Higher contrast", other values are reserved.
+fn parse_portal_contrast(reply: &str) -> Option<Contrast> {
+    let (_, value) = reply.split_once("uint32 ")?;
+    let value = value.trim_end_matches(|c: char| !c.is_ascii_digit());
+    match value.parse::<u32>().ok()? {
+        1 => Some(Contrast::High),
+        _ => Some(Contrast::NoPreference),
+    }
+}
+
+fn read_value(path: &Path, group: &str, key: &str) -> Option<String> {
+    let doc = IndexDocument::parse_from_file(path).ok()?;
+
+    // KDE marks immutable keys as `Theme[$i]`, which is still the same key
+    let value = doc
+        .get(group, key)
+        .or_else(|| doc.get(group, &format!("{key}[$i]")))?
+        .trim();
+
+    (!value.is_empty()).then(|| value.into())
+}
+
+#[cfg(test)]
+mod test {
+    use crate::settings::{ColorScheme, Contrast, HIGH_CONTRAST_THEME, parse_portal_contrast};
+    use crate::theme::Theme;
+    use crate::{Environment, IconSearch};
+    use std::fs;
+    use std::sync::Arc;
+
+    #[test]
+    fn test_kde_theme() {
+        let dir = tempfile::tempdir().unwrap();
+        let user = dir.path().join("user");
+        let system = dir.path().join("system");
+        fs::create_dir_all(&user).unwrap();
+        fs::create_dir_all(&system).unwrap();
+
+        let mut env = Environment {
+            config_home: Some(user.clone()),
+            config_dirs: vec![system.clone()],
+            current_desktop: vec!["KDE".into()],
+            ..Environment::default()
+        };
+
+        // nothing configured: Plasma defaults to breeze
+        assert_eq!(env.current_theme().as_deref(), Some("breeze"));
+        assert_eq!(env.fallback_themes(), ["breeze"]);
+
+        fs::write(system.join("kdeglobals"), "[Icons]\nTheme=Oxygen\n").unwrap();
+        assert_eq!(env.current_theme().as_deref(), Some("Oxygen"));
+
+        fs::write(
+            user.join("kdeglobals"),
+            "[General]\nColorScheme=BreezeDark\n\n[Icons]\nTheme=Papirus-Dark\n",
+        )
+        .unwrap();
+        assert_eq!(env.current_theme().as_deref(), Some("Papirus-Dark"));
+
+        // not on KDE: GTK's settings take precedence, and there are no implicit fallbacks
+        env.current_desktop = vec!["GNOME".into()];
+        fs::create_dir_all(user.join("gtk-3.0")).unwrap();
+        fs::write(
+            user.join("gtk-3.0/settings.ini"),
+            "[Settings]\ngtk-icon-theme-name = Adwaita\n",
+        )
+        .unwrap();
+        assert_eq!(env.current_theme().as_deref(), Some("Adwaita"));
+        assert!(env.fallback_themes().is_empty());
+    }
+
+    #[test]
+    fn test_contrast() {
+        let dir = tempfile::tempdir().unwrap();
+        let env = Environment {
+            config_home: Some(dir.path().into()),
+            ..Environment::default()
+        };
+
+        assert_eq!(env.contrast(), Contrast::NoPreference);
+
+        fs::write(
+            dir.path().join("kdeglobals"),
+            "[General]\nColorScheme=BreezeHighContrast\n",
+        )
+        .unwrap();
+        assert_eq!(env.contrast(), Contrast::High);
+
+        assert_eq!(
+            Contrast::High.themes("Papirus"),
+            [HIGH_CONTRAST_THEME, "Papirus"]
+        );
+        assert_eq!(Contrast::NoPreference.themes("Papirus"), ["Papirus"]);
+    }
+
+    #[test]
+    fn test_portal_contrast() {
+        assert_eq!(
+            parse_portal_contrast("(<<uint32 1>>,)\n"),
+            Some(Contrast::High)
+        );
+        assert_eq!(
+            parse_portal_contrast("(<uint32 0>,)\n"),
+            Some(Contrast::NoPreference)
+        );
+        assert_eq!(parse_portal_contrast("(<<'no'>>,)\n"), None);
+    }
+
+    #[test]
+    fn test_color_scheme() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut env = Environment {
+            config_home: Some(dir.path().into()),
+            ..Environment::default()
+        };
+
+        assert_eq!(env.color_scheme(), ColorScheme::NoPreference);
+
+        fs::create_dir_all(dir.path().join("gtk-3.0")).unwrap();
+        fs::write(
+            dir.path().join("gtk-3.0/settings.ini"),
+            "[Settings]\ngtk-theme-name=Adwaita\ngtk-application-prefer-dark-theme=1\n",
+        )
+        .unwrap();
+        assert_eq!(env.color_scheme(), ColorScheme::Dark);
+
+        env.current_desktop = vec!["KDE".into()];
+        fs::write(
+            dir.path().join("kdeglobals"),
+            "[General]\nColorScheme=BreezeLight\n",
+        )
+        .unwrap();
+        assert_eq!(env.color_scheme(), ColorScheme::Light);
+    }
+
+    #[test]
+    fn test_themed_for_color_scheme() {
+        let dir = tempfile::tempdir().unwrap();
+        for theme in ["Papirus", "Papirus-Dark"] {
+            fs::create_dir_all(dir.path().join(theme)).unwrap();
+            fs::write(
+                dir.path().join(theme).join("index.theme"),
+                format!("[Icon Theme]\nName={theme}\nComment=\nDirectories=\n"),
+            )
+            .unwrap();
+        }
+
+        let icons = IconSearch::new_from(vec![dir.path().into()])
+            .search()
+            .icons();
+        let name = |theme: Option<Arc<Theme>>| theme.map(|theme| theme.info.internal_name.clone());
+
+        assert_eq!(
+            name(icons.themed_for_color_scheme("Papirus", ColorScheme::Dark)).as_deref(),
+            Some("Papirus-Dark")
+        );
+        assert_eq!(
+            name(icons.themed_for_color_scheme("papirus-dark", ColorScheme::Dark)).as_deref(),
+            Some("Papirus-Dark")
+        );
+        // there is no light variant
+        assert_eq!(
+            name(icons.themed_for_color_scheme("Papirus-Dark", ColorScheme::Light)).as_deref(),
+            Some("Papirus")
+        );
+        assert_eq!(
+            name(icons.themed_for_color_scheme("Papirus-Dark", ColorScheme::NoPreference))
+                .as_deref(),
+            Some("Papirus-Dark")
+        );
+    }
+}
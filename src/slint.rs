@@ -0,0 +1,47 @@
+//! Showing decoded icons with [Slint](https://slint.dev).
+//!
+//! Slint can't look up icons from themes itself. Decode the icons found into a [`Raster`], e.g.
+//! through a `RasterCache` of the [`raster_cache`](crate::raster_cache) module, and convert it
+//! into a [`slint::Image`] to assign it to an `image` property.
+//!
+//! This module requires the `slint` feature.
+//!
+//! # Example
+//!
+//! ```ignore
+//! let raster = cache.get_or_try_insert_with(key, || decode(&file))?;
+//! window.set_icon(slint::Image::from(&*raster));
+//! ```
+
+use crate::render::Raster;
+use slint::{Image, Rgba8Pixel, SharedPixelBuffer};
+
+impl From<&Raster> for SharedPixelBuffer<Rgba8Pixel> {
+    fn from(raster: &Raster) -> Self {
+        SharedPixelBuffer::clone_from_slice(&raster.pixels, raster.width, raster.height)
+    }
+}
+
+impl From<&Raster> for Image {
+    fn from(raster: &Raster) -> Self {
+        Image::from_rgba8(raster.into())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::render::Raster;
+    use slint::{Rgba8Pixel, SharedPixelBuffer};
+
+    #[test]
+    fn test_from_raster() {
+        let raster = Raster::new(2, 1, [255, 0, 0, 255, 0, 0, 255, 128]);
+
+        let buffer = SharedPixelBuffer::<Rgba8Pixel>::from(&raster);
+        assert_eq!((buffer.width(), buffer.height()), (2, 1));
+        assert_eq!(buffer.as_bytes(), &*raster.pixels);
+
+        let image = slint::Image::from(&raster);
+        assert_eq!((image.size().width, image.size().height), (2, 1));
+    }
+}
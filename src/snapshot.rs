@@ -0,0 +1,276 @@
+//! Storing the icon locations found by a search, to skip searching at startup.
+//!
+//! A [`Snapshot`] records the theme directories and standalone icons of [`IconLocations`] by the
+//! index of their search directory and their path relative to it. Restoring it with
+//! [`IconSearch::restore`] maps them onto that search's directories, so a snapshot taken while
+//! building a container image remains valid when the image is mounted at a different root, e.g.
+//! with [`IconSearch::with_sysroot`].
+//!
+//! With the `serde` feature, snapshots implement `Serialize` and `Deserialize`. With the `hash`
+//! feature, they record a [hash of the contents](SnapshotPath::content_hash) of every path, to
+//! tell whether the files changed since.
+//!
+//! # Example
+//!
+//! ```
+//! use icon::IconSearch;
+//!
+//! // while building the image
+//! let dirs = vec!["/usr/share/icons".into(), "/usr/share/pixmaps".into()];
+//! let snapshot = IconSearch::new_from(dirs.clone())
+//!     .search()
+//!     .icon_locations()
+//!     .snapshot();
+//!
+//! // at runtime
+//! let icons = IconSearch::new_from(dirs)
+//!     .with_sysroot("/mnt/image")
+//!     .restore(&snapshot)
+//!     .unwrap()
+//!     .icons();
+//! ```
+
+use crate::report::ScanReport;
+use crate::search::states::{Initial, LocationsFound};
+use crate::{IconLocations, IconSearch, Precedence};
+use std::collections::HashMap;
+use std::ffi::OsString;
+use std::path::{Component, Path, PathBuf};
+use thiserror::Error;
+
+/// The theme directories and standalone icons found by a search, relative to its search
+/// directories.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Snapshot {
+    /// The search directories the snapshot was taken in, in order.
+    pub base_dirs: Vec<PathBuf>,
+    pub standalone_icons: Vec<SnapshotPath>,
+    /// The directories of each theme candidate, by internal name, in the order of the search
+    /// directories.
+    pub themes: Vec<(String, Vec<SnapshotPath>)>,
+}
+
+/// A path relative to one of a [`Snapshot`]'s search directories.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SnapshotPath {
+    /// The index of the search directory in [`Snapshot::base_dirs`].
+    pub base_dir: usize,
+    pub path: PathBuf,
+    /// The hash of the contents of a standalone icon, or of the `index.theme` of a theme
+    /// directory, when the snapshot was taken, as by [`IconFile::content_hash`]. `None` if it
+    /// couldn't be read, e.g. for theme directories without an index.
+    ///
+    /// This requires the `hash` feature.
+    ///
+    /// [`IconFile::content_hash`]: crate::IconFile::content_hash
+    #[cfg(feature = "hash")]
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub content_hash: Option<u128>,
+}
+
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum SnapshotError {
+    #[error("the snapshot has {expected} search directories, but {found} were given")]
+    BaseDirCount { expected: usize, found: usize },
+    #[error("path {0:?} does not refer to a search directory of the snapshot")]
+    InvalidBaseDir(PathBuf),
+    #[error("path {0:?} is not a relative path within its search directory")]
+    InvalidPath(PathBuf),
+}
+
+impl IconLocations {
+    /// Takes a [`Snapshot`] of these locations.
+    ///
+    /// Theme names and paths that aren't valid UTF-8 are left out when serializing, as are
+    /// entries outside the search directories. With the `hash` feature, every file recorded is
+    /// read to [hash](SnapshotPath::content_hash) it.
+    pub fn snapshot(&self) -> Snapshot {
+        let relative = |path: &Path, _hashed: &Path| {
+            let base_dir = (self.base_dirs.iter()).position(|dir| path.parent() == Some(dir))?;
+            Some(SnapshotPath {
+                base_dir,
+                path: path.strip_prefix(&self.base_dirs[base_dir]).ok()?.into(),
+                #[cfg(feature = "hash")]
+                content_hash: crate::icon::hash_file(_hashed).ok(),
+            })
+        };
+
+        let mut themes = (self.themes_directories.iter())
+            .filter_map(|(name, dirs)| {
+                let mut dirs = dirs
+                    .iter()
+                    .flat_map(|dir| relative(dir, &dir.join("index.theme")))
+                    .collect::<Vec<_>>();
+                dirs.sort_by_key(|dir| dir.base_dir);
+                Some((name.to_str()?.to_owned(), dirs))
+            })
+            .collect::<Vec<_>>();
+        themes.sort_by(|a, b| a.0.cmp(&b.0));
+
+        Snapshot {
+            base_dirs: self.base_dirs.clone(),
+            standalone_icons: (self.standalone_icons.iter())
+                .flat_map(|icon| relative(&icon.path, &icon.path))
+                .collect(),
+            themes,
+        }
+    }
+}
+
+impl IconSearch<Initial> {
+    /// Restores the locations of a [`Snapshot`] instead of searching for them, mapping them onto
+    /// this search's directories (resolved relative to its sysroot).
+    ///
+    /// This search must have as many directories as the snapshot. Standalone icons whose type
+    /// isn't [registered](Self::with_file_type) are skipped.
+    pub fn restore(self, snapshot: &Snapshot) -> Result<IconSearch<LocationsFound>, SnapshotError> {
+        let base_dirs = self.resolved_dirs();
+        if base_dirs.len() != snapshot.base_dirs.len() {
+            return Err(SnapshotError::BaseDirCount {
+                expected: snapshot.base_dirs.len(),
+                found: base_dirs.len(),
+            });
+        }
+
+        // snapshots may come from anywhere, so they must not lead out of the search directories
+        let resolve = |path: &SnapshotPath| {
+            let base_dir = (base_dirs.get(path.base_dir))
+                .ok_or_else(|| SnapshotError::InvalidBaseDir(path.path.clone()))?;
+            let mut components = path.path.components();
+            if !components.all(|c| matches!(c, Component::Normal(_))) {
+                return Err(SnapshotError::InvalidPath(path.path.clone()));
+            }
+            Ok(base_dir.join(&path.path))
+        };
+
+        let mut standalone_icons = Vec::new();
+        for path in &snapshot.standalone_icons {
+            standalone_icons.extend(self.file_types.icon_file(&resolve(path)?));
+        }
+
+        let mut themes_directories = HashMap::<OsString, Vec<PathBuf>>::default();
+        for (name, dirs) in &snapshot.themes {
+            let mut dirs = dirs.iter().map(resolve).collect::<Result<Vec<_>, _>>()?;
+            if self.precedence == Precedence::SystemFirst {
+                dirs.reverse();
+            }
+            themes_directories.insert(name.into(), dirs);
+        }
+
+        let locations = IconLocations {
+            base_dirs,
+            standalone_icons,
+            themes_directories,
+            skipped_files: Vec::new(),
+            list_directories: self.list_directories,
+            adaptive_ordering: self.adaptive_ordering,
+            scale_suffixes: self.scale_suffixes,
+            infer_indexes: self.infer_indexes,
+            bootstrap_hicolor: self.bootstrap_hicolor,
+            file_types: self.file_types.clone(),
+            compliance: self.compliance,
+            audit: self.audit.clone(),
+            resolve_beneath: self.resolve_beneath,
+            environment: self.environment.clone(),
+            report: ScanReport::default(),
+        };
+        Ok(self.with_icon_locations(locations))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::IconSearch;
+    use crate::snapshot::SnapshotError;
+    use std::fs;
+    use std::path::Path;
+
+    fn write_icons(root: &Path) {
+        let theme = root.join("usr/share/icons/hicolor");
+        fs::create_dir_all(theme.join("48")).unwrap();
+        fs::write(
+            theme.join("index.theme"),
+            "[Icon Theme]\nName=Hicolor\nComment=Fallback\nDirectories=48\n\n[48]\nSize=48\n",
+        )
+        .unwrap();
+        fs::write(theme.join("48/firefox.png"), b"").unwrap();
+        fs::create_dir_all(root.join("usr/share/pixmaps")).unwrap();
+        fs::write(root.join("usr/share/pixmaps/htop.svg"), b"").unwrap();
+    }
+
+    #[test]
+    fn test_snapshot() {
+        let build = tempfile::tempdir().unwrap();
+        write_icons(build.path());
+        let dirs = vec!["/usr/share/icons".into(), "/usr/share/pixmaps".into()];
+
+        let snapshot = IconSearch::new_from(dirs.clone())
+            .with_sysroot(build.path())
+            .search()
+            .icon_locations()
+            .snapshot();
+        assert_eq!(snapshot.themes.len(), 1);
+        assert_eq!(snapshot.standalone_icons[0].base_dir, 1);
+        assert_eq!(snapshot.standalone_icons[0].path, Path::new("htop.svg"));
+
+        // the same image, mounted elsewhere
+        let runtime = tempfile::tempdir().unwrap();
+        write_icons(runtime.path());
+        let icons = IconSearch::new_from(dirs.clone())
+            .with_sysroot(runtime.path())
+            .restore(&snapshot)
+            .unwrap()
+            .icons();
+
+        let icon = icons.find_icon("firefox", 48, 1, "hicolor").unwrap();
+        assert!(icon.path.starts_with(runtime.path()));
+        let icon = icons.find_icon("htop", 48, 1, "hicolor").unwrap();
+        assert_eq!(icon.path, runtime.path().join("usr/share/pixmaps/htop.svg"));
+
+        assert_eq!(
+            IconSearch::new_from(dirs[..1].to_vec())
+                .restore(&snapshot)
+                .err(),
+            Some(SnapshotError::BaseDirCount {
+                expected: 2,
+                found: 1
+            })
+        );
+        let mut escaping = snapshot.clone();
+        escaping.standalone_icons[0].path = "../../../etc/passwd.png".into();
+        assert!(matches!(
+            IconSearch::new_from(dirs).restore(&escaping),
+            Err(SnapshotError::InvalidPath(_))
+        ));
+    }
+
+    #[cfg(feature = "hash")]
+    #[test]
+    fn test_snapshot_content_hash() {
+        let dir = tempfile::tempdir().unwrap();
+        write_icons(dir.path());
+        let icons = dir.path().join("usr/share/icons");
+        fs::create_dir_all(icons.join("Oak")).unwrap();
+
+        let snapshot =
+            IconSearch::new_from(vec![icons.clone(), dir.path().join("usr/share/pixmaps")])
+                .search()
+                .icon_locations()
+                .snapshot();
+        let hash = |path: &Path| crate::icon::hash_file(path).ok();
+        let theme = |name: &str| &snapshot.themes.iter().find(|(n, _)| n == name).unwrap().1[0];
+
+        assert_eq!(
+            snapshot.standalone_icons[0].content_hash,
+            hash(&dir.path().join("usr/share/pixmaps/htop.svg"))
+        );
+        assert!(theme("hicolor").content_hash.is_some());
+        assert_eq!(
+            theme("hicolor").content_hash,
+            hash(&icons.join("hicolor/index.theme"))
+        );
+        assert_eq!(theme("Oak").content_hash, None);
+    }
+}
@@ -0,0 +1,308 @@
+//! Where `icon` knowingly deviates from the
+//! [icon theme specification](https://specifications.freedesktop.org/icon-theme-spec/latest/).
+//!
+//! `icon` aims to find the icons users expect, which sometimes means accepting themes the
+//! specification would reject, or filling in what it leaves undefined. [`deviations`] lists all
+//! of these, and [`Theme::spec_notes`] those that affect a particular theme, so that strict
+//! consumers can decide whether to accept them.
+//!
+//! Every [`Deviation`] has a stable [identifier](Deviation::id) to refer to it by, e.g. in bug
+//! reports.
+//!
+//! Tools validating themes can opt out of most deviations with [`Compliance::Strict`].
+
+use crate::FileType;
+use crate::theme::{IndexOrigin, Theme};
+
+/// A known, intentional deviation from the icon theme specification.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[non_exhaustive]
+pub enum Deviation {
+    /// `Comment` is required, but many themes omit it. It defaults to an empty string instead.
+    MissingComment,
+    /// The specification looks up icons in parent themes depth-first, but as all themes
+    /// eventually inherit from hicolor, that would search hicolor before a theme's second parent.
+    /// Parents are searched breadth-first instead.
+    BreadthFirstInheritance,
+    /// hicolor is only required to be added to themes that don't inherit from any theme, but is
+    /// added to the end of every theme's inheritance tree.
+    HicolorAlwaysInherited,
+    /// Parents that don't exist, or that would make a theme inherit from itself, are skipped
+    /// instead of being an error.
+    SkippedParents,
+    /// Theme indexes that are too large, have directories with sizes or scales out of range, or
+    /// directories outside the theme are rejected.
+    IndexLimits,
+    /// Icon files with extensions other than `png`, `svg` and `xpm` are found, if
+    /// [registered](crate::IconSearch::with_file_type).
+    ExtraFileTypes,
+    /// With [adaptive ordering](crate::IconSearch::with_adaptive_ordering), the directories
+    /// matching a size exactly are tried in the order icons were found in most often, rather
+    /// than the order of the index. This may pick a different icon if several match exactly.
+    AdaptiveOrdering,
+    /// A hicolor installation without an `index.theme` is not a theme, but gets a
+    /// [built-in index](crate::theme::ThemeIndex::hicolor) unless
+    /// [disabled](crate::IconSearch::with_hicolor_bootstrap).
+    BuiltinHicolorIndex,
+    /// With [inferred indexes](crate::IconSearch::with_inferred_indexes), directories without an
+    /// `index.theme` are themes, with an index synthesized from the names of their directories.
+    InferredIndexes,
+}
+
+impl Deviation {
+    /// All deviations, in the order of their identifiers.
+    pub const ALL: &'static [Deviation] = &[
+        Deviation::AdaptiveOrdering,
+        Deviation::BreadthFirstInheritance,
+        Deviation::BuiltinHicolorIndex,
+        Deviation::ExtraFileTypes,
+        Deviation::HicolorAlwaysInherited,
+        Deviation::IndexLimits,
+        Deviation::InferredIndexes,
+        Deviation::MissingComment,
+        Deviation::SkippedParents,
+    ];
+
+    /// An identifier that won't change between versions.
+    pub fn id(&self) -> &'static str {
+        match self {
+            Deviation::AdaptiveOrdering => "adaptive-ordering",
+            Deviation::BreadthFirstInheritance => "breadth-first-inheritance",
+            Deviation::BuiltinHicolorIndex => "builtin-hicolor-index",
+            Deviation::ExtraFileTypes => "extra-file-types",
+            Deviation::HicolorAlwaysInherited => "hicolor-always-inherited",
+            Deviation::IndexLimits => "index-limits",
+            Deviation::InferredIndexes => "inferred-indexes",
+            Deviation::MissingComment => "missing-comment",
+            Deviation::SkippedParents => "skipped-parents",
+        }
+    }
+
+    pub fn from_id(id: &str) -> Option<Self> {
+        Self::ALL
+            .iter()
+            .copied()
+            .find(|deviation| deviation.id() == id)
+    }
+}
+
+/// How closely to follow the specification, see
+/// [`IconSearch::with_compliance`](crate::IconSearch::with_compliance).
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Default)]
+pub enum Compliance {
+    /// Accepts themes and looks up icons the way users expect, with all [`deviations`].
+    #[default]
+    Lenient,
+    /// Follows the specification to the letter, for validating themes. Only
+    /// [`Deviation::SkippedParents`], [`Deviation::IndexLimits`] and opted-in deviations remain:
+    ///
+    /// - themes without a `Comment` are rejected.
+    /// - parent themes are searched depth-first, and hicolor is only inherited by themes that
+    ///   don't inherit from any theme.
+    /// - if an icon isn't found in a theme or its parents, it is looked up in hicolor next. The
+    ///   [fallback theme](crate::IconSearch::with_fallback_theme) and
+    ///   [preferred fallbacks](crate::IconSearch::with_preferred_fallbacks) are ignored.
+    /// - [adaptive ordering](crate::IconSearch::with_adaptive_ordering) is ignored.
+    /// - directories without an `index.theme` aren't themes: neither is hicolor
+    ///   [bootstrapped](crate::IconSearch::with_hicolor_bootstrap), nor are indexes
+    ///   [inferred](crate::IconSearch::with_inferred_indexes).
+    Strict,
+}
+
+impl Deviation {
+    /// Whether this deviation is still made with the given compliance.
+    pub fn applies_to(&self, compliance: Compliance) -> bool {
+        match compliance {
+            Compliance::Lenient => true,
+            Compliance::Strict => matches!(
+                self,
+                Deviation::SkippedParents | Deviation::IndexLimits | Deviation::ExtraFileTypes
+            ),
+        }
+    }
+}
+
+/// Lists every known deviation from the specification.
+pub fn deviations() -> &'static [Deviation] {
+    Deviation::ALL
+}
+
+impl Theme {
+    /// Lists the deviations from the specification that affect how this theme is read or how
+    /// icons are looked up in it.
+    ///
+    /// [`Deviation::IndexLimits`] is never listed, as such themes aren't read at all.
+    pub fn spec_notes(&self) -> Vec<Deviation> {
+        let index = &self.info.index;
+        let mut notes = Vec::new();
+
+        if self.statistics.is_adaptive() {
+            notes.push(Deviation::AdaptiveOrdering);
+        }
+        match self.info.index_origin {
+            IndexOrigin::Builtin => notes.push(Deviation::BuiltinHicolorIndex),
+            IndexOrigin::Inferred => notes.push(Deviation::InferredIndexes),
+            IndexOrigin::File | IndexOrigin::Memory => {}
+        }
+
+        // the order only matters if a parent has parents of its own, before another parent
+        let parent_has_parents = index.inherits.iter().rev().skip(1).any(|parent| {
+            (self.inherits_from.iter())
+                .find(|theme| theme.info.internal_name == *parent)
+                .is_some_and(|theme| !theme.info.index.inherits.is_empty())
+        });
+        if parent_has_parents {
+            notes.push(Deviation::BreadthFirstInheritance);
+        }
+
+        if (self.file_types.iter()).any(|file_type| matches!(file_type, FileType::Other(_))) {
+            notes.push(Deviation::ExtraFileTypes);
+        }
+
+        // per the specification, hicolor is inherited by every theme that doesn't inherit from
+        // any theme, and thereby by their children
+        let (hicolor, others): (Vec<_>, Vec<_>) =
+            (self.inherits_from.iter()).partition(|theme| theme.info.internal_name == "hicolor");
+        let hicolor_by_spec = (others.iter().map(|theme| &theme.info.index))
+            .chain([index])
+            .any(|index| {
+                index.inherits.is_empty() || index.inherits.iter().any(|p| p == "hicolor")
+            });
+        if !hicolor.is_empty() && !hicolor_by_spec {
+            notes.push(Deviation::HicolorAlwaysInherited);
+        }
+
+        if index.comment.is_empty() {
+            notes.push(Deviation::MissingComment);
+        }
+
+        let skipped_parent = index.inherits.iter().any(|parent| {
+            !(self.inherits_from.iter()).any(|theme| theme.info.internal_name == *parent)
+        });
+        if skipped_parent {
+            notes.push(Deviation::SkippedParents);
+        }
+
+        notes.retain(|deviation| deviation.applies_to(self.compliance));
+        notes
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::IconSearch;
+    use crate::spec::{Compliance, Deviation, deviations};
+
+    #[test]
+    fn test_deviations() {
+        for deviation in deviations() {
+            assert_eq!(Deviation::from_id(deviation.id()), Some(*deviation));
+        }
+        assert!(deviations().is_sorted_by_key(|deviation| deviation.id()));
+    }
+
+    #[test]
+    fn test_spec_notes() {
+        let dir = tempfile::tempdir().unwrap();
+        for (name, keys) in [
+            ("hicolor", "Comment=Fallback\n"),
+            ("Ash", "Comment=Ash\nInherits=Birch,Missing,Cedar\n"),
+            ("Birch", "Comment=Birch\nInherits=Cedar\n"),
+            ("Cedar", "Inherits=hicolor\n"),
+            ("Dogwood", "Comment=Dogwood\nInherits=Dogwood\n"),
+        ] {
+            let theme = dir.path().join(name);
+            std::fs::create_dir_all(theme.join("apps")).unwrap();
+            std::fs::write(
+                theme.join("index.theme"),
+                format!("[Icon Theme]\nName={name}\n{keys}Directories=apps\n\n[apps]\nSize=48\n"),
+            )
+            .unwrap();
+        }
+
+        let icons = IconSearch::new_from(vec![dir.path().into()])
+            .search()
+            .icons();
+        let notes = |name: &str| icons.theme(name).unwrap().spec_notes();
+
+        assert!(notes("hicolor").is_empty());
+        assert_eq!(notes("Cedar"), [Deviation::MissingComment]);
+        assert_eq!(
+            notes("Ash"),
+            [
+                Deviation::BreadthFirstInheritance,
+                Deviation::SkippedParents
+            ]
+        );
+        assert!(notes("Birch").is_empty());
+        assert_eq!(
+            notes("Dogwood"),
+            [Deviation::HicolorAlwaysInherited, Deviation::SkippedParents]
+        );
+
+        let icons = IconSearch::new_from(vec![dir.path().into()])
+            .with_adaptive_ordering(true)
+            .search()
+            .icons();
+        assert!(
+            (icons.theme("hicolor").unwrap().spec_notes()).contains(&Deviation::AdaptiveOrdering)
+        );
+    }
+
+    #[test]
+    fn test_compliance() {
+        let dir = tempfile::tempdir().unwrap();
+        for (name, keys) in [
+            ("hicolor", "Comment=Fallback\n"),
+            ("Ash", "Comment=Ash\nInherits=Birch,Cedar\n"),
+            ("Birch", "Comment=Birch\n"),
+            ("Cedar", "Comment=Cedar\nInherits=Birch\n"),
+            ("Dogwood", "Comment=Dogwood\nInherits=Cedar\n"),
+            ("Elder", "Inherits=hicolor\n"),
+        ] {
+            let theme = dir.path().join(name);
+            std::fs::create_dir_all(theme.join("apps")).unwrap();
+            std::fs::write(
+                theme.join("index.theme"),
+                format!("[Icon Theme]\nName={name}\n{keys}Directories=apps\n\n[apps]\nSize=48\n"),
+            )
+            .unwrap();
+        }
+        std::fs::write(dir.path().join("Dogwood/apps/firefox.png"), b"").unwrap();
+        std::fs::write(dir.path().join("hicolor/apps/htop.png"), b"").unwrap();
+
+        let search = |compliance| {
+            IconSearch::new_from(vec![dir.path().into()])
+                .with_compliance(compliance)
+                .with_fallback_theme("Dogwood")
+                .search()
+                .icons()
+        };
+        let chain = |icons: &crate::Icons, name: &str| {
+            (icons.theme(name).unwrap().inherits_from.iter())
+                .map(|theme| theme.info.internal_name.clone())
+                .collect::<Vec<_>>()
+        };
+
+        let lenient = search(Compliance::Lenient);
+        assert_eq!(chain(&lenient, "Ash"), ["Cedar", "Birch", "hicolor"]);
+        assert!(lenient.theme("Elder").is_some());
+        assert!(lenient.find_icon("firefox", 48, 1, "Missing").is_some());
+
+        let strict = search(Compliance::Strict);
+        // depth-first, with hicolor inherited through Birch, which doesn't inherit any theme
+        assert_eq!(chain(&strict, "Ash"), ["Birch", "hicolor", "Cedar"]);
+        assert_eq!(chain(&strict, "Dogwood"), ["Cedar", "Birch", "hicolor"]);
+        // `Comment` is required
+        assert!(strict.theme("Elder").is_none());
+        // the fallback theme is ignored, but hicolor is always searched
+        assert!(strict.find_icon("firefox", 48, 1, "Missing").is_none());
+        assert!(strict.find_icon("htop", 48, 1, "Missing").is_some());
+
+        assert!(
+            (strict.theme("Ash").unwrap().spec_notes())
+                .iter()
+                .all(|deviation| deviation.applies_to(Compliance::Strict))
+        );
+    }
+}
@@ -0,0 +1,117 @@
+//! Statistics about which directories of a theme icons are found in.
+//!
+//! Every theme counts how often lookups find an icon in each of its directories. With
+//! [`IconSearch::with_adaptive_ordering`](crate::IconSearch::with_adaptive_ordering), directories
+//! matching the requested size exactly are tried in order of these counts, most hits first, which
+//! shortens the search for applications that repeatedly request similar sizes.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// How often icons were found in each directory of a theme.
+#[derive(Debug, Default)]
+pub struct HitStatistics {
+    /// Whether lookups try directories in order of their hits.
+    pub(crate) adaptive: bool,
+    hits: Box<[AtomicU64]>,
+}
+
+impl HitStatistics {
+    pub(crate) fn new(directories: usize, adaptive: bool) -> Self {
+        Self {
+            adaptive,
+            hits: (0..directories).map(|_| AtomicU64::new(0)).collect(),
+        }
+    }
+
+    pub(crate) fn record(&self, directory: usize) {
+        if let Some(hits) = self.hits.get(directory) {
+            hits.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    /// Sorts directory indices by their hits, most hits first, keeping the order of directories
+    /// with the same number of hits.
+    pub(crate) fn sort(&self, directories: &mut [usize]) {
+        directories.sort_by_key(|directory| std::cmp::Reverse(self.hits(*directory)));
+    }
+
+    /// How often an icon was found in the directory at `directory` in the theme's
+    /// [`directories`](crate::theme::ThemeIndex::directories).
+    pub fn hits(&self, directory: usize) -> u64 {
+        self.hits
+            .get(directory)
+            .map_or(0, |hits| hits.load(Ordering::Relaxed))
+    }
+
+    /// Whether lookups try directories in order of their hits.
+    pub fn is_adaptive(&self) -> bool {
+        self.adaptive
+    }
+}
+
+impl Clone for HitStatistics {
+    fn clone(&self) -> Self {
+        Self {
+            adaptive: self.adaptive,
+            hits: self
+                .hits
+                .iter()
+                .map(|hits| AtomicU64::new(hits.load(Ordering::Relaxed)))
+                .collect(),
+        }
+    }
+}
+
+// statistics don't affect what a theme is
+impl PartialEq for HitStatistics {
+    fn eq(&self, _other: &Self) -> bool {
+        true
+    }
+}
+
+impl Eq for HitStatistics {}
+
+impl std::hash::Hash for HitStatistics {
+    fn hash<H: std::hash::Hasher>(&self, _state: &mut H) {}
+}
+
+#[cfg(test)]
+mod test {
+    use crate::IconSearch;
+    use std::fs;
+
+    #[test]
+    fn test_adaptive_ordering() {
+        let dir = tempfile::tempdir().unwrap();
+        let theme = dir.path().join("hicolor");
+        for sub_dir in ["a", "b"] {
+            fs::create_dir_all(theme.join(sub_dir)).unwrap();
+            fs::write(theme.join(sub_dir).join("both.png"), b"").unwrap();
+        }
+        fs::write(theme.join("b/only-b.png"), b"").unwrap();
+        fs::write(
+            theme.join("index.theme"),
+            "[Icon Theme]\nName=Hicolor\nComment=\nDirectories=a,b\n\n[a]\nSize=48\n\n[b]\nSize=48\n",
+        )
+        .unwrap();
+
+        for adaptive in [false, true] {
+            let icons = IconSearch::new_from(vec![dir.path().into()])
+                .with_adaptive_ordering(adaptive)
+                .search()
+                .icons();
+
+            for _ in 0..3 {
+                icons.find_default_icon("only-b", 48, 1).unwrap();
+            }
+
+            let hicolor = icons.theme("hicolor").unwrap();
+            assert_eq!(hicolor.statistics.hits(0), 0);
+            assert_eq!(hicolor.statistics.hits(1), 3);
+
+            let icon = icons.find_default_icon("both", 48, 1).unwrap();
+            let expected = if adaptive { "b/both.png" } else { "a/both.png" };
+            assert_eq!(icon.path, theme.join(expected));
+        }
+    }
+}
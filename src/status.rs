@@ -0,0 +1,256 @@
+//! Icons for dynamic device status, as shown by panels and bars.
+//!
+//! Batteries, network connections and audio volume are shown with icons that change with their
+//! state. Themes name these differently: newer themes have an icon per battery level, e.g.
+//! `battery-level-40-charging`, while older ones follow the
+//! [icon naming specification](https://specifications.freedesktop.org/icon-naming-spec/latest/)
+//! and its common extensions, e.g. `battery-low-charging`. The functions here produce the names
+//! for a state from the most to the least specific, and the `find_*` methods of [`Icons`] look
+//! them up in that order.
+//!
+//! # Example
+//!
+//! ```
+//! use icon::Icons;
+//! use icon::status::battery_icon_names;
+//!
+//! assert_eq!(battery_icon_names(42, true)[0], "battery-level-40-charging");
+//!
+//! let icons = Icons::new();
+//! let battery = icons.find_battery_icon(42, true, 16, 1, "Adwaita");
+//! ```
+
+use crate::IconFile;
+use crate::theme::Icons;
+
+/// The kind of a network connection, see [`network_icon_names`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum NetworkKind {
+    Wired,
+    Wireless,
+    Cellular,
+    /// Not connected to any network.
+    Offline,
+}
+
+/// The names of the icons for a battery charged `percent`, which is clamped to 100.
+///
+/// A charging battery at 100% is `charged`. The per-level names go in steps of ten, rounded down.
+pub fn battery_icon_names(percent: u8, charging: bool) -> Vec<String> {
+    let percent = percent.min(100);
+    let mut names = Vec::new();
+
+    let level = percent / 10 * 10;
+    names.push(match (level, charging) {
+        (100, true) => "battery-level-100-charged".to_owned(),
+        (level, true) => format!("battery-level-{level}-charging"),
+        (level, false) => format!("battery-level-{level}"),
+    });
+
+    let legacy = match percent {
+        90.. => "battery-full",
+        50.. => "battery-good",
+        20.. => "battery-low",
+        5.. => "battery-caution",
+        _ => "battery-empty",
+    };
+    if charging {
+        if percent == 100 {
+            names.push("battery-full-charged".to_owned());
+        }
+        names.push(format!("{legacy}-charging"));
+    }
+    names.push(legacy.to_owned());
+    names.push("battery".to_owned());
+
+    names
+}
+
+/// The names of the icons for a network connection with a signal `strength` in percent, which is
+/// ignored for wired connections.
+pub fn network_icon_names(strength: u8, kind: NetworkKind) -> Vec<String> {
+    let signal = match strength {
+        81.. => "excellent",
+        56.. => "good",
+        31.. => "ok",
+        6.. => "weak",
+        _ => "none",
+    };
+
+    match kind {
+        NetworkKind::Wired => vec!["network-wired".into()],
+        NetworkKind::Wireless => vec![
+            format!("network-wireless-signal-{signal}"),
+            "network-wireless".into(),
+        ],
+        NetworkKind::Cellular => vec![
+            format!("network-cellular-signal-{signal}"),
+            "network-cellular".into(),
+            "network-wireless".into(),
+        ],
+        NetworkKind::Offline => vec![
+            "network-offline".into(),
+            "network-wired-disconnected".into(),
+        ],
+    }
+}
+
+/// The names of the icons for audio at `level` percent. Levels above 100 are overamplified.
+pub fn volume_icon_names(level: u8, muted: bool) -> Vec<String> {
+    let names: &[&str] = match level {
+        _ if muted => &["audio-volume-muted"],
+        0 => &["audio-volume-muted"],
+        1..=33 => &["audio-volume-low"],
+        34..=66 => &["audio-volume-medium"],
+        67..=100 => &["audio-volume-high"],
+        _ => &["audio-volume-overamplified", "audio-volume-high"],
+    };
+    names.iter().map(|&name| name.to_owned()).collect()
+}
+
+impl Icons {
+    /// Looks up the first of `names` that exists in the nearest theme of the chain, see
+    /// [`choose_icon`](Self::choose_icon).
+    pub(crate) fn find_first_icon(
+        &self,
+        names: &[String],
+        size: u32,
+        scale: u32,
+        theme: &str,
+    ) -> Option<IconFile> {
+        let names = names.iter().map(String::as_str).collect::<Vec<_>>();
+        self.choose_icon(&names, size, scale, theme)
+    }
+
+    /// Looks up the icon of a battery, see [`battery_icon_names`].
+    pub fn find_battery_icon(
+        &self,
+        percent: u8,
+        charging: bool,
+        size: u32,
+        scale: u32,
+        theme: &str,
+    ) -> Option<IconFile> {
+        self.find_first_icon(&battery_icon_names(percent, charging), size, scale, theme)
+    }
+
+    /// Looks up the icon of a network connection, see [`network_icon_names`].
+    pub fn find_network_icon(
+        &self,
+        strength: u8,
+        kind: NetworkKind,
+        size: u32,
+        scale: u32,
+        theme: &str,
+    ) -> Option<IconFile> {
+        self.find_first_icon(&network_icon_names(strength, kind), size, scale, theme)
+    }
+
+    /// Looks up the icon of the audio volume, see [`volume_icon_names`].
+    pub fn find_volume_icon(
+        &self,
+        level: u8,
+        muted: bool,
+        size: u32,
+        scale: u32,
+        theme: &str,
+    ) -> Option<IconFile> {
+        self.find_first_icon(&volume_icon_names(level, muted), size, scale, theme)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::IconSearch;
+    use crate::status::{NetworkKind, battery_icon_names, network_icon_names, volume_icon_names};
+    use std::fs;
+    use std::path::Path;
+
+    #[test]
+    fn test_status_icon_names() {
+        assert_eq!(
+            battery_icon_names(100, true),
+            [
+                "battery-level-100-charged",
+                "battery-full-charged",
+                "battery-full-charging",
+                "battery-full",
+                "battery",
+            ]
+        );
+        assert_eq!(
+            battery_icon_names(19, false),
+            ["battery-level-10", "battery-caution", "battery"]
+        );
+        assert_eq!(battery_icon_names(250, false)[0], "battery-level-100");
+        assert_eq!(battery_icon_names(0, false)[1], "battery-empty");
+
+        assert_eq!(
+            network_icon_names(60, NetworkKind::Wireless),
+            ["network-wireless-signal-good", "network-wireless"]
+        );
+        assert_eq!(
+            network_icon_names(0, NetworkKind::Cellular)[0],
+            "network-cellular-signal-none"
+        );
+        assert_eq!(
+            network_icon_names(60, NetworkKind::Wired),
+            ["network-wired"]
+        );
+
+        assert_eq!(volume_icon_names(50, true), ["audio-volume-muted"]);
+        assert_eq!(volume_icon_names(0, false), ["audio-volume-muted"]);
+        assert_eq!(volume_icon_names(33, false), ["audio-volume-low"]);
+        assert_eq!(volume_icon_names(100, false), ["audio-volume-high"]);
+        assert_eq!(volume_icon_names(150, false)[1], "audio-volume-high");
+    }
+
+    #[test]
+    fn test_find_battery_icon() {
+        let dir = tempfile::tempdir().unwrap();
+        let theme = dir.path().join("hicolor");
+        fs::create_dir_all(theme.join("16")).unwrap();
+        fs::write(
+            theme.join("index.theme"),
+            "[Icon Theme]\nName=Hicolor\nComment=\nDirectories=16\n\n[16]\nSize=16\n",
+        )
+        .unwrap();
+        for icon in ["battery-level-40-charging", "battery-low", "battery"] {
+            fs::write(theme.join(format!("16/{icon}.svg")), b"").unwrap();
+        }
+
+        let oak = dir.path().join("Oak");
+        fs::create_dir_all(oak.join("16")).unwrap();
+        fs::write(
+            oak.join("index.theme"),
+            "[Icon Theme]\nName=Oak\nComment=\nDirectories=16\n\n[16]\nSize=16\n",
+        )
+        .unwrap();
+        fs::write(oak.join("16/battery.svg"), b"").unwrap();
+
+        let icons = IconSearch::new_from(vec![dir.path().into()])
+            .search()
+            .icons();
+        let find = |percent, charging, theme| {
+            let icon = icons.find_battery_icon(percent, charging, 16, 1, theme)?;
+            Some(icon.path.strip_prefix(dir.path()).ok()?.to_owned())
+        };
+        assert_eq!(
+            find(45, true, "hicolor").unwrap(),
+            Path::new("hicolor/16/battery-level-40-charging.svg")
+        );
+        assert_eq!(
+            find(95, false, "hicolor").unwrap(),
+            Path::new("hicolor/16/battery.svg")
+        );
+        assert_eq!(
+            find(30, true, "hicolor").unwrap(),
+            Path::new("hicolor/16/battery-low.svg")
+        );
+        // the theme's generic icon wins over a more specific one in hicolor
+        assert_eq!(
+            find(45, true, "Oak").unwrap(),
+            Path::new("Oak/16/battery.svg")
+        );
+    }
+}
@@ -0,0 +1,187 @@
+//! Helpers for testing code that looks icons up.
+//!
+//! [`MockIcons`] is an [`IconResolver`] whose icons are registered by the test, so that tests
+//! of icon-dependent logic don't depend on what is installed on the machine running them. No
+//! file system is touched: the registered paths don't have to exist.
+//!
+//! Icons are registered either at a size, like the files in a theme's fixed-size directories, or
+//! at any size, like scalable ones. Lookups pick between them deterministically, see
+//! [`MockIcons::find_icon`](IconResolver::find_icon).
+//!
+//! # Example
+//!
+//! ```
+//! use icon::resolver::IconResolver;
+//! use icon::testing::MockIcons;
+//!
+//! let icons = MockIcons::new()
+//!     .with_icon("hicolor", "firefox", "/icons/firefox.svg")
+//!     .with_sized_icon("Adwaita", "folder", 16, "/icons/folder-16.png")
+//!     .with_sized_icon("Adwaita", "folder", 32, "/icons/folder-32.png");
+//!
+//! let folder = icons.find_icon("folder", 16, 2, "Adwaita").unwrap();
+//! assert_eq!(folder.path.to_str(), Some("/icons/folder-32.png"));
+//! // like installed themes, every theme falls back to hicolor
+//! assert!(icons.has_icon("firefox", "Adwaita"));
+//! ```
+
+use crate::resolver::IconResolver;
+use crate::{FileType, IconFile};
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::PathBuf;
+
+/// An [`IconResolver`] with registered icons, see [`testing`](crate::testing).
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Default)]
+pub struct MockIcons {
+    /// The files of each icon of each theme, with the size in pixels they were registered at,
+    /// in the order they were registered in.
+    themes: BTreeMap<String, BTreeMap<String, MockFiles>>,
+}
+
+/// The files of a mock icon, with the size they were registered at, if any.
+type MockFiles = Vec<(Option<u32>, IconFile)>;
+
+impl MockIcons {
+    /// Mock icons without any icons.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers a file of the icon `icon_name` of `theme` at `path` that is found at any size
+    /// and scale, creating the theme if needed.
+    ///
+    /// # Panics
+    ///
+    /// If `path` doesn't have the extension of a built-in [`FileType`].
+    pub fn with_icon(
+        self,
+        theme: impl Into<String>,
+        icon_name: impl Into<String>,
+        path: impl Into<PathBuf>,
+    ) -> Self {
+        self.with_file(theme.into(), icon_name.into(), None, path.into())
+    }
+
+    /// Like [`with_icon`](Self::with_icon), registering a file that is `size` pixels large,
+    /// i.e. the size times the scale it is meant for.
+    pub fn with_sized_icon(
+        self,
+        theme: impl Into<String>,
+        icon_name: impl Into<String>,
+        size: u32,
+        path: impl Into<PathBuf>,
+    ) -> Self {
+        self.with_file(theme.into(), icon_name.into(), Some(size), path.into())
+    }
+
+    fn with_file(
+        mut self,
+        theme: String,
+        icon_name: String,
+        size: Option<u32>,
+        path: PathBuf,
+    ) -> Self {
+        let file_type = FileType::from_path_ext(&path)
+            .unwrap_or_else(|| panic!("{path:?} isn't a PNG, SVG or XPM file"));
+        let files = self.themes.entry(theme).or_default();
+        (files.entry(icon_name).or_default()).push((size, IconFile { path, file_type }));
+        self
+    }
+}
+
+impl IconResolver for MockIcons {
+    /// Finds the icon in `theme`, or else in `hicolor`.
+    ///
+    /// Of the icon's files, the first one registered at `size` times `scale` pixels wins, then
+    /// the first one registered at any size, then the one closest to the size, preferring the
+    /// larger one of two equally close files.
+    fn find_icon(&self, icon_name: &str, size: u32, scale: u32, theme: &str) -> Option<IconFile> {
+        let files = [theme, "hicolor"]
+            .into_iter()
+            .find_map(|theme| self.themes.get(theme)?.get(icon_name))?;
+
+        let pixels = size.saturating_mul(scale);
+        let (_, file) = (files.iter())
+            .find(|(size, _)| *size == Some(pixels))
+            .or_else(|| files.iter().find(|(size, _)| size.is_none()))
+            .or_else(|| {
+                (files.iter()).min_by_key(|(size, _)| {
+                    let size = size.unwrap_or_default();
+                    (size.abs_diff(pixels), size < pixels)
+                })
+            })?;
+        Some(file.clone())
+    }
+
+    fn has_icon(&self, icon_name: &str, theme: &str) -> bool {
+        self.themes.contains_key(theme) && self.find_icon(icon_name, 48, 1, theme).is_some()
+    }
+
+    /// The themes in alphabetical order.
+    fn list_themes(&self) -> Vec<String> {
+        self.themes.keys().cloned().collect()
+    }
+
+    fn list_icons(&self, theme: &str) -> BTreeSet<String> {
+        (self.themes.get(theme))
+            .map(|icons| icons.keys().cloned().collect())
+            .unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::resolver::IconResolver;
+    use crate::testing::MockIcons;
+    use std::path::Path;
+
+    #[test]
+    fn test_mock_icons() {
+        let icons = MockIcons::new()
+            .with_icon("hicolor", "firefox", "/icons/firefox.png")
+            .with_icon("Oak", "acorn", "/icons/acorn.svg")
+            .with_icon("Oak", "firefox", "/icons/oak-firefox.svg");
+
+        let path = |name, theme| icons.find_icon(name, 16, 2, theme).map(|icon| icon.path);
+        assert_eq!(
+            path("firefox", "Oak").unwrap(),
+            Path::new("/icons/oak-firefox.svg")
+        );
+        assert_eq!(
+            path("firefox", "Birch").unwrap(),
+            Path::new("/icons/firefox.png")
+        );
+        assert_eq!(path("acorn", "hicolor"), None);
+        assert!(icons.has_icon("firefox", "Oak"));
+        assert!(!icons.has_icon("firefox", "Birch"));
+
+        assert_eq!(icons.list_themes(), ["Oak", "hicolor"]);
+        assert_eq!(
+            icons.list_icons("Oak").into_iter().collect::<Vec<_>>(),
+            ["acorn", "firefox"]
+        );
+    }
+
+    #[test]
+    fn test_mock_icon_sizes() {
+        let icons = MockIcons::new()
+            .with_sized_icon("Oak", "acorn", 16, "/icons/16.png")
+            .with_sized_icon("Oak", "acorn", 32, "/icons/32.png")
+            .with_sized_icon("Oak", "leaf", 16, "/icons/leaf-16.png")
+            .with_icon("Oak", "leaf", "/icons/leaf.svg");
+
+        let path = |name, size, scale| {
+            let icon = icons.find_icon(name, size, scale, "Oak").unwrap();
+            icon.path.to_str().unwrap().to_owned()
+        };
+        assert_eq!(path("acorn", 16, 1), "/icons/16.png");
+        assert_eq!(path("acorn", 16, 2), "/icons/32.png");
+        assert_eq!(path("acorn", 20, 1), "/icons/16.png");
+        // equally close, so the larger one
+        assert_eq!(path("acorn", 24, 1), "/icons/32.png");
+        assert_eq!(path("acorn", 256, 1), "/icons/32.png");
+
+        assert_eq!(path("leaf", 16, 1), "/icons/leaf-16.png");
+        assert_eq!(path("leaf", 48, 1), "/icons/leaf.svg");
+    }
+}
@@ -1,11 +1,22 @@
-use crate::IconSearch;
-use crate::icon::IconFile;
+use crate::audit::{AccessKind, Audit, allows};
+use crate::change::Listeners;
+use crate::edit::ICON_THEME_GROUP;
+use crate::hash_map::HashMap as FastHashMap;
+use crate::icon::{FileType, FileTypes, IconFile, IconMatch, MatchKind, SkipReason};
+use crate::listing::{DirectoryListing, IconLocation};
+use crate::naming::fallback_names;
+use crate::report::{ScanReport, UnreadableDirs};
+use crate::spec::Compliance;
+use crate::statistics::HitStatistics;
 use crate::theme::ThemeParseError::MissingRequiredAttribute;
+use crate::{Environment, IconQuery, IconSearch, Precedence};
 use freedesktop_entry_parser::low_level::{EntryIter, SectionBytes};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
 use std::ffi::{OsStr, OsString};
-use std::path::{Path, PathBuf};
-use std::sync::Arc;
+use std::io::Read;
+use std::path::{Component, Path, PathBuf};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::{Duration, Instant};
 
 /// Main struct to locate icon files.
 ///
@@ -19,20 +30,116 @@ use std::sync::Arc;
 ///
 /// Icons::new().find_icon("firefox", 32, 1, "hicolor");
 /// ```
+#[derive(Debug, Clone)]
 pub struct Icons {
-    pub standalone_icons: HashMap<String, IconFile>,
+    /// The directories that were searched for icons, in order.
+    pub base_dirs: Vec<PathBuf>,
+    /// The standalone icons by name, in the order they are preferred in, see
+    /// [`standalone_icons`](Self::standalone_icons). Iterating this map yields them in an order
+    /// that changes between runs, see [`iter_standalone_icons`](Self::iter_standalone_icons) for
+    /// a stable one.
+    pub standalone_icons: HashMap<String, Vec<IconFile>>,
+    /// The themes by internal name. Iterating this map yields them in an order that changes
+    /// between runs, see [`iter_themes`](Self::iter_themes) for a stable one.
     pub themes: HashMap<OsString, Arc<Theme>>,
+    /// The theme used when a requested theme doesn't exist. This is `hicolor` unless
+    /// [configured otherwise](IconSearch::with_fallback_theme).
+    pub fallback_theme: String,
+    /// Themes looked up after the requested theme and before hicolor, see
+    /// [`IconSearch::with_preferred_fallbacks`].
+    pub preferred_fallbacks: Vec<String>,
+    /// See [`IconSearch::with_name_fallback`].
+    pub name_fallback: bool,
+    /// See [`IconSearch::with_precedence`].
+    pub precedence: Precedence,
+    /// See [`IconSearch::with_directory_listing`].
+    pub list_directories: bool,
+    /// See [`IconSearch::with_adaptive_ordering`].
+    pub adaptive_ordering: bool,
+    /// See [`IconSearch::with_scale_suffixes`].
+    pub scale_suffixes: bool,
+    /// See [`IconSearch::with_inferred_indexes`].
+    pub infer_indexes: bool,
+    /// See [`IconSearch::with_hicolor_bootstrap`].
+    pub bootstrap_hicolor: bool,
+    /// See [`IconSearch::with_file_type`].
+    pub file_types: FileTypes,
+    /// See [`IconSearch::with_compliance`].
+    pub compliance: Compliance,
+    /// See [`IconSearch::with_audit`].
+    pub audit: Option<Audit>,
+    pub(crate) resolve_beneath: bool,
+    /// See [`IconSearch::with_parallelism`].
+    #[cfg(feature = "rayon")]
+    pub parallelism: crate::parallel::Parallelism,
+    /// What happened during the scan that found these icons, see [`report`](crate::report).
+    pub report: ScanReport,
+    /// Bumped whenever the icons that would be found may have changed, see
+    /// [`generation`](Self::generation).
+    pub(crate) generation: u64,
+    /// The environment the search was [constructed from](IconSearch::from_environment), if any.
+    pub(crate) environment: Option<Environment>,
+    /// The user's current theme, read on first use after the scan or the last
+    /// [refresh](Self::refresh), see [`current_theme`](Self::current_theme).
+    pub(crate) current_theme: OnceLock<Option<String>>,
+    /// The themes added with [`add_memory_theme`](Self::add_memory_theme).
+    pub(crate) memory_themes: Vec<Arc<crate::memory::MemoryTheme>>,
+    pub(crate) listeners: Listeners,
+}
+
+// two `Icons` are equal if they find the same icons the same way: the report and generation of the
+// scan, the current theme read since, the threads lookups run on and the callbacks registered
+// don't matter, and neither does what an audit recorded.
+impl PartialEq for Icons {
+    fn eq(&self, other: &Self) -> bool {
+        self.base_dirs == other.base_dirs
+            && self.standalone_icons == other.standalone_icons
+            && self.themes == other.themes
+            && self.fallback_theme == other.fallback_theme
+            && self.preferred_fallbacks == other.preferred_fallbacks
+            && self.name_fallback == other.name_fallback
+            && self.precedence == other.precedence
+            && self.list_directories == other.list_directories
+            && self.adaptive_ordering == other.adaptive_ordering
+            && self.scale_suffixes == other.scale_suffixes
+            && self.infer_indexes == other.infer_indexes
+            && self.bootstrap_hicolor == other.bootstrap_hicolor
+            && self.file_types == other.file_types
+            && self.compliance == other.compliance
+            && self.audit.is_some() == other.audit.is_some()
+            && self.resolve_beneath == other.resolve_beneath
+            && self.environment == other.environment
+            && self.memory_themes == other.memory_themes
+    }
 }
 
+impl Eq for Icons {}
+
 impl Icons {
     /// Creates a new `Icons`, performing a search in the standard directories.
     ///
     /// This function collects all standalone icons and icon themes on the system.
     /// To configure what directories are searched, use [`IconSearch`] instead.
+    ///
+    /// This never fails: directories that don't exist and themes that can't be parsed are
+    /// skipped, so the result may be empty. Use [`try_new`](Self::try_new) to find out why, or
+    /// inspect the [`report`](Self::report).
     pub fn new() -> Self {
         IconSearch::new().search().icons()
     }
 
+    /// Like [`new`](Self::new), but failing if the home directory can't be determined, or if
+    /// nothing usable was found: none of the directories could be read, or every theme failed to
+    /// parse.
+    pub fn try_new() -> Result<Self, crate::Error> {
+        if Environment::current().home.is_none() {
+            return Err(crate::Error::NoHomeDirectory);
+        }
+        let icons = Self::new();
+        crate::Error::check(&icons)?;
+        Ok(icons)
+    }
+
     /// Access a known icon theme by name
     pub fn theme(&self, theme_name: &str) -> Option<Arc<Theme>> {
         let theme_name: &OsStr = theme_name.as_ref();
@@ -46,10 +153,20 @@ impl Icons {
 
     /// Look up an icon by name, size, scale and theme.
     ///
-    /// - If no theme by the given name exists, the `"hicolor"` theme (default theme) is used instead.
+    /// - If no theme by the given name exists, the [fallback theme](Self::fallback_theme) is used
+    ///   instead, which is the `"hicolor"` theme (default theme) unless configured otherwise.
     /// - If the icon is not found in the provided theme, its parents are checked.
     /// - If the icon is not found in any of the themes, the standalone icon list is checked.
     ///
+    /// With [preferred fallbacks](IconSearch::with_preferred_fallbacks), those themes and their
+    /// parents are checked after the provided theme and its parents, but before hicolor.
+    ///
+    /// With [`Compliance::Strict`], hicolor is checked after the provided theme and its parents
+    /// instead, and neither the fallback theme nor the preferred fallbacks are used.
+    ///
+    /// With [name fallback](IconSearch::with_name_fallback), if the icon isn't found anywhere,
+    /// each of its [more generic names](crate::naming::fallback_names) is looked up the same way.
+    ///
     /// # Icon matching
     ///
     /// This function will return an icon matching the specified size and scale exactly if it exists.
@@ -58,6 +175,7 @@ impl Icons {
     /// This will only return `None` if no icon by the specified name exists in the specified theme
     /// and its parents, and no standalone icon by the same name exists either.
     ///
+    /// For more options, use [`lookup`](Self::lookup) with an [`IconQuery`].
     pub fn find_icon(
         &self,
         icon_name: &str,
@@ -65,14 +183,507 @@ impl Icons {
         scale: u32,
         theme: &str,
     ) -> Option<IconFile> {
+        self.lookup(
+            &IconQuery::new(icon_name, size)
+                .with_scale(scale)
+                .with_theme(theme),
+        )
+    }
+
+    /// Looks up the first of several names for the same icon, in order of preference, as
+    /// `gtk_icon_theme_choose_icon` does.
+    ///
+    /// Like GTK, this prefers the theme over the name: each theme in the chain of
+    /// [`find_icon`](Self::find_icon) is searched for all of `icon_names` before its parents are,
+    /// so a less preferred name from the requested theme wins over a more preferred one from
+    /// hicolor. The standalone icons are searched last. The chain is resolved only once.
+    ///
+    /// With [name fallback](IconSearch::with_name_fallback), the
+    /// [more generic names](crate::naming::fallback_names) of all names are looked up the same
+    /// way if none is found.
+    pub fn choose_icon(
+        &self,
+        icon_names: &[&str],
+        size: u32,
+        scale: u32,
+        theme: &str,
+    ) -> Option<IconFile> {
+        let icon_names = (icon_names.iter().copied())
+            .filter(|name| !name.is_empty())
+            .collect::<Vec<_>>();
+        if icon_names.is_empty() {
+            return None;
+        }
+
+        let chain = self.lookup_chain(theme);
+        let lookup = |icon_names: &[&str]| {
+            (chain.iter())
+                .find_map(|theme| {
+                    (icon_names.iter()).find_map(|name| theme.find_icon_here(name, size, scale))
+                })
+                .or_else(|| {
+                    (icon_names.iter()).find_map(|name| self.find_standalone_scaled(name, scale))
+                })
+        };
+
+        let icon = lookup(&icon_names);
+        if icon.is_some() || !self.name_fallback {
+            return icon;
+        }
+        let generic = (icon_names.iter())
+            .flat_map(|name| fallback_names(name).into_iter().skip(1))
+            .collect::<Vec<_>>();
+        lookup(&generic.iter().map(String::as_str).collect::<Vec<_>>())
+    }
+
+    /// Like [`find_icon`](Self::find_icon), but giving up after `budget` has passed, for
+    /// interactive applications that can't afford to block on slow file systems such as network
+    /// mounts.
+    ///
+    /// Once the budget is exceeded, this returns a [`TimedOut`] error with the best icon found so
+    /// far. The budget only applies to probing the file system, so lookups in
+    /// [listed](IconSearch::with_directory_listing) themes never time out. It is checked between
+    /// probes: a single call to the file system that hangs can't be interrupted.
+    pub fn find_icon_within(
+        &self,
+        icon_name: &str,
+        size: u32,
+        scale: u32,
+        theme: &str,
+        budget: Duration,
+    ) -> Result<Option<IconFile>, TimedOut> {
+        let Some(deadline) = Instant::now().checked_add(budget) else {
+            return Ok(self.find_icon(icon_name, size, scale, theme));
+        };
+        if icon_name.is_empty() {
+            return Ok(None);
+        }
+
+        for theme in self.lookup_chain(theme) {
+            match theme.locate_icon_until(icon_name, size, scale, Some(deadline)) {
+                Ok(Some((_, _, icon))) => return Ok(Some(icon)),
+                Ok(None) => {}
+                Err(Expired) => {
+                    // standalone icons are known without touching the file system
+                    return Err(TimedOut {
+                        best: self.find_standalone_scaled(icon_name, scale),
+                    });
+                }
+            }
+        }
+
+        Ok(self.find_standalone_scaled(icon_name, scale))
+    }
+
+    /// Looks up an icon given a prioritized list of acceptable sizes, as tray icon specifications
+    /// and some toolkits express their requirements.
+    ///
+    /// Every size is tried at the first scale, then at the next one, and so on, returning the
+    /// first icon that matches a size exactly in the theme or its parents. If none does, this
+    /// falls back to [`find_icon`](Self::find_icon) with the first size and scale. An empty list
+    /// of scales is treated as `&[1]`.
+    pub fn find_icon_sized(
+        &self,
+        icon_name: &str,
+        sizes: &[u32],
+        scales: &[u32],
+        theme: &str,
+    ) -> Option<IconFile> {
+        let scales = if scales.is_empty() { &[1] } else { scales };
+        let (&first_size, &first_scale) = (sizes.first()?, scales.first()?);
+        if icon_name.is_empty() {
+            return None;
+        }
+
+        let chain = self.lookup_chain(theme);
+        for &scale in scales {
+            for &size in sizes {
+                let exact =
+                    (chain.iter()).find_map(|theme| theme.locate_exact(icon_name, size, scale));
+                if let Some((_, _, icon)) = exact {
+                    return Some(icon);
+                }
+            }
+        }
+
+        self.find_icon(icon_name, first_size, first_scale, theme)
+    }
+
+    /// The themes [`find_icon`](Self::find_icon) looks up an icon in, in order, including their
+    /// parents.
+    pub(crate) fn lookup_chain(&self, theme: &str) -> Vec<Arc<Theme>> {
+        self.iter_chain(theme).cloned().collect()
+    }
+
+    // like `lookup_chain`, without allocating.
+    pub(crate) fn iter_chain<'a>(
+        &'a self,
+        theme: &str,
+    ) -> impl Iterator<Item = &'a Arc<Theme>> + use<'a> {
+        let get = |name: &str| self.themes.get(OsStr::new(name));
+        let (first, preferred, hicolor) = match self.compliance {
+            // "If [the icon] is not found in the current theme, it looks in the default theme,
+            //  hicolor", and then outside of themes
+            Compliance::Strict => (get(theme), &[][..], get("hicolor")),
+            Compliance::Lenient => (
+                (get(theme))
+                    .or_else(|| get(&self.fallback_theme))
+                    .or_else(|| get("hicolor")),
+                self.preferred_fallbacks.as_slice(),
+                None,
+            ),
+        };
+
+        let themes = move || {
+            (first.into_iter())
+                .chain((preferred.iter()).filter_map(move |name| get(name)))
+                .chain(hicolor)
+                .flat_map(|theme| std::iter::once(theme).chain(&theme.inherits_from))
+        };
+
+        // preferred fallbacks come before hicolor, which ends the chain of the requested theme
+        let defer_hicolor = !preferred.is_empty();
+        let is_hicolor = |theme: &Arc<Theme>| theme.info.internal_name == "hicolor";
+        let deferred = defer_hicolor
+            .then(|| themes().find(|theme| is_hicolor(theme)))
+            .flatten();
+
+        (themes().enumerate())
+            .filter(move |&(idx, theme)| !themes().take(idx).any(|other| Arc::ptr_eq(other, theme)))
+            .map(|(_, theme)| theme)
+            .filter(move |theme| !(defer_hicolor && is_hicolor(theme)))
+            .chain(deferred)
+    }
+
+    /// The icon theme selected by the user, as [`find_icon_any`](Self::find_icon_any) uses it.
+    ///
+    /// This is read from the [environment](IconSearch::from_environment) the search was
+    /// constructed from, or else from the [current process](crate::settings::current_theme), the
+    /// first time it's needed after the scan or the last [refresh](Self::refresh). Audited icons
+    /// don't consult the user's settings, so this is always `None` for them.
+    pub fn current_theme(&self) -> Option<String> {
+        if self.audit.is_some() {
+            return None;
+        }
+        let theme = self.current_theme.get_or_init(|| match &self.environment {
+            Some(env) => env.current_theme(),
+            None => crate::settings::current_theme(),
+        });
+        theme.clone()
+    }
+
+    /// Looks up an icon without knowing which theme to use, as a notification daemon receiving
+    /// icon names from arbitrary applications would.
+    ///
+    /// The icon is looked up in
+    /// 1. the user's [current theme](Environment::current_theme) and its parents, if it can be
+    ///    determined, as of the first lookup after the scan or the last [refresh](Self::refresh),
+    /// 2. `hicolor`,
+    /// 3. all other themes, in alphabetical order of their internal names,
+    /// 4. the standalone icons.
+    pub fn find_icon_any(&self, icon_name: &str, size: u32, scale: u32) -> Option<IconFile> {
+        if icon_name.is_empty() {
+            return None;
+        }
+
+        if let Some(theme) = self.current_theme().and_then(|name| self.theme(&name))
+            && let Some(icon) = theme.find_icon(icon_name, size, scale)
+        {
+            return Some(icon);
+        }
+
+        let mut themes = self.themes.iter().collect::<Vec<_>>();
+        themes.sort_by_key(|(name, _)| (*name != "hicolor", *name));
+
+        themes
+            .into_iter()
+            .find_map(|(_, theme)| theme.find_icon_here(icon_name, size, scale))
+            .or_else(|| self.find_standalone_scaled(icon_name, scale))
+    }
+
+    /// Looks up an icon in every installed theme, returning the best match of each theme by its
+    /// internal name.
+    ///
+    /// Only themes that contain the icon themselves are included, not those that would inherit
+    /// it from a parent. This is useful for showing how each theme renders an icon.
+    pub fn find_icon_everywhere(
+        &self,
+        icon_name: &str,
+        size: u32,
+        scale: u32,
+    ) -> BTreeMap<String, IconFile> {
+        if icon_name.is_empty() {
+            return BTreeMap::new();
+        }
+
+        self.themes
+            .iter()
+            .filter_map(|(name, theme)| {
+                let icon = theme.find_icon_here(icon_name, size, scale)?;
+                Some((name.to_string_lossy().into_owned(), icon))
+            })
+            .collect()
+    }
+
+    /// Looks up an icon in the given themes, in order, followed by the standalone icons.
+    ///
+    /// Unlike [`find_icon`](Self::find_icon), the themes' parents aren't consulted: `chain` is the
+    /// whole inheritance chain. This lets applications layer themes by their own policy, e.g. a
+    /// branding theme on top of the user's theme:
+    ///
+    /// ```
+    /// use icon::Icons;
+    ///
+    /// let icons = Icons::new();
+    /// let chain = ["Corporate", "Adwaita", "hicolor"]
+    ///     .into_iter()
+    ///     .filter_map(|name| icons.theme(name))
+    ///     .collect::<Vec<_>>();
+    /// let chain = chain.iter().map(|theme| theme.as_ref()).collect::<Vec<_>>();
+    ///
+    /// let firefox = icons.find_icon_in_chain("firefox", 48, 1, &chain);
+    /// ```
+    pub fn find_icon_in_chain(
+        &self,
+        icon_name: &str,
+        size: u32,
+        scale: u32,
+        chain: &[&Theme],
+    ) -> Option<IconFile> {
+        if icon_name.is_empty() {
+            return None;
+        }
+
+        (chain.iter())
+            .find_map(|theme| theme.find_icon_here(icon_name, size, scale))
+            .or_else(|| self.find_standalone_scaled(icon_name, scale))
+    }
+
+    /// Re-reads a single theme from disk, replacing it (and the themes inheriting from it) in
+    /// place.
+    ///
+    /// This is much cheaper than [`refresh`](Self::refresh) when only one theme is known to have
+    /// changed, e.g. after a tool edited its files. Only if the theme is new or now inherits from
+    /// different themes does this fall back to a full refresh, as the inheritance trees may change.
+    ///
+    /// Returns an error if the theme no longer exists or has become invalid, in which case the
+    /// old version is kept.
+    pub fn rescan_theme(&mut self, theme_name: &str) -> std::io::Result<Arc<Theme>> {
+        let mut folders = self
+            .base_dirs
+            .iter()
+            .map(|base_dir| base_dir.join(theme_name))
+            .filter(|dir| allows(self.audit.as_ref(), dir, AccessKind::Probe) && dir.is_dir())
+            .collect::<Vec<_>>();
+        if self.precedence == Precedence::SystemFirst {
+            folders.reverse();
+        }
+        let info = ThemeInfo::load(
+            theme_name.into(),
+            folders,
+            self.compliance,
+            self.audit.as_ref(),
+            self.infer_indexes,
+            self.bootstrap_hicolor,
+        )?;
+
+        let Some(old) = self
+            .theme(theme_name)
+            .filter(|old| old.info.index.inherits == info.index.inherits)
+        else {
+            self.refresh();
+            return self
+                .theme(theme_name)
+                .ok_or_else(|| std::io::Error::other(ThemeParseError::NotAnIconTheme));
+        };
+
+        let unreadable = UnreadableDirs::scan(&info, self.audit.as_ref());
+        let new = Arc::new(Theme {
+            listing: old.listing.as_ref().map(|_| {
+                Arc::new(DirectoryListing::build_recording(
+                    &info,
+                    &self.file_types,
+                    &unreadable,
+                    self.audit.as_ref(),
+                    self.resolve_beneath,
+                ))
+            }),
+            unreadable,
+            selections: SubDirSelections::default(),
+            statistics: HitStatistics::new(
+                info.index.directories.len(),
+                self.adaptive_ordering && self.compliance == Compliance::Lenient,
+            ),
+            file_types: self.file_types.clone(),
+            compliance: self.compliance,
+            audit: self.audit.clone(),
+            resolve_beneath: self.resolve_beneath,
+            scale_suffixes: self.scale_suffixes,
+            info,
+            inherits_from: old.inherits_from.clone(),
+        });
+
+        // every theme lists all of its ancestors, which have shorter lists than it does: going
+        // from short to long lists, parents are always replaced before their children.
+        let mut replaced = vec![(old, new.clone())];
+        let mut themes = self.themes.iter_mut().collect::<Vec<_>>();
+        themes.sort_by_key(|(_, theme)| theme.inherits_from.len());
+
+        for (_, theme) in themes {
+            if let Some((_, replacement)) = replaced.iter().find(|(old, _)| Arc::ptr_eq(old, theme))
+            {
+                *theme = replacement.clone();
+                continue;
+            }
+
+            if !theme
+                .inherits_from
+                .iter()
+                .any(|parent| replaced.iter().any(|(old, _)| Arc::ptr_eq(old, parent)))
+            {
+                continue;
+            }
+
+            let inherits_from = theme
+                .inherits_from
+                .iter()
+                .map(|parent| {
+                    replaced
+                        .iter()
+                        .find(|(old, _)| Arc::ptr_eq(old, parent))
+                        .map_or_else(|| parent.clone(), |(_, new)| new.clone())
+                })
+                .collect();
+            let updated = Arc::new(Theme {
+                info: theme.info.clone(),
+                inherits_from,
+                listing: theme.listing.clone(),
+                statistics: theme.statistics.clone(),
+                unreadable: theme.unreadable.clone(),
+                selections: theme.selections.clone(),
+                file_types: theme.file_types.clone(),
+                compliance: theme.compliance,
+                audit: theme.audit.clone(),
+                resolve_beneath: theme.resolve_beneath,
+                scale_suffixes: theme.scale_suffixes,
+            });
+
+            replaced.push((std::mem::replace(theme, updated.clone()), updated));
+        }
+
+        self.generation = crate::change::next_generation();
+        Ok(new)
+    }
+
+    /// The search directory `file` was found in, if any.
+    ///
+    /// With themes installed in more than one directory, this tells whether an icon came from
+    /// the user's or the system's copy.
+    pub fn base_dir_of(&self, file: &IconFile) -> Option<&Path> {
+        self.base_dirs
+            .iter()
+            .find(|dir| file.path.starts_with(dir))
+            .map(PathBuf::as_path)
+    }
+
+    /// Like [`find_icon`](Self::find_icon), but also returning where the icon was found: the
+    /// theme, its base directory and the [`DirectoryIndex`] of the icon's directory.
+    pub fn find_icon_match(
+        &self,
+        icon_name: &str,
+        size: u32,
+        scale: u32,
+        theme: &str,
+    ) -> Option<IconMatch> {
         if icon_name.is_empty() {
             return None;
         }
 
-        let theme = self.theme(theme).or_else(|| self.theme("hicolor"))?;
-        theme
-            .find_icon(icon_name, size, scale)
-            .or_else(|| self.find_standalone_icon(icon_name))
+        let chain = self.lookup_chain(theme);
+        let found = (chain.iter()).find_map(|theme| theme.match_here(icon_name, size, scale));
+        let found = found.or_else(|| {
+            let file = self.find_standalone_scaled(icon_name, scale)?;
+            Some(IconMatch {
+                base_dir: file.path.parent()?.to_owned(),
+                file,
+                theme: None,
+                directory: None,
+                kind: MatchKind::Unsized,
+                generation: 0,
+            })
+        });
+
+        found.map(|found| IconMatch {
+            generation: self.generation,
+            ..found
+        })
+    }
+
+    /// Finds every file of an icon in the chain of [`find_icon`](Self::find_icon), followed by
+    /// the standalone icons, for icon pickers and theme previews that show all renditions of an
+    /// icon.
+    ///
+    /// The files are sorted by their directory's distance from the size and scale, with exact
+    /// matches first, then by the preference of their [file type](IconSearch::with_file_type).
+    /// Files that tie stay in the order of the chain and of the themes' directories. The
+    /// standalone icons come last, in the order of [`standalone_icons`](Self::standalone_icons).
+    pub fn find_all_icons(
+        &self,
+        icon_name: &str,
+        size: u32,
+        scale: u32,
+        theme: &str,
+    ) -> Vec<IconMatch> {
+        if icon_name.is_empty() {
+            return Vec::new();
+        }
+
+        let mut found = Vec::new();
+        for theme in self.iter_chain(theme) {
+            for (base_dir, directory, file) in theme.all_files_here(icon_name) {
+                let sub_dir = &theme.info.index.directories[directory];
+                let is_type = |file_type| file_type == file.file_type;
+                let (kind, distance, rank) = match sub_dir.matches_size(size, scale) {
+                    true => (
+                        MatchKind::Exact,
+                        0,
+                        theme.file_types.iter().position(is_type),
+                    ),
+                    false => (
+                        MatchKind::Nearest,
+                        sub_dir.size_distance(size, scale),
+                        theme.file_types.fallback_order().position(is_type),
+                    ),
+                };
+
+                let icon = IconMatch {
+                    file,
+                    theme: Some(theme.clone()),
+                    base_dir: theme.info.base_dirs[base_dir].clone(),
+                    directory: Some(directory),
+                    kind,
+                    generation: self.generation,
+                };
+                found.push(((distance, kind != MatchKind::Exact, rank), icon));
+            }
+        }
+        // the sort is stable, so ties keep their order
+        found.sort_by_key(|(key, _)| *key);
+
+        let standalone = self
+            .standalone_icons(icon_name)
+            .iter()
+            .map(|file| IconMatch {
+                base_dir: file.path.parent().unwrap_or(&file.path).to_owned(),
+                file: file.clone(),
+                theme: None,
+                directory: None,
+                kind: MatchKind::Unsized,
+                generation: self.generation,
+            });
+        (found.into_iter().map(|(_, icon)| icon))
+            .chain(standalone)
+            .collect()
     }
 
     /// Look up a standalone icon by name.
@@ -80,105 +691,616 @@ impl Icons {
     /// "Standalone" icons are icons that live outside icon themes, residing at the root in the
     /// search directories instead.
     ///
-    /// These icons do not have any size or scalability information attached to them.
+    /// These icons do not have any size or scalability information attached to them. If several
+    /// files share the name, e.g. `htop.png` and `htop.svg`, the first of
+    /// [`standalone_icons`](Self::standalone_icons) is returned.
     pub fn find_standalone_icon(&self, icon_name: &str) -> Option<IconFile> {
-        self.standalone_icons.get(icon_name).cloned()
+        self.standalone_icons(icon_name).first().cloned()
+    }
+
+    // like `find_standalone_icon`, preferring `{icon_name}@{scale}x` with scale suffixes.
+    pub(crate) fn find_standalone_scaled(&self, icon_name: &str, scale: u32) -> Option<IconFile> {
+        let suffixed = (self.scale_suffixes && scale > 1)
+            .then(|| self.find_standalone_icon(&format!("{icon_name}@{scale}x")))
+            .flatten();
+        suffixed.or_else(|| self.find_standalone_icon(icon_name))
+    }
+
+    /// All standalone icons named `icon_name`, in the order they are preferred in: by
+    /// [search directory](Self::base_dirs), then by the order of the
+    /// [file types](IconSearch::with_file_type).
+    pub fn standalone_icons(&self, icon_name: &str) -> &[IconFile] {
+        self.standalone_icons
+            .get(icon_name)
+            .map_or(&[], |files| files.as_slice())
+    }
+
+    /// Iterates over all themes by internal name, in a stable order: by the first
+    /// [search directory](Self::base_dirs) each theme is installed in, then by name.
+    pub fn iter_themes(&self) -> impl Iterator<Item = (&OsStr, &Arc<Theme>)> {
+        let mut themes = (self.themes.iter())
+            .map(|(name, theme)| {
+                let position = (theme.info.base_dirs.iter())
+                    .map(|dir| search_order(&self.base_dirs, dir))
+                    .min();
+                (position, name.as_os_str(), theme)
+            })
+            .collect::<Vec<_>>();
+        themes.sort_unstable_by_key(|(position, name, _)| (*position, *name));
+
+        themes.into_iter().map(|(_, name, theme)| (name, theme))
+    }
+
+    /// Iterates over all standalone icons by name, in a stable order: by the
+    /// [search directory](Self::base_dirs) they are in, then by name. Of several files with the
+    /// same name, only the preferred one is yielded.
+    pub fn iter_standalone_icons(&self) -> impl Iterator<Item = (&str, &IconFile)> {
+        let mut icons = (self.standalone_icons.iter())
+            .filter_map(|(name, files)| Some((name, files.first()?)))
+            .map(|(name, file)| {
+                (
+                    search_order(&self.base_dirs, &file.path),
+                    name.as_str(),
+                    file,
+                )
+            })
+            .collect::<Vec<_>>();
+        icons.sort_unstable_by_key(|(position, name, _)| (*position, *name));
+
+        icons.into_iter().map(|(_, name, file)| (name, file))
     }
 }
 
+/// The position of the search directory `path` is directly inside of, for sorting.
+pub(crate) fn search_order(base_dirs: &[PathBuf], path: &Path) -> usize {
+    (base_dirs.iter())
+        .position(|base_dir| path.parent() == Some(base_dir))
+        .unwrap_or(base_dirs.len())
+}
+
 impl Default for Icons {
     fn default() -> Self {
         Self::new()
     }
 }
 
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct Theme {
     pub info: ThemeInfo,
-    pub inherits_from: Vec<Arc<Theme>>,
+    /// Every theme this one inherits from, directly or through its parents, in the order they are
+    /// searched, with hicolor last.
+    pub inherits_from: Box<[Arc<Theme>]>,
+    /// The files in this theme's directories, if [listed](IconSearch::with_directory_listing).
+    pub listing: Option<Arc<DirectoryListing>>,
+    /// How often icons were found in each of this theme's directories.
+    pub statistics: HitStatistics,
+    /// The directories of this theme that couldn't be read, see [`is_partial`](Self::is_partial).
+    pub unreadable: UnreadableDirs,
+    pub(crate) selections: SubDirSelections,
+    /// The file types icons are looked up with.
+    pub file_types: FileTypes,
+    /// How closely this theme follows the specification.
+    pub compliance: Compliance,
+    /// See [`IconSearch::with_audit`].
+    pub audit: Option<Audit>,
+    pub(crate) resolve_beneath: bool,
+    /// See [`IconSearch::with_scale_suffixes`].
+    pub scale_suffixes: bool,
 }
 
 impl Theme {
+    /// Whether some of this theme's directories couldn't be read, e.g. because of their
+    /// permissions. Icons in those directories are not found.
+    ///
+    /// Without [directory listing](IconSearch::with_directory_listing), only the directories the
+    /// theme is installed in are checked up front; the others are found to be unreadable by the
+    /// lookups that try them.
+    pub fn is_partial(&self) -> bool {
+        !self.unreadable.is_empty()
+    }
+
+    /// The directories of this theme that couldn't be read so far, see
+    /// [`is_partial`](Self::is_partial).
+    pub fn unreadable_dirs(&self) -> Vec<PathBuf> {
+        self.unreadable.to_vec()
+    }
+
     pub fn find_icon_unscaled(&self, icon_name: &str, size: u32) -> Option<IconFile> {
         self.find_icon(icon_name, size, 1)
     }
 
+    /// Iterates over all icon files in this theme (but not its parents), along with the
+    /// directory they were found in.
+    ///
+    /// Files are returned in the order of the theme's directories, and by name within each
+    /// directory. This reads the theme's directories from disk, one at a time, as the iterator
+    /// advances.
+    pub fn iter_files(&self) -> impl Iterator<Item = (&DirectoryIndex, IconFile)> {
+        self.info.index.directories.iter().flat_map(move |sub_dir| {
+            self.info
+                .base_dirs
+                .iter()
+                .flat_map(move |base_dir| {
+                    let dir = base_dir.join(&sub_dir.directory_name);
+                    let mut files = (allows(self.audit.as_ref(), &dir, AccessKind::ListDirectory))
+                        .then(|| dir.read_dir())
+                        .into_iter()
+                        .flatten()
+                        .flatten()
+                        .flatten()
+                        .filter_map(|entry| self.file_types.icon_file(&entry.path()))
+                        .collect::<Vec<_>>();
+                    files.sort_unstable_by(|a, b| a.path.cmp(&b.path));
+                    files
+                })
+                .map(move |file| (sub_dir, file))
+        })
+    }
+
+    /// Like [`iter_files`](Self::iter_files), resuming after the file at `path`, for paging
+    /// through the files of large themes.
+    ///
+    /// The directories before the one of `path` are read as well.
+    pub fn iter_files_after<'a>(
+        &'a self,
+        path: &'a Path,
+    ) -> impl Iterator<Item = (&'a DirectoryIndex, IconFile)> {
+        self.iter_files()
+            .skip_while(move |(_, file)| file.path != path)
+            .skip(1)
+    }
+
+    /// Lists the names of all icons in this theme (but not its parents), in alphabetical order.
+    pub fn list_icons(&self) -> BTreeSet<String> {
+        self.iter_files()
+            .filter_map(|(_, file)| file.icon_name().map(Into::into))
+            .collect()
+    }
+
+    /// Lists up to `limit` names of icons in this theme (but not its parents), in alphabetical
+    /// order, starting after the name `after`.
+    ///
+    /// Pass the last name of a page as `after` to get the next one. Unlike
+    /// [`list_icons`](Self::list_icons), this only holds a page of names in memory.
+    pub fn list_icons_page(&self, after: Option<&str>, limit: usize) -> Vec<String> {
+        self.page_icons(after, limit, |_| true)
+    }
+
+    /// Like [`list_icons_page`](Self::list_icons_page), but only listing icons whose names
+    /// contain `query`, ignoring case.
+    pub fn search_icons(&self, query: &str, after: Option<&str>, limit: usize) -> Vec<String> {
+        let query = query.to_lowercase();
+        self.page_icons(after, limit, |name| name.to_lowercase().contains(&query))
+    }
+
+    fn page_icons(
+        &self,
+        after: Option<&str>,
+        limit: usize,
+        matches: impl Fn(&str) -> bool,
+    ) -> Vec<String> {
+        let mut page = BTreeSet::<String>::new();
+
+        for (_, file) in self.iter_files() {
+            let Some(name) = file.icon_name() else {
+                continue;
+            };
+            if after.is_some_and(|after| name <= after)
+                || page.len() == limit && page.last().is_some_and(|last| name >= last.as_str())
+                || !matches(name)
+            {
+                continue;
+            }
+
+            page.insert(name.to_owned());
+            if page.len() > limit {
+                page.pop_last();
+            }
+        }
+
+        page.into_iter().collect()
+    }
+
     pub fn find_icon(&self, icon_name: &str, size: u32, scale: u32) -> Option<IconFile> {
         self.find_icon_here(icon_name, size, scale).or_else(|| {
-            // or find it in one of our parents
+            // or find it in one of our ancestors, which `inherits_from` lists in full
             self.inherits_from
                 .iter()
                 .find_map(|theme| theme.find_icon_here(icon_name, size, scale))
         })
     }
 
-    // find an icon in this theme only, not checking parents.
-    fn find_icon_here(&self, icon_name: &str, size: u32, scale: u32) -> Option<IconFile> {
-        const EXTENSIONS: [&str; 3] = ["png", "xmp", "svg"];
-        let file_names = EXTENSIONS.map(|ext| format!("{icon_name}.{ext}"));
+    /// Like [`find_icon`](Self::find_icon), but only finding icons that match the size and scale
+    /// exactly.
+    pub fn find_icon_exact(&self, icon_name: &str, size: u32, scale: u32) -> Option<IconFile> {
+        std::iter::once(self)
+            .chain(self.inherits_from.iter().map(Arc::as_ref))
+            .find_map(|theme| theme.locate_exact(icon_name, size, scale))
+            .map(|(_, _, file)| file)
+    }
 
-        let base_dirs = &self.info.base_dirs;
+    /// Like [`find_icon`](Self::find_icon), but also returning where the icon was found.
+    pub fn find_icon_match(
+        self: &Arc<Self>,
+        icon_name: &str,
+        size: u32,
+        scale: u32,
+    ) -> Option<IconMatch> {
+        std::iter::once(self)
+            .chain(&self.inherits_from)
+            .find_map(|theme| theme.match_here(icon_name, size, scale))
+    }
 
+    // like `find_icon_match`, not checking parents.
+    fn match_here(self: &Arc<Self>, icon_name: &str, size: u32, scale: u32) -> Option<IconMatch> {
+        let (base_dir, directory, file) = self.locate_icon_here(icon_name, size, scale)?;
+        // files with a scale suffix are only found in directories matching the size exactly
+        let exact = self.info.index.directories[directory].matches_size(size, scale)
+            || file.icon_name() != Some(icon_name);
+        Some(IconMatch {
+            file,
+            theme: Some(self.clone()),
+            base_dir: self.info.base_dirs[base_dir].clone(),
+            directory: Some(directory),
+            kind: match exact {
+                true => MatchKind::Exact,
+                false => MatchKind::Nearest,
+            },
+            generation: 0,
+        })
+    }
+
+    // every file of an icon in this theme only, not checking parents, in the order of base
+    // directories, directories and file types.
+    fn all_files_here(&self, icon_name: &str) -> Vec<(usize, usize, IconFile)> {
         let sub_dirs = &self.info.index.directories;
-        // first, try to find an exact icon size match:
-        let exact_sub_dirs = sub_dirs
-            .iter()
-            .filter(|sub_dir| sub_dir.matches_size(size, scale));
-
-        for base_dir in base_dirs {
-            for sub_dir in exact_sub_dirs.clone() {
-                for file_name in &file_names {
-                    let path = base_dir
-                        .join(sub_dir.directory_name.as_str())
-                        .join(file_name);
-
-                    if path.exists() {
-                        if let Some(file) = IconFile::from_path(&path) {
-                            // exact match!
-                            return Some(file);
+        let icon_file = |base_idx: usize, sub_idx: usize, file_type: FileType| {
+            let path = self.info.base_dirs[base_idx]
+                .join(&sub_dirs[sub_idx].directory_name)
+                .join(format!("{icon_name}.{}", file_type.ext()));
+            (base_idx, sub_idx, IconFile { path, file_type })
+        };
+
+        let mut files = Vec::new();
+        match &self.listing {
+            Some(listing) => {
+                for location in listing.locations(icon_name) {
+                    for file_type in self.file_types.iter() {
+                        if (self.file_types.position(file_type))
+                            .is_some_and(|position| location.extensions.contains(position))
+                        {
+                            files.push(icon_file(location.base_dir, location.directory, file_type));
                         }
                     }
                 }
             }
+            None => {
+                for (base_idx, base_dir) in self.info.base_dirs.iter().enumerate() {
+                    let root = self.resolve_beneath.then(|| base_dir.parent()).flatten();
+                    for sub_idx in 0..sub_dirs.len() {
+                        for file_type in self.file_types.iter() {
+                            let file = icon_file(base_idx, sub_idx, file_type);
+                            let path = &file.2.path;
+                            if allows(self.audit.as_ref(), path, AccessKind::Probe)
+                                && SkipReason::probe_in(root, path).is_ok()
+                            {
+                                files.push(file);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        files
+    }
+
+    // find an icon in this theme only, not checking parents.
+    pub(crate) fn find_icon_here(
+        &self,
+        icon_name: &str,
+        size: u32,
+        scale: u32,
+    ) -> Option<IconFile> {
+        self.locate_icon_here(icon_name, size, scale)
+            .map(|(_, _, file)| file)
+    }
+
+    // like `find_icon_here`, also returning the indices of the base directory and subdirectory.
+    pub(crate) fn locate_icon_here(
+        &self,
+        icon_name: &str,
+        size: u32,
+        scale: u32,
+    ) -> Option<(usize, usize, IconFile)> {
+        self.locate_icon_until(icon_name, size, scale, None)
+            .unwrap_or_default()
+    }
+
+    // like `locate_icon_here`, giving up on probing the file system once `deadline` has passed.
+    fn locate_icon_until(
+        &self,
+        icon_name: &str,
+        size: u32,
+        scale: u32,
+        deadline: Option<Instant>,
+    ) -> Result<Option<(usize, usize, IconFile)>, Expired> {
+        let selection = self.select_sub_dirs(size, scale);
+        self.locate_selected(icon_name, size, scale, selection.dirs(), deadline)
+    }
+
+    // like `locate_icon_here`, only finding icons that match the size exactly.
+    fn locate_exact(
+        &self,
+        icon_name: &str,
+        size: u32,
+        scale: u32,
+    ) -> Option<(usize, usize, IconFile)> {
+        let selection = self.select_sub_dirs(size, scale);
+        self.locate_selected(icon_name, size, scale, selection.dirs().exact_only(), None)
+            .unwrap_or_default()
+    }
+
+    fn locate_selected(
+        &self,
+        icon_name: &str,
+        size: u32,
+        scale: u32,
+        selection: SubDirs,
+        deadline: Option<Instant>,
+    ) -> Result<Option<(usize, usize, IconFile)>, Expired> {
+        let found = match self.scale_suffixes && scale > 1 {
+            true => self.locate_suffixed(icon_name, size, scale, selection, deadline)?,
+            false => self.locate_unrecorded(icon_name, size, scale, selection, deadline)?,
+        };
+
+        if let Some((_, directory, _)) = &found {
+            self.statistics.record(*directory);
+        }
+
+        Ok(found)
+    }
+
+    // like `locate_selected`, trying `{icon_name}@{scale}x` in the directories for scale 1 after
+    // the exact matches and before the closest ones.
+    fn locate_suffixed(
+        &self,
+        icon_name: &str,
+        size: u32,
+        scale: u32,
+        selection: SubDirs,
+        deadline: Option<Instant>,
+    ) -> Result<Option<(usize, usize, IconFile)>, Expired> {
+        let exact = selection.exact_only();
+        if let Some(found) = self.locate_unrecorded(icon_name, size, scale, exact, deadline)? {
+            return Ok(Some(found));
+        }
+
+        let unscaled = self.select_sub_dirs(size, 1);
+        let unscaled = unscaled.dirs().exact_only();
+        let suffixed = format!("{icon_name}@{scale}x");
+        if let Some(found) = self.locate_unrecorded(&suffixed, size, 1, unscaled, deadline)? {
+            return Ok(Some(found));
+        }
+
+        let closest = selection.closest_only();
+        self.locate_unrecorded(icon_name, size, scale, closest, deadline)
+    }
+
+    // like `locate_selected`, without recording where the icon was found.
+    fn locate_unrecorded(
+        &self,
+        icon_name: &str,
+        size: u32,
+        scale: u32,
+        selection: SubDirs,
+        deadline: Option<Instant>,
+    ) -> Result<Option<(usize, usize, IconFile)>, Expired> {
+        match &self.listing {
+            Some(listing) => {
+                Ok(self.locate_listed_icon(listing, icon_name, size, scale, selection))
+            }
+            None => self.probe_icon(icon_name, selection, deadline),
         }
+    }
+
+    /// Like [`find_icon`](Self::find_icon), but giving up once `deadline` has passed, see
+    /// [`Icons::find_icon_within`].
+    pub fn find_icon_until(
+        &self,
+        icon_name: &str,
+        size: u32,
+        scale: u32,
+        deadline: Instant,
+    ) -> Result<Option<IconFile>, TimedOut> {
+        for theme in std::iter::once(self).chain(self.inherits_from.iter().map(Arc::as_ref)) {
+            match theme.locate_icon_until(icon_name, size, scale, Some(deadline)) {
+                Ok(Some((_, _, file))) => return Ok(Some(file)),
+                Ok(None) => {}
+                Err(Expired) => return Err(TimedOut { best: None }),
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// The candidate directories for an icon of the given size, in the order they should be tried
+    /// in.
+    fn select_sub_dirs(&self, size: u32, scale: u32) -> Arc<SubDirSelection> {
+        let selection = (self.selections).get(&self.info.index.directories, size, scale);
+        if !self.statistics.adaptive {
+            return selection;
+        }
+
+        // the order changes with every hit, so only the selection itself is cached
+        let mut selection = Arc::unwrap_or_clone(selection);
+        self.statistics.sort(&mut selection.exact);
+        Arc::new(selection)
+    }
+
+    // find an icon by probing the file system for every candidate.
+    fn probe_icon(
+        &self,
+        icon_name: &str,
+        selection: SubDirs,
+        deadline: Option<Instant>,
+    ) -> Result<Option<(usize, usize, IconFile)>, Expired> {
+        let mut candidate = CandidatePath::new(icon_name, &self.unreadable, deadline);
+        candidate.audit = self.audit.as_ref();
+        candidate.resolve_beneath = self.resolve_beneath;
+
+        let base_dirs = &self.info.base_dirs;
+        let sub_dirs = &self.info.index.directories;
 
-        drop(exact_sub_dirs);
+        // first, try to find an exact icon size match:
+        for (base_idx, base_dir) in base_dirs.iter().enumerate() {
+            for &sub_idx in selection.exact {
+                let sub_dir = &sub_dirs[sub_idx];
+                let file_types = self.file_types.iter();
+                if let Some(file) = candidate.find(base_dir, &sub_dir.directory_name, file_types) {
+                    // exact match!
+                    return Ok(Some((base_idx, sub_idx, file)));
+                }
+            }
+        }
 
         // no exact match: try to find a match as close as possible instead.
-        let mut min_dist = u32::MAX;
-        let mut best_icon = None;
-
-        for base_dir in base_dirs {
-            for sub_dir in sub_dirs {
-                let distance = sub_dir.size_distance(size, scale);
-
-                if distance < min_dist {
-                    for file_name in &file_names {
-                        let path = base_dir
-                            .join(sub_dir.directory_name.as_str())
-                            .join(file_name);
-                        if path.exists() {
-                            if let Some(file) = IconFile::from_path(&path) {
-                                min_dist = distance;
-                                best_icon = Some(file);
-                            }
-                        }
+        // the first file found at the smallest distance wins, so try them nearest first.
+        for group in selection.closest.chunk_by(|(a, _), (b, _)| a == b) {
+            for (base_idx, base_dir) in base_dirs.iter().enumerate() {
+                for &(_, sub_idx) in group {
+                    let sub_dir = &sub_dirs[sub_idx];
+                    let file_types = self.file_types.fallback_order();
+                    if let Some(file) =
+                        candidate.find(base_dir, &sub_dir.directory_name, file_types)
+                    {
+                        return Ok(Some((base_idx, sub_idx, file)));
                     }
                 }
             }
         }
 
-        best_icon
+        match candidate.expired {
+            true => Err(Expired),
+            false => Ok(None),
+        }
+    }
+
+    // like `locate_icon_here`, but consulting the directory listing instead of the file system.
+    fn locate_listed_icon(
+        &self,
+        listing: &DirectoryListing,
+        icon_name: &str,
+        size: u32,
+        scale: u32,
+        selection: SubDirs,
+    ) -> Option<(usize, usize, IconFile)> {
+        let sub_dirs = &self.info.index.directories;
+        let icon_file = |location: &IconLocation, file_type: FileType| {
+            let path = self.info.base_dirs[location.base_dir]
+                .join(&sub_dirs[location.directory].directory_name)
+                .join(format!("{icon_name}.{}", file_type.ext()));
+            let file = IconFile { path, file_type };
+            Some((location.base_dir, location.directory, file))
+        };
+
+        // locations are in the same order as the file system is probed in
+        let locations = listing.locations(icon_name);
+        if locations.is_empty() {
+            return None;
+        }
+
+        for base_idx in 0..self.info.base_dirs.len() {
+            for &sub_idx in selection.exact {
+                let Some(location) = locations.iter().find(|location| {
+                    (location.base_dir, location.directory) == (base_idx, sub_idx)
+                }) else {
+                    continue;
+                };
+
+                let file_type = self.listed_file_type(location, self.file_types.iter())?;
+                return icon_file(location, file_type);
+            }
+        }
+
+        let location = (locations.iter())
+            .filter(|location| {
+                (selection.closest.iter()).any(|&(_, idx)| idx == location.directory)
+            })
+            .min_by_key(|location| sub_dirs[location.directory].size_distance(size, scale))?;
+        let file_type = self.listed_file_type(location, self.file_types.fallback_order())?;
+        icon_file(location, file_type)
+    }
+
+    // like `locate_listed_icon`, without allocating: the path isn't built, and the directories
+    // matching the size exactly are tried in index order even with adaptive ordering.
+    pub(crate) fn locate_listed_ref(
+        &self,
+        listing: &DirectoryListing,
+        icon_name: &str,
+        size: u32,
+        scale: u32,
+    ) -> Option<(usize, usize, FileType)> {
+        let sub_dirs = &self.info.index.directories;
+        // locations are in the same order as the file system is probed in
+        let locations = listing.locations(icon_name);
+
+        let exact = (locations.iter())
+            .find(|location| sub_dirs[location.directory].matches_size(size, scale));
+        let (location, file_type) = match exact {
+            Some(location) => (
+                location,
+                self.listed_file_type(location, self.file_types.iter())?,
+            ),
+            None => {
+                let location = (locations.iter()).min_by_key(|location| {
+                    sub_dirs[location.directory].size_distance(size, scale)
+                })?;
+                let file_type = self.listed_file_type(location, self.file_types.fallback_order());
+                (location, file_type?)
+            }
+        };
+
+        self.statistics.record(location.directory);
+        Some((location.base_dir, location.directory, file_type))
+    }
+
+    // the first of `file_types` that `location` has a file of.
+    fn listed_file_type(
+        &self,
+        location: &IconLocation,
+        mut file_types: impl Iterator<Item = FileType>,
+    ) -> Option<FileType> {
+        file_types.find(|&file_type| {
+            (self.file_types.position(file_type))
+                .is_some_and(|position| location.extensions.contains(position))
+        })
     }
 }
 
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct ThemeInfo {
     pub internal_name: String,
     pub base_dirs: Vec<PathBuf>,
     pub index_location: PathBuf,
     pub index: ThemeIndex,
+    pub index_origin: IndexOrigin,
     // additional groups?
 }
 
+/// Where the [index](ThemeInfo::index) of a theme came from.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum IndexOrigin {
+    /// The theme's `index.theme`, at its [`index_location`](ThemeInfo::index_location).
+    File,
+    /// hicolor's [built-in index](ThemeIndex::hicolor), see
+    /// [`IconSearch::with_hicolor_bootstrap`].
+    Builtin,
+    /// Synthesized from the theme's directories, see [`IconSearch::with_inferred_indexes`].
+    Inferred,
+    /// Given to [`Icons::add_memory_theme`].
+    Memory,
+}
+
 #[derive(Debug, thiserror::Error)]
 pub enum ThemeParseError {
     #[error("missing Icon Theme index or section")]
@@ -195,45 +1317,156 @@ pub enum ThemeParseError {
     InvalidDirectoryType,
     #[error("invalid format for a freedesktop entry file")]
     ParseError(#[from] freedesktop_entry_parser::ParseError),
+    #[error("the index is larger than {MAX_INDEX_LEN} bytes")]
+    TooLarge,
+    #[error("attribute `{0}` is out of range")]
+    OutOfRange(&'static str),
+    #[error("directory {0:?} is not a relative path within the theme")]
+    InvalidDirectoryName(String),
+}
+
+/// The error of a lookup that exceeded its time budget, see [`Icons::find_icon_within`].
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+#[error("looking up the icon took longer than its budget")]
+pub struct TimedOut {
+    /// The best icon found before the budget was exceeded, if any.
+    pub best: Option<IconFile>,
 }
 
+// a deadline that passed while probing the file system.
+struct Expired;
+
+/// The largest `index.theme` that is parsed, in bytes.
+///
+/// Theme indexes are downloaded from third parties and treated as untrusted input. Real indexes
+/// are a few dozen kilobytes at most.
+pub const MAX_INDEX_LEN: usize = 1 << 20;
+
+/// The largest size (and minimum size, maximum size and threshold) a directory may have.
+pub const MAX_DIRECTORY_SIZE: u32 = 1 << 16;
+
+/// The largest scale a directory may have.
+pub const MAX_DIRECTORY_SCALE: u32 = 64;
+
 impl ThemeInfo {
     pub fn new_from_folders(internal_name: String, folders: Vec<PathBuf>) -> std::io::Result<Self> {
+        Self::new_from_folders_with(internal_name, folders, Compliance::Lenient)
+    }
+
+    /// Like [`new_from_folders`](Self::new_from_folders), parsing the index with the given
+    /// compliance.
+    pub fn new_from_folders_with(
+        internal_name: String,
+        folders: Vec<PathBuf>,
+        compliance: Compliance,
+    ) -> std::io::Result<Self> {
+        Self::load(internal_name, folders, compliance, None, false, false)
+    }
+
+    // like `new_from_folders_with`, only reading the files `audit` allows. If there is no index,
+    // hicolor gets the built-in one if `bootstrap_hicolor`, and other themes one inferred from
+    // their directories if `infer_index`, unless `compliance` is strict.
+    pub(crate) fn load(
+        internal_name: String,
+        folders: Vec<PathBuf>,
+        compliance: Compliance,
+        audit: Option<&Audit>,
+        infer_index: bool,
+        bootstrap_hicolor: bool,
+    ) -> std::io::Result<Self> {
+        // a folder that can't be read may hide the index, which is worth reporting
+        let mut unreadable = None;
         let index_location = folders
             .iter()
             .map(|f| f.join("index.theme"))
-            .find(|index_path| index_path.exists())
-            .ok_or_else(|| std::io::Error::other(ThemeParseError::NotAnIconTheme))?;
+            .filter(|index_path| allows(audit, index_path, AccessKind::ReadFile))
+            .find(|index_path| match index_path.try_exists() {
+                Ok(exists) => exists,
+                Err(e) => {
+                    unreadable.get_or_insert(e);
+                    false
+                }
+            });
+        let Some(index_location) = index_location else {
+            // without an index, the theme may still be described by its directories
+            let inferred = match internal_name.as_str() {
+                _ if unreadable.is_some() || folders.is_empty() => None,
+                _ if compliance == Compliance::Strict => None,
+                "hicolor" if bootstrap_hicolor => {
+                    Some((ThemeIndex::hicolor(), IndexOrigin::Builtin))
+                }
+                _ if infer_index => ThemeIndex::infer_with(&internal_name, &folders, audit)
+                    .map(|index| (index, IndexOrigin::Inferred)),
+                _ => None,
+            };
+            let Some((index, index_origin)) = inferred else {
+                return Err(unreadable
+                    .unwrap_or_else(|| std::io::Error::other(ThemeParseError::NotAnIconTheme)));
+            };
+            // where the index would be, in the first folder
+            return Ok(Self {
+                index_location: folders[0].join("index.theme"),
+                internal_name,
+                base_dirs: folders,
+                index,
+                index_origin,
+            });
+        };
 
-        let index = ThemeIndex::parse_from_file(index_location.as_path())?;
+        let index = ThemeIndex::parse_from_file_with(index_location.as_path(), compliance)?;
 
         Ok(Self {
             internal_name,
             base_dirs: folders,
             index_location,
             index,
+            index_origin: IndexOrigin::File,
         })
     }
 }
 
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct ThemeIndex {
     pub name: String,
     pub comment: String,
-    pub inherits: Vec<String>,
-    pub directories: Vec<DirectoryIndex>,
+    pub inherits: Box<[String]>,
+    pub directories: Box<[DirectoryIndex]>,
     pub hidden: bool,
     pub example: Option<String>,
+    /// All groups other than the directories', including `Icon Theme`, by name, with their keys
+    /// and values. Vendors keep metadata such as the theme's version in groups of their own,
+    /// e.g. `[X-Vendor]`. Localized keys keep their locale, e.g. `Name[de]`, and keys or values
+    /// that aren't UTF-8 are left out.
+    pub groups: BTreeMap<String, BTreeMap<String, String>>,
 }
 
 impl ThemeIndex {
     pub fn parse_from_file(path: &Path) -> std::io::Result<Self> {
-        let bytes = std::fs::read(path)?;
-        let index = ThemeIndex::parse(&bytes).map_err(std::io::Error::other)?;
+        Self::parse_from_file_with(path, Compliance::Lenient)
+    }
+
+    pub fn parse_from_file_with(path: &Path, compliance: Compliance) -> std::io::Result<Self> {
+        // read one byte too many, so that `parse` notices when the file is too large
+        let mut bytes = vec![];
+        std::fs::File::open(path)?
+            .take(MAX_INDEX_LEN as u64 + 1)
+            .read_to_end(&mut bytes)?;
+        let index = ThemeIndex::parse_with(&bytes, compliance).map_err(std::io::Error::other)?;
 
         Ok(index)
     }
 
     pub fn parse(bytes: &[u8]) -> Result<Self, ThemeParseError> {
+        Self::parse_with(bytes, Compliance::Lenient)
+    }
+
+    /// Parses an index, rejecting it if it is missing keys the specification requires when
+    /// `compliance` is [strict](Compliance::Strict).
+    pub fn parse_with(bytes: &[u8], compliance: Compliance) -> Result<Self, ThemeParseError> {
+        if bytes.len() > MAX_INDEX_LEN {
+            return Err(ThemeParseError::TooLarge);
+        }
+
         let mut entry: EntryIter = freedesktop_entry_parser::low_level::parse_entry(bytes);
 
         let icon_theme_section: SectionBytes =
@@ -242,8 +1475,11 @@ impl ThemeIndex {
 
         // SPEC: `Comment` is required, but most icon theme developers can't be arsed to
         // include it! To make `icon` practical, we choose a default of an empty string instead.
-        // `let comment = find_attr_req(&icon_theme_section, "Comment")?;`
-        let comment = find_attr(&icon_theme_section, "Comment")?.unwrap_or("");
+        // (listed as `spec::Deviation::MissingComment`)
+        let comment = match compliance {
+            Compliance::Lenient => find_attr(&icon_theme_section, "Comment")?.unwrap_or(""),
+            Compliance::Strict => find_attr_req(&icon_theme_section, "Comment")?,
+        };
         // If no theme is specified, implementations are required to add the "hicolor" theme to the inheritance tree.
         let inherits = find_attr(&icon_theme_section, "Inherits")?
             .iter()
@@ -252,15 +1488,21 @@ impl ThemeIndex {
             .collect::<Vec<_>>();
         let directories = find_attr_req(&icon_theme_section, "Directories")?
             .split(',')
-            .collect::<Vec<_>>();
+            .collect::<HashSet<_>>();
         let scaled_directories = find_attr(&icon_theme_section, "ScaledDirectories")?
-            .map(|s| s.split(',').collect::<Vec<_>>());
+            .map(|s| s.split(',').collect::<HashSet<_>>());
         let hidden = find_attr(&icon_theme_section, "Hidden")?
             .map(|s| s.parse())
             .transpose()?
             .unwrap_or(false);
         let example = find_attr(&icon_theme_section, "Example")?;
 
+        let mut groups = BTreeMap::new();
+        groups.insert(
+            ICON_THEME_GROUP.into(),
+            section_entries(&icon_theme_section),
+        );
+
         // all other sections should describe a directory in the directory list
         let directories = entry
             .filter_map(Result::ok)
@@ -273,16 +1515,16 @@ impl ThemeIndex {
                     .unwrap_or(false);
 
                 if !directories.contains(&title) && !is_scaled_dir {
-                    // this section isn't a listed directory! ignore!
+                    // this section isn't a listed directory, but may hold vendor metadata
+                    groups.insert(title.to_owned(), section_entries(&section));
                     return None;
                 }
 
                 let mut index = DirectoryIndex::parse(section);
 
-                if is_scaled_dir {
-                    if let Ok(index) = &mut index {
-                        index.is_scaled_dir = true;
-                    }
+                if is_scaled_dir && let Ok(index) = &mut index {
+                    index.is_scaled_dir = true;
+                    index.in_scaled_directories = true;
                 }
 
                 Some(index)
@@ -292,17 +1534,27 @@ impl ThemeIndex {
         Ok(Self {
             name: name.into(),
             comment: comment.into(),
-            inherits,
-            directories,
+            inherits: inherits.into(),
+            directories: directories.into(),
             hidden,
             example: example.map(Into::into),
+            groups,
         })
     }
+
+    /// The keys and values of a group other than the directories', see
+    /// [`groups`](Self::groups).
+    pub fn group(&self, name: &str) -> Option<&BTreeMap<String, String>> {
+        self.groups.get(name)
+    }
 }
 
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct DirectoryIndex {
     pub directory_name: String,
     pub is_scaled_dir: bool,
+    /// Whether the directory is listed in `ScaledDirectories` rather than `Directories`.
+    pub in_scaled_directories: bool,
     pub size: u32,
     pub scale: u32,
     pub context: Option<String>,
@@ -316,6 +1568,14 @@ pub struct DirectoryIndex {
 impl DirectoryIndex {
     fn parse(section: SectionBytes) -> Result<Self, ThemeParseError> {
         let dir_name = str::from_utf8(section.title)?;
+        // directories are joined onto the theme's base directories, so they must not escape them
+        let is_contained = Path::new(dir_name)
+            .components()
+            .all(|component| matches!(component, Component::Normal(_) | Component::CurDir));
+        if !is_contained {
+            return Err(ThemeParseError::InvalidDirectoryName(dir_name.into()));
+        }
+
         let size: u32 = find_attr_req(&section, "Size")?.parse()?;
         let scale: u32 = find_attr(&section, "Scale")?
             .map(|s| s.parse())
@@ -343,9 +1603,24 @@ impl DirectoryIndex {
             .transpose()?
             .unwrap_or(2);
 
+        for (name, value) in [
+            ("Size", size),
+            ("MaxSize", max_size),
+            ("MinSize", min_size),
+            ("Threshold", threshold),
+        ] {
+            if value > MAX_DIRECTORY_SIZE {
+                return Err(ThemeParseError::OutOfRange(name));
+            }
+        }
+        if !(1..=MAX_DIRECTORY_SCALE).contains(&scale) {
+            return Err(ThemeParseError::OutOfRange("Scale"));
+        }
+
         Ok(Self {
             directory_name: dir_name.into(),
             is_scaled_dir: scale != 1,
+            in_scaled_directories: false,
             size,
             scale,
             context: context.map(Into::into),
@@ -357,14 +1632,14 @@ impl DirectoryIndex {
     }
 
     fn size_distance(&self, icon_size: u32, icon_scale: u32) -> u32 {
-        let size = icon_size * icon_scale;
+        let size = icon_size.saturating_mul(icon_scale);
 
         match self.directory_type {
             DirectoryType::Fixed | DirectoryType::Scalable => {
                 (self.size * self.scale).abs_diff(size)
             }
             DirectoryType::Threshold => {
-                let lower = (self.size - self.threshold) * self.scale;
+                let lower = self.size.saturating_sub(self.threshold) * self.scale;
                 let higher = (self.size + self.threshold) * self.scale;
 
                 if size < lower {
@@ -404,13 +1679,24 @@ impl DirectoryIndex {
     }
 }
 
-#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
 pub enum DirectoryType {
     Fixed,
     Scalable,
     Threshold,
 }
 
+impl DirectoryType {
+    /// The name of this type as used in `index.theme` files.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DirectoryType::Fixed => "Fixed",
+            DirectoryType::Scalable => "Scalable",
+            DirectoryType::Threshold => "Threshold",
+        }
+    }
+}
+
 impl TryFrom<&str> for DirectoryType {
     type Error = ();
 
@@ -426,6 +1712,205 @@ impl TryFrom<&str> for DirectoryType {
     }
 }
 
+/// The subdirectories of a theme to look for an icon of some size in.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct SubDirSelection {
+    /// The indices of the directories matching the size exactly, in index order.
+    exact: Vec<usize>,
+    /// The distances and indices of all other directories, nearest first. Directories at the same
+    /// distance stay in index order.
+    closest: Vec<(u32, usize)>,
+}
+
+impl SubDirSelection {
+    fn new(sub_dirs: &[DirectoryIndex], size: u32, scale: u32) -> Self {
+        let mut exact = vec![];
+        let mut closest = vec![];
+
+        for (idx, sub_dir) in sub_dirs.iter().enumerate() {
+            if sub_dir.matches_size(size, scale) {
+                exact.push(idx);
+            } else {
+                closest.push((sub_dir.size_distance(size, scale), idx));
+            }
+        }
+
+        // the sort is stable, so ties keep their order
+        closest.sort_by_key(|&(distance, _)| distance);
+
+        Self { exact, closest }
+    }
+
+    fn dirs(&self) -> SubDirs<'_> {
+        SubDirs {
+            exact: &self.exact,
+            closest: &self.closest,
+        }
+    }
+}
+
+/// Some of the directories of a [`SubDirSelection`], borrowed from it.
+#[derive(Debug, Clone, Copy)]
+struct SubDirs<'a> {
+    exact: &'a [usize],
+    closest: &'a [(u32, usize)],
+}
+
+impl SubDirs<'_> {
+    fn exact_only(self) -> Self {
+        Self {
+            closest: &[],
+            ..self
+        }
+    }
+
+    fn closest_only(self) -> Self {
+        Self { exact: &[], ..self }
+    }
+}
+
+/// The [selections](SubDirSelection) of a theme's directories for every size and scale looked up
+/// so far, so that lookups don't select them again.
+#[derive(Debug, Default)]
+pub(crate) struct SubDirSelections(Mutex<FastHashMap<(u32, u32), Arc<SubDirSelection>>>);
+
+impl SubDirSelections {
+    // sizes are chosen by callers, so not every one of them is kept
+    const MAX_LEN: usize = 64;
+
+    fn get(&self, sub_dirs: &[DirectoryIndex], size: u32, scale: u32) -> Arc<SubDirSelection> {
+        let mut selections = self.0.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(selection) = selections.get(&(size, scale)) {
+            return selection.clone();
+        }
+
+        let selection = Arc::new(SubDirSelection::new(sub_dirs, size, scale));
+        if selections.len() < Self::MAX_LEN {
+            selections.insert((size, scale), selection.clone());
+        }
+        selection
+    }
+}
+
+impl Clone for SubDirSelections {
+    fn clone(&self) -> Self {
+        Self(Mutex::new(
+            self.0.lock().unwrap_or_else(|e| e.into_inner()).clone(),
+        ))
+    }
+}
+
+// like statistics, the cache doesn't affect what a theme is
+impl PartialEq for SubDirSelections {
+    fn eq(&self, _other: &Self) -> bool {
+        true
+    }
+}
+
+impl Eq for SubDirSelections {}
+
+impl std::hash::Hash for SubDirSelections {
+    fn hash<H: std::hash::Hasher>(&self, _state: &mut H) {}
+}
+
+/// A reusable buffer for the paths of icon file candidates, so that looking up an icon doesn't
+/// allocate for every combination of base directory, subdirectory and extension.
+struct CandidatePath<'a> {
+    path: PathBuf,
+    /// `{icon_name}.`, followed by the extension of the current candidate.
+    file_name: String,
+    stem_len: usize,
+    /// Where to record directories that turn out to be unreadable.
+    unreadable: &'a UnreadableDirs,
+    /// When to stop probing, after which no more candidates are found.
+    deadline: Option<Instant>,
+    expired: bool,
+    /// Which candidates may be probed.
+    audit: Option<&'a Audit>,
+    /// Whether to open candidates without leaving the search directory.
+    resolve_beneath: bool,
+}
+
+impl<'a> CandidatePath<'a> {
+    fn new(icon_name: &str, unreadable: &'a UnreadableDirs, deadline: Option<Instant>) -> Self {
+        let mut file_name = String::with_capacity(icon_name.len() + 4);
+        file_name.push_str(icon_name);
+        file_name.push('.');
+
+        Self {
+            path: PathBuf::new(),
+            stem_len: file_name.len(),
+            file_name,
+            unreadable,
+            deadline,
+            expired: false,
+            audit: None,
+            resolve_beneath: false,
+        }
+    }
+
+    /// Finds the first usable file `{base_dir}/{sub_dir}/{icon_name}.{ext}` in the order of
+    /// `file_types`.
+    fn find(
+        &mut self,
+        base_dir: &Path,
+        sub_dir: &str,
+        file_types: impl IntoIterator<Item = FileType>,
+    ) -> Option<IconFile> {
+        // clearing keeps the allocation
+        self.path.as_mut_os_string().clear();
+        self.path.push(base_dir);
+        self.path.push(sub_dir);
+
+        for file_type in file_types {
+            if self
+                .deadline
+                .is_some_and(|deadline| Instant::now() >= deadline)
+            {
+                self.expired = true;
+                return None;
+            }
+
+            self.file_name.truncate(self.stem_len);
+            self.file_name.push_str(file_type.ext());
+
+            self.path.push(&self.file_name);
+            if !allows(self.audit, &self.path, AccessKind::Probe) {
+                self.path.pop();
+                continue;
+            }
+            let root = self.resolve_beneath.then(|| base_dir.parent()).flatten();
+            match SkipReason::probe_in(root, &self.path) {
+                Ok(()) => {
+                    return Some(IconFile {
+                        path: self.path.clone(),
+                        file_type,
+                    });
+                }
+                Err(None) => {}
+                Err(Some(_reason)) => {
+                    #[cfg(feature = "log")]
+                    log::debug!("skipping icon {:?} because of {_reason:?}", self.path);
+
+                    // files can't be read in a directory that can't be read, no use trying
+                    // the other extensions
+                    self.path.pop();
+                    match self.path.read_dir() {
+                        Err(e) if e.kind() != std::io::ErrorKind::NotFound => {
+                            self.unreadable.insert(&self.path, e.kind());
+                            return None;
+                        }
+                        _ => continue,
+                    }
+                }
+            }
+            self.path.pop();
+        }
+
+        None
+    }
+}
+
 fn find_attr<'a>(
     section: &'a SectionBytes,
     name: &str,
@@ -438,6 +1923,16 @@ fn find_attr<'a>(
         .transpose()
 }
 
+// the keys of a section and their values, with localized keys as `Key[locale]`
+fn section_entries(section: &SectionBytes) -> BTreeMap<String, String> {
+    (section.attrs.iter())
+        .filter_map(|attr| {
+            let key = str::from_utf8(attr.name).ok()?;
+            Some((key.to_owned(), str::from_utf8(attr.value).ok()?.to_owned()))
+        })
+        .collect()
+}
+
 fn find_attr_req<'a>(
     section: &'a SectionBytes,
     name: &'static str,
@@ -447,11 +1942,16 @@ fn find_attr_req<'a>(
 
 #[cfg(test)]
 mod test {
-    use crate::Icons;
-    use crate::icon::{FileType, IconFile};
-    use crate::theme::{DirectoryType, ThemeIndex};
+    use crate::icon::{FileType, IconFile, MatchKind};
+    use crate::spec::Compliance;
+    use crate::theme::{
+        DirectoryIndex, DirectoryType, MAX_INDEX_LEN, SubDirSelection, SubDirSelections,
+        ThemeIndex, ThemeParseError,
+    };
+    use crate::{IconSearch, Icons};
     use std::error::Error;
-    use std::path::Path;
+    use std::path::{Path, PathBuf};
+    use std::sync::Arc;
     use std::time::{Duration, Instant};
 
     #[test]
@@ -539,14 +2039,635 @@ mod test {
     }
 
     #[test]
+    fn test_rescan_theme() {
+        let dir = tempfile::tempdir().unwrap();
+        let write_theme = |name: &str, comment: &str, inherits: &str| {
+            std::fs::create_dir_all(dir.path().join(name)).unwrap();
+            std::fs::write(
+                dir.path().join(name).join("index.theme"),
+                format!(
+                    "[Icon Theme]\nName={name}\nComment={comment}\nInherits={inherits}\nDirectories=\n"
+                ),
+            )
+            .unwrap();
+        };
+
+        write_theme("hicolor", "Fallback", "");
+        write_theme("Oak", "Wooden", "hicolor");
+        write_theme("Birch", "Lighter wood", "Oak");
+
+        let mut icons = IconSearch::new_from(vec![dir.path().into()])
+            .search()
+            .icons();
+        let hicolor = icons.theme("hicolor").unwrap();
+
+        write_theme("Oak", "Darker wood", "hicolor");
+        let oak = icons.rescan_theme("Oak").unwrap();
+        assert_eq!(oak.info.index.comment, "Darker wood");
+        assert!(Arc::ptr_eq(&oak, &icons.theme("Oak").unwrap()));
+
+        // children see the new version, unrelated themes are untouched
+        let birch = icons.theme("Birch").unwrap();
+        assert!(Arc::ptr_eq(&birch.inherits_from[0], &oak));
+        assert!(Arc::ptr_eq(&hicolor, &icons.theme("hicolor").unwrap()));
+
+        // changing the parents requires resolving the inheritance trees again
+        write_theme("Birch", "Lighter wood", "hicolor");
+        let birch = icons.rescan_theme("Birch").unwrap();
+        assert_eq!(birch.inherits_from.len(), 1);
+
+        std::fs::remove_dir_all(dir.path().join("Oak")).unwrap();
+        assert!(icons.rescan_theme("Oak").is_err());
+    }
+
+    #[test]
+    fn test_find_icon_everywhere() {
+        let dir = tempfile::tempdir().unwrap();
+        for (name, inherits, has_icon) in [
+            ("hicolor", "", true),
+            ("Oak", "hicolor", true),
+            ("Birch", "Oak", false),
+        ] {
+            let theme = dir.path().join(name);
+            std::fs::create_dir_all(theme.join("apps")).unwrap();
+            std::fs::write(
+                theme.join("index.theme"),
+                format!("[Icon Theme]\nName={name}\nComment=\nInherits={inherits}\nDirectories=apps\n\n[apps]\nSize=48\n"),
+            )
+            .unwrap();
+            if has_icon {
+                std::fs::write(theme.join("apps/firefox.svg"), b"").unwrap();
+            }
+        }
+
+        let icons = IconSearch::new_from(vec![dir.path().into()])
+            .search()
+            .icons();
+
+        let found = icons.find_icon_everywhere("firefox", 48, 1);
+        // Birch only inherits the icon from Oak
+        assert_eq!(found.keys().collect::<Vec<_>>(), ["Oak", "hicolor"]);
+        assert_eq!(found["Oak"].path, dir.path().join("Oak/apps/firefox.svg"));
+    }
+
+    #[test]
+    fn test_find_icon_deep_inheritance() {
+        let dir = tempfile::tempdir().unwrap();
+        for (name, inherits, icon) in [
+            ("hicolor", "", "htop"),
+            ("breeze", "hicolor", "firefox"),
+            ("Papirus", "breeze", "gimp"),
+            ("Papirus-Dark", "Papirus", "vlc"),
+        ] {
+            let theme = dir.path().join(name);
+            std::fs::create_dir_all(theme.join("apps")).unwrap();
+            std::fs::write(
+                theme.join("index.theme"),
+                format!("[Icon Theme]\nName={name}\nComment=\nInherits={inherits}\nDirectories=apps\n\n[apps]\nSize=48\n"),
+            )
+            .unwrap();
+            std::fs::write(theme.join(format!("apps/{icon}.svg")), b"").unwrap();
+        }
+
+        let icons = IconSearch::new_from(vec![dir.path().into()])
+            .search()
+            .icons();
+        let dark = icons.theme("Papirus-Dark").unwrap();
+        let ancestry = (dark.inherits_from.iter())
+            .map(|theme| theme.info.internal_name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(ancestry, ["Papirus", "breeze", "hicolor"]);
+
+        for (icon, theme) in [
+            ("vlc", "Papirus-Dark"),
+            ("gimp", "Papirus"),
+            ("firefox", "breeze"),
+            ("htop", "hicolor"),
+        ] {
+            let found = dark.find_icon(icon, 48, 1).unwrap();
+            assert_eq!(
+                found.path,
+                dir.path().join(format!("{theme}/apps/{icon}.svg"))
+            );
+        }
+    }
+
+    #[test]
+    fn test_choose_icon() {
+        let dir = tempfile::tempdir().unwrap();
+        for (name, inherits, icons) in [
+            ("hicolor", "", &["web-browser", "htop"][..]),
+            ("Oak", "hicolor", &["firefox"]),
+        ] {
+            let theme = dir.path().join(name);
+            std::fs::create_dir_all(theme.join("apps")).unwrap();
+            std::fs::write(
+                theme.join("index.theme"),
+                format!("[Icon Theme]\nName={name}\nComment=\nInherits={inherits}\nDirectories=apps\n\n[apps]\nSize=48\n"),
+            )
+            .unwrap();
+            for icon in icons {
+                std::fs::write(theme.join(format!("apps/{icon}.svg")), b"").unwrap();
+            }
+        }
+        std::fs::write(dir.path().join("internet.png"), b"").unwrap();
+
+        let icons = IconSearch::new_from(vec![dir.path().into()])
+            .search()
+            .icons();
+        let choose = |names: &[&str]| {
+            let icon = icons.choose_icon(names, 48, 1, "Oak")?;
+            Some(icon.path.strip_prefix(dir.path()).ok()?.to_owned())
+        };
+
+        // the requested theme wins over the order of the names
+        assert_eq!(
+            choose(&["web-browser", "firefox", "internet"]),
+            Some("Oak/apps/firefox.svg".into())
+        );
+        assert_eq!(
+            choose(&["", "web-browser", "htop"]),
+            Some("hicolor/apps/web-browser.svg".into())
+        );
+        assert_eq!(
+            choose(&["missing", "internet"]),
+            Some("internet.png".into())
+        );
+        assert_eq!(choose(&["missing"]), None);
+        assert_eq!(choose(&[]), None);
+    }
+
+    #[test]
+    fn test_find_icon_in_chain() {
+        let dir = tempfile::tempdir().unwrap();
+        for (name, inherits, icons) in [
+            ("hicolor", "", &["firefox", "htop"][..]),
+            ("Oak", "hicolor", &["firefox"]),
+            ("Brand", "", &["firefox", "gimp"]),
+        ] {
+            let theme = dir.path().join(name);
+            std::fs::create_dir_all(theme.join("apps")).unwrap();
+            std::fs::write(
+                theme.join("index.theme"),
+                format!("[Icon Theme]\nName={name}\nComment=\nInherits={inherits}\nDirectories=apps\n\n[apps]\nSize=48\n"),
+            )
+            .unwrap();
+            for icon in icons {
+                std::fs::write(theme.join(format!("apps/{icon}.svg")), b"").unwrap();
+            }
+        }
+
+        let icons = IconSearch::new_from(vec![dir.path().into()])
+            .search()
+            .icons();
+        let brand = icons.theme("Brand").unwrap();
+        let oak = icons.theme("Oak").unwrap();
+        let chain = [brand.as_ref(), oak.as_ref()];
+
+        let find = |name| {
+            icons
+                .find_icon_in_chain(name, 48, 1, &chain)
+                .map(|icon| icon.path)
+        };
+        assert_eq!(
+            find("firefox"),
+            Some(dir.path().join("Brand/apps/firefox.svg"))
+        );
+        assert_eq!(find("gimp"), Some(dir.path().join("Brand/apps/gimp.svg")));
+        // Oak's parents aren't part of the chain
+        assert_eq!(find("htop"), None);
+    }
+
+    #[test]
+    fn test_preferred_fallbacks() {
+        let dir = tempfile::tempdir().unwrap();
+        for (name, inherits, icons) in [
+            ("hicolor", "", &["firefox", "htop"][..]),
+            ("Oak", "hicolor", &["gimp"]),
+            ("Breeze", "hicolor", &["firefox"]),
+        ] {
+            let theme = dir.path().join(name);
+            std::fs::create_dir_all(theme.join("apps")).unwrap();
+            std::fs::write(
+                theme.join("index.theme"),
+                format!("[Icon Theme]\nName={name}\nComment=\nInherits={inherits}\nDirectories=apps\n\n[apps]\nSize=48\n"),
+            )
+            .unwrap();
+            for icon in icons {
+                std::fs::write(theme.join(format!("apps/{icon}.svg")), b"").unwrap();
+            }
+        }
+
+        let find = |search: IconSearch, name| {
+            let icons = search.search().icons();
+            icons.find_icon(name, 48, 1, "Oak").map(|icon| icon.path)
+        };
+        let search = IconSearch::new_from(vec![dir.path().into()]);
+        assert_eq!(
+            find(search.clone(), "firefox"),
+            Some(dir.path().join("hicolor/apps/firefox.svg"))
+        );
+
+        let preferred = search.with_preferred_fallbacks(["Missing", "Breeze"]);
+        assert_eq!(
+            find(preferred.clone(), "firefox"),
+            Some(dir.path().join("Breeze/apps/firefox.svg"))
+        );
+        assert_eq!(
+            find(preferred.clone(), "gimp"),
+            Some(dir.path().join("Oak/apps/gimp.svg"))
+        );
+        assert_eq!(
+            find(preferred.clone(), "htop"),
+            Some(dir.path().join("hicolor/apps/htop.svg"))
+        );
+
+        let strict = preferred.with_compliance(Compliance::Strict);
+        assert_eq!(
+            find(strict, "firefox"),
+            Some(dir.path().join("hicolor/apps/firefox.svg"))
+        );
+    }
+
+    #[test]
+    fn test_scale_suffixes() {
+        let dir = tempfile::tempdir().unwrap();
+        let theme = dir.path().join("hicolor");
+        for sub_dir in ["48", "48@2", "96"] {
+            std::fs::create_dir_all(theme.join(sub_dir)).unwrap();
+        }
+        std::fs::write(
+            theme.join("index.theme"),
+            "[Icon Theme]\nName=Hicolor\nComment=\nDirectories=48,48@2,96\n\n[48]\nSize=48\n\n\
+             [48@2]\nSize=48\nScale=2\n\n[96]\nSize=96\n",
+        )
+        .unwrap();
+        for file in [
+            "hicolor/48/firefox.png",
+            "hicolor/48/firefox@2x.png",
+            "hicolor/96/firefox.png",
+            "hicolor/48/gimp@2x.png",
+            "hicolor/48@2/gimp.png",
+            "htop.png",
+            "htop@2x.png",
+        ] {
+            std::fs::write(dir.path().join(file), b"").unwrap();
+        }
+
+        for list_directories in [false, true] {
+            let search = IconSearch::new_from(vec![dir.path().into()])
+                .with_directory_listing(list_directories);
+            let icons = search.clone().with_scale_suffixes(true).search().icons();
+            let find = |name, scale| {
+                let icon = icons.find_default_icon(name, 48, scale).unwrap();
+                icon.path.strip_prefix(dir.path()).unwrap().to_owned()
+            };
+
+            assert_eq!(find("firefox", 2), Path::new("hicolor/48/firefox@2x.png"));
+            assert_eq!(find("firefox", 1), Path::new("hicolor/48/firefox.png"));
+            // directories for the scale still win
+            assert_eq!(find("gimp", 2), Path::new("hicolor/48@2/gimp.png"));
+            assert_eq!(find("htop", 2), Path::new("htop@2x.png"));
+            assert_eq!(find("htop", 1), Path::new("htop.png"));
+
+            // the convention is opt-in
+            let icons = search.search().icons();
+            let firefox = icons.find_default_icon("firefox", 48, 2).unwrap();
+            assert_ne!(firefox.path, theme.join("48/firefox@2x.png"));
+            let htop = icons.find_default_icon("htop", 48, 2).unwrap();
+            assert_eq!(htop.path, dir.path().join("htop.png"));
+        }
+    }
+
+    #[test]
+    fn test_find_icon_sized() {
+        let dir = tempfile::tempdir().unwrap();
+        let theme = dir.path().join("hicolor");
+        for sub_dir in ["16", "32", "64", "32@2"] {
+            std::fs::create_dir_all(theme.join(sub_dir)).unwrap();
+        }
+        std::fs::write(
+            theme.join("index.theme"),
+            "[Icon Theme]\nName=Hicolor\nComment=\nDirectories=16,32,64,32@2\n\n[16]\nSize=16\n\n\
+             [32]\nSize=32\n\n[64]\nSize=64\n\n[32@2]\nSize=32\nScale=2\n",
+        )
+        .unwrap();
+        for file in ["16/firefox.png", "64/firefox.png", "32@2/firefox.png"] {
+            std::fs::write(theme.join(file), b"").unwrap();
+        }
+
+        for listing in [false, true] {
+            let icons = IconSearch::new_from(vec![dir.path().into()])
+                .with_directory_listing(listing)
+                .search()
+                .icons();
+            let find = |sizes: &[u32], scales: &[u32]| {
+                let icon = icons.find_icon_sized("firefox", sizes, scales, "hicolor");
+                icon.map(|icon| icon.path.strip_prefix(&theme).unwrap().to_owned())
+            };
+
+            assert_eq!(find(&[48, 64, 16], &[]), Some("64/firefox.png".into()));
+            assert_eq!(find(&[32, 16], &[2, 1]), Some("32@2/firefox.png".into()));
+            assert_eq!(find(&[32, 16], &[1, 2]), Some("16/firefox.png".into()));
+            // without an exact match, the first size is looked up as usual
+            assert_eq!(find(&[56, 24], &[1]), Some("64/firefox.png".into()));
+            assert_eq!(find(&[], &[1]), None);
+        }
+    }
+
+    #[test]
+    fn test_stable_order() {
+        let user = tempfile::tempdir().unwrap();
+        let system = tempfile::tempdir().unwrap();
+        for (dir, name) in [
+            (&system, "hicolor"),
+            (&system, "Oak"),
+            (&user, "Oak"),
+            (&user, "Pine"),
+            (&system, "Birch"),
+        ] {
+            let theme = dir.path().join(name);
+            std::fs::create_dir_all(&theme).unwrap();
+            std::fs::write(
+                theme.join("index.theme"),
+                format!("[Icon Theme]\nName={name}\nComment=\nDirectories=\n"),
+            )
+            .unwrap();
+        }
+        for (dir, name) in [(&system, "b.png"), (&user, "c.png"), (&system, "a.png")] {
+            std::fs::write(dir.path().join(name), b"").unwrap();
+        }
+
+        let search = IconSearch::new_from(vec![user.path().into(), system.path().into()]).search();
+        let expected = ["Oak", "Pine", "Birch", "hicolor"];
+        assert_eq!(search.icon_locations().theme_names(), expected);
+
+        let icons = search.icons();
+        let themes = icons
+            .iter_themes()
+            .map(|(name, _)| name)
+            .collect::<Vec<_>>();
+        assert_eq!(themes, expected);
+        let standalone_icons = icons.iter_standalone_icons().map(|(name, _)| name);
+        assert_eq!(standalone_icons.collect::<Vec<_>>(), ["c", "a", "b"]);
+    }
+
+    #[test]
+    fn test_standalone_formats() {
+        let user = tempfile::tempdir().unwrap();
+        let system = tempfile::tempdir().unwrap();
+        for (dir, name) in [
+            (&system, "htop.svg"),
+            (&system, "htop.png"),
+            (&user, "htop.svg"),
+        ] {
+            std::fs::write(dir.path().join(name), b"").unwrap();
+        }
+
+        let icons = IconSearch::new_from(vec![user.path().into(), system.path().into()])
+            .search()
+            .icons();
+        let paths = (icons.standalone_icons("htop").iter())
+            .map(|file| file.path.clone())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            paths,
+            [
+                user.path().join("htop.svg"),
+                system.path().join("htop.png"),
+                system.path().join("htop.svg"),
+            ]
+        );
+        assert_eq!(icons.find_standalone_icon("htop").unwrap().path, paths[0]);
+        assert!(icons.standalone_icons("firefox").is_empty());
+    }
+
+    #[test]
+    fn test_pagination() {
+        let dir = tempfile::tempdir().unwrap();
+        let theme = dir.path().join("hicolor");
+        for sub_dir in ["16", "48"] {
+            std::fs::create_dir_all(theme.join(sub_dir)).unwrap();
+        }
+        std::fs::write(
+            theme.join("index.theme"),
+            "[Icon Theme]\nName=Hicolor\nComment=\nDirectories=48,16\n\n[48]\nSize=48\n\n[16]\nSize=16\n",
+        )
+        .unwrap();
+        for file in [
+            "48/firefox.png",
+            "48/htop.svg",
+            "16/firefox.png",
+            "16/Files.png",
+            "16/gimp.png",
+        ] {
+            std::fs::write(theme.join(file), b"").unwrap();
+        }
+
+        let icons = IconSearch::new_from(vec![dir.path().into()])
+            .search()
+            .icons();
+        let hicolor = icons.theme("hicolor").unwrap();
+
+        assert_eq!(hicolor.list_icons_page(None, 2), ["Files", "firefox"]);
+        assert_eq!(
+            hicolor.list_icons_page(Some("firefox"), 2),
+            ["gimp", "htop"]
+        );
+        assert!(hicolor.list_icons_page(Some("htop"), 2).is_empty());
+        assert_eq!(hicolor.search_icons("F", None, 10), ["Files", "firefox"]);
+        assert_eq!(hicolor.search_icons("f", Some("Files"), 10), ["firefox"]);
+
+        let paths = |files: Vec<(&DirectoryIndex, IconFile)>| {
+            (files.into_iter())
+                .map(|(_, file)| file.path.strip_prefix(&theme).unwrap().to_owned())
+                .collect::<Vec<_>>()
+        };
+        let first = paths(hicolor.iter_files().take(3).collect());
+        assert_eq!(
+            first,
+            ["48/firefox.png", "48/htop.svg", "16/Files.png"].map(PathBuf::from)
+        );
+        let rest = paths(hicolor.iter_files_after(&theme.join(&first[2])).collect());
+        assert_eq!(rest, ["16/firefox.png", "16/gimp.png"].map(PathBuf::from));
+    }
+
+    #[test]
+    fn test_find_icon_any() {
+        let dir = tempfile::tempdir().unwrap();
+        for (name, has_icon) in [("hicolor", false), ("Oak", true), ("Birch", true)] {
+            let theme = dir.path().join(name);
+            std::fs::create_dir_all(theme.join("apps")).unwrap();
+            std::fs::write(
+                theme.join("index.theme"),
+                format!(
+                    "[Icon Theme]\nName={name}\nComment=\nDirectories=apps\n\n[apps]\nSize=48\n"
+                ),
+            )
+            .unwrap();
+            if has_icon {
+                std::fs::write(theme.join("apps/firefox.svg"), b"").unwrap();
+            }
+        }
+        std::fs::write(dir.path().join("htop.png"), b"").unwrap();
+
+        let icons = IconSearch::new_from(vec![dir.path().into()])
+            .search()
+            .icons();
+
+        let icon = icons.find_icon_any("firefox", 48, 1).unwrap();
+        assert_eq!(icon.path, dir.path().join("Birch/apps/firefox.svg"));
+
+        let icon = icons.find_icon_any("htop", 48, 1).unwrap();
+        assert_eq!(icon.path, dir.path().join("htop.png"));
+        assert_eq!(icons.find_icon_any("missing", 48, 1), None);
+    }
+
+    #[test]
+    fn test_find_icon_match() {
+        let dir = tempfile::tempdir().unwrap();
+        for (name, inherits) in [("hicolor", ""), ("Oak", "hicolor")] {
+            let theme = dir.path().join(name);
+            std::fs::create_dir_all(theme.join("48x48/apps")).unwrap();
+            std::fs::write(
+                theme.join("index.theme"),
+                format!("[Icon Theme]\nName={name}\nComment=\nInherits={inherits}\nDirectories=48x48/apps\n\n[48x48/apps]\nSize=48\nContext=Applications\n"),
+            )
+            .unwrap();
+        }
+        std::fs::write(dir.path().join("hicolor/48x48/apps/firefox.png"), b"").unwrap();
+        std::fs::write(dir.path().join("htop.png"), b"").unwrap();
+
+        let mut icons = IconSearch::new_from(vec![dir.path().into()])
+            .search()
+            .icons();
+
+        let found = icons.find_icon_match("firefox", 48, 1, "Oak").unwrap();
+        let generation = icons.generation();
+        assert_eq!(found.generation, generation);
+        assert_eq!(found.theme_name(), Some("hicolor"));
+        assert_eq!(found.base_dir, dir.path().join("hicolor"));
+        let directory = found.directory().unwrap();
+        assert_eq!(directory.directory_name, "48x48/apps");
+        assert_eq!(directory.context.as_deref(), Some("Applications"));
+        assert_eq!(found.kind, MatchKind::Exact);
+
+        let found = icons.find_icon_match("firefox", 48, 2, "Oak").unwrap();
+        assert_eq!(found.kind, MatchKind::Nearest);
+        assert!(!found.is_exact());
+
+        let found = icons.find_icon_match("htop", 48, 1, "Oak").unwrap();
+        assert_eq!(found.theme_name(), None);
+        assert_eq!(found.base_dir, dir.path());
+        assert!(found.directory().is_none());
+        assert_eq!(found.kind, MatchKind::Unsized);
+
+        icons.rescan_theme("hicolor").unwrap();
+        let found = icons.find_icon_match("htop", 48, 1, "Oak").unwrap();
+        assert_eq!(found.generation, icons.generation());
+        assert!(found.generation > generation);
+    }
+
+    #[test]
+    fn test_find_all_icons() {
+        let dir = tempfile::tempdir().unwrap();
+        for (name, inherits) in [("hicolor", ""), ("Oak", "hicolor")] {
+            let theme = dir.path().join(name);
+            for sub_dir in ["16", "48", "scalable"] {
+                std::fs::create_dir_all(theme.join(sub_dir)).unwrap();
+            }
+            std::fs::write(
+                theme.join("index.theme"),
+                format!(
+                    "[Icon Theme]\nName={name}\nComment=\nInherits={inherits}\n\
+                     Directories=16,48,scalable\n\n[16]\nSize=16\nType=Fixed\n\n\
+                     [48]\nSize=48\nType=Fixed\n\n\
+                     [scalable]\nSize=48\nType=Scalable\nMinSize=32\nMaxSize=256\n"
+                ),
+            )
+            .unwrap();
+        }
+        for file in [
+            "Oak/16/acorn.png",
+            "Oak/48/acorn.svg",
+            "Oak/48/acorn.png",
+            "Oak/scalable/acorn.svg",
+            "hicolor/48/acorn.png",
+            "acorn.png",
+        ] {
+            std::fs::write(dir.path().join(file), b"").unwrap();
+        }
+
+        for listing in [false, true] {
+            let icons = IconSearch::new_from(vec![dir.path().into()])
+                .with_directory_listing(listing)
+                .search()
+                .icons();
+
+            let found = icons.find_all_icons("acorn", 48, 1, "Oak");
+            let paths = (found.iter())
+                .map(|icon| icon.file.path.strip_prefix(dir.path()).unwrap())
+                .collect::<Vec<_>>();
+            assert_eq!(
+                paths,
+                [
+                    "Oak/48/acorn.png",
+                    "hicolor/48/acorn.png",
+                    "Oak/48/acorn.svg",
+                    "Oak/scalable/acorn.svg",
+                    "Oak/16/acorn.png",
+                    "acorn.png",
+                ]
+                .map(Path::new)
+            );
+            let kinds = found.iter().map(|icon| icon.kind).collect::<Vec<_>>();
+            assert_eq!(kinds[0], MatchKind::Exact);
+            assert_eq!(kinds[4], MatchKind::Nearest);
+            assert_eq!(kinds[5], MatchKind::Unsized);
+            assert_eq!(
+                found[0].file,
+                icons.find_icon("acorn", 48, 1, "Oak").unwrap()
+            );
+
+            assert!(icons.find_all_icons("missing", 48, 1, "Oak").is_empty());
+        }
+    }
+
+    #[test]
+    fn test_icons_equality() {
+        let dir = tempfile::tempdir().unwrap();
+        let theme = dir.path().join("hicolor");
+        std::fs::create_dir_all(theme.join("48")).unwrap();
+        std::fs::write(
+            theme.join("index.theme"),
+            "[Icon Theme]\nName=Hicolor\nComment=\nDirectories=48\n\n[48]\nSize=48\n",
+        )
+        .unwrap();
+        std::fs::write(theme.join("48/htop.png"), b"").unwrap();
+
+        let search = IconSearch::new_from(vec![dir.path().into()]);
+        let icons = search.clone().search().icons();
+        // another scan has its own generation and timings, and looking up or listening for
+        // changes doesn't change which icons are found
+        let other = search.clone().search().icons();
+        assert!(other.find_default_icon("htop", 48, 1).is_some());
+        other.on_change(|_| {});
+        assert_ne!(icons.generation(), other.generation());
+        assert_eq!(icons, other);
+
+        assert_ne!(icons, search.with_name_fallback(true).search().icons());
+    }
+
+    #[test]
+    #[allow(clippy::bool_assert_comparison)]
     fn test_parse_example_theme() -> Result<(), Box<dyn Error>> {
-        static EXAMPLE: &'static str = include_str!("../resources/example.index.theme");
+        static EXAMPLE: &str = include_str!("../resources/example.index.theme");
 
         let index = ThemeIndex::parse(EXAMPLE.as_bytes())?;
 
         assert_eq!(index.name, "Birch");
         assert_eq!(index.comment, "Icon theme with a wooden look");
-        assert_eq!(index.inherits, vec!["wood", "default"]);
+        assert_eq!(*index.inherits, ["wood", "default"]);
 
         let directories = index.directories;
 
@@ -568,4 +2689,168 @@ mod test {
 
         Ok(())
     }
+
+    #[test]
+    fn test_parse_custom_groups() -> Result<(), Box<dyn Error>> {
+        let index = ThemeIndex::parse(
+            b"[Icon Theme]\nName=Oak\nName[de]=Eiche\nComment=\nDirectories=48\nFollowsColorScheme=true\n\n\
+              [48]\nSize=48\n\n\
+              [X-Vendor]\nVersion=2.4\nGeneration=3\n",
+        )?;
+
+        assert_eq!(index.directories.len(), 1);
+        let vendor = index.group("X-Vendor").unwrap();
+        assert_eq!(vendor["Version"], "2.4");
+        assert_eq!(vendor["Generation"], "3");
+        assert!(index.group("48").is_none());
+
+        let icon_theme = index.group("Icon Theme").unwrap();
+        assert_eq!(icon_theme["Name[de]"], "Eiche");
+        assert_eq!(icon_theme["FollowsColorScheme"], "true");
+
+        // written indexes keep them
+        let written = crate::edit::IndexDocument::from(&index).to_string();
+        assert_eq!(ThemeIndex::parse(written.as_bytes())?.groups, index.groups);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_sub_dir_selection() -> Result<(), Box<dyn Error>> {
+        static EXAMPLE: &str = include_str!("../resources/example.index.theme");
+        let index = ThemeIndex::parse(EXAMPLE.as_bytes())?;
+
+        let selection = SubDirSelection::new(&index.directories, 32, 1);
+        assert_eq!(selection.exact, [0, 1, 2]);
+        assert_eq!(selection.closest, [(16, 4), (16, 6), (32, 3), (64, 5)]);
+
+        let selection = SubDirSelection::new(&index.directories, 48, 2);
+        assert_eq!(selection.exact, [5]);
+        assert_eq!(
+            selection.closest,
+            [(32, 3), (48, 0), (48, 1), (48, 4), (48, 6), (64, 2)]
+        );
+
+        // themes select the directories for each size once
+        let selections = SubDirSelections::default();
+        let selection = selections.get(&index.directories, 32, 1);
+        assert!(Arc::ptr_eq(
+            &selection,
+            &selections.get(&index.directories, 32, 1)
+        ));
+        assert_eq!(*selection, SubDirSelection::new(&index.directories, 32, 1));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_untrusted() {
+        let index = |directory: &str, section: &str| {
+            ThemeIndex::parse(
+                format!(
+                    "[Icon Theme]\nName=Birch\nDirectories={directory}\n\n[{directory}]\n{section}"
+                )
+                .as_bytes(),
+            )
+        };
+
+        let padding = vec![b'#'; MAX_INDEX_LEN + 1];
+        assert!(matches!(
+            ThemeIndex::parse(&padding),
+            Err(ThemeParseError::TooLarge)
+        ));
+
+        assert!(matches!(
+            index("../../etc", "Size=48"),
+            Err(ThemeParseError::InvalidDirectoryName(_))
+        ));
+        assert!(matches!(
+            index("/etc", "Size=48"),
+            Err(ThemeParseError::InvalidDirectoryName(_))
+        ));
+        assert!(matches!(
+            index("apps", "Size=4294967295"),
+            Err(ThemeParseError::OutOfRange("Size"))
+        ));
+        assert!(matches!(
+            index("apps", "Size=48\nScale=0"),
+            Err(ThemeParseError::OutOfRange("Scale"))
+        ));
+
+        // a threshold larger than the size must not underflow
+        let index = index("apps", "Size=2\nThreshold=10").unwrap();
+        assert_eq!(
+            index.directories[0].size_distance(u32::MAX, 2),
+            u32::MAX - 2
+        );
+    }
+
+    #[test]
+    fn test_unreadable_dirs() {
+        let dir = tempfile::tempdir().unwrap();
+        let theme = dir.path().join("hicolor");
+        std::fs::create_dir_all(theme.join("16")).unwrap();
+        std::fs::write(
+            theme.join("index.theme"),
+            "[Icon Theme]\nName=Hicolor\nComment=Fallback\nDirectories=16,48\n\n\
+             [16]\nSize=16\n\n[48]\nSize=48\n",
+        )
+        .unwrap();
+        std::fs::write(theme.join("16/firefox.png"), b"").unwrap();
+        // a file can't be read as a directory, just like a directory without permissions
+        std::fs::write(theme.join("48"), b"").unwrap();
+
+        for list_directories in [false, true] {
+            let icons = IconSearch::new_from(vec![dir.path().into()])
+                .with_directory_listing(list_directories)
+                .search()
+                .icons();
+            let hicolor = icons.theme("hicolor").unwrap();
+
+            // the directory is only found to be unreadable by listing or looking it up
+            assert_eq!(hicolor.is_partial(), list_directories);
+            assert_eq!(icons.report.partial_themes.is_empty(), !list_directories);
+
+            let icon = icons.find_icon("firefox", 48, 1, "hicolor").unwrap();
+            assert_eq!(icon.path, theme.join("16/firefox.png"));
+            assert!(hicolor.is_partial());
+            assert_eq!(hicolor.unreadable_dirs(), [theme.join("48")]);
+        }
+    }
+
+    #[test]
+    fn test_time_budget() {
+        let dir = tempfile::tempdir().unwrap();
+        let theme = dir.path().join("hicolor");
+        std::fs::create_dir_all(theme.join("48")).unwrap();
+        std::fs::write(
+            theme.join("index.theme"),
+            "[Icon Theme]\nName=Hicolor\nComment=Fallback\nDirectories=48\n\n[48]\nSize=48\n",
+        )
+        .unwrap();
+        std::fs::write(theme.join("48/firefox.png"), b"").unwrap();
+        std::fs::write(dir.path().join("htop.png"), b"").unwrap();
+
+        let search = || IconSearch::new_from(vec![dir.path().into()]);
+        let icons = search().search().icons();
+
+        let found = icons.find_icon_within("firefox", 48, 1, "hicolor", Duration::from_secs(60));
+        assert_eq!(found.unwrap().unwrap().path, theme.join("48/firefox.png"));
+
+        // without any time, only the standalone icon is known
+        let timed_out = icons.find_icon_within("htop", 48, 1, "hicolor", Duration::ZERO);
+        let best = timed_out.unwrap_err().best.unwrap();
+        assert_eq!(best.path, dir.path().join("htop.png"));
+        assert_eq!(
+            icons.find_icon_within("firefox", 48, 1, "hicolor", Duration::ZERO),
+            Err(crate::theme::TimedOut { best: None })
+        );
+
+        // listed themes don't touch the file system
+        let listed = search().with_directory_listing(true).search().icons();
+        assert!(
+            (listed.find_icon_within("firefox", 48, 1, "hicolor", Duration::ZERO))
+                .is_ok_and(|icon| icon.is_some())
+        );
+    }
 }
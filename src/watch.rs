@@ -0,0 +1,222 @@
+//! Refreshing [`Icons`] automatically when icon themes change on disk.
+//!
+//! An [`IconWatcher`] watches the base directories of an [`Icons`], the themes in them and the
+//! user's configuration files, and calls [`Icons::refresh`] whenever they change. Callbacks
+//! registered with [`Icons::on_change`] are called from the watcher's thread.
+//!
+//! Installing a theme touches thousands of files in a burst, so changes are
+//! [debounced](WatchOptions): only one refresh runs once the burst settles.
+//!
+//! This module requires the `watch` feature.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use icon::Icons;
+//! use icon::watch::IconWatcher;
+//!
+//! let icons = Icons::new();
+//! icons.on_change(|diff| println!("icons changed: {diff:?}"));
+//!
+//! let watcher = IconWatcher::new(icons).unwrap();
+//! let firefox = watcher.icons().read().unwrap().find_icon("firefox", 32, 1, "hicolor");
+//! ```
+
+use crate::environment::Environment;
+use crate::theme::Icons;
+use notify::{RecommendedWatcher, RecursiveMode, Watcher};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex, RwLock, mpsc};
+use std::time::{Duration, Instant};
+
+/// Options for an [`IconWatcher`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct WatchOptions {
+    /// How long to wait after a change for more changes, before refreshing.
+    pub debounce: Duration,
+    /// The longest time to postpone a refresh after the first change while changes keep coming.
+    pub max_delay: Duration,
+}
+
+impl Default for WatchOptions {
+    fn default() -> Self {
+        Self {
+            debounce: Duration::from_millis(500),
+            max_delay: Duration::from_secs(5),
+        }
+    }
+}
+
+/// Watches the directories of an [`Icons`] and refreshes it when they change.
+///
+/// Watching stops when this is dropped.
+#[derive(Debug)]
+pub struct IconWatcher {
+    icons: Arc<RwLock<Icons>>,
+    watcher: Arc<Mutex<Option<RecommendedWatcher>>>,
+}
+
+impl IconWatcher {
+    /// Starts watching the directories of `icons`, with the default [`WatchOptions`].
+    ///
+    /// Only the base directories and the top level of each theme directory are watched, which
+    /// is enough to notice themes being installed, removed or having their `index.theme` changed.
+    pub fn new(icons: Icons) -> notify::Result<Self> {
+        Self::with_options(icons, WatchOptions::default())
+    }
+
+    /// Starts watching the directories of `icons`, debouncing changes as configured.
+    pub fn with_options(icons: Icons, options: WatchOptions) -> notify::Result<Self> {
+        let (sender, receiver) = mpsc::channel::<notify::Result<notify::Event>>();
+
+        let mut watcher = notify::recommended_watcher(sender)?;
+        for path in watched_paths(&icons) {
+            // directories that don't exist (yet) can't be watched
+            let _ = watcher.watch(&path, RecursiveMode::NonRecursive);
+        }
+
+        let icons = Arc::new(RwLock::new(icons));
+        let watcher = Arc::new(Mutex::new(Some(watcher)));
+
+        let shared_icons = icons.clone();
+        let shared_watcher = watcher.clone();
+        std::thread::spawn(move || {
+            let is_change = |event: &notify::Result<notify::Event>| match event {
+                Ok(event) => !event.kind.is_access(),
+                Err(_e) => {
+                    #[cfg(feature = "log")]
+                    log::warn!("error while watching icon directories: {_e}");
+                    false
+                }
+            };
+
+            // this ends once the watcher, and with it the sender, is dropped
+            while wait_for_changes(&receiver, is_change, options) {
+                let mut icons = shared_icons.write().unwrap_or_else(|e| e.into_inner());
+                let diff = icons.refresh();
+
+                // watch the directories of newly installed themes
+                if !diff.added_themes.is_empty()
+                    && let Some(watcher) = shared_watcher.lock().unwrap().as_mut()
+                {
+                    for path in watched_paths(&icons) {
+                        let _ = watcher.watch(&path, RecursiveMode::NonRecursive);
+                    }
+                }
+            }
+        });
+
+        Ok(Self { icons, watcher })
+    }
+
+    /// The watched icons, which are kept up to date.
+    pub fn icons(&self) -> &Arc<RwLock<Icons>> {
+        &self.icons
+    }
+}
+
+impl Drop for IconWatcher {
+    fn drop(&mut self) {
+        if let Ok(mut watcher) = self.watcher.lock() {
+            watcher.take();
+        }
+    }
+}
+
+/// Blocks until a change arrives, then until changes have settled according to `options`.
+///
+/// Returns `false` if the channel was closed instead.
+fn wait_for_changes<T>(
+    receiver: &mpsc::Receiver<T>,
+    is_change: impl Fn(&T) -> bool,
+    options: WatchOptions,
+) -> bool {
+    loop {
+        match receiver.recv() {
+            Ok(event) if is_change(&event) => break,
+            Ok(_) => {}
+            Err(_) => return false,
+        }
+    }
+
+    let first = Instant::now();
+    let mut last = first;
+
+    loop {
+        let deadline = (last + options.debounce).min(first + options.max_delay);
+        let Some(timeout) = deadline.checked_duration_since(Instant::now()) else {
+            return true;
+        };
+
+        match receiver.recv_timeout(timeout) {
+            Ok(event) if is_change(&event) => last = Instant::now(),
+            Ok(_) => {}
+            Err(mpsc::RecvTimeoutError::Timeout) => return true,
+            Err(mpsc::RecvTimeoutError::Disconnected) => return false,
+        }
+    }
+}
+
+/// The paths watched for `icons`: its base directories, theme directories and the user's
+/// configuration directories, where the icon theme setting is stored.
+fn watched_paths(icons: &Icons) -> Vec<PathBuf> {
+    let mut paths = icons.base_dirs.clone();
+
+    for theme in icons.themes.values() {
+        paths.extend(theme.info.base_dirs.iter().cloned());
+    }
+
+    if let Some(config_home) = Environment::current().config_home() {
+        paths.push(config_home.join("gtk-4.0"));
+        paths.push(config_home.join("gtk-3.0"));
+        paths.push(config_home);
+    }
+
+    paths
+}
+
+#[cfg(test)]
+mod test {
+    use crate::watch::{WatchOptions, wait_for_changes};
+    use std::sync::mpsc;
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn test_debounce() {
+        let options = WatchOptions {
+            debounce: Duration::from_millis(50),
+            max_delay: Duration::from_millis(300),
+        };
+        let (sender, receiver) = mpsc::channel();
+
+        // a burst of changes results in a single refresh
+        for _ in 0..100 {
+            sender.send(true).unwrap();
+        }
+        assert!(wait_for_changes(&receiver, |e| *e, options));
+        assert!(receiver.try_recv().is_err());
+
+        // irrelevant events don't trigger a refresh
+        sender.send(false).unwrap();
+        drop(sender);
+        assert!(!wait_for_changes(&receiver, |e| *e, options));
+
+        // changes that keep coming are only postponed up to the maximum delay
+        let (sender, receiver) = mpsc::channel();
+        let burst = std::thread::spawn(move || {
+            for _ in 0..100 {
+                if sender.send(true).is_err() {
+                    break;
+                }
+                std::thread::sleep(Duration::from_millis(10));
+            }
+        });
+
+        let start = Instant::now();
+        assert!(wait_for_changes(&receiver, |e| *e, options));
+        assert!(start.elapsed() < Duration::from_millis(900));
+
+        drop(receiver);
+        burst.join().unwrap();
+    }
+}
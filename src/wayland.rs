@@ -0,0 +1,132 @@
+//! Drawing icons into Wayland shared memory buffers.
+//!
+//! Clients without a toolkit, such as layer shell bars, display icons by copying their pixels into
+//! a buffer of a `wl_shm` pool. [`Raster::write_to_shm`] does so in one step, converting the
+//! pixels to the buffer's [`Format`] and respecting its stride.
+//!
+//! This module requires the `wayland` feature.
+//!
+//! # Example
+//!
+//! ```ignore
+//! use icon::wayland::Format;
+//!
+//! // with smithay-client-toolkit's `SlotPool`
+//! let stride = raster.width as i32 * 4;
+//! let (buffer, canvas) = pool
+//!     .create_buffer(raster.width as i32, raster.height as i32, stride, Format::Argb8888)
+//!     .unwrap();
+//! raster.write_to_shm(canvas, stride as usize, Format::Argb8888).unwrap();
+//! ```
+
+use crate::render::{AlphaMode, ByteOrder, PixelFormat, Raster};
+use thiserror::Error;
+pub use wayland_client::protocol::wl_shm::Format;
+
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum ShmError {
+    #[error("unsupported shm format {0:?}")]
+    UnsupportedFormat(Format),
+    #[error("a stride of {stride} bytes can't hold a row of {width} pixels")]
+    StrideTooSmall { stride: usize, width: u32 },
+    #[error("the buffer of {len} bytes can't hold {height} rows")]
+    BufferTooSmall { len: usize, height: u32 },
+}
+
+impl Raster {
+    /// Writes this icon into `canvas`, a buffer of `format` whose rows are `stride` bytes apart.
+    ///
+    /// Supports the formats every compositor supports, [`Format::Argb8888`] and
+    /// [`Format::Xrgb8888`], as well as their BGR counterparts. Transparent pixels are drawn on
+    /// black in the formats without alpha.
+    pub fn write_to_shm(
+        &self,
+        canvas: &mut [u8],
+        stride: usize,
+        format: Format,
+    ) -> Result<(), ShmError> {
+        // the formats are little endian, regardless of the platform
+        let (order, opaque) = match format {
+            Format::Argb8888 => (ByteOrder::Bgra, false),
+            Format::Xrgb8888 => (ByteOrder::Bgra, true),
+            Format::Abgr8888 => (ByteOrder::Rgba, false),
+            Format::Xbgr8888 => (ByteOrder::Rgba, true),
+            _ => return Err(ShmError::UnsupportedFormat(format)),
+        };
+        let format = PixelFormat::new(order, AlphaMode::Premultiplied);
+
+        let row_len = self.width as usize * 4;
+        if stride < row_len {
+            return Err(ShmError::StrideTooSmall {
+                stride,
+                width: self.width,
+            });
+        }
+        let len = match self.height {
+            0 => 0,
+            height => (height as usize - 1) * stride + row_len,
+        };
+        if canvas.len() < len {
+            return Err(ShmError::BufferTooSmall {
+                len: canvas.len(),
+                height: self.height,
+            });
+        }
+
+        let pixels = self.to_pixels(format);
+        for (row, dst) in pixels
+            .chunks_exact(row_len.max(1))
+            .zip(canvas.chunks_mut(stride))
+        {
+            let dst = &mut dst[..row_len];
+            dst.copy_from_slice(row);
+            if opaque {
+                dst.chunks_exact_mut(4).for_each(|pixel| pixel[3] = 0xff);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::render::Raster;
+    use crate::wayland::{Format, ShmError};
+
+    #[test]
+    fn test_write_to_shm() {
+        let raster = Raster::new(1, 2, vec![255, 0, 0, 255, 0, 0, 255, 128]);
+
+        // rows are 8 bytes apart, the padding is left alone
+        let mut canvas = [0xaa; 12];
+        raster
+            .write_to_shm(&mut canvas, 8, Format::Argb8888)
+            .unwrap();
+        assert_eq!(
+            canvas,
+            [0, 0, 255, 255, 0xaa, 0xaa, 0xaa, 0xaa, 128, 0, 0, 128]
+        );
+
+        raster
+            .write_to_shm(&mut canvas, 8, Format::Xbgr8888)
+            .unwrap();
+        assert_eq!(canvas[8..], [0, 0, 128, 255]);
+
+        assert_eq!(
+            raster.write_to_shm(&mut canvas, 2, Format::Argb8888),
+            Err(ShmError::StrideTooSmall {
+                stride: 2,
+                width: 1
+            })
+        );
+        assert_eq!(
+            raster.write_to_shm(&mut canvas[..11], 8, Format::Argb8888),
+            Err(ShmError::BufferTooSmall { len: 11, height: 2 })
+        );
+        assert_eq!(
+            raster.write_to_shm(&mut canvas, 8, Format::Rgb565),
+            Err(ShmError::UnsupportedFormat(Format::Rgb565))
+        );
+    }
+}
@@ -0,0 +1,277 @@
+//! Decoding legacy XPM icons.
+//!
+//! XPM (X PixMap) images are C source files declaring an array of strings: a header with the
+//! size and number of colors, a color table and one string of characters per row. Icons from the
+//! X11 era, e.g. in `/usr/share/pixmaps`, still come in this format, which few image libraries
+//! decode. [`decode_xpm`] turns such an icon into a [`Raster`].
+//!
+//! Colors are read from the `c` key of the color table, falling back to the gray scale and
+//! monochrome keys. Hexadecimal colors, `None` and the common X11 color names are understood.
+//! XPM extensions and hotspots are ignored.
+//!
+//! This module requires the `xpm` feature.
+//!
+//! # Example
+//!
+//! ```
+//! use icon::xpm::decode_xpm;
+//!
+//! let source = br##"/* XPM */
+//! static char *dot[] = {
+//! "2 1 2 1",
+//! ". c None",
+//! "# c #ff0000",
+//! ".#"
+//! };"##;
+//!
+//! let raster = decode_xpm(source).unwrap();
+//! assert_eq!(&raster.pixels[..], [0, 0, 0, 0, 255, 0, 0, 255]);
+//! ```
+
+use crate::render::Raster;
+use crate::{FileType, IconFile, ReadError};
+use std::collections::HashMap;
+use thiserror::Error;
+
+/// The most pixels [`decode_xpm`] decodes, which no icon comes close to.
+const MAX_PIXELS: u64 = 1 << 24;
+
+#[derive(Error, Debug)]
+pub enum XpmError {
+    #[error(transparent)]
+    Read(#[from] ReadError),
+    #[error("the file is not an XPM image")]
+    NotXpm,
+    #[error("invalid XPM header {0:?}")]
+    InvalidHeader(String),
+    #[error("an XPM image of {width}x{height} pixels is too large")]
+    TooLarge { width: u32, height: u32 },
+    #[error("the XPM image ends early")]
+    Truncated,
+    #[error("invalid XPM color {0:?}")]
+    InvalidColor(String),
+    #[error("unknown XPM pixel {0:?}")]
+    UnknownPixel(String),
+}
+
+impl IconFile {
+    /// Decodes this XPM icon, see [`decode_xpm`].
+    pub fn decode_xpm(&self) -> Result<Raster, XpmError> {
+        decode_xpm(&self.read()?)
+    }
+}
+
+/// Decodes the XPM image `data` into a raster of its own size.
+pub fn decode_xpm(data: &[u8]) -> Result<Raster, XpmError> {
+    if FileType::sniff(data) != Some(FileType::Xpm) {
+        return Err(XpmError::NotXpm);
+    }
+    let mut strings = strings(data);
+
+    let header = strings.next().ok_or(XpmError::NotXpm)?;
+    let invalid_header = || XpmError::InvalidHeader(String::from_utf8_lossy(&header).into());
+    let values = (header.split(u8::is_ascii_whitespace))
+        .filter(|value| !value.is_empty())
+        .take(4)
+        .map(|value| std::str::from_utf8(value).ok()?.parse::<u32>().ok())
+        .collect::<Option<Vec<_>>>()
+        .ok_or_else(invalid_header)?;
+    let &[width, height, colors, chars_per_pixel] = &values[..] else {
+        return Err(invalid_header());
+    };
+    if !(1..=8).contains(&chars_per_pixel) {
+        return Err(invalid_header());
+    }
+    if u64::from(width) * u64::from(height) > MAX_PIXELS {
+        return Err(XpmError::TooLarge { width, height });
+    }
+    let cpp = chars_per_pixel as usize;
+
+    let mut palette = HashMap::new();
+    for _ in 0..colors {
+        let line = strings.next().ok_or(XpmError::Truncated)?;
+        let (key, spec) = line.split_at_checked(cpp).ok_or(XpmError::Truncated)?;
+        palette.insert(key.to_vec(), parse_color_spec(spec)?);
+    }
+
+    let mut pixels = Vec::with_capacity(width as usize * height as usize * 4);
+    for _ in 0..height {
+        let row = strings.next().ok_or(XpmError::Truncated)?;
+        let row = row.get(..width as usize * cpp).ok_or(XpmError::Truncated)?;
+        for key in row.chunks_exact(cpp) {
+            let color = palette
+                .get(key)
+                .ok_or_else(|| XpmError::UnknownPixel(String::from_utf8_lossy(key).into_owned()))?;
+            pixels.extend_from_slice(color);
+        }
+    }
+
+    Ok(Raster::new(width, height, pixels))
+}
+
+// the contents of the string literals in `data`, outside of comments.
+fn strings(data: &[u8]) -> impl Iterator<Item = Vec<u8>> + '_ {
+    let mut rest = data;
+    std::iter::from_fn(move || {
+        loop {
+            match rest {
+                [] => return None,
+                [b'/', b'*', after @ ..] => {
+                    let end = after.windows(2).position(|w| w == b"*/");
+                    rest = end.map_or(&[], |end| &after[end + 2..]);
+                }
+                [b'"', after @ ..] => {
+                    let mut string = Vec::new();
+                    let mut bytes = after.iter();
+                    while let Some(&byte) = bytes.next() {
+                        match byte {
+                            b'"' => break,
+                            b'\\' => string.extend(bytes.next()),
+                            byte => string.push(byte),
+                        }
+                    }
+                    rest = bytes.as_slice();
+                    return Some(string);
+                }
+                [_, after @ ..] => rest = after,
+            }
+        }
+    })
+}
+
+// the color of a color table entry such as `c #ff0000 m black`.
+fn parse_color_spec(spec: &[u8]) -> Result<[u8; 4], XpmError> {
+    const KEYS: &[&str] = &["c", "g", "g4", "m", "s"];
+
+    let spec = String::from_utf8_lossy(spec);
+    let mut values = HashMap::<&str, Vec<&str>>::new();
+    let mut key = None;
+    for token in spec.split_ascii_whitespace() {
+        match KEYS.contains(&token) {
+            true => key = Some(token),
+            // color names may consist of several words, e.g. `light gray`
+            false => values.entry(key.unwrap_or("c")).or_default().push(token),
+        }
+    }
+
+    // colors are preferred over gray scale and monochrome, symbolic names carry no color
+    let value = ["c", "g", "g4", "m"]
+        .iter()
+        .find_map(|key| values.get(key))
+        .ok_or_else(|| XpmError::InvalidColor(spec.trim().into()))?;
+    let value = value.join(" ");
+    parse_color(&value).ok_or(XpmError::InvalidColor(value))
+}
+
+fn parse_color(value: &str) -> Option<[u8; 4]> {
+    if value.eq_ignore_ascii_case("none") {
+        return Some([0; 4]);
+    }
+
+    if let Some(hex) = value.strip_prefix('#') {
+        // #rgb, #rrggbb, #rrrgggbbb or #rrrrggggbbbb
+        let digits = hex.len() / 3;
+        if hex.len() % 3 != 0 || !(1..=4).contains(&digits) {
+            return None;
+        }
+        let max = (1u32 << (4 * digits)) - 1;
+        let mut color = [255; 4];
+        for (channel, digits) in color.iter_mut().zip(hex.as_bytes().chunks(digits)) {
+            let value = u32::from_str_radix(std::str::from_utf8(digits).ok()?, 16).ok()?;
+            *channel = ((value * 255 + max / 2) / max) as u8;
+        }
+        return Some(color);
+    }
+
+    let name = (value.chars())
+        .filter(|c| !c.is_ascii_whitespace())
+        .collect::<String>()
+        .to_ascii_lowercase();
+    let gray = (name
+        .strip_prefix("gray")
+        .or_else(|| name.strip_prefix("grey")))
+    .and_then(|level| level.parse::<u32>().ok())
+    .filter(|&level| level <= 100);
+    if let Some(level) = gray {
+        let value = ((level * 255 + 50) / 100) as u8;
+        return Some([value, value, value, 255]);
+    }
+
+    let [r, g, b] = match &name[..] {
+        "black" => [0, 0, 0],
+        "white" => [255, 255, 255],
+        "red" => [255, 0, 0],
+        "green" => [0, 255, 0],
+        "blue" => [0, 0, 255],
+        "yellow" => [255, 255, 0],
+        "cyan" => [0, 255, 255],
+        "magenta" => [255, 0, 255],
+        "gray" | "grey" => [190, 190, 190],
+        "lightgray" | "lightgrey" => [211, 211, 211],
+        "darkgray" | "darkgrey" => [169, 169, 169],
+        "dimgray" | "dimgrey" => [105, 105, 105],
+        "navy" | "navyblue" => [0, 0, 128],
+        "darkblue" => [0, 0, 139],
+        "darkred" => [139, 0, 0],
+        "darkgreen" => [0, 100, 0],
+        "orange" => [255, 165, 0],
+        "brown" => [165, 42, 42],
+        "purple" => [160, 32, 240],
+        "pink" => [255, 192, 203],
+        "gold" => [255, 215, 0],
+        _ => return None,
+    };
+    Some([r, g, b, 255])
+}
+
+#[cfg(test)]
+mod test {
+    use crate::xpm::{XpmError, decode_xpm};
+
+    #[test]
+    fn test_decode_xpm() {
+        let source = br###"/* XPM */
+/* a comment with "quotes" */
+static char * icon_xpm[] = {
+/* width height colors chars-per-pixel */
+"3 2 4 2",
+"  c None",
+".. c #f00 m white",
+"## s border m black c light gray",
+"++ g gray50",
+/* pixels */
+"  ..##",
+"++++  "};
+"###;
+
+        let raster = decode_xpm(source).unwrap();
+        assert_eq!((raster.width, raster.height), (3, 2));
+        let pixel = |x: usize, y: usize| &raster.pixels[(y * 3 + x) * 4..][..4];
+        assert_eq!(pixel(0, 0), [0, 0, 0, 0]);
+        assert_eq!(pixel(1, 0), [255, 0, 0, 255]);
+        assert_eq!(pixel(2, 0), [211, 211, 211, 255]);
+        assert_eq!(pixel(0, 1), [128, 128, 128, 255]);
+
+        assert!(matches!(decode_xpm(b"<svg/>"), Err(XpmError::NotXpm)));
+        assert!(matches!(
+            decode_xpm(b"/* XPM */ { \"1 1\" }"),
+            Err(XpmError::InvalidHeader(_))
+        ));
+        assert!(matches!(
+            decode_xpm(b"/* XPM */ { \"1 2 1 1\", \". c red\", \".\" }"),
+            Err(XpmError::Truncated)
+        ));
+        assert!(matches!(
+            decode_xpm(b"/* XPM */ { \"1 1 1 1\", \". c red\", \"#\" }"),
+            Err(XpmError::UnknownPixel(_))
+        ));
+        assert!(matches!(
+            decode_xpm(b"/* XPM */ { \"1 1 1 1\", \". c chartreuse\", \".\" }"),
+            Err(XpmError::InvalidColor(_))
+        ));
+        assert!(matches!(
+            decode_xpm(b"/* XPM */ { \"100000 100000 1 1\" }"),
+            Err(XpmError::TooLarge { .. })
+        ));
+    }
+}
@@ -0,0 +1,315 @@
+//! Reading the icon theme from the X11 XSETTINGS manager.
+//!
+//! On X11 sessions without a desktop environment of their own (e.g. plain i3 or awesome setups),
+//! a settings daemon such as `xsettingsd` publishes the user's preferences, including the icon
+//! theme as `Net/IconThemeName`, following the
+//! [XSETTINGS specification](https://specifications.freedesktop.org/xsettings-spec/latest/).
+//!
+//! This module requires the `x11` feature.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use icon::xsettings::XSettings;
+//!
+//! let mut settings = XSettings::connect().unwrap();
+//! println!("icon theme: {:?}", settings.icon_theme_name().unwrap());
+//!
+//! loop {
+//!     let theme = settings.wait_for_icon_theme_change().unwrap();
+//!     println!("icon theme changed to {theme:?}");
+//! }
+//! ```
+
+use std::collections::HashMap;
+use x11rb::connection::Connection;
+use x11rb::errors::{ConnectError, ConnectionError, ReplyError};
+use x11rb::protocol::Event;
+use x11rb::protocol::xproto::{
+    Atom, AtomEnum, ChangeWindowAttributesAux, ConnectionExt, EventMask, Window,
+};
+use x11rb::rust_connection::RustConnection;
+
+/// The name of the setting holding the icon theme name.
+pub const ICON_THEME_NAME: &str = "Net/IconThemeName";
+
+/// The value of a single XSETTINGS setting.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum XSetting {
+    Integer(i32),
+    String(String),
+    /// Red, green, blue and alpha.
+    Color([u16; 4]),
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum XSettingsError {
+    #[error("could not connect to the X server")]
+    Connect(#[from] ConnectError),
+    #[error("the connection to the X server failed")]
+    Connection(#[from] ConnectionError),
+    #[error("the X server returned an error")]
+    Reply(#[from] ReplyError),
+    #[error("no XSETTINGS manager is running")]
+    NoManager,
+    #[error("the XSETTINGS data is malformed")]
+    Malformed,
+}
+
+/// A connection to the X server for reading XSETTINGS.
+pub struct XSettings {
+    conn: RustConnection,
+    root: Window,
+    /// `_XSETTINGS_S<screen>`
+    selection: Atom,
+    /// `_XSETTINGS_SETTINGS`
+    settings_atom: Atom,
+    /// `MANAGER`
+    manager_atom: Atom,
+    /// The window of the settings manager, if one is running.
+    owner: Option<Window>,
+}
+
+impl XSettings {
+    /// Connects to the X server named by `$DISPLAY`.
+    pub fn connect() -> Result<Self, XSettingsError> {
+        let (conn, screen_num) = x11rb::connect(None)?;
+        let root = conn.setup().roots[screen_num].root;
+
+        let selection = format!("_XSETTINGS_S{screen_num}");
+        let selection = conn.intern_atom(false, selection.as_bytes())?;
+        let settings_atom = conn.intern_atom(false, b"_XSETTINGS_SETTINGS")?;
+        let manager_atom = conn.intern_atom(false, b"MANAGER")?;
+
+        let mut settings = Self {
+            selection: selection.reply()?.atom,
+            settings_atom: settings_atom.reply()?.atom,
+            manager_atom: manager_atom.reply()?.atom,
+            conn,
+            root,
+            owner: None,
+        };
+
+        // a new manager announces itself with a `MANAGER` client message on the root window
+        let aux = ChangeWindowAttributesAux::new().event_mask(EventMask::STRUCTURE_NOTIFY);
+        settings.conn.change_window_attributes(root, &aux)?;
+        settings.find_owner()?;
+
+        Ok(settings)
+    }
+
+    /// Reads all settings published by the settings manager.
+    pub fn settings(&self) -> Result<HashMap<String, XSetting>, XSettingsError> {
+        let owner = self.owner.ok_or(XSettingsError::NoManager)?;
+
+        let property = self
+            .conn
+            .get_property(
+                false,
+                owner,
+                self.settings_atom,
+                AtomEnum::ANY,
+                0,
+                u32::MAX / 4,
+            )?
+            .reply()?;
+
+        parse(&property.value)
+    }
+
+    /// The current icon theme name, if the settings manager publishes one.
+    pub fn icon_theme_name(&self) -> Result<Option<String>, XSettingsError> {
+        match self.settings()?.remove(ICON_THEME_NAME) {
+            Some(XSetting::String(name)) if !name.is_empty() => Ok(Some(name)),
+            _ => Ok(None),
+        }
+    }
+
+    /// Blocks until the icon theme name changes, returning the new name.
+    ///
+    /// This also notices when a settings manager is started or replaced.
+    pub fn wait_for_icon_theme_change(&mut self) -> Result<Option<String>, XSettingsError> {
+        let current = self.icon_theme_name().unwrap_or(None);
+
+        loop {
+            let changed = match self.conn.wait_for_event()? {
+                Event::PropertyNotify(event) => {
+                    Some(event.window) == self.owner && event.atom == self.settings_atom
+                }
+                Event::DestroyNotify(event) if Some(event.window) == self.owner => {
+                    self.owner = None;
+                    false
+                }
+                Event::ClientMessage(event) if event.type_ == self.manager_atom => {
+                    let data = event.data.as_data32();
+                    if data[1] == self.selection {
+                        self.find_owner()?;
+                        true
+                    } else {
+                        false
+                    }
+                }
+                _ => false,
+            };
+
+            if !changed {
+                continue;
+            }
+
+            let theme = self.icon_theme_name().unwrap_or(None);
+            if theme != current {
+                return Ok(theme);
+            }
+        }
+    }
+
+    fn find_owner(&mut self) -> Result<(), XSettingsError> {
+        let owner = self
+            .conn
+            .get_selection_owner(self.selection)?
+            .reply()?
+            .owner;
+
+        self.owner = (owner != x11rb::NONE).then_some(owner);
+
+        if let Some(owner) = self.owner {
+            let aux = ChangeWindowAttributesAux::new()
+                .event_mask(EventMask::PROPERTY_CHANGE | EventMask::STRUCTURE_NOTIFY);
+            self.conn.change_window_attributes(owner, &aux)?;
+        }
+
+        self.conn.flush()?;
+
+        Ok(())
+    }
+}
+
+impl std::fmt::Debug for XSettings {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("XSettings")
+            .field("root", &self.root)
+            .field("owner", &self.owner)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Connects to the X server and reads the icon theme name, returning `None` on any failure.
+pub fn icon_theme_name() -> Option<String> {
+    XSettings::connect().ok()?.icon_theme_name().ok()?
+}
+
+/// Parses the contents of the `_XSETTINGS_SETTINGS` property.
+pub fn parse(data: &[u8]) -> Result<HashMap<String, XSetting>, XSettingsError> {
+    struct Reader<'a> {
+        data: &'a [u8],
+        big_endian: bool,
+    }
+
+    impl<'a> Reader<'a> {
+        fn take(&mut self, len: usize) -> Result<&'a [u8], XSettingsError> {
+            if self.data.len() < len {
+                return Err(XSettingsError::Malformed);
+            }
+            let (taken, rest) = self.data.split_at(len);
+            self.data = rest;
+            Ok(taken)
+        }
+
+        /// Takes `len` bytes, skipping padding up to a multiple of 4.
+        fn take_padded(&mut self, len: usize) -> Result<&'a [u8], XSettingsError> {
+            let taken = self.take(len)?;
+            self.take((4 - len % 4) % 4)?;
+            Ok(taken)
+        }
+
+        fn u16(&mut self) -> Result<u16, XSettingsError> {
+            let bytes = self.take(2)?.try_into().unwrap();
+            Ok(match self.big_endian {
+                true => u16::from_be_bytes(bytes),
+                false => u16::from_le_bytes(bytes),
+            })
+        }
+
+        fn u32(&mut self) -> Result<u32, XSettingsError> {
+            let bytes = self.take(4)?.try_into().unwrap();
+            Ok(match self.big_endian {
+                true => u32::from_be_bytes(bytes),
+                false => u32::from_le_bytes(bytes),
+            })
+        }
+    }
+
+    let mut reader = Reader {
+        data,
+        big_endian: false,
+    };
+
+    reader.big_endian = match reader.take(4)?[0] {
+        0 => false, // LSBFirst
+        1 => true,  // MSBFirst
+        _ => return Err(XSettingsError::Malformed),
+    };
+    let _serial = reader.u32()?;
+    let count = reader.u32()?;
+
+    let mut settings = HashMap::new();
+
+    for _ in 0..count {
+        let setting_type = reader.take(2)?[0];
+        let name_len = reader.u16()? as usize;
+        let name = reader.take_padded(name_len)?;
+        let name = String::from_utf8_lossy(name).into_owned();
+        let _last_change_serial = reader.u32()?;
+
+        let value = match setting_type {
+            0 => XSetting::Integer(reader.u32()? as i32),
+            1 => {
+                let len = reader.u32()? as usize;
+                let value = reader.take_padded(len)?;
+                XSetting::String(String::from_utf8_lossy(value).into_owned())
+            }
+            2 => XSetting::Color([reader.u16()?, reader.u16()?, reader.u16()?, reader.u16()?]),
+            _ => return Err(XSettingsError::Malformed),
+        };
+
+        settings.insert(name, value);
+    }
+
+    Ok(settings)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::xsettings::{XSetting, parse};
+
+    #[test]
+    fn test_parse() {
+        let mut data = vec![0, 0, 0, 0]; // LSBFirst
+        data.extend(7u32.to_le_bytes()); // serial
+        data.extend(2u32.to_le_bytes()); // settings
+
+        // Net/IconThemeName = "Papirus"
+        data.extend([1, 0]);
+        data.extend(17u16.to_le_bytes());
+        data.extend(b"Net/IconThemeName\0\0\0");
+        data.extend(0u32.to_le_bytes());
+        data.extend(7u32.to_le_bytes());
+        data.extend(b"Papirus\0");
+
+        // Xft/DPI = 98304
+        data.extend([0, 0]);
+        data.extend(7u16.to_le_bytes());
+        data.extend(b"Xft/DPI\0");
+        data.extend(0u32.to_le_bytes());
+        data.extend(98304i32.to_le_bytes());
+
+        let settings = parse(&data).unwrap();
+        assert_eq!(
+            settings["Net/IconThemeName"],
+            XSetting::String("Papirus".into())
+        );
+        assert_eq!(settings["Xft/DPI"], XSetting::Integer(98304));
+
+        assert!(parse(&data[..data.len() - 1]).is_err());
+    }
+}